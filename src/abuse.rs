@@ -0,0 +1,233 @@
+use crate::{
+    auditlog::{self, AuditEntry},
+    duration, State,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use twilight_model::id::{GuildId, UserId};
+
+const COMMAND_LIMIT: usize = 8;
+const COMMAND_WINDOW: Duration = Duration::from_secs(10);
+const FAILURE_LIMIT: usize = 5;
+const FAILURE_WINDOW: Duration = Duration::from_secs(2 * 60);
+pub const BAN_DURATION: Duration = Duration::from_secs(15 * 60);
+
+/// Why a user was banned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Offence {
+    CommandSpam,
+    RepeatedFailures,
+}
+
+impl fmt::Display for Offence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Offence::CommandSpam => write!(
+                f,
+                "more than {} commands in {} seconds",
+                COMMAND_LIMIT,
+                COMMAND_WINDOW.as_secs()
+            ),
+            Offence::RepeatedFailures => write!(
+                f,
+                "{} failed lookups in {}",
+                FAILURE_LIMIT,
+                duration::humanize(FAILURE_WINDOW)
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Record {
+    commands: VecDeque<Instant>,
+    failures: VecDeque<Instant>,
+    banned_until: Option<Instant>,
+}
+
+/// Tracks recent commands and failed resolutions per user, and bans
+/// users who go over the limits. Banned users are ignored without a
+/// reply until the ban ends or an admin lifts it.
+#[derive(Debug, Default)]
+pub struct AbuseGuard {
+    records: Mutex<HashMap<(GuildId, UserId), Record>>,
+}
+
+impl AbuseGuard {
+    pub fn is_banned(&self, guild_id: GuildId, user_id: UserId, now: Instant) -> bool {
+        self.records
+            .lock()
+            .unwrap()
+            .get(&(guild_id, user_id))
+            .and_then(|record| record.banned_until)
+            .is_some_and(|until| now < until)
+    }
+
+    /// Records a command, returning the offence if it started a ban.
+    pub fn command(&self, guild_id: GuildId, user_id: UserId, now: Instant) -> Option<Offence> {
+        self.record(guild_id, user_id, now, Offence::CommandSpam)
+    }
+
+    /// Records a failed resolution, returning the offence if it started a
+    /// ban.
+    pub fn failure(&self, guild_id: GuildId, user_id: UserId, now: Instant) -> Option<Offence> {
+        self.record(guild_id, user_id, now, Offence::RepeatedFailures)
+    }
+
+    /// Lifts a ban, returning whether there was one.
+    pub fn unban(&self, guild_id: GuildId, user_id: UserId, now: Instant) -> bool {
+        let mut records = self.records.lock().unwrap();
+
+        match records.remove(&(guild_id, user_id)) {
+            Some(record) => record.banned_until.is_some_and(|until| now < until),
+            None => false,
+        }
+    }
+
+    fn record(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        now: Instant,
+        offence: Offence,
+    ) -> Option<Offence> {
+        let mut records = self.records.lock().unwrap();
+        let record = records.entry((guild_id, user_id)).or_default();
+
+        if record.banned_until.is_some_and(|until| now < until) {
+            return None;
+        }
+
+        let (events, window, limit) = match offence {
+            Offence::CommandSpam => (&mut record.commands, COMMAND_WINDOW, COMMAND_LIMIT),
+            Offence::RepeatedFailures => (&mut record.failures, FAILURE_WINDOW, FAILURE_LIMIT),
+        };
+
+        events.push_back(now);
+        while events
+            .front()
+            .is_some_and(|first| now.saturating_duration_since(*first) > window)
+        {
+            events.pop_front();
+        }
+
+        if events.len() > limit || (offence == Offence::RepeatedFailures && events.len() == limit) {
+            *record = Record {
+                banned_until: Some(now + BAN_DURATION),
+                ..Default::default()
+            };
+
+            return Some(offence);
+        }
+
+        None
+    }
+}
+
+/// Tells the guild owner by DM, and the log channel, that a user was
+/// banned.
+pub async fn notify(
+    state: &State,
+    guild_id: GuildId,
+    user_id: UserId,
+    offence: Offence,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let action = format!(
+        "Ignoring <@{}> for {} after {}. Use `{prefix}admin unban <@{}>` to lift it.",
+        user_id,
+        duration::humanize(BAN_DURATION),
+        offence,
+        user_id,
+        prefix = state.prefix(guild_id)
+    );
+
+    auditlog::record(state, guild_id, AuditEntry::action(user_id, &action)).await;
+
+    let guild = state.http.guild(guild_id).exec().await?.model().await?;
+    let channel = state
+        .http
+        .create_private_channel(guild.owner_id)
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    state
+        .http
+        .create_message(channel.id)
+        .content(&format!("In **{}**: {}", guild.name, action))?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD: GuildId = GuildId(1);
+    const USER: UserId = UserId(2);
+
+    #[test]
+    fn bans_command_spam() {
+        let guard = AbuseGuard::default();
+        let start = Instant::now();
+
+        for i in 0..COMMAND_LIMIT {
+            let now = start + Duration::from_millis(i as u64 * 100);
+            assert_eq!(guard.command(GUILD, USER, now), None);
+        }
+
+        let now = start + Duration::from_secs(1);
+        assert_eq!(guard.command(GUILD, USER, now), Some(Offence::CommandSpam));
+        assert!(guard.is_banned(GUILD, USER, now));
+        assert!(!guard.is_banned(GUILD, UserId(3), now));
+        assert!(!guard.is_banned(GUILD, USER, now + BAN_DURATION));
+    }
+
+    #[test]
+    fn slow_commands_are_fine() {
+        let guard = AbuseGuard::default();
+        let start = Instant::now();
+
+        for i in 0..(COMMAND_LIMIT * 3) {
+            let now = start + Duration::from_secs(i as u64 * 2);
+            assert_eq!(guard.command(GUILD, USER, now), None);
+        }
+    }
+
+    #[test]
+    fn bans_repeated_failures() {
+        let guard = AbuseGuard::default();
+        let now = Instant::now();
+
+        for _ in 1..FAILURE_LIMIT {
+            assert_eq!(guard.failure(GUILD, USER, now), None);
+        }
+
+        assert_eq!(
+            guard.failure(GUILD, USER, now),
+            Some(Offence::RepeatedFailures)
+        );
+    }
+
+    #[test]
+    fn unban_lifts_ban() {
+        let guard = AbuseGuard::default();
+        let now = Instant::now();
+
+        for _ in 0..FAILURE_LIMIT {
+            guard.failure(GUILD, USER, now);
+        }
+
+        assert!(guard.unban(GUILD, USER, now));
+        assert!(!guard.is_banned(GUILD, USER, now));
+        assert!(!guard.unban(GUILD, USER, now));
+    }
+}
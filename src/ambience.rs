@@ -0,0 +1,99 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use songbird::input::{reader::Reader, Input, Metadata};
+use std::time::Duration;
+
+/// Volume of the ambient layer under the main track.
+pub const VOLUME: f32 = 0.15;
+
+/// Length of the generated loop; noise hides the seam.
+const LOOP_LENGTH: Duration = Duration::from_secs(10);
+const SAMPLE_RATE: usize = 48_000;
+
+/// The built-in ambiences, generated on demand so the bot ships no audio.
+pub const NAMES: &[&str] = &["rain", "crackle", "noise"];
+
+/// A looping stereo source for the named ambience.
+pub fn input(name: &str) -> Option<Input> {
+    let samples = generate(name)?;
+
+    let bytes = samples
+        .iter()
+        .flat_map(|sample| sample.to_le_bytes())
+        .collect::<Vec<_>>();
+
+    let mut input = Input::float_pcm(true, Reader::from_memory(bytes));
+    input.metadata = Box::new(Metadata {
+        title: Some(format!("Ambience: {}", name)),
+        duration: Some(LOOP_LENGTH),
+        ..Default::default()
+    });
+
+    Some(input)
+}
+
+/// Interleaved stereo samples for one loop, within `-1.0..=1.0`.
+fn generate(name: &str) -> Option<Vec<f32>> {
+    let frames = SAMPLE_RATE * LOOP_LENGTH.as_secs() as usize;
+    // Seeded so the same ambience always sounds the same.
+    let mut rng = StdRng::seed_from_u64(0x6d38);
+    let mut state = [0.0f32; 2];
+    let mut decay = [0.0f32; 2];
+    let mut samples = Vec::with_capacity(frames * 2);
+
+    for _ in 0..frames {
+        for channel in 0..2 {
+            let white = rng.gen_range(-1.0f32..1.0);
+
+            let sample = match name {
+                // Low-passed noise with the odd louder drop.
+                "rain" => {
+                    state[channel] += 0.3 * (white - state[channel]);
+                    if rng.gen_bool(0.0002) {
+                        decay[channel] = rng.gen_range(0.3..0.8);
+                    }
+                    decay[channel] *= 0.995;
+                    state[channel] * 0.5 + white * decay[channel]
+                }
+                // Faint hiss with sparse clicks.
+                "crackle" => {
+                    if rng.gen_bool(0.0004) {
+                        decay[channel] = rng.gen_range(0.4..1.0);
+                    }
+                    decay[channel] *= 0.8;
+                    white * 0.02 + decay[channel] * white.signum()
+                }
+                // Brown noise: leaky integration of white noise.
+                "noise" => {
+                    state[channel] = (state[channel] * 0.998 + white * 0.04).clamp(-1.0, 1.0);
+                    state[channel]
+                }
+                _ => return None,
+            };
+
+            samples.push(sample.clamp(-1.0, 1.0));
+        }
+    }
+
+    Some(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_every_ambience() {
+        for name in NAMES {
+            let samples = generate(name).unwrap();
+
+            assert_eq!(samples.len(), SAMPLE_RATE * 10 * 2);
+            assert!(samples.iter().all(|sample| sample.abs() <= 1.0));
+            assert!(samples.iter().any(|sample| *sample != 0.0));
+        }
+    }
+
+    #[test]
+    fn unknown_ambience() {
+        assert!(input("whales").is_none());
+    }
+}
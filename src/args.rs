@@ -0,0 +1,318 @@
+use std::time::Duration;
+use twilight_model::id::{ChannelId, RoleId, UserId};
+
+/// Splits a command message into its command word and the rest of the
+/// line, trimmed. The rest is empty when no arguments were given.
+pub fn split(content: &str) -> (&str, &str) {
+    let content = content.trim_start();
+
+    match content.find(char::is_whitespace) {
+        Some(end) => (&content[..end], content[end..].trim()),
+        None => (content, ""),
+    }
+}
+
+/// A command's arguments: the words after the command, and any
+/// `--flag` or `--name=value` options among them.
+///
+/// A word that starts with a quote runs to the matching quote, so
+/// `"two words"` is a single argument; inside double quotes `\"` and `\\`
+/// stand for the characters themselves. A quote left open runs to the end
+/// of the line rather than failing the command. Quotes further into a
+/// word, as in `don't`, are just characters. A lone `--` ends the options,
+/// and quoted words are never options.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Args {
+    words: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    /// Parses the arguments of a whole command message, skipping the
+    /// command word itself.
+    pub fn parse(content: &str) -> Self {
+        Self::parse_line(split(content).1)
+    }
+
+    /// Parses a line holding only arguments.
+    pub fn parse_line(line: &str) -> Self {
+        let mut args = Self::default();
+        let mut options_ended = false;
+
+        for (word, quoted) in tokenize(line) {
+            match word.strip_prefix("--") {
+                Some("") if !quoted && !options_ended => options_ended = true,
+                Some(option) if !quoted && !options_ended => {
+                    let option = match option.split_once('=') {
+                        Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                        None => (option.to_string(), None),
+                    };
+                    args.options.push(option);
+                }
+                _ => args.words.push(word),
+            }
+        }
+
+        args
+    }
+
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.words.iter().map(String::as_str)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.words.get(index).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Whether `--name` was given, with or without a value.
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(option, _)| option == name)
+    }
+
+    /// The value of the last `--name=value`.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(option, _)| option == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+}
+
+/// Splits `line` into words as described on [`Args`], noting which of
+/// them were quoted.
+fn tokenize(line: &str) -> Vec<(String, bool)> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        let quote = match chars.peek() {
+            None => return words,
+            Some(&c @ ('"' | '\'')) => {
+                chars.next();
+                Some(c)
+            }
+            Some(_) => None,
+        };
+
+        let mut word = String::new();
+
+        if let Some(quote) = quote {
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' if quote == '"' && matches!(chars.peek(), Some('"' | '\\')) => {
+                        word.extend(chars.next());
+                    }
+                    c if c == quote => break,
+                    c => word.push(c),
+                }
+            }
+        }
+
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+            word.push(c);
+        }
+
+        words.push((word, quote.is_some()));
+    }
+}
+
+/// Puts `word` in double quotes, so [`Args`] reads it back as one word.
+pub fn quote(word: &str) -> String {
+    format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Parses a `<#id>` channel mention or a bare channel ID.
+pub fn channel(arg: &str) -> Option<ChannelId> {
+    id(mention(arg, "#").unwrap_or(arg)).map(ChannelId)
+}
+
+/// Parses a `<@&id>` role mention or a bare role ID.
+pub fn role(arg: &str) -> Option<RoleId> {
+    id(mention(arg, "@&").unwrap_or(arg)).map(RoleId)
+}
+
+/// Parses a `<@id>`/`<@!id>` user mention or a bare user ID.
+pub fn user(arg: &str) -> Option<UserId> {
+    let id_part = mention(arg, "@!")
+        .or_else(|| mention(arg, "@").filter(|rest| !rest.starts_with('&')))
+        .unwrap_or(arg);
+
+    id(id_part).map(UserId)
+}
+
+/// Parses a position or length, as `90`, `1:30` or `1m30s`.
+pub fn duration(arg: &str) -> Option<Duration> {
+    crate::duration::parse(arg)
+}
+
+fn mention<'a>(arg: &'a str, sigil: &str) -> Option<&'a str> {
+    arg.strip_prefix('<')?
+        .strip_prefix(sigil)?
+        .strip_suffix('>')
+}
+
+/// Discord IDs are never zero; Rust's integer parsing would also take a
+/// leading `+`, which no mention has.
+fn id(digits: &str) -> Option<u64> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    digits.parse().ok().filter(|&id| id != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+    /// Characters the property tests build arguments from, weighted
+    /// towards the ones the parser treats specially.
+    const ALPHABET: &[char] = &[
+        'a', 'Z', '0', '7', ' ', ' ', '\t', '\n', '"', '"', '\'', '\\', '\\', '-', '=', '<', '>',
+        '@', '&', '#', '!', ':', 'é', '🎵',
+    ];
+
+    fn random_text(rng: &mut StdRng) -> String {
+        let len = rng.gen_range(0..24);
+        (0..len).map(|_| *ALPHABET.choose(rng).unwrap()).collect()
+    }
+
+    #[test]
+    fn splits_off_the_command() {
+        assert_eq!(split("j/play"), ("j/play", ""));
+        assert_eq!(
+            split("j/play  https://example.com/song \n"),
+            ("j/play", "https://example.com/song")
+        );
+        assert_eq!(split("j/join\n123"), ("j/join", "123"));
+    }
+
+    #[test]
+    fn keeps_inner_spacing() {
+        assert_eq!(split("j/play some  song"), ("j/play", "some  song"));
+        assert_eq!(split("   "), ("", ""));
+    }
+
+    #[test]
+    fn parses_words_quotes_and_options() {
+        let args = Args::parse(
+            "j/jingle  add \"Friday \\\"night\\\"\" 'two words'  don't --now --at=20:00 -- --kept",
+        );
+
+        assert_eq!(
+            args.words().collect::<Vec<_>>(),
+            ["add", "Friday \"night\"", "two words", "don't", "--kept"]
+        );
+        assert!(args.flag("now"));
+        assert!(args.flag("at"));
+        assert!(!args.flag("kept"));
+        assert_eq!(args.option("at"), Some("20:00"));
+        assert_eq!(args.option("now"), None);
+    }
+
+    #[test]
+    fn open_quotes_run_to_the_end() {
+        let args = Args::parse("j/theme set \"https://example.com/a b");
+        assert_eq!(
+            args.words().collect::<Vec<_>>(),
+            ["set", "https://example.com/a b"]
+        );
+        assert_eq!(Args::parse("j/x \"").get(0), Some(""));
+    }
+
+    #[test]
+    fn parses_mentions_and_ids() {
+        assert_eq!(channel("<#123>"), Some(ChannelId(123)));
+        assert_eq!(channel("123"), Some(ChannelId(123)));
+        assert_eq!(role("<@&45>"), Some(RoleId(45)));
+        assert_eq!(user("<@67>"), Some(UserId(67)));
+        assert_eq!(user("<@!67>"), Some(UserId(67)));
+
+        for bad in [
+            "", "<#>", "<#12", "<@&45>x", "+5", "0", "<@0>", "1 2", "<@!!6>",
+        ] {
+            assert_eq!(user(bad), None, "{:?}", bad);
+            assert_eq!(channel(bad), None, "{:?}", bad);
+        }
+        assert_eq!(user("<@&45>"), None);
+        assert_eq!(role("<@45>"), None);
+    }
+
+    #[test]
+    fn quoted_words_survive_any_text() {
+        let mut rng = StdRng::seed_from_u64(268);
+
+        for _ in 0..2000 {
+            let words = (0..rng.gen_range(0..5))
+                .map(|_| random_text(&mut rng))
+                .collect::<Vec<_>>();
+            let line = words.iter().map(|word| quote(word)).collect::<Vec<_>>();
+            let args = Args::parse_line(&line.join(" "));
+
+            assert_eq!(args.words().collect::<Vec<_>>(), words, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn plain_words_split_on_whitespace() {
+        let mut rng = StdRng::seed_from_u64(2682);
+
+        for _ in 0..2000 {
+            let line = random_text(&mut rng).replace(['"', '\'', '-'], "x");
+            let args = Args::parse_line(&line);
+
+            assert_eq!(
+                args.words().collect::<Vec<_>>(),
+                line.split_whitespace().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn any_text_parses_without_losing_words() {
+        let mut rng = StdRng::seed_from_u64(2683);
+
+        for _ in 0..5000 {
+            let line = random_text(&mut rng);
+            let args = Args::parse(&line);
+
+            // Every word or option came from somewhere in the line, and
+            // whitespace only survives inside quotes.
+            assert!(args.len() + args.options.len() <= line.split_whitespace().count());
+            for word in args.words() {
+                assert!(word.chars().all(|c| line.contains(c)), "{:?}", line);
+            }
+
+            channel(&line);
+            role(&line);
+            user(&line);
+            duration(&line);
+        }
+    }
+
+    #[test]
+    fn mentions_round_trip() {
+        let mut rng = StdRng::seed_from_u64(2684);
+
+        for _ in 0..1000 {
+            let id = rng.gen_range(1..=u64::MAX);
+
+            assert_eq!(channel(&format!("<#{}>", id)), Some(ChannelId(id)));
+            assert_eq!(role(&format!("<@&{}>", id)), Some(RoleId(id)));
+            assert_eq!(user(&format!("<@{}>", id)), Some(UserId(id)));
+            assert_eq!(user(&format!("<@!{}>", id)), Some(UserId(id)));
+        }
+    }
+}
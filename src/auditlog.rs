@@ -0,0 +1,89 @@
+use crate::State;
+use chrono::Utc;
+use twilight_model::{
+    channel::embed::Embed,
+    id::{GuildId, UserId},
+};
+
+const INFO_COLOR: u32 = 0x5865f2;
+const ERROR_COLOR: u32 = 0xed4245;
+
+/// A notable event worth recording for a guild's admins.
+#[derive(Debug)]
+pub struct AuditEntry<'a> {
+    pub actor: Option<UserId>,
+    pub action: &'a str,
+    pub error: bool,
+}
+
+impl<'a> AuditEntry<'a> {
+    pub fn action(actor: UserId, action: &'a str) -> Self {
+        Self {
+            actor: Some(actor),
+            action,
+            error: false,
+        }
+    }
+
+    pub fn error(actor: Option<UserId>, action: &'a str) -> Self {
+        Self {
+            actor,
+            action,
+            error: true,
+        }
+    }
+}
+
+/// Logs the entry and mirrors it as a compact embed to the guild's log
+/// channel, if one is configured. Failing to post is only logged, so
+/// callers never have to handle it.
+pub async fn record(state: &State, guild_id: GuildId, entry: AuditEntry<'_>) {
+    tracing::info!(guild_id = %guild_id, error = entry.error, "audit: {}", entry.action);
+
+    let log_channel = state
+        .settings
+        .read()
+        .await
+        .get(&guild_id)
+        .and_then(|settings| settings.log_channel);
+
+    let channel_id = match log_channel {
+        Some(channel_id) => channel_id,
+        None => return,
+    };
+
+    let description = match entry.actor {
+        Some(actor) => format!("{}\nby <@{}>", entry.action, actor),
+        None => entry.action.to_string(),
+    };
+
+    let embed = Embed {
+        author: None,
+        color: Some(if entry.error { ERROR_COLOR } else { INFO_COLOR }),
+        description: Some(description),
+        fields: Vec::new(),
+        footer: None,
+        image: None,
+        kind: "rich".to_string(),
+        provider: None,
+        thumbnail: None,
+        timestamp: Some(Utc::now().to_rfc3339()),
+        title: None,
+        url: None,
+        video: None,
+    };
+
+    let embeds = [embed];
+
+    let request = match state.http.create_message(channel_id).embeds(&embeds) {
+        Ok(request) => request,
+        Err(e) => {
+            tracing::warn!(guild_id = %guild_id, "invalid audit embed: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = request.exec().await {
+        tracing::warn!(guild_id = %guild_id, "failed to mirror audit entry: {}", e);
+    }
+}
@@ -0,0 +1,263 @@
+use crate::migrations::SCHEMA_VERSION;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Scheduled copies of the data directory, set with `backups` in the
+/// config file.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Where each backup gets a directory of its own, named after when it
+    /// was taken.
+    pub dir: PathBuf,
+    /// Hours between backups.
+    pub interval_hours: u64,
+    /// How many of the newest backups are kept; older ones are deleted.
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("backups"),
+            interval_hours: 24,
+            keep: 7,
+        }
+    }
+}
+
+impl BackupConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_hours * 60 * 60)
+    }
+}
+
+/// `manifest.json` in each backup, written last so a backup cut short has
+/// none. The CRC-32 of every file lets a restore tell a damaged backup
+/// apart before it replaces anything.
+#[derive(Debug, Deserialize, Serialize)]
+struct Manifest {
+    schema_version: usize,
+    files: BTreeMap<String, u32>,
+}
+
+/// Copies every file in `data_dir` into a new backup under `config.dir`,
+/// then deletes the oldest beyond `config.keep`. Returns where the new
+/// backup is.
+pub fn take(
+    data_dir: &Path,
+    config: &BackupConfig,
+    now: DateTime<Utc>,
+) -> Result<PathBuf, Box<dyn Error + Send + Sync + 'static>> {
+    let backup = config.dir.join(now.format("%Y%m%dT%H%M%SZ").to_string());
+    fs::create_dir_all(&backup)?;
+
+    let mut files = BTreeMap::new();
+    for name in data_files(data_dir)? {
+        let contents = fs::read(data_dir.join(&name))?;
+        fs::write(backup.join(&name), &contents)?;
+        files.insert(name, checksum(&contents));
+    }
+
+    let manifest = Manifest {
+        schema_version: SCHEMA_VERSION,
+        files,
+    };
+    fs::write(
+        backup.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+
+    let mut backups = fs::read_dir(&config.dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.join("manifest.json").is_file())
+        .collect::<Vec<_>>();
+    // Named after when they were taken, so they sort oldest first.
+    backups.sort();
+    let excess = backups.len().saturating_sub(config.keep);
+    for old in &backups[..excess] {
+        fs::remove_dir_all(old)?;
+    }
+
+    Ok(backup)
+}
+
+/// Swaps the backup at `backup` in for `data_dir`, once every file in it
+/// has been checked against its manifest. The directory it replaces is
+/// kept beside it, with `.old` and the time added to its name, and that
+/// path is returned if there was one.
+///
+/// The bot should be stopped first, or it may save over the restored
+/// files.
+pub fn restore(
+    backup: &Path,
+    data_dir: &Path,
+    now: DateTime<Utc>,
+) -> Result<Option<PathBuf>, Box<dyn Error + Send + Sync + 'static>> {
+    let manifest: Manifest = match fs::read_to_string(backup.join("manifest.json")) {
+        Ok(contents) => serde_json::from_str(&contents)?,
+        Err(e) => return Err(format!("{} has no manifest: {}", backup.display(), e).into()),
+    };
+
+    if manifest.schema_version > SCHEMA_VERSION {
+        return Err(format!(
+            "the backup is at schema version {}, newer than this build's {}",
+            manifest.schema_version, SCHEMA_VERSION
+        )
+        .into());
+    }
+
+    let mut verified = Vec::with_capacity(manifest.files.len());
+    for (name, crc) in &manifest.files {
+        let contents = fs::read(backup.join(name))
+            .map_err(|e| format!("{} is missing from the backup: {}", name, e))?;
+
+        if checksum(&contents) != *crc {
+            return Err(format!("{} doesn't match the backup's manifest", name).into());
+        }
+
+        verified.push((name, contents));
+    }
+
+    // Written beside the data directory first, so it's only replaced once
+    // the whole backup is in place.
+    let stamp = now.format("%Y%m%dT%H%M%SZ");
+    let incoming = with_suffix(data_dir, &format!(".restoring-{}", stamp));
+    fs::create_dir_all(&incoming)?;
+    for (name, contents) in verified {
+        fs::write(incoming.join(name), contents)?;
+    }
+
+    let old = if data_dir.exists() {
+        let old = with_suffix(data_dir, &format!(".old-{}", stamp));
+        fs::rename(data_dir, &old)?;
+        Some(old)
+    } else {
+        None
+    };
+    fs::rename(&incoming, data_dir)?;
+
+    Ok(old)
+}
+
+/// The saved state in `data_dir`, leaving out files still being written.
+fn data_files(data_dir: &Path) -> Result<Vec<String>, Box<dyn Error + Send + Sync + 'static>> {
+    let mut names = Vec::new();
+
+    for entry in fs::read_dir(data_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        if entry.file_type()?.is_file() && name.ends_with(".json") {
+            names.push(name);
+        }
+    }
+
+    Ok(names)
+}
+
+fn checksum(contents: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(contents);
+    hasher.finalize()
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn scratch() -> PathBuf {
+        std::env::temp_dir().join(format!("musicm8-backup-{}", rand::random::<u64>()))
+    }
+
+    #[test]
+    fn restores_what_was_backed_up() {
+        let root = scratch();
+        let data_dir = root.join("data");
+        let config = BackupConfig {
+            dir: root.join("backups"),
+            ..BackupConfig::default()
+        };
+        fs::create_dir_all(&data_dir).unwrap();
+        fs::write(data_dir.join("prefixes.json"), r#"{"1": "!"}"#).unwrap();
+        fs::write(data_dir.join("queues.json.tmp"), "half a fi").unwrap();
+
+        let now = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+        let backup = take(&data_dir, &config, now).unwrap();
+        fs::write(data_dir.join("prefixes.json"), r#"{"1": "?"}"#).unwrap();
+
+        let old = restore(&backup, &data_dir, now).unwrap().unwrap();
+        assert_eq!(
+            fs::read_to_string(data_dir.join("prefixes.json")).unwrap(),
+            r#"{"1": "!"}"#
+        );
+        assert!(!data_dir.join("queues.json.tmp").exists());
+        assert_eq!(
+            fs::read_to_string(old.join("prefixes.json")).unwrap(),
+            r#"{"1": "?"}"#
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn damaged_backups_replace_nothing() {
+        let root = scratch();
+        let data_dir = root.join("data");
+        let config = BackupConfig {
+            dir: root.join("backups"),
+            ..BackupConfig::default()
+        };
+        fs::create_dir_all(&data_dir).unwrap();
+        fs::write(data_dir.join("prefixes.json"), r#"{"1": "!"}"#).unwrap();
+
+        let now = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+        let backup = take(&data_dir, &config, now).unwrap();
+        fs::write(backup.join("prefixes.json"), r#"{"1": "?"}"#).unwrap();
+
+        assert!(restore(&backup, &data_dir, now).is_err());
+        assert_eq!(
+            fs::read_to_string(data_dir.join("prefixes.json")).unwrap(),
+            r#"{"1": "!"}"#
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_newest_backups() {
+        let root = scratch();
+        let data_dir = root.join("data");
+        let config = BackupConfig {
+            dir: root.join("backups"),
+            keep: 2,
+            ..BackupConfig::default()
+        };
+        fs::create_dir_all(&data_dir).unwrap();
+
+        let start = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+        let backups = (0..3)
+            .map(|day| take(&data_dir, &config, start + chrono::Duration::days(day)).unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(!backups[0].exists());
+        assert!(backups[1].exists() && backups[2].exists());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,116 @@
+use crate::programs::Program;
+use serde::Deserialize;
+use std::{error::Error, time::Duration};
+
+/// One chapter of an upload, as its description or uploader marked it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chapter {
+    pub title: String,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+/// What of youtube-dl's `-j` output describes the chapters.
+#[derive(Deserialize)]
+struct Info {
+    #[serde(default)]
+    chapters: Option<Vec<Entry>>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    title: Option<String>,
+    start_time: f64,
+    end_time: f64,
+}
+
+/// Parses youtube-dl's `-j` output for one video into its chapters, in
+/// order. Chapters that don't run forwards are left out.
+pub fn parse_ytdl(output: &str) -> Vec<Chapter> {
+    let info = output
+        .lines()
+        .find_map(|line| serde_json::from_str::<Info>(line).ok());
+
+    info.and_then(|info| info.chapters)
+        .unwrap_or_default()
+        .into_iter()
+        .enumerate()
+        .filter(|(_, entry)| {
+            entry.start_time.is_finite()
+                && entry.end_time.is_finite()
+                && 0.0 <= entry.start_time
+                && entry.start_time < entry.end_time
+        })
+        .map(|(i, entry)| Chapter {
+            title: entry
+                .title
+                .filter(|title| !title.trim().is_empty())
+                .unwrap_or_else(|| format!("Chapter {}", i + 1)),
+            start: Duration::from_secs_f64(entry.start_time),
+            end: Duration::from_secs_f64(entry.end_time),
+        })
+        .collect()
+}
+
+/// Lists the chapters of the video at `url` through youtube-dl.
+pub async fn ytdl(
+    youtube_dl: &Program,
+    url: &str,
+) -> Result<Vec<Chapter>, Box<dyn Error + Send + Sync + 'static>> {
+    let output = youtube_dl
+        .command()
+        .arg("-j")
+        .arg("--no-playlist")
+        .arg(url)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "youtube-dl failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(parse_ytdl(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_chapters() {
+        let output = concat!(
+            r#"{"title": "Album", "chapters": ["#,
+            r#"{"start_time": 0.0, "end_time": 245.5, "title": "Intro"}, "#,
+            r#"{"start_time": 245.5, "end_time": 512.0, "title": ""}, "#,
+            r#"{"start_time": 512.0, "end_time": 512.0, "title": "Empty"}"#,
+            r#"]}"#,
+        );
+
+        assert_eq!(
+            parse_ytdl(output),
+            [
+                Chapter {
+                    title: "Intro".to_string(),
+                    start: Duration::ZERO,
+                    end: Duration::from_secs_f64(245.5),
+                },
+                Chapter {
+                    title: "Chapter 2".to_string(),
+                    start: Duration::from_secs_f64(245.5),
+                    end: Duration::from_secs(512),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn videos_without_chapters_have_none() {
+        assert!(parse_ytdl(r#"{"title": "Song", "chapters": null}"#).is_empty());
+        assert!(parse_ytdl(r#"{"title": "Song"}"#).is_empty());
+        assert!(parse_ytdl("not json").is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    fmt,
+    future::Future,
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+
+/// Source of time for every timer in the bot.
+///
+/// Handlers and background tasks take time from here rather than from
+/// `Utc::now`, `Instant::now` or `tokio::time`, so tests can swap in a
+/// [`ManualClock`] and fast-forward through timeouts and schedules.
+#[async_trait]
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Wall-clock time, for schedules such as quiet hours and curfews.
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Monotonic time, for measuring how long ago something happened.
+    fn instant(&self) -> Instant;
+
+    async fn sleep(&self, duration: Duration);
+}
+
+/// How long ago `earlier` was according to `clock`.
+pub fn elapsed(clock: &dyn Clock, earlier: Instant) -> Duration {
+    clock.instant().saturating_duration_since(earlier)
+}
+
+/// Runs `future`, giving up with `None` once `duration` has passed.
+pub async fn timeout<F: Future>(
+    clock: &dyn Clock,
+    duration: Duration,
+    future: F,
+) -> Option<F::Output> {
+    tokio::select! {
+        output = future => Some(output),
+        _ = clock.sleep(duration) => None,
+    }
+}
+
+/// The real clock.
+#[derive(Debug)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+/// A clock that only moves when told to. Sleepers wake once
+/// [`advance`](Self::advance) has moved it past their deadline.
+#[derive(Debug)]
+pub struct ManualClock {
+    start: DateTime<Utc>,
+    start_instant: Instant,
+    elapsed: watch::Sender<Duration>,
+    // Keeps the channel open while nobody is sleeping.
+    _elapsed_rx: watch::Receiver<Duration>,
+}
+
+impl ManualClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        let (elapsed, _elapsed_rx) = watch::channel(Duration::default());
+
+        Self {
+            start,
+            start_instant: Instant::now(),
+            elapsed,
+            _elapsed_rx,
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let elapsed = *self.elapsed.borrow() + duration;
+        let _ = self.elapsed.send(elapsed);
+    }
+
+    fn elapsed(&self) -> Duration {
+        *self.elapsed.borrow()
+    }
+}
+
+#[async_trait]
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        // Durations handed to `advance` are far below chrono's limits.
+        self.start + chrono::Duration::from_std(self.elapsed()).unwrap()
+    }
+
+    fn instant(&self) -> Instant {
+        self.start_instant + self.elapsed()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        let mut elapsed = self.elapsed.subscribe();
+        let deadline = *elapsed.borrow() + duration;
+
+        while *elapsed.borrow() < deadline {
+            if elapsed.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    /// Lets spawned tasks run until they block.
+    async fn run_pending() {
+        for _ in 0..10 {
+            let _ = tokio::task::yield_now().await;
+        }
+    }
+
+    fn clock() -> Arc<ManualClock> {
+        Arc::new(ManualClock::new(Utc.ymd(2021, 1, 1).and_hms(12, 0, 0)))
+    }
+
+    #[test]
+    fn advance_moves_both_times() {
+        let clock = clock();
+        let before = clock.instant();
+
+        clock.advance(Duration::from_secs(90));
+
+        assert_eq!(clock.now(), Utc.ymd(2021, 1, 1).and_hms(12, 1, 30));
+        assert_eq!(elapsed(&*clock, before), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn elapsed_saturates_for_later_instants() {
+        let clock = clock();
+        let later = clock.instant() + Duration::from_secs(5);
+
+        assert_eq!(elapsed(&*clock, later), Duration::default());
+    }
+
+    #[tokio::test]
+    async fn sleep_waits_for_deadline() {
+        let clock = clock();
+        let done = Arc::new(AtomicBool::new(false));
+        let sleeper = {
+            let clock = Arc::clone(&clock);
+            let done = Arc::clone(&done);
+            tokio::spawn(async move {
+                clock.sleep(Duration::from_secs(10)).await;
+                done.store(true, Ordering::SeqCst);
+            })
+        };
+
+        run_pending().await;
+        clock.advance(Duration::from_secs(9));
+        run_pending().await;
+        assert!(!done.load(Ordering::SeqCst));
+
+        clock.advance(Duration::from_secs(1));
+        sleeper.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn zero_sleep_returns_immediately() {
+        clock().sleep(Duration::default()).await;
+    }
+
+    #[tokio::test]
+    async fn timeout_gives_up_after_duration() {
+        let clock = clock();
+        let pending = {
+            let clock = Arc::clone(&clock);
+            tokio::spawn(async move {
+                timeout(
+                    &*clock,
+                    Duration::from_secs(30),
+                    futures::future::pending::<()>(),
+                )
+                .await
+            })
+        };
+
+        run_pending().await;
+        clock.advance(Duration::from_secs(30));
+
+        assert_eq!(pending.await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn timeout_returns_output_if_ready() {
+        let clock = clock();
+
+        assert_eq!(
+            timeout(&*clock, Duration::from_secs(1), async { 5 }).await,
+            Some(5)
+        );
+    }
+}
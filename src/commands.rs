@@ -0,0 +1,4880 @@
+use crate::{
+    ambience,
+    args::{self, Args},
+    auditlog::{self, AuditEntry},
+    clock, debug, duration, event, fade,
+    hooks::{SegmentEnd, TrackEndNotifier},
+    idle, jingle, permissions,
+    playback::PlaybackSnapshot,
+    playlist, prefixes,
+    providers::Provider,
+    queue::{self, LoopMode, QueuedTrack},
+    ratings, reconnect, recording, search,
+    settings::{
+        self, Announcements, Curfew, GuildSettings, NoRepeats, QuietHours, QUIET_HOURS_VOLUME,
+    },
+    snapshot::Snapshot,
+    sources::{self, SearchResult, Source, TrackInfo},
+    template::Template,
+    themes::{self, Theme},
+    tour, track,
+    track::Segment,
+    vibe, voteskip, State,
+};
+#[cfg(feature = "spotify")]
+use crate::{
+    spotify::{Spotify, SpotifyTrack},
+    spotifylinks::{self, LINK_TIMEOUT},
+};
+use chrono::{DateTime, Utc};
+use songbird::{
+    input::Input,
+    tracks::{PlayMode, TrackHandle},
+    Call, TrackEvent,
+};
+use std::{
+    error::Error,
+    fmt::Write as _,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use twilight_gateway::Event;
+use twilight_http::request::channel::reaction::RequestReactionType;
+use twilight_model::{
+    application::{
+        callback::{CallbackData, InteractionResponse},
+        component::{button::ButtonStyle, ActionRow, Button, Component},
+        interaction::Interaction,
+    },
+    channel::{
+        embed::{Embed, EmbedField, EmbedThumbnail},
+        message::AllowedMentions,
+        Attachment, Channel, GuildChannel, Message,
+    },
+    gateway::payload::MessageCreate,
+    id::{ChannelId, GuildId, UserId},
+};
+
+const NOW_PLAYING_REACTION: RequestReactionType<'static> =
+    RequestReactionType::Unicode { name: "🎶" };
+
+/// How long `j/queue`'s page buttons keep working after the last press.
+const PAGE_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(30);
+const RESUME_GRACE: Duration = Duration::from_secs(10 * 60);
+/// Kinds of attached file `j/play` takes.
+const ATTACHMENT_EXTENSIONS: &[&str] = &["mp3", "ogg", "wav", "flac", "m4a", "opus"];
+/// Bigger attachments are turned away unread. The length limit applies to
+/// the rest once ffmpeg has looked at them.
+const MAX_ATTACHMENT_BYTES: u64 = 50 * 1024 * 1024;
+
+/// How often a chapter checks whether it has reached its end.
+const SEGMENT_CHECK: Duration = Duration::from_millis(500);
+
+/// `j/play` offers to split uploads at least this long into their
+/// chapters.
+const CHAPTER_OFFER_LENGTH: Duration = Duration::from_secs(20 * 60);
+
+/// Rate limit waits shorter than this go unmentioned.
+const NOTICEABLE_WAIT: Duration = Duration::from_secs(5);
+
+/// The last track `j/stop` interrupted, kept so `j/resume` can restart it.
+#[derive(Debug)]
+pub struct StoppedTrack {
+    snapshot: PlaybackSnapshot,
+    stopped_at: Instant,
+}
+
+/// A text bar with a knob at `position`'s share of `duration`.
+fn progress_bar(position: Duration, duration: Duration) -> String {
+    const WIDTH: usize = 16;
+
+    let filled = if duration.is_zero() {
+        0
+    } else {
+        ((position.as_secs_f64() / duration.as_secs_f64()) * WIDTH as f64) as usize
+    };
+    let filled = filled.min(WIDTH - 1);
+
+    format!("{}🔘{}", "▬".repeat(filled), "▬".repeat(WIDTH - 1 - filled))
+}
+
+async fn active_quiet_hours(state: &State, guild_id: GuildId) -> Option<QuietHours> {
+    state
+        .settings
+        .read()
+        .await
+        .get(&guild_id)
+        .and_then(|settings| {
+            settings
+                .quiet_hours
+                .filter(|quiet_hours| quiet_hours.contains(state.clock.now(), settings.zone()))
+        })
+}
+
+/// Whether the bot joining would leave a user-limited voice channel with
+/// no free slot for humans. Occupancy comes from the tracked voice states.
+async fn would_fill_last_slot(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<bool, Box<dyn Error + Send + Sync + 'static>> {
+    if let Some(call_lock) = state.songbird.get(guild_id) {
+        let current = call_lock.lock().await.current_channel();
+
+        if current.map(|channel| channel.0) == Some(channel_id.0) {
+            return Ok(false);
+        }
+    }
+
+    let user_limit = match state.http.channel(channel_id).exec().await?.model().await? {
+        Channel::Guild(GuildChannel::Voice(channel)) => channel.user_limit.unwrap_or(0),
+        _ => 0,
+    };
+
+    if user_limit == 0 {
+        return Ok(false);
+    }
+
+    let occupancy = state
+        .voice_states
+        .read()
+        .await
+        .iter()
+        .filter(|((guild, _), channel)| *guild == guild_id && **channel == channel_id)
+        .count() as u64;
+
+    Ok(occupancy + 1 >= user_limit)
+}
+
+/// Checks the guild's daily quota, replying with the reason if it's used up.
+async fn within_quota(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<bool, Box<dyn Error + Send + Sync + 'static>> {
+    let today = state.clock.now().date().naive_utc();
+
+    match state.quotas.check(guild_id, today) {
+        Ok(()) => Ok(true),
+        Err(exceeded) => {
+            state
+                .http
+                .create_message(channel_id)
+                .content(&exceeded.to_string())?
+                .exec()
+                .await?;
+
+            Ok(false)
+        }
+    }
+}
+
+/// Refuses a request from a non-admin while a listening event has the
+/// queue locked, returning whether it was refused.
+async fn locked_out(
+    state: &State,
+    msg: &Message,
+) -> Result<bool, Box<dyn Error + Send + Sync + 'static>> {
+    if !event::is_locked(state, msg.guild_id.unwrap()).await
+        || permissions::is_admin(state, msg).await?
+    {
+        return Ok(false);
+    }
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content("Requests are locked for the listening event.")?
+        .exec()
+        .await?;
+
+    Ok(true)
+}
+
+/// The choices of a yes-or-no question for [`choose`].
+const YES_NO: &[&str] = &["Yes", "No"];
+
+/// Posts `question` with a button for each of `choices` and waits for the
+/// author to press one or reply with its label, returning the index of
+/// the choice. `None` if they answer anything else, or not within
+/// [`CONFIRM_TIMEOUT`].
+///
+/// Buttons keep questions answerable when the bot runs without message
+/// events, as with slash commands only.
+async fn choose(
+    state: &State,
+    msg: &Message,
+    question: &str,
+    choices: &[impl AsRef<str>],
+) -> Result<Option<usize>, Box<dyn Error + Send + Sync + 'static>> {
+    let buttons = choices
+        .iter()
+        .enumerate()
+        .map(|(index, label)| {
+            Component::Button(Button {
+                custom_id: Some(format!("choice:{}", index)),
+                disabled: false,
+                emoji: None,
+                label: Some(label.as_ref().to_string()),
+                style: ButtonStyle::Secondary,
+                url: None,
+            })
+        })
+        .collect();
+    let rows = [Component::ActionRow(ActionRow {
+        components: buttons,
+    })];
+
+    let prompt = state
+        .http
+        .create_message(msg.channel_id)
+        .content(question)?
+        .components(&rows)?
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    let (author_id, channel_id, prompt_id) = (msg.author.id, msg.channel_id, prompt.id);
+    let answer = clock::timeout(
+        &*state.clock,
+        CONFIRM_TIMEOUT,
+        state
+            .standby
+            .wait_for_event(move |event: &Event| match event {
+                Event::MessageCreate(reply) => {
+                    reply.channel_id == channel_id && reply.author.id == author_id
+                }
+                Event::InteractionCreate(interaction) => matches!(
+                    &interaction.0,
+                    Interaction::MessageComponent(press)
+                        if press.message.id == prompt_id && press.author_id() == Some(author_id)
+                ),
+                _ => false,
+            }),
+    )
+    .await;
+
+    let chosen = match answer {
+        Some(Ok(Event::MessageCreate(reply))) => choices
+            .iter()
+            .position(|choice| reply.content.trim().eq_ignore_ascii_case(choice.as_ref())),
+        Some(Ok(Event::InteractionCreate(interaction))) => match interaction.0 {
+            Interaction::MessageComponent(press) => {
+                // Acknowledged without touching the prompt.
+                state
+                    .http
+                    .interaction_callback(
+                        press.id,
+                        &press.token,
+                        &InteractionResponse::DeferredUpdateMessage,
+                    )
+                    .exec()
+                    .await?;
+
+                press
+                    .data
+                    .custom_id
+                    .strip_prefix("choice:")
+                    .and_then(|index| index.parse().ok())
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+
+    Ok(chosen)
+}
+
+/// The arguments after the command word, or failing that the author's
+/// next message in the channel after asking `question`. Also returns the
+/// message the argument came from.
+///
+/// Without message events there's no reply to wait for, so `None` means
+/// the author was asked to run the command again with the argument.
+async fn argument_or_ask(
+    state: &State,
+    msg: Message,
+    question: &str,
+) -> Result<Option<(String, Message)>, Box<dyn Error + Send + Sync + 'static>> {
+    let argument = args::split(&msg.content).1;
+
+    if !argument.is_empty() {
+        return Ok(Some((argument.to_string(), msg)));
+    }
+
+    if !state.config.message_commands {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content(&format!(
+                "{} Run the command again with it filled in.",
+                question
+            ))?
+            .exec()
+            .await?;
+
+        return Ok(None);
+    }
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(question)?
+        .exec()
+        .await?;
+
+    let author_id = msg.author.id;
+    let reply = state
+        .standby
+        .wait_for_message(msg.channel_id, move |new_msg: &MessageCreate| {
+            new_msg.author.id == author_id
+        })
+        .await?;
+
+    Ok(Some((reply.content.trim().to_string(), reply.0)))
+}
+
+const JOIN_USAGE: &str = "Usage: `{prefix}join <#channel>`, or the channel's ID";
+
+pub async fn join(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.ok_or("Can't join a non-guild channel.")?;
+
+    if let Some(quiet_hours) = active_quiet_hours(&state, guild_id).await {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content(&format!(
+                "It's quiet hours here ({}), so I won't join right now.",
+                quiet_hours
+            ))?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    let (channel_id, msg) =
+        match argument_or_ask(&state, msg, "What's the channel ID you want me to join?").await? {
+            Some(answer) => answer,
+            None => return Ok(()),
+        };
+    let channel_id = match args::channel(channel_id.trim()) {
+        Some(channel_id) => channel_id,
+        None => {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content(&state.prefixed(guild_id, JOIN_USAGE))?
+                .exec()
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    if would_fill_last_slot(&state, guild_id, channel_id).await? {
+        if !permissions::is_admin(&state, &msg).await? {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content(&format!(
+                    "Joining <#{}> would take its last free slot, so I'll stay out. \
+                     An admin can run `{prefix}join` to override.",
+                    channel_id,
+                    prefix = state.prefix(guild_id)
+                ))?
+                .exec()
+                .await?;
+
+            return Ok(());
+        }
+
+        let question = format!(
+            "Joining <#{}> would take its last free slot. Reply `yes` to join anyway.",
+            channel_id
+        );
+
+        if choose(&state, &msg, &question, YES_NO).await? != Some(0) {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content("Okay, not joining.")?
+                .exec()
+                .await?;
+
+            return Ok(());
+        }
+    }
+
+    let (call_lock, success) = state.songbird.join(guild_id, channel_id.0).await;
+    let joined = success.is_ok();
+
+    let content = match success {
+        Ok(()) => {
+            let mut call = call_lock.lock().await;
+            if let Some(bitrate) = state.profile.bitrate() {
+                call.set_bitrate(bitrate);
+            }
+            reconnect::watch(&state, guild_id, &mut call);
+            drop(call);
+
+            state
+                .text_channels
+                .write()
+                .await
+                .insert(guild_id, msg.channel_id);
+            state.sessions.start(guild_id, state.clock.instant());
+
+            format!("Joined <#{}>!", channel_id)
+        }
+        Err(e) => format!("Failed to join <#{}>! Why: {:?}", channel_id, e),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    // Picks up a queue left waiting, e.g. one restored after a restart.
+    if joined && queue::current(&state, guild_id).await.is_none() {
+        play_next(&state, guild_id).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn leave(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "leave command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    if let Some((path, length)) = recording::stop(&state, guild_id).await? {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content(&recording_saved(&path, length))?
+            .exec()
+            .await?;
+    }
+
+    state.queue.clear(guild_id);
+    state.loops.write().await.remove(&guild_id);
+    state.ambience.write().await.remove(&guild_id);
+    state.songbird.leave(guild_id).await?;
+
+    auditlog::record(
+        &state,
+        guild_id,
+        AuditEntry::action(msg.author.id, "Left the voice channel"),
+    )
+    .await;
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&crate::with_recap(&state, guild_id, "Left the channel"))?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+async fn start_track(
+    state: &State,
+    guild_id: GuildId,
+    call: &mut Call,
+    input: Input,
+    info: TrackInfo,
+) -> Result<TrackHandle, Box<dyn Error + Send + Sync + 'static>> {
+    let handle = call.play_source(input);
+    state.hooks.track_start(guild_id, &info);
+
+    if state.loops.read().await.get(&guild_id) == Some(&LoopMode::Track) {
+        handle.enable_loop()?;
+    }
+
+    handle.set_volume(playing_volume(state, guild_id).await)?;
+
+    handle.add_event(
+        songbird::Event::Track(TrackEvent::End),
+        TrackEndNotifier {
+            guild_id,
+            state: Arc::clone(state),
+        },
+    )?;
+
+    state.now_playing.write().await.insert(guild_id, info);
+    let mut store = state.trackdata.write().await;
+    store.insert(guild_id, handle.clone());
+
+    Ok(handle)
+}
+
+/// The volume set with `j/volume`, or the configured default.
+async fn guild_volume(state: &State, guild_id: GuildId) -> f32 {
+    state
+        .settings
+        .read()
+        .await
+        .get(&guild_id)
+        .and_then(|settings| settings.volume)
+        .map_or(state.config.default_volume, |percent| {
+            percent as f32 / 100.0
+        })
+}
+
+/// What the guild's tracks play at right now: its volume, turned down
+/// during quiet hours.
+async fn playing_volume(state: &State, guild_id: GuildId) -> f32 {
+    let mut volume = guild_volume(state, guild_id).await;
+    if active_quiet_hours(state, guild_id).await.is_some() {
+        volume *= QUIET_HOURS_VOLUME;
+    }
+
+    volume
+}
+
+/// Hands `track` to the guild's call, if the bot is in one, returning the
+/// handle it plays under.
+pub async fn start_queued(
+    state: &State,
+    guild_id: GuildId,
+    track: QueuedTrack,
+) -> Result<Option<TrackHandle>, Box<dyn Error + Send + Sync + 'static>> {
+    let (call_lock, mut input) = match (state.songbird.get(guild_id), track.input) {
+        (Some(call_lock), Some(input)) => (call_lock, input),
+        _ => return Ok(None),
+    };
+
+    let segment = track.info.segment;
+    if segment.is_some() {
+        // So the driver's metadata, which most commands read, names the
+        // chapter rather than the whole upload.
+        input.metadata.title = track.info.title.clone();
+        input.metadata.duration = track.info.duration;
+    }
+
+    let mut call = call_lock.lock().await;
+    let handle = start_track(state, guild_id, &mut call, input, track.info).await?;
+
+    if let Some(start) = track.start.or(segment.map(|segment| segment.start)) {
+        handle.seek_time(start)?;
+    }
+    if let Some(segment) = segment {
+        handle.add_event(
+            songbird::Event::Periodic(SEGMENT_CHECK, None),
+            SegmentEnd { end: segment.end },
+        )?;
+    }
+
+    Ok(Some(handle))
+}
+
+/// Searches for `query` and asks the author to pick one of the results
+/// by number, returning its URL. `None` means there's nothing to play
+/// and the author has been told why.
+async fn pick_search_result(
+    state: &State,
+    msg: &Message,
+    query: &str,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync + 'static>> {
+    let results = state.resolver.search(query, search::RESULTS).await?;
+
+    if results.is_empty() {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content("I couldn't find anything for that.")?
+            .exec()
+            .await?;
+
+        return Ok(None);
+    }
+
+    let mut content = "Pick a track by replying with its number:".to_string();
+    for (i, result) in results.iter().enumerate() {
+        let _ = write!(content, "\n{}. **{}**", i + 1, result.title);
+        if let Some(duration) = result.duration {
+            let _ = write!(content, " ({})", duration::format(duration));
+        }
+    }
+
+    let numbers = (1..=results.len())
+        .map(|number| number.to_string())
+        .collect::<Vec<_>>();
+    let picked = choose(state, msg, &content, &numbers)
+        .await?
+        .and_then(|index| results.get(index));
+
+    match picked {
+        Some(result) => Ok(Some(result.url.clone())),
+        None => {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content("Okay, not playing anything.")?
+                .exec()
+                .await?;
+
+            Ok(None)
+        }
+    }
+}
+
+/// The YouTube searches standing in for the tracks of the Spotify link of
+/// `kind` with `id`, and how many tracks it has in all. Replies and gives
+/// `None` when they can't be looked up.
+#[cfg(feature = "spotify")]
+async fn spotify_entries(
+    state: &State,
+    msg: &Message,
+    kind: &str,
+    id: &str,
+) -> Result<Option<(Vec<SearchResult>, usize)>, Box<dyn Error + Send + Sync + 'static>> {
+    let content = match &state.spotify {
+        // A linked account also reaches the requester's private playlists.
+        Some(spotify) => match spotify
+            .tracks(kind, id, linked_token(state, msg).await.as_deref())
+            .await
+        {
+            Ok(Some(found)) => {
+                let entries = found.tracks.iter().map(SpotifyTrack::entry).collect();
+                return Ok(Some((entries, found.total)));
+            }
+            Ok(None) => "I can only play Spotify tracks, albums and playlists.",
+            Err(why) => {
+                state.hooks.error(msg.guild_id, &*why);
+                "I couldn't look that up on Spotify."
+            }
+        },
+        None => "I can't play Spotify links: no Spotify app is set up.",
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(content)?
+        .exec()
+        .await?;
+
+    Ok(None)
+}
+
+/// The access token of the account `msg`'s author linked, if they did and
+/// it still works.
+#[cfg(feature = "spotify")]
+async fn linked_token(state: &State, msg: &Message) -> Option<String> {
+    match spotifylinks::access_token(state, msg.author.id).await {
+        Ok(token) => token,
+        Err(why) => {
+            state.hooks.error(msg.guild_id, &*why);
+            None
+        }
+    }
+}
+
+#[cfg(feature = "spotify")]
+const SPOTIFY_USAGE: &str = "Usage: `{prefix}spotify link` to link your Spotify account, \
+     `{prefix}spotify liked` to queue your Liked Songs, `{prefix}spotify playlists` to list \
+     your playlists, or `{prefix}spotify unlink` to forget your account. Linked accounts' \
+     private playlists play with `{prefix}play <link>`.";
+
+/// Links the author's Spotify account through a one-time URL sent by DM,
+/// and queues from it once it's linked.
+#[cfg(feature = "spotify")]
+pub async fn spotify(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "spotify command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+    let command = Args::parse(&msg.content).get(0).map(str::to_string);
+
+    let content = match (&state.spotify, command.as_deref()) {
+        (_, None) => state.prefixed(guild_id, SPOTIFY_USAGE),
+        (None, Some(_)) => "I can't reach Spotify: no Spotify app is set up.".to_string(),
+        // The callback that finishes linking is served with the overlay.
+        (Some(_), Some("link"))
+            if !cfg!(feature = "overlay") || !state.spotify_links.is_enabled() =>
+        {
+            "Linking Spotify accounts isn't set up on this bot.".to_string()
+        }
+        (Some(spotify), Some("link")) => send_spotify_link(&state, &msg, spotify).await?,
+        (Some(_), Some("unlink")) => {
+            if state.spotify_links.unlink(msg.author.id)? {
+                "I've forgotten your Spotify account.".to_string()
+            } else {
+                "You haven't linked a Spotify account.".to_string()
+            }
+        }
+        (Some(spotify), Some("liked")) => return queue_liked(&state, &msg, spotify).await,
+        (Some(spotify), Some("playlists")) => match linked_token(&state, &msg).await {
+            Some(token) => {
+                let (playlists, total) = spotify.playlists(&token).await?;
+
+                if playlists.is_empty() {
+                    "You don't have any Spotify playlists.".to_string()
+                } else {
+                    let mut content = "Your Spotify playlists:".to_string();
+                    for (i, playlist) in playlists.iter().enumerate() {
+                        let _ = write!(
+                            content,
+                            "\n{}. **{}** <https://open.spotify.com/playlist/{}>",
+                            i + 1,
+                            playlist.name,
+                            playlist.id
+                        );
+                    }
+                    if total > playlists.len() {
+                        let _ = write!(content, "\n…and {} more.", total - playlists.len());
+                    }
+                    let _ = write!(
+                        content,
+                        "\nQueue one with `{prefix}play <link>`.",
+                        prefix = state.prefix(guild_id)
+                    );
+                    content
+                }
+            }
+            None => not_linked(&state, guild_id),
+        },
+        (Some(_), Some(_)) => state.prefixed(guild_id, SPOTIFY_USAGE),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "spotify"))]
+pub async fn spotify(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content("I can't reach Spotify: this build leaves it out.")?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// DMs `msg`'s author a one-time URL to link their account with, so it
+/// isn't posted where someone else could use it first. Returns what to
+/// say in the channel.
+#[cfg(feature = "spotify")]
+async fn send_spotify_link(
+    state: &State,
+    msg: &Message,
+    spotify: &Spotify,
+) -> Result<String, Box<dyn Error + Send + Sync + 'static>> {
+    let oauth_state = state
+        .spotify_links
+        .begin(msg.author.id, state.clock.instant());
+    let url = spotify
+        .authorize_url(&oauth_state)
+        .ok_or("spotify has no redirect_uri")?;
+    let content = format!(
+        "Open this within {} to let me read your Spotify playlists and Liked Songs. \
+         It only works once:\n<{}>",
+        duration::humanize(LINK_TIMEOUT),
+        url
+    );
+
+    let channel = state
+        .http
+        .create_private_channel(msg.author.id)
+        .exec()
+        .await?
+        .model()
+        .await?;
+    let sent = state
+        .http
+        .create_message(channel.id)
+        .content(&content)?
+        .exec()
+        .await;
+
+    Ok(match sent {
+        Ok(_) => "I've sent you a link in your DMs.".to_string(),
+        Err(_) => "I couldn't DM you. Let members of this server message you, \
+                   then try again."
+            .to_string(),
+    })
+}
+
+/// Queues the author's Liked Songs, newest first.
+#[cfg(feature = "spotify")]
+async fn queue_liked(
+    state: &State,
+    msg: &Message,
+    spotify: &Spotify,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.unwrap();
+
+    if !crate::in_session(state, guild_id).await {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content(&state.prefixed(
+                guild_id,
+                "I'm not in a voice channel. Use `{prefix}join` to bring me in first.",
+            ))?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    if locked_out(state, msg).await? || !within_quota(state, guild_id, msg.channel_id).await? {
+        return Ok(());
+    }
+
+    let token = match linked_token(state, msg).await {
+        Some(token) => token,
+        None => {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content(&not_linked(state, guild_id))?
+                .exec()
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let liked = spotify.liked(&token).await?;
+    let entries = liked.tracks.iter().map(SpotifyTrack::entry).collect();
+
+    queue_entries(state, msg, entries, liked.total, "Liked Songs playlist").await
+}
+
+#[cfg(feature = "spotify")]
+fn not_linked(state: &State, guild_id: GuildId) -> String {
+    state.prefixed(
+        guild_id,
+        "You haven't linked a Spotify account. Use `{prefix}spotify link` to link one.",
+    )
+}
+
+#[cfg(not(feature = "spotify"))]
+async fn spotify_entries(
+    state: &State,
+    msg: &Message,
+    _kind: &str,
+    _id: &str,
+) -> Result<Option<(Vec<SearchResult>, usize)>, Box<dyn Error + Send + Sync + 'static>> {
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content("I can't play Spotify links: this build leaves them out.")?
+        .exec()
+        .await?;
+
+    Ok(None)
+}
+
+/// Where to play an attached audio file from, or `None` after saying why
+/// it can't be. Discord serves attachments by URL, so they play like any
+/// other link to a file.
+async fn attachment_url(
+    state: &State,
+    channel_id: ChannelId,
+    attachment: &Attachment,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync + 'static>> {
+    let extension = attachment
+        .filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase());
+    let is_audio = attachment
+        .content_type
+        .as_deref()
+        .is_none_or(|content_type| {
+            content_type.starts_with("audio/") || content_type.ends_with("/ogg")
+        });
+
+    let content = if !is_audio
+        || !extension.is_some_and(|extension| ATTACHMENT_EXTENSIONS.contains(&extension.as_str()))
+    {
+        format!(
+            "I can only play attached audio files: {}.",
+            ATTACHMENT_EXTENSIONS.join(", ")
+        )
+    } else if attachment.size > MAX_ATTACHMENT_BYTES {
+        format!(
+            "That file is over {} MB, which is more than I'll play.",
+            MAX_ATTACHMENT_BYTES / (1024 * 1024)
+        )
+    } else {
+        return Ok(Some(attachment.url.clone()));
+    };
+
+    state
+        .http
+        .create_message(channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(None)
+}
+
+/// How long ago the track at `url` finished, if that's within the guild's
+/// no-repeats window and the author can't override it.
+async fn recent_repeat(
+    state: &State,
+    msg: &Message,
+    url: &str,
+) -> Result<Option<(NoRepeats, Duration)>, Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.unwrap();
+
+    let no_repeats = match state
+        .settings
+        .read()
+        .await
+        .get(&guild_id)
+        .and_then(|settings| settings.no_repeats)
+    {
+        Some(no_repeats) => no_repeats,
+        None => return Ok(None),
+    };
+    let finished = match state.history.last_played(guild_id, url) {
+        Some(finished) => finished,
+        None => return Ok(None),
+    };
+
+    let ago = state.clock.now() - finished;
+    if ago >= no_repeats.window()
+        || (no_repeats.dj_override && permissions::is_dj(state, msg).await?)
+    {
+        return Ok(None);
+    }
+
+    Ok(Some((no_repeats, ago.to_std().unwrap_or_default())))
+}
+
+/// Why [`play`] turned a track away as a [`recent_repeat`].
+fn repeat_refusal(title: &str, no_repeats: NoRepeats, ago: Duration) -> String {
+    let mut content = format!(
+        "**{}** finished {} ago, and this server doesn't repeat tracks within {}.",
+        title,
+        duration::humanize(ago),
+        duration::humanize(no_repeats.window().to_std().unwrap_or_default())
+    );
+    if no_repeats.dj_override {
+        content.push_str(" A DJ can queue it anyway.");
+    }
+
+    content
+}
+
+/// Tells `channel_id` how long `count` lookups like `query` will wait for
+/// their provider's rate limit, if it's long enough to look stuck.
+async fn warn_of_wait(
+    state: &State,
+    channel_id: ChannelId,
+    query: &str,
+    count: usize,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let provider = Provider::of(query);
+    let wait = state
+        .provider_limits
+        .expected_wait(provider, count, state.clock.instant());
+
+    if wait < NOTICEABLE_WAIT {
+        return Ok(());
+    }
+
+    let content = format!(
+        "{} is busy, so this will take about {}.",
+        provider.name(),
+        duration::humanize(wait)
+    );
+    state
+        .http
+        .create_message(channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Queues the playlist at `url` (see [`queue_entries`]).
+async fn queue_playlist(
+    state: &State,
+    msg: &Message,
+    url: &str,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let entries = state.resolver.playlist(url).await?;
+    let total = entries.len();
+
+    queue_entries(state, msg, entries, total, "playlist").await
+}
+
+/// Resolves each of `entries` and queues it, up to
+/// [`playlist::MAX_TRACKS`] of the `total` the `list` they came from has.
+/// Entries that fail to resolve or are over the length limit are left
+/// out. Playback starts with the first entry if nothing was playing.
+async fn queue_entries(
+    state: &State,
+    msg: &Message,
+    mut entries: Vec<SearchResult>,
+    total: usize,
+    list: &str,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.unwrap();
+
+    entries.truncate(playlist::MAX_TRACKS);
+    let cut_off = total.saturating_sub(entries.len());
+
+    let idle = queue::current(state, guild_id).await.is_none() && state.queue.is_empty(guild_id);
+    if idle {
+        state
+            .queue_channels
+            .write()
+            .await
+            .insert(guild_id, msg.channel_id);
+    }
+
+    // Only what will play soon is resolved now; the rest waits for
+    // `queue::warm`, so a long playlist doesn't hold the command up.
+    let up_front = queue::WARM_AHEAD + usize::from(idle);
+
+    if let Some(first) = entries.first() {
+        warn_of_wait(
+            state,
+            msg.channel_id,
+            &first.url,
+            entries.len().min(up_front),
+        )
+        .await?;
+    }
+
+    let mut added = 0;
+    let mut skipped = 0;
+    let mut repeats = 0;
+
+    for entry in entries {
+        let track = if added < up_front {
+            let input = match state.resolver.resolve(&entry.url).await {
+                Ok(input) if state.quotas.check_length(input.metadata.duration).is_ok() => input,
+                Ok(_) => {
+                    skipped += 1;
+                    continue;
+                }
+                Err(why) => {
+                    state.hooks.error(Some(guild_id), &*why);
+                    skipped += 1;
+                    continue;
+                }
+            };
+
+            // Searches stand in for some entries, so where they were found
+            // is what gets played again.
+            let url = input.metadata.source_url.clone().unwrap_or(entry.url);
+            if recent_repeat(state, msg, &url).await?.is_some() {
+                repeats += 1;
+                continue;
+            }
+            let mut track = QueuedTrack::new(input, &url, msg.author.id);
+            track.info.title.get_or_insert(entry.title);
+            track
+        } else if recent_repeat(state, msg, &entry.url).await?.is_some() {
+            repeats += 1;
+            continue;
+        } else {
+            QueuedTrack::pending(TrackInfo {
+                title: Some(entry.title),
+                duration: entry.duration,
+                source_url: Some(entry.url),
+                requester: Some(msg.author.id),
+                ..Default::default()
+            })
+        };
+
+        let today = state.clock.now().date().naive_utc();
+        state.quotas.record_track(guild_id, today);
+        state.sessions.record_track(guild_id, msg.author.id);
+        state.hooks.enqueue(guild_id, &track.info);
+
+        state.queue.push(guild_id, track);
+        added += 1;
+
+        // Get the music going while the rest resolve.
+        if idle && added == 1 {
+            play_next(state, guild_id).await?;
+        }
+    }
+
+    queue::warm(state, guild_id);
+
+    let mut content = match added {
+        0 => format!("I couldn't queue anything from that {}.", list),
+        1 => format!("Added 1 track from the {} to the queue.", list),
+        n => format!("Added {} tracks from the {} to the queue.", n, list),
+    };
+    if skipped > 0 {
+        let _ = write!(content, " {} couldn't be played.", skipped);
+    }
+    if repeats > 0 {
+        let _ = write!(content, " {} played too recently to repeat.", repeats);
+    }
+    if cut_off > 0 {
+        let _ = write!(
+            content,
+            " Only the first {} were taken, so {} were left out.",
+            playlist::MAX_TRACKS,
+            cut_off
+        );
+    }
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const CHAPTERS_USAGE: &str =
+    "Usage: `{prefix}chapters <url>` to queue each chapter of a long upload as its own track";
+
+/// Queues each chapter of the upload at the URL given as its own track,
+/// seeking to where it starts and stopping where it ends, so an album
+/// upload skips and shuffles like an album.
+pub async fn chapters(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "chapters command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let url = match Args::parse(&msg.content).get(0) {
+        Some(url) if search::is_url(url) => url.to_string(),
+        _ => {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content(&state.prefixed(guild_id, CHAPTERS_USAGE))?
+                .exec()
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    if !crate::in_session(&state, guild_id).await {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content(&state.prefixed(
+                guild_id,
+                "I'm not in a voice channel. Use `{prefix}join` to bring me in first.",
+            ))?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    if locked_out(&state, &msg).await? || !within_quota(&state, guild_id, msg.channel_id).await? {
+        return Ok(());
+    }
+
+    warn_of_wait(&state, msg.channel_id, &url, 1).await?;
+
+    let mut chapters = state.resolver.chapters(&url).await?;
+    if chapters.is_empty() {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content("That doesn't have any chapters to split it into.")?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+    let cut_off = chapters.len().saturating_sub(playlist::MAX_TRACKS);
+    chapters.truncate(playlist::MAX_TRACKS);
+
+    // Every chapter downloads the whole upload, so it's the upload's
+    // length that counts.
+    let input = state.resolver.resolve(&url).await?;
+    if let Err(too_long) = state.quotas.check_length(input.metadata.duration) {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content(&too_long.to_string())?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    let upload = QueuedTrack::new(input, &url, msg.author.id);
+    let title = upload.title().to_string();
+    let mut input = upload.input;
+
+    let idle = queue::current(&state, guild_id).await.is_none() && state.queue.is_empty(guild_id);
+    if idle {
+        state
+            .queue_channels
+            .write()
+            .await
+            .insert(guild_id, msg.channel_id);
+    }
+
+    let count = chapters.len();
+    for chapter in chapters {
+        let info = TrackInfo {
+            title: Some(chapter.title),
+            duration: Some(chapter.end.saturating_sub(chapter.start)),
+            segment: Some(Segment {
+                start: chapter.start,
+                end: chapter.end,
+            }),
+            live: false,
+            ..upload.info.clone()
+        };
+        // The first chapter plays the input already resolved; the rest
+        // resolve as they near the front.
+        let track = match input.take() {
+            Some(input) => QueuedTrack::resolved(input, info),
+            None => QueuedTrack::pending(info),
+        };
+
+        let today = state.clock.now().date().naive_utc();
+        state.quotas.record_track(guild_id, today);
+        state.sessions.record_track(guild_id, msg.author.id);
+        state.hooks.enqueue(guild_id, &track.info);
+        state.queue.push(guild_id, track);
+    }
+
+    if idle {
+        play_next(&state, guild_id).await?;
+    }
+    queue::warm(&state, guild_id);
+
+    let mut content = format!(
+        "Added the {} chapter{} of **{}** to the queue.",
+        count,
+        if count == 1 { "" } else { "s" },
+        title
+    );
+    if cut_off > 0 {
+        let _ = write!(
+            content,
+            " Only the first {} were taken, so {} were left out.",
+            playlist::MAX_TRACKS,
+            cut_off
+        );
+    }
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Points out that the upload at `url`, long enough to be an album, could
+/// be queued a chapter at a time, if it has chapters. Looking them up
+/// takes a moment, so it's done in the background.
+fn offer_chapters(state: &State, guild_id: GuildId, channel_id: ChannelId, url: String) {
+    let state = Arc::clone(state);
+
+    tokio::spawn(async move {
+        if let Err(why) = send_chapter_offer(&state, guild_id, channel_id, &url).await {
+            state.hooks.error(Some(guild_id), &*why);
+        }
+    });
+}
+
+async fn send_chapter_offer(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    url: &str,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let chapters = state.resolver.chapters(url).await?;
+    if chapters.is_empty() {
+        return Ok(());
+    }
+
+    state
+        .http
+        .create_message(channel_id)
+        .content(&format!(
+            "That has {} chapters. Use `{prefix}chapters {}` to queue them as \
+             separate tracks instead.",
+            chapters.len(),
+            url,
+            prefix = state.prefix(guild_id)
+        ))?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Moves on from `finished`, the guild's current track, once it has ended
+/// or been skipped: back to the end of the queue first if the queue loops,
+/// then the next track starts, or the hooks hear that the queue is empty.
+pub async fn advance(
+    state: &State,
+    guild_id: GuildId,
+    finished: &TrackHandle,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let info = state
+        .now_playing
+        .read()
+        .await
+        .get(&guild_id)
+        .cloned()
+        .unwrap_or_else(|| TrackInfo::of(finished));
+
+    if let (Some(LoopMode::Queue), Some(url)) =
+        (state.loops.read().await.get(&guild_id), &info.source_url)
+    {
+        // The finished input is used up, so the track is resolved afresh
+        // when it nears the front again.
+        state.queue.push(
+            guild_id,
+            QueuedTrack::pending(TrackInfo {
+                source_url: Some(url.clone()),
+                requester: info.requester.or(Some(state.user_id)),
+                ..info.clone()
+            }),
+        );
+    }
+
+    if !play_next(state, guild_id).await? {
+        state.hooks.queue_empty(guild_id);
+    }
+
+    Ok(())
+}
+
+/// Announces the guild's current track in the channel its queue was
+/// started from, after the track before it ended. Only guilds with full
+/// announcements get one; a minimal announcement reacts to the request,
+/// which queued tracks are long past.
+pub async fn announce_next(
+    state: &State,
+    guild_id: GuildId,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let settings = state
+        .settings
+        .read()
+        .await
+        .get(&guild_id)
+        .cloned()
+        .unwrap_or_default();
+    if settings.announcements != Announcements::Full {
+        return Ok(());
+    }
+
+    // The queue may have run out instead.
+    if queue::current(state, guild_id).await.is_none() {
+        return Ok(());
+    }
+
+    let channel_id = match state.queue_channels.read().await.get(&guild_id) {
+        Some(channel_id) => *channel_id,
+        None => return Ok(()),
+    };
+    let info = match state.now_playing.read().await.get(&guild_id) {
+        Some(info) => info.clone(),
+        None => return Ok(()),
+    };
+
+    announce_track(state, guild_id, channel_id, &info, &settings).await
+}
+
+/// Posts the guild's now playing message for `info` in `channel_id`, with
+/// reactions to rate the track by.
+async fn announce_track(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    info: &TrackInfo,
+    settings: &GuildSettings,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let content = settings.now_playing_template().render(&[
+        ("title", info.title()),
+        ("artist", info.artist()),
+        ("url", info.source_url()),
+    ]);
+
+    let announcement = state
+        .http
+        .create_message(channel_id)
+        .content(&content)?
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    // Votes still count until the next restart if this can't be saved.
+    if let Err(why) =
+        state
+            .ratings
+            .track_message(announcement.id, guild_id, info.source_url(), info.title())
+    {
+        state.hooks.error(Some(guild_id), &*why);
+    }
+
+    for vote in &[ratings::UPVOTE, ratings::DOWNVOTE] {
+        state
+            .http
+            .create_reaction(
+                channel_id,
+                announcement.id,
+                &RequestReactionType::Unicode { name: vote },
+            )
+            .exec()
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Starts the next queued track, if there is one, resolving it first if
+/// [`queue::warm`] hasn't got to it. Tracks that no longer resolve or are
+/// over the length limit are passed over. Returns whether there was one.
+pub async fn play_next(
+    state: &State,
+    guild_id: GuildId,
+) -> Result<bool, Box<dyn Error + Send + Sync + 'static>> {
+    while let Some(mut track) = state.queue.pop(guild_id) {
+        if track.input.is_none() {
+            match state.resolver.resolve(track.url()).await {
+                Ok(input) if state.quotas.check_length(input.metadata.duration).is_ok() => {
+                    track.fill(input)
+                }
+                Ok(_) => continue,
+                Err(why) => {
+                    state.hooks.error(Some(guild_id), &*why);
+                    continue;
+                }
+            }
+        }
+
+        start_queued(state, guild_id, track).await?;
+        queue::warm(state, guild_id);
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+pub async fn play(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "play command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    // Checked before anything is counted or announced, since a track
+    // started outside a call is dropped.
+    if !crate::in_session(&state, msg.guild_id.unwrap()).await {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content(&state.prefixed(
+                msg.guild_id.unwrap(),
+                "I'm not in a voice channel. Use `{prefix}join` to bring me in first.",
+            ))?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    if locked_out(&state, &msg).await?
+        || !within_quota(&state, msg.guild_id.unwrap(), msg.channel_id).await?
+    {
+        return Ok(());
+    }
+
+    let (mut query, msg) = match msg.attachments.first() {
+        // An attached file is played in place of a URL.
+        Some(attachment) => match attachment_url(&state, msg.channel_id, attachment).await? {
+            Some(url) => (url, msg),
+            None => return Ok(()),
+        },
+        None => match argument_or_ask(&state, msg, "What's the URL of the audio to play?").await? {
+            Some(answer) => answer,
+            None => return Ok(()),
+        },
+    };
+
+    let guild_id = msg.guild_id.unwrap();
+
+    if let Some(Source::Spotify { kind, id }) =
+        sources::identify(&query).map(|identified| identified.source)
+    {
+        let (mut entries, total) = match spotify_entries(&state, &msg, &kind, &id).await? {
+            Some(found) => found,
+            None => return Ok(()),
+        };
+
+        if kind != "track" || entries.is_empty() {
+            return queue_entries(&state, &msg, entries, total, &kind).await;
+        }
+        query = entries.remove(0).url;
+    } else if !search::is_url(&query) {
+        query = match pick_search_result(&state, &msg, &query).await? {
+            Some(url) => url,
+            None => return Ok(()),
+        };
+    }
+
+    if let Some(video) = sources::mix_video(&query) {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content("That's a YouTube mix, so I'll just play the video it starts with.")?
+            .exec()
+            .await?;
+
+        query = video;
+    }
+
+    if playlist::is_playlist(&query) {
+        return queue_playlist(&state, &msg, &query).await;
+    }
+
+    warn_of_wait(&state, msg.channel_id, &query, 1).await?;
+
+    match state.resolver.resolve(&query).await {
+        Ok(input) => {
+            // Sources are lazy, so nothing has been downloaded yet.
+            if let Err(too_long) = state.quotas.check_length(input.metadata.duration) {
+                state
+                    .http
+                    .create_message(msg.channel_id)
+                    .content(&too_long.to_string())?
+                    .exec()
+                    .await?;
+
+                return Ok(());
+            }
+
+            let url = input
+                .metadata
+                .source_url
+                .clone()
+                .unwrap_or_else(|| query.clone());
+            let mut track = QueuedTrack::new(input, &url, msg.author.id);
+            track.start = sources::start_time(&query);
+            let title = track.title().to_string();
+
+            if let Some((no_repeats, ago)) = recent_repeat(&state, &msg, &url).await? {
+                state
+                    .http
+                    .create_message(msg.channel_id)
+                    .content(&repeat_refusal(&title, no_repeats, ago))?
+                    .exec()
+                    .await?;
+
+                return Ok(());
+            }
+
+            let today = state.clock.now().date().naive_utc();
+            state.quotas.record_track(guild_id, today);
+            state.sessions.record_track(guild_id, msg.author.id);
+            state.hooks.enqueue(guild_id, &track.info);
+
+            // A start time means someone already picked their place in it.
+            if track.start.is_none()
+                && track
+                    .info
+                    .duration
+                    .is_some_and(|duration| duration >= CHAPTER_OFFER_LENGTH)
+            {
+                offer_chapters(&state, guild_id, msg.channel_id, url.clone());
+            }
+
+            if queue::current(&state, guild_id).await.is_some() || !state.queue.is_empty(guild_id) {
+                let position = state.queue.push(guild_id, track);
+                let content = format!("Added **{}** to the queue (#{}).", title, position);
+
+                state
+                    .http
+                    .create_message(msg.channel_id)
+                    .content(&content)?
+                    .exec()
+                    .await?;
+
+                return Ok(());
+            }
+
+            let settings = state
+                .settings
+                .read()
+                .await
+                .get(&guild_id)
+                .cloned()
+                .unwrap_or_default();
+
+            state
+                .queue_channels
+                .write()
+                .await
+                .insert(guild_id, msg.channel_id);
+
+            match settings.announcements {
+                Announcements::Full => {
+                    announce_track(&state, guild_id, msg.channel_id, &track.info, &settings)
+                        .await?;
+                }
+                // Votes go on the request itself, next to the 🎶.
+                Announcements::Minimal => {
+                    if let Err(why) = state.ratings.track_message(msg.id, guild_id, &url, &title) {
+                        state.hooks.error(Some(guild_id), &*why);
+                    }
+
+                    state
+                        .http
+                        .create_reaction(msg.channel_id, msg.id, &NOW_PLAYING_REACTION)
+                        .exec()
+                        .await?;
+                }
+                Announcements::Off => {}
+            }
+
+            start_queued(&state, guild_id, track).await?;
+        }
+        Err(e) => {
+            state.hooks.error(Some(guild_id), &*e);
+
+            let now = state.clock.instant();
+            if let Some(offence) = state.abuse.failure(guild_id, msg.author.id, now) {
+                crate::report_abuse(&state, guild_id, msg.author.id, offence);
+            }
+
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content(&format!("error {}", e))?
+                .exec()
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Clears the queue and stops everything in the call, keeping the
+/// current track's position for `j/resume`.
+pub async fn stop_playback(state: &State, guild_id: GuildId) {
+    // Cleared before the track stops so it doesn't advance to the next.
+    state.queue.clear(guild_id);
+    let handle = state.trackdata.write().await.remove(&guild_id);
+
+    if let Some(handle) = handle {
+        if let Some(snapshot) = PlaybackSnapshot::capture(state, guild_id, &handle).await {
+            state.stopped.write().await.insert(
+                guild_id,
+                StoppedTrack {
+                    snapshot,
+                    stopped_at: state.clock.instant(),
+                },
+            );
+        }
+    }
+
+    // Stopping the call ends every track, the ambience included.
+    state.ambience.write().await.remove(&guild_id);
+
+    if let Some(call_lock) = state.songbird.get(guild_id) {
+        let mut call = call_lock.lock().await;
+        call.stop();
+    }
+}
+
+pub async fn stop(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "stop command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    let guild_id = msg.guild_id.unwrap();
+
+    stop_playback(&state, guild_id).await;
+
+    auditlog::record(
+        &state,
+        guild_id,
+        AuditEntry::action(msg.author.id, "Stopped the track"),
+    )
+    .await;
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&format!(
+            "Stopped the track. Use `{prefix}resume` within {} to pick it back up.",
+            duration::humanize(RESUME_GRACE),
+            prefix = state.prefix(guild_id)
+        ))?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Stops the current track and starts the next queued one, returning the
+/// skipped track if anything was playing.
+pub async fn skip_current(
+    state: &State,
+    guild_id: GuildId,
+) -> Result<Option<TrackHandle>, Box<dyn Error + Send + Sync + 'static>> {
+    let handle = match queue::current(state, guild_id).await {
+        Some(handle) => handle,
+        None => return Ok(None),
+    };
+
+    // Out of `trackdata` first so the track's end doesn't advance the queue
+    // a second time.
+    state.trackdata.write().await.remove(&guild_id);
+    handle.stop()?;
+    advance(state, guild_id, &handle).await?;
+
+    Ok(Some(handle))
+}
+
+pub async fn skip(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "skip command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let last = state.queue.is_empty(guild_id);
+
+    let content = match skip_current(&state, guild_id).await? {
+        Some(handle) => {
+            auditlog::record(
+                &state,
+                guild_id,
+                AuditEntry::action(msg.author.id, "Skipped the track"),
+            )
+            .await;
+
+            let title = track_title(&handle);
+            if last {
+                format!("Skipped **{}**. That was the last track.", title)
+            } else {
+                format!("Skipped **{}**.", title)
+            }
+        }
+        None => "Nothing's playing, so there's nothing to skip.".to_string(),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const HANDOFF_USAGE: &str =
+    "Usage: `{prefix}handoff <server ID>` to take your queued tracks with you \
+    to a voice channel you're in on another server";
+
+pub async fn handoff(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "handoff command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let target = match Args::parse(&msg.content).get(0).map(str::parse::<u64>) {
+        Some(Ok(target)) if target != guild_id.0 => GuildId(target),
+        _ => {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content(&state.prefixed(guild_id, HANDOFF_USAGE))?
+                .exec()
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    // Voice states are only tracked in servers the bot is in, so this also
+    // checks that the bot can follow.
+    let channel_id = state
+        .voice_states
+        .read()
+        .await
+        .get(&(target, msg.author.id))
+        .copied();
+
+    let channel_id = match channel_id {
+        None => Err("Join a voice channel on that server first, then hand off."),
+        Some(_) if event::is_locked(&state, target).await => {
+            Err("Requests are locked for a listening event over there.")
+        }
+        Some(channel_id) => Ok(channel_id),
+    };
+
+    let content = match channel_id {
+        Err(refusal) => refusal.to_string(),
+        Ok(channel_id) => {
+            let tracks = state.queue.take_requested_by(guild_id, msg.author.id);
+            let requester = state
+                .now_playing
+                .read()
+                .await
+                .get(&guild_id)
+                .and_then(|info| info.requester);
+            let current = match queue::current(&state, guild_id).await {
+                Some(handle) if requester == Some(msg.author.id) => Some(handle),
+                _ => None,
+            };
+
+            if tracks.is_empty() && current.is_none() {
+                "You don't have any tracks playing or queued to hand off.".to_string()
+            } else {
+                match hand_off(&state, &msg, target, channel_id, current, tracks).await {
+                    Ok(moved) => {
+                        auditlog::record(
+                            &state,
+                            guild_id,
+                            AuditEntry::action(
+                                msg.author.id,
+                                &format!("Handed off {} tracks to another server", moved),
+                            ),
+                        )
+                        .await;
+
+                        format!(
+                            "Took {} of your track{} over to <#{}>.",
+                            moved,
+                            if moved == 1 { "" } else { "s" },
+                            channel_id
+                        )
+                    }
+                    Err((tracks, why)) => {
+                        // Nothing moved, so they go back to the front, where
+                        // the next of them was.
+                        state.queue.push_front(guild_id, tracks);
+
+                        format!("I couldn't join you there: {:?}", why)
+                    }
+                }
+            }
+        }
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Joins `channel_id` in `target` unless the bot is already in a call
+/// there, then queues `tracks` and starts them if nothing is playing.
+/// Hands the tracks back if joining failed.
+///
+/// `current`, the requester's track playing here, carries on over there
+/// from where it was if nothing is playing there, and this server moves
+/// on to its next track.
+///
+/// Until `target` has text channels of its own, what the bot announces
+/// about playback there goes to the channel `msg` came from, which the
+/// requester can see.
+async fn hand_off(
+    state: &State,
+    msg: &Message,
+    target: GuildId,
+    channel_id: ChannelId,
+    current: Option<TrackHandle>,
+    tracks: Vec<QueuedTrack>,
+) -> Result<usize, (Vec<QueuedTrack>, songbird::error::JoinError)> {
+    let in_call = match state.songbird.get(target) {
+        Some(call_lock) => call_lock.lock().await.current_channel().is_some(),
+        None => false,
+    };
+
+    if !in_call {
+        let (call_lock, joined) = state.songbird.join(target, channel_id.0).await;
+
+        if let Err(why) = joined {
+            return Err((tracks, why));
+        }
+
+        let mut call = call_lock.lock().await;
+        if let Some(bitrate) = state.profile.bitrate() {
+            call.set_bitrate(bitrate);
+        }
+        reconnect::watch(state, target, &mut call);
+        drop(call);
+        state.sessions.start(target, state.clock.instant());
+    }
+
+    for channels in [&state.text_channels, &state.queue_channels] {
+        channels
+            .write()
+            .await
+            .entry(target)
+            .or_insert(msg.channel_id);
+    }
+
+    let mut moved = tracks.len();
+    for track in tracks {
+        state.queue.push(target, track);
+    }
+
+    let source = msg.guild_id.unwrap();
+    let idle = queue::current(state, target).await.is_none();
+    let snapshot = match current {
+        Some(handle) if idle => PlaybackSnapshot::capture(state, source, &handle).await,
+        _ => None,
+    };
+
+    if let Some(snapshot) = snapshot {
+        match snapshot.restore(state, target).await {
+            Ok(_) => {
+                moved += 1;
+                if let Err(why) = skip_current(state, source).await {
+                    state.hooks.error(Some(source), &*why);
+                }
+            }
+            Err(why) => {
+                state.hooks.error(Some(target), &*why);
+                if let Err(why) = play_next(state, target).await {
+                    state.hooks.error(Some(target), &*why);
+                }
+            }
+        }
+    } else if idle {
+        if let Err(why) = play_next(state, target).await {
+            state.hooks.error(Some(target), &*why);
+        }
+    } else {
+        queue::warm(state, target);
+    }
+
+    let content = format!(
+        "<@{}> brought {} track{} over from another server.",
+        msg.author.id,
+        moved,
+        if moved == 1 { "" } else { "s" }
+    );
+    if let Err(why) = crate::announce(state, target, &content).await {
+        state.hooks.error(Some(target), &*why);
+    }
+
+    Ok(moved)
+}
+
+const QUEUE_USAGE: &str = "Usage: `{prefix}queue [page]`";
+
+pub async fn queue(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "queue command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let mut page = match args::split(&msg.content).1 {
+        "" => 1,
+        page => match page.parse::<usize>() {
+            Ok(page) if page > 0 => page,
+            _ => {
+                state
+                    .http
+                    .create_message(msg.channel_id)
+                    .content(&state.prefixed(guild_id, QUEUE_USAGE))?
+                    .exec()
+                    .await?;
+
+                return Ok(());
+            }
+        },
+    };
+
+    let pages = queue::page_count(state.queue.list(guild_id).len());
+    if page > pages {
+        let content = format!(
+            "The queue only has {} page{}.",
+            pages,
+            if pages == 1 { "" } else { "s" }
+        );
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content(&content)?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    let (content, pages) = queue_page(&state, guild_id, page).await;
+    let rows = page_buttons(page, pages);
+
+    // Requesters are named without pinging them every time someone looks.
+    let listing = state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .allowed_mentions(AllowedMentions::default())
+        .components(&rows)?
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    // Anyone can page through the listing until it's left alone a while.
+    let listing_id = listing.id;
+    while let Some(Ok(Event::InteractionCreate(interaction))) = clock::timeout(
+        &*state.clock,
+        PAGE_TIMEOUT,
+        state
+            .standby
+            .wait_for_event(move |event: &Event| match event {
+                Event::InteractionCreate(interaction) => matches!(
+                    &interaction.0,
+                    Interaction::MessageComponent(press) if press.message.id == listing_id
+                ),
+                _ => false,
+            }),
+    )
+    .await
+    {
+        let press = match interaction.0 {
+            Interaction::MessageComponent(press) => press,
+            _ => continue,
+        };
+
+        if let Some(pressed) = press
+            .data
+            .custom_id
+            .strip_prefix("queue:")
+            .and_then(|page| page.parse().ok())
+        {
+            page = pressed;
+        }
+
+        // The queue may have moved on since the last page was shown.
+        let (content, pages) = queue_page(&state, guild_id, page).await;
+        page = page.min(pages);
+
+        let response = InteractionResponse::UpdateMessage(CallbackData {
+            allowed_mentions: Some(AllowedMentions::default()),
+            components: Some(page_buttons(page, pages)),
+            content: Some(content),
+            embeds: Vec::new(),
+            flags: None,
+            tts: None,
+        });
+        state
+            .http
+            .interaction_callback(press.id, &press.token, &response)
+            .exec()
+            .await?;
+    }
+
+    state
+        .http
+        .update_message(msg.channel_id, listing_id)
+        .components(Some(&[]))?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// The guild's current track and page `page` of its queue, or the last
+/// page if the queue has shrunk since, with how many pages there are.
+async fn queue_page(state: &State, guild_id: GuildId, page: usize) -> (String, usize) {
+    let mut content = match queue::current(state, guild_id).await {
+        Some(handle) => {
+            let requester = state
+                .now_playing
+                .read()
+                .await
+                .get(&guild_id)
+                .and_then(|current| current.requester);
+
+            match requester {
+                Some(requester) => format!(
+                    "Now playing: **{}** for <@{}>\n",
+                    track_title(&handle),
+                    requester
+                ),
+                None => format!("Now playing: **{}**\n", track_title(&handle)),
+            }
+        }
+        None => String::new(),
+    };
+
+    let upcoming = state.queue.list(guild_id);
+    let pages = queue::page_count(upcoming.len());
+    content.push_str(&queue::render_page(&upcoming, page.min(pages)));
+
+    (content, pages)
+}
+
+/// Buttons to the pages either side of `page`, or none with only one page.
+fn page_buttons(page: usize, pages: usize) -> Vec<Component> {
+    if pages <= 1 {
+        return Vec::new();
+    }
+
+    let button = |label: &str, target: usize, disabled: bool| {
+        Component::Button(Button {
+            custom_id: Some(format!("queue:{}", target)),
+            disabled,
+            emoji: None,
+            label: Some(label.to_string()),
+            style: ButtonStyle::Secondary,
+            url: None,
+        })
+    };
+
+    vec![Component::ActionRow(ActionRow {
+        components: vec![
+            button("◀ Previous", page.saturating_sub(1).max(1), page == 1),
+            button("Next ▶", (page + 1).min(pages), page == pages),
+        ],
+    })]
+}
+
+const REMOVE_USAGE: &str =
+    "Usage: `{prefix}remove <position>`, with the position from `{prefix}queue`";
+
+pub async fn remove(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "remove command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let requester_removals = state
+        .settings
+        .read()
+        .await
+        .get(&guild_id)
+        .is_some_and(|settings| settings.requester_removals);
+
+    // Everyone else's tracks are left to DJs, who may remove any.
+    let only_own = if requester_removals {
+        !permissions::has_dj_rights(&state, &msg).await?
+    } else if permissions::is_dj(&state, &msg).await? {
+        false
+    } else {
+        return not_a_dj(msg, state).await;
+    };
+
+    let content = match args::split(&msg.content).1.parse::<usize>() {
+        Err(_) => state.prefixed(guild_id, REMOVE_USAGE),
+        Ok(position) => match state.queue.remove(guild_id, position, |info| {
+            !only_own || info.requester == Some(msg.author.id)
+        }) {
+            Some(Ok(track)) => {
+                let action = format!("Removed {} from the queue", track.title());
+                auditlog::record(&state, guild_id, AuditEntry::action(msg.author.id, &action))
+                    .await;
+                queue::warm(&state, guild_id);
+
+                format!("Removed **{}** from the queue.", track.title())
+            }
+            Some(Err(info)) => match info.requester {
+                Some(requester) => format!(
+                    "**{}** was queued by <@{}>, so only they or a DJ can remove it.",
+                    info.title(),
+                    requester
+                ),
+                None => format!("Only a DJ can remove **{}**.", info.title()),
+            },
+            None => no_such_position(&state, guild_id, position),
+        },
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .allowed_mentions(AllowedMentions::default())
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const MOVE_USAGE: &str = "Usage: `{prefix}move <from> <to>`, with positions from `{prefix}queue`";
+
+pub async fn move_track(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "move command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    let guild_id = msg.guild_id.unwrap();
+    let args = Args::parse(&msg.content);
+    let mut positions = args.words().map(str::parse::<usize>);
+
+    let content = match (positions.next(), positions.next(), positions.next()) {
+        (Some(Ok(from)), Some(Ok(to)), None) => match state.queue.move_track(guild_id, from, to) {
+            Some(title) => {
+                let action = format!("Moved {} to #{} in the queue", title, to);
+                auditlog::record(&state, guild_id, AuditEntry::action(msg.author.id, &action))
+                    .await;
+                queue::warm(&state, guild_id);
+
+                format!("Moved **{}** to #{}.", title, to)
+            }
+            None => {
+                let out_of_range = if state.queue.list(guild_id).len() < from {
+                    from
+                } else {
+                    to
+                };
+                no_such_position(&state, guild_id, out_of_range)
+            }
+        },
+        _ => state.prefixed(guild_id, MOVE_USAGE),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// The reply for a queue position that isn't there.
+fn no_such_position(state: &State, guild_id: GuildId, position: usize) -> String {
+    match state.queue.list(guild_id).len() {
+        0 => "The queue is empty.".to_string(),
+        1 => format!("There's no #{} in the queue, only #1.", position),
+        len => format!(
+            "There's no #{} in the queue, it goes from #1 to #{}.",
+            position, len
+        ),
+    }
+}
+
+pub async fn vote_skip(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "voteskip command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let channel_id = match state.songbird.get(guild_id) {
+        Some(call_lock) => call_lock.lock().await.current_channel(),
+        None => None,
+    };
+    let (handle, channel_id) = match (queue::current(&state, guild_id).await, channel_id) {
+        (Some(handle), Some(channel_id)) => (handle, ChannelId(channel_id.0)),
+        _ => {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content("Nothing's playing, so there's nothing to skip.")?
+                .exec()
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    // Other bots in the call don't get a say, or they could make the
+    // vote impossible to pass.
+    let listeners = idle::listeners(
+        &*state.voice_states.read().await,
+        &*state.bots.read().await,
+        state.user_id,
+        guild_id,
+        channel_id,
+    );
+
+    if !listeners.contains(&msg.author.id) {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content(&format!(
+                "You need to be listening in <#{}> to vote.",
+                channel_id
+            ))?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    let percent = state
+        .settings
+        .read()
+        .await
+        .get(&guild_id)
+        .map_or(voteskip::DEFAULT_PERCENT, GuildSettings::vote_skip);
+    let needed = voteskip::needed(listeners.len(), percent);
+
+    let (added, voters) = state.vote_skips.vote(guild_id, &handle, msg.author.id);
+    // Votes from anyone who has since left the call don't count.
+    let votes = voters.intersection(&listeners).count();
+    let title = track_title(&handle);
+
+    let content = if votes >= needed {
+        state.vote_skips.clear(guild_id);
+        skip_current(&state, guild_id).await?;
+
+        let action = format!("Vote skipped {}", title);
+        auditlog::record(&state, guild_id, AuditEntry::action(msg.author.id, &action)).await;
+
+        format!("Vote passed ({}/{}), skipped **{}**.", votes, needed, title)
+    } else if added {
+        format!("Voted to skip **{}** ({}/{}).", title, votes, needed)
+    } else {
+        format!(
+            "You've already voted to skip **{}** ({}/{}).",
+            title, votes, needed
+        )
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// How many of the shuffled queue's titles `j/shuffle` shows.
+const SHUFFLE_SHOWN: usize = 3;
+
+pub async fn shuffle(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "shuffle command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let content = match state.queue.shuffle(guild_id) {
+        0 => "The queue is empty, so there's nothing to shuffle.".to_string(),
+        1 => "There's only one track queued, so there's nothing to shuffle.".to_string(),
+        shuffled => {
+            auditlog::record(
+                &state,
+                guild_id,
+                AuditEntry::action(msg.author.id, "Shuffled the queue"),
+            )
+            .await;
+
+            let mut content = format!("🔀 Shuffled {} tracks. Up next:", shuffled);
+            for (position, track) in state
+                .queue
+                .list(guild_id)
+                .iter()
+                .take(SHUFFLE_SHOWN)
+                .enumerate()
+            {
+                let _ = write!(content, "\n{}. **{}**", position + 1, track.title());
+            }
+
+            // Different tracks are at the front now.
+            queue::warm(&state, guild_id);
+
+            content
+        }
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+pub fn track_title(handle: &TrackHandle) -> String {
+    TrackInfo::of(handle).title().to_string()
+}
+
+/// The guild's current track, if it is paused.
+async fn paused(state: &State, guild_id: GuildId) -> Option<TrackHandle> {
+    let handle = queue::current(state, guild_id).await?;
+
+    match handle.get_info().await {
+        Ok(info) if info.playing == PlayMode::Pause => Some(handle),
+        _ => None,
+    }
+}
+
+pub async fn pause(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "pause command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let content = match queue::current(&state, guild_id).await {
+        Some(_) if state.breaks.read().await.contains(&guild_id) => {
+            "We're already on a break.".to_string()
+        }
+        Some(handle) if paused(&state, guild_id).await.is_some() => {
+            format!("**{}** is already paused.", track_title(&handle))
+        }
+        Some(handle) => {
+            handle.pause()?;
+            format!(
+                "Paused **{}**. Use `{prefix}resume` to carry on.",
+                track_title(&handle),
+                prefix = state.prefix(guild_id)
+            )
+        }
+        None => "Nothing's playing, so there's nothing to pause.".to_string(),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+pub async fn now_playing(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "nowplaying command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let handle = match queue::current(&state, guild_id).await {
+        Some(handle) => handle,
+        None => {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content("Nothing's playing.")?
+                .exec()
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let info = handle.get_info().await?;
+    let track = queue::current_info(&state, guild_id, &handle).await;
+    let elapsed = track.elapsed(info.position);
+
+    let progress = match track.duration {
+        Some(duration) => format!(
+            "{} {} / {}",
+            progress_bar(elapsed, duration),
+            duration::format(elapsed),
+            duration::format(duration)
+        ),
+        None => duration::format(elapsed),
+    };
+    let mode = if info.playing == PlayMode::Pause {
+        "⏸ Paused"
+    } else {
+        "▶ Now playing"
+    };
+
+    let mut fields = Vec::new();
+    if let Some(artist) = &track.artist {
+        fields.push(EmbedField {
+            inline: true,
+            name: "Artist".to_string(),
+            value: artist.clone(),
+        });
+    }
+    if let Some(duration) = track.duration {
+        fields.push(EmbedField {
+            inline: true,
+            name: "Duration".to_string(),
+            value: duration::format(duration),
+        });
+    }
+    // Who asked isn't part of the driver's info; see `StateRef::now_playing`.
+    if let Some(requester) = state
+        .now_playing
+        .read()
+        .await
+        .get(&guild_id)
+        .and_then(|current| current.requester)
+    {
+        fields.push(EmbedField {
+            inline: true,
+            name: "Requested by".to_string(),
+            value: format!("<@{}>", requester),
+        });
+    }
+
+    let embed = Embed {
+        author: None,
+        color: None,
+        description: Some(format!("{}\n{}", mode, progress)),
+        fields,
+        footer: None,
+        image: None,
+        kind: "rich".to_string(),
+        provider: None,
+        thumbnail: track.thumbnail.clone().map(|url| EmbedThumbnail {
+            height: None,
+            proxy_url: None,
+            url: Some(url),
+            width: None,
+        }),
+        timestamp: None,
+        title: Some(track.title().to_string()),
+        url: track.source_url.clone(),
+        video: None,
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .embeds(&[embed])?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const SEEK_USAGE: &str =
+    "Usage: `{prefix}seek <mm:ss>` to jump to that point in the current track, \
+    e.g. `{prefix}seek 1:30`";
+
+pub async fn seek(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "seek command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let content = match (
+        Args::parse(&msg.content).get(0).and_then(args::duration),
+        queue::current(&state, guild_id).await,
+    ) {
+        (None, _) => state.prefixed(guild_id, SEEK_USAGE),
+        (Some(_), None) => "Nothing's playing, so there's nothing to seek.".to_string(),
+        (Some(_), Some(handle)) if !handle.is_seekable() => {
+            format!("**{}** can't be seeked, sorry.", track_title(&handle))
+        }
+        (Some(position), Some(handle))
+            if TrackInfo::of(&handle)
+                .duration
+                .is_some_and(|duration| position >= duration) =>
+        {
+            let track = TrackInfo::of(&handle);
+            format!(
+                "**{}** is only {} long.",
+                track.title(),
+                duration::format(track.duration.unwrap_or_default())
+            )
+        }
+        (Some(position), Some(handle)) => {
+            // A chapter's positions count from where it starts.
+            let start = queue::current_info(&state, guild_id, &handle)
+                .await
+                .segment
+                .map_or(Duration::ZERO, |segment| segment.start);
+            handle.seek_time(start + position)?;
+            format!(
+                "Jumped to {} in **{}**.",
+                duration::format(position),
+                track_title(&handle)
+            )
+        }
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const LOOP_USAGE: &str = "Usage: `{prefix}loop [track|queue|off]`";
+
+pub async fn loop_mode(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "loop command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    let guild_id = msg.guild_id.unwrap();
+    let argument = args::split(&msg.content).1;
+
+    let mode = if argument.is_empty() {
+        None
+    } else {
+        match LoopMode::parse(argument) {
+            Some(mode) => Some(mode),
+            None => {
+                state
+                    .http
+                    .create_message(msg.channel_id)
+                    .content(&state.prefixed(guild_id, LOOP_USAGE))?
+                    .exec()
+                    .await?;
+
+                return Ok(());
+            }
+        }
+    };
+
+    let content = match mode {
+        None => match state.loops.read().await.get(&guild_id) {
+            Some(LoopMode::Track) => "Looping the current track.",
+            Some(LoopMode::Queue) => "Looping the queue.",
+            Some(LoopMode::Off) | None => "Looping is off.",
+        },
+        Some(mode) => {
+            if let Some(handle) = queue::current(&state, guild_id).await {
+                if mode == LoopMode::Track {
+                    handle.enable_loop()?;
+                } else {
+                    handle.disable_loop()?;
+                }
+            }
+
+            let mut loops = state.loops.write().await;
+            if mode == LoopMode::Off {
+                loops.remove(&guild_id);
+            } else {
+                loops.insert(guild_id, mode);
+            }
+            drop(loops);
+
+            let (content, action) = match mode {
+                LoopMode::Track => ("🔂 Looping the current track.", "Looped the track"),
+                LoopMode::Queue => ("🔁 Looping the queue.", "Looped the queue"),
+                LoopMode::Off => ("Looping is off.", "Turned looping off"),
+            };
+            auditlog::record(&state, guild_id, AuditEntry::action(msg.author.id, action)).await;
+
+            content
+        }
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const VOLUME_USAGE: &str = "Usage: `{prefix}volume [percent]`, from 0 to 200";
+
+pub async fn volume(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "volume command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    let guild_id = msg.guild_id.unwrap();
+    let argument = args::split(&msg.content).1;
+
+    let content = if argument.is_empty() {
+        format!(
+            "The volume is {}%.",
+            (guild_volume(&state, guild_id).await * 100.0).round()
+        )
+    } else {
+        match argument.trim_end_matches('%').parse::<u32>() {
+            Ok(percent) if percent <= settings::MAX_VOLUME_PERCENT => {
+                match permissions::volume_cap(&state, &msg).await? {
+                    Some(cap) if percent > cap => {
+                        format!("Your roles can set the volume up to {}%.", cap)
+                    }
+                    _ => set_volume(&state, &msg, percent).await?,
+                }
+            }
+            _ => state.prefixed(guild_id, VOLUME_USAGE),
+        }
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Sets the guild's volume for `j/volume`, returning the reply.
+async fn set_volume(
+    state: &State,
+    msg: &Message,
+    percent: u32,
+) -> Result<String, Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.unwrap();
+
+    {
+        let mut settings = state.settings.write().await;
+        settings.entry(guild_id).or_default().volume = Some(percent);
+
+        // The volume is in effect either way; a failed save only means a
+        // restart would undo it.
+        if let Err(why) = state.storage.save_settings(&settings) {
+            state.hooks.error(Some(guild_id), &*why);
+        }
+    }
+
+    if let Some(handle) = queue::current(state, guild_id).await {
+        handle.set_volume(playing_volume(state, guild_id).await)?;
+    }
+
+    let action = format!("Set the volume to {}%", percent);
+    auditlog::record(state, guild_id, AuditEntry::action(msg.author.id, &action)).await;
+
+    Ok(format!("🔊 Volume set to {}%.", percent))
+}
+
+pub async fn resume(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "resume command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    // A paused track carries on; otherwise the last stopped one restarts.
+    if let Some(handle) = paused(&state, guild_id).await {
+        let content = if state.breaks.read().await.contains(&guild_id) {
+            "We're on a break, the music comes back by itself.".to_string()
+        } else {
+            handle.play()?;
+            format!("Resumed **{}**.", track_title(&handle))
+        };
+
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content(&content)?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    // Checked first so a refusal doesn't use up the stopped track.
+    if !within_quota(&state, guild_id, msg.channel_id).await? {
+        return Ok(());
+    }
+
+    let stopped = state
+        .stopped
+        .write()
+        .await
+        .remove(&guild_id)
+        .filter(|stopped| clock::elapsed(&*state.clock, stopped.stopped_at) < RESUME_GRACE);
+
+    let stopped = match (stopped, state.songbird.get(guild_id)) {
+        (Some(stopped), Some(_)) => stopped,
+        _ => {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content("There's no recently stopped track to resume.")?
+                .exec()
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    stopped.snapshot.restore(&state, guild_id).await?;
+    state
+        .quotas
+        .record_track(guild_id, state.clock.now().date().naive_utc());
+    state.sessions.record_track(guild_id, msg.author.id);
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&format!(
+            "Resuming from {}.",
+            duration::format(stopped.snapshot.position)
+        ))?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+fn recording_saved(path: &Path, length: Duration) -> String {
+    format!(
+        "🔴 Recording stopped. Saved {} as `{}`.",
+        duration::format(length),
+        path.display()
+    )
+}
+
+const RECORD_USAGE: &str = "Usage: `{prefix}record start` or `{prefix}record stop`";
+
+pub async fn record(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "record command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let content = match Args::parse(&msg.content).get(0) {
+        Some("start") => {
+            let allowed = state
+                .settings
+                .read()
+                .await
+                .get(&guild_id)
+                .is_some_and(|settings| settings.recording);
+
+            if !allowed {
+                state.prefixed(
+                    guild_id,
+                    "Recording isn't enabled on this server. An admin can allow it with \
+                     `{prefix}settings recording on`.",
+                )
+            } else if !permissions::is_admin(&state, &msg).await? {
+                "Only server admins can start a recording.".to_string()
+            } else {
+                match recording::start(&state, guild_id).await {
+                    Ok(_) => {
+                        auditlog::record(
+                            &state,
+                            guild_id,
+                            AuditEntry::action(msg.author.id, "Started recording"),
+                        )
+                        .await;
+
+                        format!(
+                            "🔴 <@{}> started recording this voice channel. Everyone who \
+                             speaks in it will be recorded; leave the channel if you don't \
+                             consent. Anyone can end it with `{prefix}record stop`.",
+                            msg.author.id,
+                            prefix = state.prefix(guild_id)
+                        )
+                    }
+                    Err(why) => why.to_string(),
+                }
+            }
+        }
+        // Anyone may stop, so no one is recorded against their will.
+        Some("stop") => match recording::stop(&state, guild_id).await? {
+            Some((path, length)) => {
+                auditlog::record(
+                    &state,
+                    guild_id,
+                    AuditEntry::action(msg.author.id, "Stopped recording"),
+                )
+                .await;
+
+                recording_saved(&path, length)
+            }
+            None => "I'm not recording.".to_string(),
+        },
+        _ => state.prefixed(guild_id, RECORD_USAGE),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const TOP_USAGE: &str = "Usage: `{prefix}top rated`";
+
+pub async fn top(msg: Message, state: State) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "top command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let content = match Args::parse(&msg.content).get(0) {
+        Some("rated") => {
+            let top = state.ratings.top(guild_id, 10);
+
+            if top.is_empty() {
+                format!(
+                    "No tracks have been rated yet. Vote with {} or {} on a now playing message.",
+                    ratings::UPVOTE,
+                    ratings::DOWNVOTE
+                )
+            } else {
+                let mut content = "Top rated tracks:".to_string();
+
+                for (rank, rated) in top.iter().enumerate() {
+                    content.push_str(&format!(
+                        "\n{}. **{}** (+{}) <{}>",
+                        rank + 1,
+                        rated.title,
+                        rated.score(),
+                        rated.url
+                    ));
+                }
+
+                content
+            }
+        }
+        _ => state.prefixed(guild_id, TOP_USAGE),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const THEME_USAGE: &str =
+    "Usage: `{prefix}theme set <url> [start] [seconds]`, `{prefix}theme clear`, \
+    or `{prefix}theme` to see yours";
+
+pub async fn theme(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "theme command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+    let args = Args::parse(&msg.content);
+    let mut words = args.words();
+
+    let content = match (
+        words.next(),
+        words.next(),
+        words.next(),
+        words.next(),
+        words.next(),
+    ) {
+        (None, ..) => match state.themes.get(guild_id, msg.author.id) {
+            Some(theme) => format!(
+                "Your theme is <{}> from {} for {} seconds.",
+                theme.url,
+                duration::format(theme.start),
+                theme.length.as_secs()
+            ),
+            None => state.prefixed(
+                guild_id,
+                "You don't have a theme. Set one with `{prefix}theme set <url>`.",
+            ),
+        },
+        (Some("clear"), None, ..) => {
+            if state.themes.clear(guild_id, msg.author.id)? {
+                "Your theme is cleared.".to_string()
+            } else {
+                "You don't have a theme.".to_string()
+            }
+        }
+        (Some("set"), Some(url), start, length, None) => {
+            let start = start.map(args::duration);
+            let length = length.map(|secs| secs.parse().map(Duration::from_secs));
+
+            match (start, length) {
+                (Some(None), _) | (_, Some(Err(_))) => state.prefixed(guild_id, THEME_USAGE),
+                (start, length) => {
+                    let start = start.flatten().unwrap_or_default();
+                    let length = length
+                        .and_then(Result::ok)
+                        .unwrap_or(themes::DEFAULT_LENGTH)
+                        .min(themes::MAX_LENGTH);
+
+                    set_theme(&state, &msg, url, start, length).await
+                }
+            }
+        }
+        _ => state.prefixed(guild_id, THEME_USAGE),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Checks that the clip resolves and is long enough before saving it.
+async fn set_theme(
+    state: &State,
+    msg: &Message,
+    url: &str,
+    start: Duration,
+    length: Duration,
+) -> String {
+    let input = match state.resolver.resolve(url).await {
+        Ok(input) => input,
+        Err(why) => return format!("I couldn't load that clip: {}", why),
+    };
+
+    if input
+        .metadata
+        .duration
+        .is_some_and(|duration| duration <= start)
+    {
+        return "That clip ends before the start you gave.".to_string();
+    }
+
+    let theme = Theme {
+        url: url.to_string(),
+        start,
+        length,
+    };
+    if let Err(why) = state
+        .themes
+        .set(msg.guild_id.unwrap(), msg.author.id, theme)
+    {
+        return format!("I couldn't save your theme: {}", why);
+    }
+
+    format!(
+        "Your theme is set: **{}** from {} for {} seconds.",
+        TrackInfo::from_metadata(&input.metadata).title(),
+        duration::format(start),
+        length.as_secs()
+    )
+}
+
+pub async fn vibe(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "vibe command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let handle = state.trackdata.read().await.get(&guild_id).cloned();
+    let playing = match handle {
+        Some(handle) => {
+            let track = TrackInfo::of(&handle);
+
+            match (handle.get_info().await, track.source_url) {
+                (Ok(info), Some(url)) if !info.playing.is_done() => {
+                    Some((url, track.title, info.position))
+                }
+                _ => None,
+            }
+        }
+        None => None,
+    };
+
+    let (url, title, position) = match playing {
+        Some(playing) => playing,
+        None => {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content("Nothing's playing, so there's nothing to draw.")?
+                .exec()
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let png = vibe::snapshot(&state, &url, position).await?;
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&format!(
+            "〰️ **{}** from {}",
+            title.as_deref().unwrap_or("<UNKNOWN>"),
+            duration::format(position)
+        ))?
+        .files(&[("vibe.png", &png)])
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+pub async fn ambience(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "ambience command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let content = match Args::parse(&msg.content).get(0) {
+        Some("off") => match state.ambience.write().await.remove(&guild_id) {
+            Some(handle) => {
+                // It may already have ended with the call.
+                let _ = handle.stop();
+                "Ambience off.".to_string()
+            }
+            None => "There's no ambience playing.".to_string(),
+        },
+        Some(name) => match (
+            ambience::NAMES.contains(&name),
+            state.songbird.get(guild_id),
+        ) {
+            (false, _) => format!(
+                "I don't know that ambience. Try one of: {}.",
+                ambience::NAMES.join(", ")
+            ),
+            (true, None) => "I'm not in a voice channel.".to_string(),
+            (true, Some(call_lock)) => {
+                let input = ambience::input(name).ok_or("unknown ambience")?;
+                let handle = call_lock.lock().await.play_source(input);
+                handle.set_volume(ambience::VOLUME)?;
+                handle.enable_loop()?;
+
+                if let Some(previous) = state.ambience.write().await.insert(guild_id, handle) {
+                    let _ = previous.stop();
+                }
+
+                format!("Playing {} ambience underneath the music.", name)
+            }
+        },
+        None => format!(
+            "Usage: `{prefix}ambience <name>` or `{prefix}ambience off`, with one of: {}.",
+            ambience::NAMES.join(", "),
+            prefix = state.prefix(guild_id)
+        ),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const BREAK_USAGE: &str = "Usage: `{prefix}break <minutes>`, up to two hours";
+
+fn break_countdown(minutes_left: u64) -> String {
+    format!(
+        "☕ On a break, back in {} minute{}.",
+        minutes_left,
+        if minutes_left == 1 { "" } else { "s" }
+    )
+}
+
+/// `j/break`: fades out and pauses the current track, counts the break
+/// down in one edited message, then resumes and fades back in.
+pub async fn intermission(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "break command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let minutes = match Args::parse(&msg.content).get(0).map(str::parse::<u64>) {
+        Some(Ok(minutes)) if (1..=120).contains(&minutes) => minutes,
+        _ => {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content(&state.prefixed(guild_id, BREAK_USAGE))?
+                .exec()
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    if locked_out(&state, &msg).await? {
+        return Ok(());
+    }
+
+    let handle = state.trackdata.read().await.get(&guild_id).cloned();
+
+    let refusal = match handle {
+        None => Some("Nothing's playing, so there's nothing to take a break from."),
+        Some(_) if !state.breaks.write().await.insert(guild_id) => {
+            Some("We're already on a break.")
+        }
+        Some(_) => None,
+    };
+
+    if let Some(refusal) = refusal {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content(refusal)?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    let handle = handle.unwrap();
+    let result = take_break(&state, &msg, &handle, minutes).await;
+    state.breaks.write().await.remove(&guild_id);
+
+    result
+}
+
+async fn take_break(
+    state: &State,
+    msg: &Message,
+    handle: &TrackHandle,
+    minutes: u64,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.unwrap();
+    let volume = handle.get_info().await?.volume;
+
+    fade::fade(state, handle, volume, 0.0).await;
+    handle.pause()?;
+
+    let countdown = state
+        .http
+        .create_message(msg.channel_id)
+        .content(&break_countdown(minutes))?
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    auditlog::record(
+        state,
+        guild_id,
+        AuditEntry::action(
+            msg.author.id,
+            &format!("Started a {} minute break", minutes),
+        ),
+    )
+    .await;
+
+    for minutes_left in (0..minutes).rev() {
+        state.clock.sleep(Duration::from_secs(60)).await;
+
+        let content = match minutes_left {
+            0 => "☕ Break's over!".to_string(),
+            minutes_left => break_countdown(minutes_left),
+        };
+
+        state
+            .http
+            .update_message(msg.channel_id, countdown.id)
+            .content(Some(&content))?
+            .exec()
+            .await?;
+    }
+
+    // Someone may have stopped or replaced the track during the break.
+    let still_current = state
+        .trackdata
+        .read()
+        .await
+        .get(&guild_id)
+        .is_some_and(|current| current.uuid() == handle.uuid());
+
+    if still_current {
+        handle.play()?;
+        fade::fade(state, handle, 0.0, volume).await;
+    }
+
+    Ok(())
+}
+
+const EVENT_USAGE: &str =
+    "Usage: `{prefix}event start <minutes>`, `{prefix}event stop`, or `{prefix}event` to see what's scheduled";
+
+pub async fn event(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "event command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    if !permissions::is_admin(&state, &msg).await? {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content("Only server admins can run listening events.")?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    let args = Args::parse(&msg.content);
+    let mut words = args.words();
+
+    let content = match (
+        words.next(),
+        words.next().map(str::parse::<u64>),
+        words.next(),
+    ) {
+        (Some("start"), Some(Ok(minutes)), None) if minutes <= 24 * 60 => {
+            // The start is announced where events are scheduled from.
+            state
+                .text_channels
+                .write()
+                .await
+                .entry(guild_id)
+                .or_insert(msg.channel_id);
+
+            let starts_at =
+                event::schedule(&state, guild_id, Duration::from_secs(minutes * 60)).await;
+            let role = state
+                .settings
+                .read()
+                .await
+                .get(&guild_id)
+                .and_then(|settings| settings.event_role);
+
+            auditlog::record(
+                &state,
+                guild_id,
+                AuditEntry::action(msg.author.id, "Scheduled a listening event"),
+            )
+            .await;
+
+            format!(
+                "{}Listening event <t:{}:R>! Requests lock to admins when it starts.",
+                role.map_or_else(String::new, |role| format!("<@&{}> ", role)),
+                starts_at.timestamp()
+            )
+        }
+        (None, None, None) => match state.events.read().await.get(&guild_id) {
+            Some(scheduled) if scheduled.locked => {
+                "A listening event is running, requests are locked to admins.".to_string()
+            }
+            Some(scheduled) => {
+                format!("Listening event <t:{}:R>.", scheduled.starts_at.timestamp())
+            }
+            None => "There's no listening event scheduled.".to_string(),
+        },
+        (Some("stop"), None, None) => match event::cancel(&state, guild_id).await {
+            Some(ended) => {
+                auditlog::record(
+                    &state,
+                    guild_id,
+                    AuditEntry::action(msg.author.id, "Ended the listening event"),
+                )
+                .await;
+
+                if ended.locked {
+                    "The listening event is over, requests are open again.".to_string()
+                } else {
+                    "The listening event was called off.".to_string()
+                }
+            }
+            None => "There's no listening event scheduled.".to_string(),
+        },
+        _ => state.prefixed(guild_id, EVENT_USAGE),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const JINGLE_USAGE: &str =
+    "Usage: `{prefix}jingle add <YYYY-MM-DD> <HH:MM> <#voice channel> <url>` \
+    in the server time zone, `{prefix}jingle cancel <number>`, or `{prefix}jingle` to list them";
+
+pub async fn jingle(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "jingle command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    if !permissions::is_admin(&state, &msg).await? {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content("Only server admins can schedule jingles.")?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    let args = Args::parse(&msg.content);
+    let args = args.words().collect::<Vec<_>>();
+
+    let content = match args.as_slice() {
+        [] => {
+            let jingles = jingle::list(&state, guild_id).await;
+
+            if jingles.is_empty() {
+                "No jingles are scheduled.".to_string()
+            } else {
+                jingles.iter().enumerate().fold(
+                    "Scheduled jingles:".to_string(),
+                    |mut content, (position, jingle)| {
+                        content.push_str(&format!(
+                            "\n{}. **{}** in <#{}> <t:{}:F>",
+                            position + 1,
+                            jingle.title,
+                            jingle.channel_id,
+                            jingle.at.timestamp()
+                        ));
+                        content
+                    },
+                )
+            }
+        }
+        ["cancel", index] => match index.parse() {
+            Ok(index) => match jingle::cancel(&state, guild_id, index).await {
+                Some(cancelled) => {
+                    auditlog::record(
+                        &state,
+                        guild_id,
+                        AuditEntry::action(msg.author.id, "Cancelled a jingle"),
+                    )
+                    .await;
+
+                    format!("Cancelled **{}**.", cancelled.title)
+                }
+                None => "There's no jingle with that number.".to_string(),
+            },
+            Err(_) => state.prefixed(guild_id, JINGLE_USAGE),
+        },
+        ["add", date, time, channel, url] => {
+            let zone = state
+                .settings
+                .read()
+                .await
+                .get(&guild_id)
+                .map_or_else(|| GuildSettings::default().zone(), GuildSettings::zone);
+
+            match (jingle::parse_time(date, time, zone), args::channel(channel)) {
+                (Some(at), Some(channel_id)) => {
+                    return add_jingle(&state, &msg, at, channel_id, url).await;
+                }
+                _ => state.prefixed(guild_id, JINGLE_USAGE),
+            }
+        }
+        _ => state.prefixed(guild_id, JINGLE_USAGE),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Checks and resolves a new jingle, then schedules it once the admin
+/// confirms.
+async fn add_jingle(
+    state: &State,
+    msg: &Message,
+    at: DateTime<Utc>,
+    channel_id: ChannelId,
+    url: &str,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.unwrap();
+
+    let refusal = if at <= state.clock.now() {
+        Some("That time has already passed.".to_string())
+    } else if jingle::list(state, guild_id).await.len() >= jingle::MAX_SCHEDULED {
+        Some(format!(
+            "There are already {} jingles scheduled.",
+            jingle::MAX_SCHEDULED
+        ))
+    } else {
+        None
+    };
+
+    let title = match (refusal, state.resolver.resolve(url).await) {
+        (Some(refusal), _) => Err(refusal),
+        (None, Ok(input)) => Ok(TrackInfo::from_metadata(&input.metadata)
+            .title
+            .unwrap_or_else(|| url.to_string())),
+        (None, Err(why)) => Err(format!("I couldn't load that jingle: {}", why)),
+    };
+
+    let title = match title {
+        Ok(title) => title,
+        Err(refusal) => {
+            state
+                .http
+                .create_message(msg.channel_id)
+                .content(&refusal)?
+                .exec()
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let question = format!(
+        "Play **{}** in <#{}> on <t:{}:F>? Reply `yes` to schedule it.",
+        title,
+        channel_id,
+        at.timestamp()
+    );
+    let confirmation = choose(state, msg, &question, YES_NO).await?;
+
+    let content = match confirmation {
+        Some(0) => {
+            // Jingles are announced where they're scheduled from.
+            state
+                .text_channels
+                .write()
+                .await
+                .entry(guild_id)
+                .or_insert(msg.channel_id);
+
+            jingle::schedule(
+                state,
+                guild_id,
+                at,
+                channel_id,
+                url.to_string(),
+                title.clone(),
+            )
+            .await;
+
+            auditlog::record(
+                state,
+                guild_id,
+                AuditEntry::action(msg.author.id, "Scheduled a jingle"),
+            )
+            .await;
+
+            format!("Scheduled **{}** for <t:{}:F>.", title, at.timestamp())
+        }
+        _ => "Okay, not scheduling it.".to_string(),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// The guild's `j/volume` caps for the settings listing, by role.
+fn volume_caps(settings: &GuildSettings) -> String {
+    if settings.volume_caps.is_empty() {
+        return "off".to_string();
+    }
+
+    let mut caps = settings.volume_caps.iter().collect::<Vec<_>>();
+    caps.sort_by_key(|(role_id, _)| role_id.0);
+
+    caps.iter()
+        .map(|(role_id, cap)| format!("<@&{}> {}%", role_id, cap))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+const SETTINGS_USAGE: &str = "Usage:\n\
+    `{prefix}settings quiet <HH:MM>-<HH:MM> [+HH:MM]` or `{prefix}settings quiet off`\n\
+    `{prefix}settings curfew <HH:MM> [+HH:MM]` or `{prefix}settings curfew off`\n\
+    `{prefix}settings logchannel <#channel>` or `{prefix}settings logchannel off`\n\
+    `{prefix}settings timezone <+HH:MM>` or `{prefix}settings timezone off`\n\
+    `{prefix}settings announcements off|minimal|full`\n\
+    `{prefix}settings recording on|off` to allow `{prefix}record` here\n\
+    `{prefix}settings dislikes <listeners>` to skip tracks that many listeners downvote, or \
+    `{prefix}settings dislikes off`\n\
+    `{prefix}settings voteskip <percent>` for the share of listeners `{prefix}voteskip` needs\n\
+    `{prefix}settings eventrole <@&role>` to ping for listening events, or `{prefix}settings eventrole off`\n\
+    `{prefix}settings themes on|off` to play members' `{prefix}theme` clips when they join\n\
+    `{prefix}settings idle <minutes>` to leave after that long with nothing playing, or \
+    `{prefix}settings idle off` to stay\n\
+    `{prefix}settings norepeats <hours> [strict|dj]` to refuse tracks that finished that recently, \
+    letting DJs override it with `dj`, or `{prefix}settings norepeats off`\n\
+    `{prefix}settings volumecap <@&role> <percent>` to limit how loud that role can set \
+    `{prefix}volume`, or `{prefix}settings volumecap <@&role> off`\n\
+    `{prefix}settings removals requester` to let members `{prefix}remove` their own tracks while \
+    DJs remove any, or `{prefix}settings removals dj` to leave it to DJs\n\
+    `{prefix}settings export`, or `{prefix}settings import <json>` to copy another server's settings\n\
+    `{prefix}settings template nowplaying <text>` or `{prefix}settings template nowplaying off`, \
+    with `{title}`, `{artist}` and `{url}` as placeholders\n\
+    Quiet hours and curfews without their own offset follow the server time zone.";
+
+pub async fn settings(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "settings command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    if !permissions::is_admin(&state, &msg).await? {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content("Only server admins can change my settings.")?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    let args = Args::parse(&msg.content);
+    let mut words = args.words();
+
+    // Arms that change a setting report `true` so the change is audited.
+    let (content, changed) = match (words.next(), words.next(), words.next()) {
+        (None, _, _) => {
+            let settings = state.settings.read().await;
+            let settings = settings.get(&guild_id).cloned().unwrap_or_default();
+
+            let content = format!(
+                "Time zone: UTC{}\nQuiet hours: {}\nCurfew: {}\nLog channel: {}\n\
+                 Announcements: {}\nNow playing template: {}\nRecording: {}\n\
+                 Skip after dislikes: {}\nVote skip: {}% of listeners\nNo repeats: {}\n\
+                 Removals: {}\nEvent role: {}\n\
+                 DJ role: {}\nVolume caps: {}\nThemes: {}\nIdle timeout: {}",
+                settings.zone(),
+                settings
+                    .quiet_hours
+                    .map_or_else(|| "off".to_string(), |quiet_hours| quiet_hours.to_string()),
+                settings
+                    .curfew
+                    .map_or_else(|| "off".to_string(), |curfew| curfew.to_string()),
+                settings
+                    .log_channel
+                    .map_or_else(|| "off".to_string(), |channel| format!("<#{}>", channel)),
+                settings.announcements,
+                settings.now_playing_template(),
+                if settings.recording { "allowed" } else { "off" },
+                settings
+                    .dislike_skip
+                    .map_or_else(|| "off".to_string(), |threshold| threshold.to_string()),
+                settings.vote_skip(),
+                settings.no_repeats.map_or_else(
+                    || "off".to_string(),
+                    |no_repeats| format!(
+                        "within {}{}",
+                        duration::humanize(no_repeats.window().to_std().unwrap_or_default()),
+                        if no_repeats.dj_override {
+                            ", unless a DJ queues it"
+                        } else {
+                            ""
+                        }
+                    )
+                ),
+                if settings.requester_removals {
+                    "requesters and DJs"
+                } else {
+                    "DJs"
+                },
+                settings
+                    .event_role
+                    .map_or_else(|| "off".to_string(), |role| format!("<@&{}>", role)),
+                settings
+                    .dj_role
+                    .map_or_else(|| "off".to_string(), |role| format!("<@&{}>", role)),
+                volume_caps(&settings),
+                if settings.themes { "on" } else { "off" },
+                settings
+                    .idle_timeout(state.profile.idle_timeout())
+                    .map_or_else(|| "off".to_string(), duration::humanize),
+            );
+
+            (content, false)
+        }
+        (Some("quiet"), Some("off"), None) => {
+            let mut settings = state.settings.write().await;
+            settings.entry(guild_id).or_default().quiet_hours = None;
+
+            ("Quiet hours disabled.".to_string(), true)
+        }
+        (Some("quiet"), Some(range), offset) => match QuietHours::parse(range, offset) {
+            Some(quiet_hours) => {
+                let mut settings = state.settings.write().await;
+                settings.entry(guild_id).or_default().quiet_hours = Some(quiet_hours);
+
+                (format!("Quiet hours set to {}.", quiet_hours), true)
+            }
+            None => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+        },
+        (Some("curfew"), Some("off"), None) => {
+            let mut settings = state.settings.write().await;
+            settings.entry(guild_id).or_default().curfew = None;
+
+            ("Curfew disabled.".to_string(), true)
+        }
+        (Some("curfew"), Some(time), offset) => match Curfew::parse(time, offset) {
+            Some(curfew) => {
+                let mut settings = state.settings.write().await;
+                settings.entry(guild_id).or_default().curfew = Some(curfew);
+
+                (format!("Curfew set to {}.", curfew), true)
+            }
+            None => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+        },
+        (Some("logchannel"), Some("off"), None) => {
+            let mut settings = state.settings.write().await;
+            settings.entry(guild_id).or_default().log_channel = None;
+
+            ("Log channel disabled.".to_string(), true)
+        }
+        (Some("logchannel"), Some(channel), None) => match args::channel(channel) {
+            Some(channel_id) => {
+                let mut settings = state.settings.write().await;
+                settings.entry(guild_id).or_default().log_channel = Some(channel_id);
+
+                (format!("Log channel set to <#{}>.", channel_id), true)
+            }
+            None => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+        },
+        (Some("export"), None, None) => {
+            let settings = state.settings.read().await;
+            let settings = settings.get(&guild_id).cloned().unwrap_or_default();
+
+            let snapshot = Snapshot {
+                prefix: state.prefixes.get(guild_id),
+                ..Snapshot::export(&settings)
+            };
+            let json = serde_json::to_string_pretty(&snapshot)?;
+
+            (format!("```json\n{}\n```", json), false)
+        }
+        (Some("import"), Some(_), _) => {
+            let json = msg
+                .content
+                .split_once("import")
+                .map_or("", |(_, json)| json.trim())
+                .trim_start_matches("```json")
+                .trim_matches('`');
+
+            match serde_json::from_str::<Snapshot>(json) {
+                Ok(snapshot) => {
+                    let mut settings = state.settings.write().await;
+
+                    match snapshot.apply(settings.entry(guild_id).or_default()) {
+                        Ok(()) => {
+                            state.prefixes.set(guild_id, snapshot.prefix)?;
+
+                            ("Settings imported.".to_string(), true)
+                        }
+                        Err(why) => (why, false),
+                    }
+                }
+                Err(why) => (format!("That isn't a settings export: {}", why), false),
+            }
+        }
+        (Some("announcements"), Some(level), None) => match Announcements::parse(level) {
+            Some(announcements) => {
+                let mut settings = state.settings.write().await;
+                settings.entry(guild_id).or_default().announcements = announcements;
+
+                (format!("Announcements set to {}.", announcements), true)
+            }
+            None => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+        },
+        (Some("recording"), Some(toggle @ ("on" | "off")), None) => {
+            let allowed = toggle == "on";
+            state
+                .settings
+                .write()
+                .await
+                .entry(guild_id)
+                .or_default()
+                .recording = allowed;
+
+            // Withdrawing permission also ends a recording in progress.
+            if !allowed && recording::stop(&state, guild_id).await?.is_some() {
+                ("Recording disabled and stopped.".to_string(), true)
+            } else if allowed {
+                (
+                    state.prefixed(guild_id, "Recording allowed with `{prefix}record start`."),
+                    true,
+                )
+            } else {
+                ("Recording disabled.".to_string(), true)
+            }
+        }
+        (Some("dislikes"), Some("off"), None) => {
+            let mut settings = state.settings.write().await;
+            settings.entry(guild_id).or_default().dislike_skip = None;
+
+            ("Tracks won't be skipped for dislikes.".to_string(), true)
+        }
+        (Some("dislikes"), Some(threshold), None) => match threshold.parse::<u32>() {
+            Ok(threshold) if threshold > 0 => {
+                let mut settings = state.settings.write().await;
+                settings.entry(guild_id).or_default().dislike_skip = Some(threshold);
+
+                (
+                    format!(
+                        "Tracks will be skipped once {} listener{} downvote them.",
+                        threshold,
+                        if threshold == 1 { "" } else { "s" }
+                    ),
+                    true,
+                )
+            }
+            _ => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+        },
+        (Some("voteskip"), Some(percent), None) => match percent.trim_end_matches('%').parse() {
+            Ok(percent) if (1..=100).contains(&percent) => {
+                let mut settings = state.settings.write().await;
+                settings.entry(guild_id).or_default().vote_skip = Some(percent);
+
+                (
+                    format!(
+                        "`{prefix}voteskip` now needs {}% of the listeners to agree.",
+                        percent,
+                        prefix = state.prefix(guild_id)
+                    ),
+                    true,
+                )
+            }
+            _ => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+        },
+        (Some("volumecap"), Some(role), Some(cap)) => match (args::role(role), cap) {
+            (Some(role_id), "off") => {
+                let mut settings = state.settings.write().await;
+                settings
+                    .entry(guild_id)
+                    .or_default()
+                    .volume_caps
+                    .remove(&role_id);
+
+                (format!("<@&{}> can set any volume.", role_id), true)
+            }
+            (Some(role_id), cap) => match cap.trim_end_matches('%').parse::<u32>() {
+                Ok(cap) if cap <= settings::MAX_VOLUME_PERCENT => {
+                    let mut settings = state.settings.write().await;
+                    settings
+                        .entry(guild_id)
+                        .or_default()
+                        .volume_caps
+                        .insert(role_id, cap);
+
+                    (
+                        format!("<@&{}> can set the volume up to {}%.", role_id, cap),
+                        true,
+                    )
+                }
+                _ => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+            },
+            (None, _) => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+        },
+        (Some("eventrole"), Some("off"), None) => {
+            let mut settings = state.settings.write().await;
+            settings.entry(guild_id).or_default().event_role = None;
+
+            ("Listening events won't ping a role.".to_string(), true)
+        }
+        (Some("eventrole"), Some(role), None) => match args::role(role) {
+            Some(role_id) => {
+                let mut settings = state.settings.write().await;
+                settings.entry(guild_id).or_default().event_role = Some(role_id);
+
+                (format!("Listening events will ping <@&{}>.", role_id), true)
+            }
+            None => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+        },
+        (Some("themes"), Some(toggle @ ("on" | "off")), None) => {
+            let mut settings = state.settings.write().await;
+            settings.entry(guild_id).or_default().themes = toggle == "on";
+
+            (format!("Theme songs turned {}.", toggle), true)
+        }
+        (Some("idle"), Some("off"), None) => {
+            let mut settings = state.settings.write().await;
+            settings.entry(guild_id).or_default().idle_minutes = Some(0);
+
+            (
+                "I'll stay in the channel when nothing's playing, until everyone leaves."
+                    .to_string(),
+                true,
+            )
+        }
+        (Some("idle"), Some(minutes), None) => match minutes.parse::<u32>() {
+            Ok(minutes) if (1..=settings::MAX_IDLE_MINUTES).contains(&minutes) => {
+                let mut settings = state.settings.write().await;
+                settings.entry(guild_id).or_default().idle_minutes = Some(minutes);
+
+                (
+                    format!(
+                        "I'll leave after {} with nothing playing.",
+                        duration::humanize(Duration::from_secs(u64::from(minutes) * 60))
+                    ),
+                    true,
+                )
+            }
+            _ => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+        },
+        (Some("removals"), Some(mode @ ("requester" | "dj")), None) => {
+            let mut settings = state.settings.write().await;
+            settings.entry(guild_id).or_default().requester_removals = mode == "requester";
+
+            let content = if mode == "requester" {
+                "Members can remove the tracks they queued, and DJs can remove any."
+            } else {
+                "Only DJs can remove tracks from the queue."
+            };
+
+            (content.to_string(), true)
+        }
+        (Some("norepeats"), Some("off"), None) => {
+            let mut settings = state.settings.write().await;
+            settings.entry(guild_id).or_default().no_repeats = None;
+
+            (
+                "Tracks can be queued again as soon as they finish.".to_string(),
+                true,
+            )
+        }
+        (Some("norepeats"), Some(hours), mode) => match NoRepeats::parse(hours, mode) {
+            Some(no_repeats) => {
+                let mut settings = state.settings.write().await;
+                settings.entry(guild_id).or_default().no_repeats = Some(no_repeats);
+
+                let mut content = format!(
+                    "Tracks that finished within {} can't be queued again",
+                    duration::humanize(no_repeats.window().to_std().unwrap_or_default())
+                );
+                content.push_str(if no_repeats.dj_override {
+                    ", except by DJs."
+                } else {
+                    "."
+                });
+
+                (content, true)
+            }
+            None => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+        },
+        (Some("template"), Some("nowplaying"), Some(_)) => {
+            let text = msg
+                .content
+                .split_once("nowplaying")
+                .map_or("", |(_, text)| text.trim());
+
+            let template = match text {
+                "off" => Ok(None),
+                text => Template::parse(text, settings::NOW_PLAYING_FIELDS).map(Some),
+            };
+
+            match template {
+                Ok(template) => {
+                    let content = match &template {
+                        Some(template) => format!("Now playing template set to: {}", template),
+                        None => "Now playing template reset.".to_string(),
+                    };
+
+                    let mut settings = state.settings.write().await;
+                    settings.entry(guild_id).or_default().now_playing_template = template;
+
+                    (content, true)
+                }
+                Err(why) => (why, false),
+            }
+        }
+        (Some("timezone"), Some("off"), None) => {
+            let mut settings = state.settings.write().await;
+            settings.entry(guild_id).or_default().timezone = None;
+
+            ("Time zone reset to UTC.".to_string(), true)
+        }
+        (Some("timezone"), Some(offset), None) => match settings::parse_offset(offset) {
+            Some(offset) => {
+                let mut settings = state.settings.write().await;
+                settings.entry(guild_id).or_default().timezone = Some(offset);
+
+                (format!("Time zone set to UTC{}.", offset), true)
+            }
+            None => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+        },
+        _ => (state.prefixed(guild_id, SETTINGS_USAGE), false),
+    };
+
+    if changed {
+        let action = format!("Settings changed: {}", content);
+        auditlog::record(&state, guild_id, AuditEntry::action(msg.author.id, &action)).await;
+
+        // The change is in effect either way; a failed save only means a
+        // restart would undo it.
+        if let Err(why) = state.storage.save_settings(&*state.settings.read().await) {
+            state.hooks.error(Some(guild_id), &*why);
+        }
+    }
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+pub async fn stats(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "stats command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let content = debug::stats(&state).await;
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+pub async fn debug(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "debug command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    if !permissions::is_admin(&state, &msg).await? {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content("Only server admins can request a debug report.")?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    let report = debug::report(&state, guild_id).await;
+    let filename = format!("debug-{}.txt", guild_id);
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content("Here's the debug report, attach it when filing a bug.")?
+        .files(&[(&filename, report.as_bytes())])
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const DEV_USAGE: &str = "Usage: `{prefix}dev state` or `{prefix}dev sync`";
+
+/// Development helpers, only routed in the configured dev guild.
+pub async fn dev(msg: Message, state: State) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "dev command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    if !permissions::is_admin(&state, &msg).await? {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content("Only server admins can use the dev commands.")?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    let content = match args::split(&msg.content).1 {
+        "state" => format!("```\n{}```", debug::dump(&state, guild_id).await),
+        // Picks up a changed command table without a restart.
+        "sync" => {
+            let changes = crate::slash::sync(&state.http, Some(guild_id)).await?;
+
+            if changes.is_empty() {
+                "Slash commands are up to date.".to_string()
+            } else {
+                format!("Slash commands {}.", changes.join(", "))
+            }
+        }
+        _ => state.prefixed(guild_id, DEV_USAGE),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const SIMULATE_USAGE: &str =
+    "Usage: `{prefix}simulate play <url>`, `{prefix}simulate join <channel>`, \
+    `{prefix}simulate stop` or `{prefix}simulate resume`";
+
+/// Runs a command's checks and resolution without touching playback, and
+/// reports what the real command would do.
+pub async fn simulate(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "simulate command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    if !permissions::is_admin(&state, &msg).await? {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content("Only server admins can simulate commands.")?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    let args = Args::parse(&msg.content);
+    let mut words = args.words();
+
+    let report = match (words.next(), words.next()) {
+        (Some("play"), Some(url)) => simulate_play(&state, &msg, url).await?,
+        (Some("join"), Some(channel)) => match args::channel(channel) {
+            Some(channel_id) => simulate_join(&state, guild_id, channel_id).await?,
+            None => vec![state.prefixed(guild_id, SIMULATE_USAGE)],
+        },
+        (Some("stop"), None) => match state.trackdata.read().await.get(&guild_id) {
+            Some(handle) => vec![format!(
+                "Would stop {:?} and keep it resumable for {}.",
+                TrackInfo::of(handle)
+                    .source_url
+                    .as_deref()
+                    .unwrap_or(track::UNKNOWN),
+                duration::humanize(RESUME_GRACE)
+            )],
+            None => vec!["Nothing is playing, so stopping would have no effect.".to_string()],
+        },
+        (Some("resume"), None) => {
+            let stopped = state.stopped.read().await;
+
+            match stopped
+                .get(&guild_id)
+                .filter(|stopped| clock::elapsed(&*state.clock, stopped.stopped_at) < RESUME_GRACE)
+            {
+                Some(stopped) => vec![format!(
+                    "Would resume {} from {}.",
+                    stopped.snapshot.info.source_url(),
+                    duration::format(stopped.snapshot.position)
+                )],
+                None => {
+                    vec!["There's no recently stopped track, so resume would fail.".to_string()]
+                }
+            }
+        }
+        _ => vec![state.prefixed(guild_id, SIMULATE_USAGE)],
+    };
+
+    let content = format!(
+        "Dry run, nothing was changed:\n{}",
+        report
+            .iter()
+            .map(|line| format!("• {}", line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Runs the checks [`play`] makes, in the same order, reporting where
+/// the track would be refused instead of telling the author.
+async fn simulate_play(
+    state: &State,
+    msg: &Message,
+    url: &str,
+) -> Result<Vec<String>, Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.unwrap();
+    let mut report = Vec::new();
+
+    if !crate::in_session(state, guild_id).await {
+        report.push("I'm not in a voice channel, so nothing would play.".to_string());
+    }
+
+    if event::is_locked(state, guild_id).await && !permissions::is_admin(state, msg).await? {
+        report.push(
+            "Requests are locked for the listening event, so it would be refused.".to_string(),
+        );
+        return Ok(report);
+    }
+
+    let today = state.clock.now().date().naive_utc();
+    if let Err(exceeded) = state.quotas.check(guild_id, today) {
+        report.push(format!("It would be refused: {}", exceeded));
+        return Ok(report);
+    }
+
+    let mix_video = sources::mix_video(url);
+
+    if mix_video.is_some() {
+        report
+            .push("That's a YouTube mix, so only the video it starts with would play.".to_string());
+    }
+
+    // Sources are lazy, so resolving only fetches metadata and no audio
+    // pipeline is started.
+    match state
+        .resolver
+        .resolve(mix_video.as_deref().unwrap_or(url))
+        .await
+    {
+        Ok(input) => {
+            let track = TrackInfo::from_metadata(&input.metadata);
+            report.push(format!(
+                "Would play **{}** by **{}** ({}).",
+                track.title(),
+                track.artist(),
+                track
+                    .duration
+                    .map_or_else(|| "unknown length".to_string(), duration::format)
+            ));
+
+            let url = track
+                .source_url
+                .as_deref()
+                .or(mix_video.as_deref())
+                .unwrap_or(url);
+            if let Err(too_long) = state.quotas.check_length(track.duration) {
+                report.push(format!("It would then be refused: {}", too_long));
+            } else if let Some((no_repeats, ago)) = recent_repeat(state, msg, url).await? {
+                report.push(format!(
+                    "It would then be refused: {}",
+                    repeat_refusal(track.title(), no_repeats, ago)
+                ));
+            }
+        }
+        Err(e) => report.push(format!("Resolving the URL would fail: {}", e)),
+    }
+
+    if let Some(quiet_hours) = active_quiet_hours(state, guild_id).await {
+        report.push(format!(
+            "Quiet hours are active ({}), so it would play at {}% volume.",
+            quiet_hours,
+            (playing_volume(state, guild_id).await * 100.0).round()
+        ));
+    }
+
+    Ok(report)
+}
+
+async fn simulate_join(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<Vec<String>, Box<dyn Error + Send + Sync + 'static>> {
+    if let Some(quiet_hours) = active_quiet_hours(state, guild_id).await {
+        return Ok(vec![format!(
+            "Would refuse: it's quiet hours ({}).",
+            quiet_hours
+        )]);
+    }
+
+    if would_fill_last_slot(state, guild_id, channel_id).await? {
+        return Ok(vec![format!(
+            "Joining <#{}> would take its last free slot. Non-admins would be refused; \
+             you'd be asked to confirm.",
+            channel_id
+        )]);
+    }
+
+    Ok(vec![format!("Would join <#{}>.", channel_id)])
+}
+
+pub async fn prefix(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "prefix command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+    let argument = args::split(&msg.content).1;
+
+    let content = if argument.is_empty() {
+        format!("Commands here start with `{}`.", state.prefix(guild_id))
+    } else if !permissions::is_admin(&state, &msg).await? {
+        "Only server admins can change the prefix.".to_string()
+    } else if argument == "reset" {
+        state.prefixes.set(guild_id, None)?;
+        auditlog::record(
+            &state,
+            guild_id,
+            AuditEntry::action(msg.author.id, "Reset the command prefix"),
+        )
+        .await;
+
+        format!("Back to the default prefix `{}`.", state.config.prefix)
+    } else {
+        match prefixes::check(argument) {
+            Ok(()) => {
+                state.prefixes.set(guild_id, Some(argument.to_string()))?;
+                auditlog::record(
+                    &state,
+                    guild_id,
+                    AuditEntry::action(
+                        msg.author.id,
+                        &format!("Set the command prefix to `{}`", argument),
+                    ),
+                )
+                .await;
+
+                format!(
+                    "Commands here now start with `{0}`, e.g. `{0}play`. \
+                     `{1}prefix` keeps working in case it's forgotten.",
+                    argument, state.config.prefix
+                )
+            }
+            Err(why) => why,
+        }
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Offers the tour in a button, since only a button press can be answered
+/// with messages the presser alone sees.
+pub async fn tour(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "tour command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content("Press the button for a quick tour of what I can do. Only you will see it.")?
+        .components(&[tour::start_button(true)])?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const SETDJ_USAGE: &str = "Usage: `{prefix}setdj <@&role>` or `{prefix}setdj off`";
+
+pub async fn set_dj(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "setdj command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+    let argument = args::split(&msg.content).1;
+
+    let dj_role = match argument {
+        "off" => Some(None),
+        role => args::role(role).map(Some),
+    };
+
+    let content = if argument.is_empty() {
+        match permissions::dj_role(&state, guild_id).await {
+            Some(role_id) => format!("DJ commands need <@&{}> here.", role_id),
+            None => "Anyone can use DJ commands here.".to_string(),
+        }
+    } else if !permissions::is_admin(&state, &msg).await? {
+        "Only server admins can change the DJ role.".to_string()
+    } else if let Some(dj_role) = dj_role {
+        {
+            let mut settings = state.settings.write().await;
+            settings.entry(guild_id).or_default().dj_role = dj_role;
+
+            if let Err(why) = state.storage.save_settings(&settings) {
+                state.hooks.error(Some(guild_id), &*why);
+            }
+        }
+
+        let action = match dj_role {
+            Some(role_id) => format!("Set the DJ role to <@&{}>", role_id),
+            None => "Turned off the DJ role".to_string(),
+        };
+        auditlog::record(&state, guild_id, AuditEntry::action(msg.author.id, &action)).await;
+
+        match dj_role {
+            Some(role_id) => format!(
+                "DJ commands such as `{prefix}skip` and `{prefix}stop` now need <@&{}> \
+                 or Manage Channels.",
+                role_id,
+                prefix = state.prefix(guild_id)
+            ),
+            None => "Anyone can use DJ commands again.".to_string(),
+        }
+    } else {
+        state.prefixed(guild_id, SETDJ_USAGE)
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// The longest a `j/dj grant` lasts.
+const MAX_DJ_GRANT: Duration = Duration::from_secs(24 * 60 * 60);
+
+const DJ_USAGE: &str =
+    "Usage: `{prefix}dj grant <@user> <duration>`, such as `30m` or `2h` up to a day, or `{prefix}dj list`";
+
+pub async fn dj(msg: Message, state: State) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "dj command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+    let args = Args::parse(&msg.content);
+    let mut words = args.words();
+    let now = state.clock.now();
+
+    let content = match (words.next(), words.next(), words.next(), words.next()) {
+        (Some("list"), None, None, None) => {
+            let grants = state.dj_grants.list(guild_id, now);
+
+            if grants.is_empty() {
+                "Nobody has been made a DJ for a while.".to_string()
+            } else {
+                let mut content = "Made DJs for a while:".to_string();
+                for (user_id, until) in grants {
+                    let _ = write!(
+                        content,
+                        "\n<@{}>, until <t:{}:R>",
+                        user_id,
+                        until.timestamp()
+                    );
+                }
+                content
+            }
+        }
+        (Some("grant"), Some(user), Some(length), None) => {
+            match (args::user(user), args::duration(length)) {
+                (Some(user_id), Some(length))
+                    if length > Duration::ZERO && length <= MAX_DJ_GRANT =>
+                {
+                    if permissions::can_grant_dj(&state, &msg).await? {
+                        grant_dj(&state, guild_id, user_id, length)?;
+
+                        let action = format!(
+                            "Made <@{}> a DJ for {}",
+                            user_id,
+                            duration::humanize(length)
+                        );
+                        auditlog::record(
+                            &state,
+                            guild_id,
+                            AuditEntry::action(msg.author.id, &action),
+                        )
+                        .await;
+
+                        format!(
+                            "<@{}> can use the DJ commands for the next {}.",
+                            user_id,
+                            duration::humanize(length)
+                        )
+                    } else {
+                        "Only DJs and admins can make someone a DJ.".to_string()
+                    }
+                }
+                _ => state.prefixed(guild_id, DJ_USAGE),
+            }
+        }
+        _ => state.prefixed(guild_id, DJ_USAGE),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Makes `user_id` a DJ for `length`, and ends the grant once it's up.
+fn grant_dj(
+    state: &State,
+    guild_id: GuildId,
+    user_id: UserId,
+    length: Duration,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    // Grants are at most a day, which chrono can always represent.
+    let until = state.clock.now() + chrono::Duration::from_std(length).unwrap();
+    state.dj_grants.grant(guild_id, user_id, until)?;
+
+    let state = Arc::clone(state);
+    tokio::spawn(async move {
+        state.clock.sleep(length).await;
+
+        if let Err(why) = state.dj_grants.expire(guild_id, user_id, until) {
+            state.hooks.error(Some(guild_id), &*why);
+        }
+    });
+
+    Ok(())
+}
+
+/// Replies to someone who tried a DJ command without being a DJ.
+pub async fn not_a_dj(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.unwrap();
+
+    let content = match permissions::dj_role(&state, guild_id).await {
+        Some(role_id) => format!(
+            "Only members with <@&{}> or Manage Channels can do that here. \
+             `{prefix}voteskip` is open to everyone.",
+            role_id,
+            prefix = state.prefix(guild_id)
+        ),
+        None => return Ok(()),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+const ADMIN_USAGE: &str = "Usage: `{prefix}admin unban <@user>`";
+
+pub async fn admin(
+    msg: Message,
+    state: State,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing::debug!(
+        "admin command in channel {} by {}",
+        msg.channel_id,
+        msg.author.name
+    );
+
+    let guild_id = msg.guild_id.unwrap();
+
+    if !permissions::is_admin(&state, &msg).await? {
+        state
+            .http
+            .create_message(msg.channel_id)
+            .content("Only server admins can use admin commands.")?
+            .exec()
+            .await?;
+
+        return Ok(());
+    }
+
+    let args = Args::parse(&msg.content);
+    let mut words = args.words();
+
+    let content = match (words.next(), words.next().and_then(args::user)) {
+        (Some("unban"), Some(user_id)) => {
+            if state.abuse.unban(guild_id, user_id, state.clock.instant()) {
+                let action = format!("Lifted the ban on <@{}>", user_id);
+                auditlog::record(&state, guild_id, AuditEntry::action(msg.author.id, &action))
+                    .await;
+
+                format!("<@{}> is no longer ignored.", user_id)
+            } else {
+                format!("<@{}> isn't banned.", user_id)
+            }
+        }
+        _ => state.prefixed(guild_id, ADMIN_USAGE),
+    };
+
+    state
+        .http
+        .create_message(msg.channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,274 @@
+use crate::{
+    backup::BackupConfig, ipv6::Ipv6Block, listenalong::ListenAlong, profile::Profile,
+    programs::Programs, providers::RateLimits,
+};
+use serde::Deserialize;
+use std::{env, error::Error, fs, io::ErrorKind, path::PathBuf};
+use twilight_model::id::{GuildId, UserId};
+
+/// Operator settings read from `config.json` at startup. Every field is
+/// optional except the token, which can also come from `DISCORD_TOKEN`.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub token: String,
+    /// What typed commands start with.
+    pub prefix: String,
+    /// The volume every track starts at, where 1.0 leaves it unchanged.
+    pub default_volume: f32,
+    /// A tracing filter such as `info` or `discord_music=debug`, used
+    /// when `RUST_LOG` isn't set.
+    pub log_level: String,
+    /// Bot operators, who count as admins in every guild.
+    pub owner_ids: Vec<UserId>,
+    /// Overridden by `PLAYBACK_PROFILE`.
+    pub profile: Profile,
+    /// Whether to read typed commands, which needs the message content
+    /// intent. Without it only slash commands and buttons work.
+    pub message_commands: bool,
+    /// Whether to ask for members' presences, a privileged intent that has
+    /// to be enabled in the developer portal too. With it the bot notices
+    /// people in its channel listening along on Spotify.
+    pub presence_intent: bool,
+    /// What to do about them.
+    pub listen_along: ListenAlong,
+    /// Where state that should outlive the process is saved. `null` keeps
+    /// everything in memory.
+    pub data_dir: Option<PathBuf>,
+    /// How often the data directory is copied aside, for `musicm8
+    /// restore`. `null` takes no backups.
+    pub backups: Option<BackupConfig>,
+    /// A test server to develop against, overridden by `DEV_GUILD_ID`.
+    /// Slash commands are registered there instead of globally, so changes
+    /// show up at once, every event is logged, and `j/dev` works there.
+    pub dev_guild_id: Option<GuildId>,
+    /// Where to record gateway events for `musicm8 replay`. `null` records
+    /// nothing.
+    pub event_log: Option<PathBuf>,
+    /// Where youtube-dl and ffmpeg are, and arguments to run them with.
+    pub programs: Programs,
+    /// An IPv6 subnet youtube-dl takes a fresh source address from for
+    /// every run. `null` leaves the address to the system.
+    pub ipv6_block: Option<Ipv6Block>,
+    /// A Spotify app's credentials, for playing Spotify links. `null`
+    /// turns them away.
+    pub spotify: Option<SpotifyCredentials>,
+    /// How many lookups a minute YouTube, SoundCloud, Bandcamp and other
+    /// sites get.
+    pub rate_limits: RateLimits,
+}
+
+/// From the app's page in the Spotify developer dashboard.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SpotifyCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+    /// The overlay server's `/spotify/callback`, as users' browsers reach
+    /// it and as it's registered for the app. Without it, `j/spotify link`
+    /// is turned off.
+    #[serde(default)]
+    pub redirect_uri: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            token: String::new(),
+            prefix: "j/".to_string(),
+            default_volume: 1.0,
+            log_level: "error".to_string(),
+            owner_ids: Vec::new(),
+            profile: Profile::default(),
+            message_commands: true,
+            presence_intent: false,
+            listen_along: ListenAlong::default(),
+            data_dir: Some(PathBuf::from("data")),
+            backups: None,
+            dev_guild_id: None,
+            event_log: None,
+            programs: Programs::default(),
+            ipv6_block: None,
+            spotify: None,
+            rate_limits: RateLimits::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads `path` if it exists and applies the environment overrides. The
+    /// token comes from `DISCORD_TOKEN`, else the file, else a `.token`
+    /// file as older setups have.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        let mut config = Self::read(path)?;
+
+        match env::var("DISCORD_TOKEN") {
+            Ok(token) => config.token = token,
+            Err(env::VarError::NotPresent) if config.token.is_empty() => {
+                match fs::read_to_string(".token") {
+                    Ok(token) => config.token = token,
+                    Err(e) if e.kind() == ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            Err(env::VarError::NotPresent) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        if let Some(profile) = Profile::from_env() {
+            config.profile = profile;
+        }
+
+        match env::var("DEV_GUILD_ID") {
+            Ok(id) => {
+                let id = id
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("DEV_GUILD_ID isn't a guild ID: {}", id))?;
+                config.dev_guild_id = Some(GuildId(id));
+            }
+            Err(env::VarError::NotPresent) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        config.token = config.token.trim().to_string();
+
+        if config.token.is_empty() {
+            return Err(format!("no bot token: set DISCORD_TOKEN or \"token\" in {}", path).into());
+        }
+
+        Ok(config)
+    }
+
+    /// Reads `path`, or the defaults if it doesn't exist, without the
+    /// overrides [`load`](Self::load) applies or requiring a token.
+    pub fn read(path: &str) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Self::from_json(&contents),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Parses and checks a config file's contents, without the token
+    /// fallbacks [`load`](Self::load) applies.
+    pub fn from_json(contents: &str) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        let config: Self = serde_json::from_str(contents)?;
+
+        if config.prefix.trim().is_empty() || config.prefix.contains(char::is_whitespace) {
+            return Err("prefix must be non-empty and contain no spaces".into());
+        }
+        if !(0.0..=2.0).contains(&config.default_volume) {
+            return Err("default_volume must be between 0 and 2".into());
+        }
+        for (name, program, _) in config.programs.each() {
+            if program.path.as_os_str().is_empty() {
+                return Err(format!("programs.{} needs a path", name.replace('-', "_")).into());
+            }
+        }
+        for (name, limit) in config.rate_limits.each() {
+            if limit == Some(0) {
+                return Err(format!("rate_limits.{} must be at least 1, or null", name).into());
+            }
+        }
+        if let Some(backups) = &config.backups {
+            if backups.interval_hours == 0 || backups.keep == 0 {
+                return Err("backups.interval_hours and backups.keep must be at least 1".into());
+            }
+            if config.data_dir.is_none() {
+                return Err("backups need a data_dir to back up".into());
+            }
+        }
+        if let Some(spotify) = &config.spotify {
+            if spotify.client_id.is_empty() || spotify.client_secret.is_empty() {
+                return Err("spotify needs a client_id and a client_secret".into());
+            }
+            if spotify.redirect_uri.as_deref() == Some("") {
+                return Err("spotify.redirect_uri can't be empty".into());
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::programs::Program;
+
+    #[test]
+    fn fills_in_defaults() {
+        let config = Config::from_json(
+            r#"{ "prefix": "!", "owner_ids": ["42"], "profile": "low-resource", "dev_guild_id": "7" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.prefix, "!");
+        assert_eq!(config.owner_ids, [UserId(42)]);
+        assert_eq!(config.profile, Profile::LowResource);
+        assert_eq!(config.dev_guild_id, Some(GuildId(7)));
+        assert_eq!(config.default_volume, 1.0);
+        assert!(config.token.is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        assert!(Config::from_json(r#"{ "default_volume": 5.0 }"#).is_err());
+        assert!(Config::from_json(r#"{ "prefix": "" }"#).is_err());
+        assert!(Config::from_json(r#"{ "volume": 0.5 }"#).is_err());
+        assert!(Config::from_json(r#"{ "programs": { "ffmpeg": { "path": "" } } }"#).is_err());
+        assert!(Config::from_json(r#"{ "programs": { "ffmpeg": { "args": [] } } }"#).is_err());
+        assert!(
+            Config::from_json(r#"{ "spotify": { "client_id": "id", "client_secret": "" } }"#)
+                .is_err()
+        );
+        assert!(Config::from_json(r#"{ "backups": { "keep": 0 } }"#).is_err());
+        assert!(Config::from_json(r#"{ "data_dir": null, "backups": {} }"#).is_err());
+    }
+
+    #[test]
+    fn programs_default_to_path() {
+        let config = Config::from_json(
+            r#"{ "programs": { "youtube_dl": { "path": "/opt/yt-dlp", "args": ["--proxy", "socks5://proxy:1080"] } } }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.programs.youtube_dl.path,
+            PathBuf::from("/opt/yt-dlp")
+        );
+        assert_eq!(
+            config.programs.youtube_dl.args,
+            ["--proxy", "socks5://proxy:1080"]
+        );
+        assert_eq!(config.programs.ffmpeg, Program::named("ffmpeg"));
+    }
+
+    #[test]
+    fn rate_limits_can_be_lifted() {
+        let config =
+            Config::from_json(r#"{ "rate_limits": { "youtube": null, "other": 10 } }"#).unwrap();
+
+        assert_eq!(
+            config.rate_limits,
+            RateLimits {
+                youtube: None,
+                soundcloud: Some(30),
+                bandcamp: Some(30),
+                other: Some(10),
+            }
+        );
+    }
+
+    #[test]
+    fn reads_the_ipv6_block() {
+        let config = Config::from_json(r#"{ "ipv6_block": "2001:db8:1::/48" }"#).unwrap();
+        assert_eq!(
+            config.ipv6_block.map(|block| block.to_string()),
+            Some("2001:db8:1::/48".to_string())
+        );
+
+        assert!(Config::from_json(r#"{ "ipv6_block": "2001:db8:1::" }"#).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::{commands, queue, track::TrackInfo, State};
+use serde::Deserialize;
+use songbird::tracks::PlayMode;
+use std::{
+    error::Error,
+    fmt::Write as _,
+    fs,
+    io::{self, ErrorKind},
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    spawn,
+};
+use twilight_model::id::GuildId;
+
+/// Sent on connect; clients that speak MPD check for the `OK MPD` prefix.
+const GREETING: &str = "OK MPD 0.19.0\n";
+
+/// Operator configuration read from `control.json`.
+///
+/// The socket controls a single guild, the one a self-hosted bot usually
+/// plays in. Anyone who can open the socket file can control playback, so
+/// its permissions are what protects it.
+#[derive(Debug, Deserialize)]
+pub struct ControlConfig {
+    pub path: PathBuf,
+    pub guild_id: GuildId,
+}
+
+impl ControlConfig {
+    pub fn load(path: &str) -> Result<Option<Self>, Box<dyn Error + Send + Sync + 'static>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// The subset of the MPD protocol the socket understands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Ping,
+    Status,
+    CurrentSong,
+    /// `pause` toggles, `pause 1` pauses and `pause 0` resumes.
+    Pause(Option<bool>),
+    Play,
+    Next,
+    Stop,
+    Close,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+
+        let command = match (name, words.next()) {
+            ("ping", None) => Command::Ping,
+            ("status", None) => Command::Status,
+            ("currentsong", None) => Command::CurrentSong,
+            ("pause", None) => Command::Pause(None),
+            ("pause", Some("1")) => Command::Pause(Some(true)),
+            ("pause", Some("0")) => Command::Pause(Some(false)),
+            // MPD's play takes a playlist position; only the current
+            // track can be resumed here.
+            ("play", None) => Command::Play,
+            ("next", None) => Command::Next,
+            ("stop", None) => Command::Stop,
+            ("close", None) => Command::Close,
+            ("pause", Some(_)) => {
+                return Err(format!("ACK [2@0] {{{}}} Boolean (0/1) expected", name))
+            }
+            ("ping" | "status" | "currentsong" | "play" | "next" | "stop" | "close", Some(_)) => {
+                return Err(format!("ACK [2@0] {{{}}} too many arguments", name))
+            }
+            _ => return Err(format!("ACK [5@0] {{{}}} unknown command", name)),
+        };
+
+        match words.next() {
+            Some(_) => Err(format!("ACK [2@0] {{{}}} too many arguments", name)),
+            None => Ok(command),
+        }
+    }
+}
+
+/// Accepts clients on the configured socket until it fails.
+pub async fn serve(config: ControlConfig, state: State) -> io::Result<()> {
+    // A socket left behind by an earlier run would make binding fail.
+    match fs::remove_file(&config.path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+
+    let listener = UnixListener::bind(&config.path)?;
+    tracing::info!("serving control socket on {}", config.path.display());
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = Arc::clone(&state);
+        let guild_id = config.guild_id;
+
+        spawn(async move {
+            if let Err(why) = client(stream, &state, guild_id).await {
+                tracing::debug!("control client dropped: {}", why);
+            }
+        });
+    }
+}
+
+async fn client(stream: UnixStream, state: &State, guild_id: GuildId) -> io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    write.write_all(GREETING.as_bytes()).await?;
+
+    let mut lines = BufReader::new(read).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let reply = match Command::parse(&line) {
+            Ok(Command::Close) => return Ok(()),
+            Ok(command) => match execute(state, guild_id, command).await {
+                Ok(body) => body + "OK\n",
+                Err(why) => format!("ACK [50@0] {{{}}} {}\n", line.trim(), why),
+            },
+            Err(ack) => ack + "\n",
+        };
+
+        write.write_all(reply.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+/// Runs one command, returning the response lines before the `OK`.
+async fn execute(
+    state: &State,
+    guild_id: GuildId,
+    command: Command,
+) -> Result<String, Box<dyn Error + Send + Sync + 'static>> {
+    let current = queue::current(state, guild_id).await;
+    let mut body = String::new();
+
+    match (command, current) {
+        // Close never gets here; the client loop hangs up first.
+        (Command::Ping | Command::Close, _) | (Command::CurrentSong, None) => {}
+        (Command::Status, current) => {
+            let info = match &current {
+                Some(handle) => Some(handle.get_info().await?),
+                None => None,
+            };
+            let state_name = match &info {
+                Some(info) if info.playing == PlayMode::Pause => "pause",
+                Some(_) => "play",
+                None => "stop",
+            };
+
+            writeln!(body, "state: {}", state_name)?;
+            writeln!(body, "playlistlength: {}", state.queue.list(guild_id).len())?;
+
+            if let (Some(handle), Some(info)) = (&current, &info) {
+                let track = queue::current_info(state, guild_id, handle).await;
+                writeln!(
+                    body,
+                    "elapsed: {:.3}",
+                    track.elapsed(info.position).as_secs_f64()
+                )?;
+                if let Some(duration) = track.duration {
+                    writeln!(body, "duration: {:.3}", duration.as_secs_f64())?;
+                }
+            }
+        }
+        (Command::CurrentSong, Some(handle)) => {
+            let track = TrackInfo::of(&handle);
+
+            if let Some(url) = &track.source_url {
+                writeln!(body, "file: {}", url)?;
+            }
+            if let Some(title) = &track.title {
+                writeln!(body, "Title: {}", title)?;
+            }
+            if let Some(artist) = &track.artist {
+                writeln!(body, "Artist: {}", artist)?;
+            }
+        }
+        (Command::Pause(_) | Command::Play, _) if state.breaks.read().await.contains(&guild_id) => {
+            return Err("on a break".into());
+        }
+        (Command::Pause(pause), Some(handle)) => {
+            let paused = handle.get_info().await?.playing == PlayMode::Pause;
+
+            if pause.unwrap_or(!paused) {
+                handle.pause()?;
+            } else {
+                handle.play()?;
+            }
+        }
+        (Command::Play, Some(handle)) => handle.play()?,
+        (Command::Next, Some(_)) => {
+            commands::skip_current(state, guild_id).await?;
+        }
+        (Command::Stop, _) => commands::stop_playback(state, guild_id).await,
+        (Command::Pause(_) | Command::Play | Command::Next, None) => {
+            return Err("nothing is playing".into());
+        }
+    }
+
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(Command::parse("status"), Ok(Command::Status));
+        assert_eq!(Command::parse("  next\r"), Ok(Command::Next));
+        assert_eq!(Command::parse("pause"), Ok(Command::Pause(None)));
+        assert_eq!(Command::parse("pause 1"), Ok(Command::Pause(Some(true))));
+        assert_eq!(Command::parse("pause 0"), Ok(Command::Pause(Some(false))));
+    }
+
+    #[test]
+    fn rejects_unknown_commands_and_arguments() {
+        assert_eq!(
+            Command::parse("lsinfo"),
+            Err("ACK [5@0] {lsinfo} unknown command".to_string())
+        );
+        assert_eq!(
+            Command::parse("pause maybe"),
+            Err("ACK [2@0] {pause} Boolean (0/1) expected".to_string())
+        );
+        assert_eq!(
+            Command::parse("next now"),
+            Err("ACK [2@0] {next} too many arguments".to_string())
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{fade, recording, State};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::{collections::HashSet, error::Error, time::Duration};
+use twilight_model::id::GuildId;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Background task enforcing every guild's curfew.
+///
+/// A guild is warned once it is within five minutes of its curfew. The
+/// session is ended on the first check after the curfew passes, which is
+/// when the time left jumps back up to almost a day.
+pub async fn run(state: State) {
+    let mut warned = HashSet::new();
+
+    loop {
+        state.clock.sleep(CHECK_INTERVAL).await;
+
+        let now = state.clock.now();
+        let curfews = state
+            .settings
+            .read()
+            .await
+            .iter()
+            .filter_map(|(guild_id, settings)| Some((*guild_id, settings.curfew?, settings.zone())))
+            .collect::<Vec<_>>();
+
+        warned.retain(|guild_id| curfews.iter().any(|(id, _, _)| id == guild_id));
+
+        for (guild_id, curfew, zone) in curfews {
+            let until = curfew.until(now, zone);
+            let warning_window = until <= ChronoDuration::minutes(5);
+
+            let result = match (warning_window, warned.contains(&guild_id)) {
+                (true, false) => {
+                    warned.insert(guild_id);
+                    warn(&state, guild_id, now + until).await
+                }
+                (false, true) => {
+                    warned.remove(&guild_id);
+                    disconnect(&state, guild_id).await
+                }
+                _ => Ok(()),
+            };
+
+            if let Err(why) = result {
+                state.hooks.error(Some(guild_id), &*why);
+            }
+        }
+    }
+}
+
+async fn warn(
+    state: &State,
+    guild_id: GuildId,
+    at: DateTime<Utc>,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    if !crate::in_session(state, guild_id).await {
+        return Ok(());
+    }
+
+    // Discord renders the timestamp relative to each reader's clock.
+    let content = format!(
+        "Curfew <t:{}:R>! I'll fade out and leave then.",
+        at.timestamp()
+    );
+
+    crate::announce(state, guild_id, &content).await
+}
+
+async fn disconnect(
+    state: &State,
+    guild_id: GuildId,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    if !crate::in_session(state, guild_id).await {
+        return Ok(());
+    }
+
+    state.queue.clear(guild_id);
+    let handle = state.trackdata.write().await.remove(&guild_id);
+
+    if let Some(handle) = handle {
+        if let Ok(info) = handle.get_info().await {
+            fade::fade(state, &handle, info.volume, 0.0).await;
+        }
+    }
+
+    recording::stop(state, guild_id).await?;
+    state.songbird.leave(guild_id).await?;
+
+    let content = crate::with_recap(state, guild_id, "Curfew reached, goodnight!");
+
+    crate::announce(state, guild_id, &content).await
+}
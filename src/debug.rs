@@ -0,0 +1,228 @@
+use crate::{migrations::SCHEMA_VERSION, programs::Programs, track::TrackInfo, State};
+use songbird::driver::opus::{coder::Encoder, Application, Channels, SampleRate};
+use std::fmt::Write;
+use twilight_model::id::GuildId;
+
+/// Optional subsystems compiled into this build.
+const FEATURES: &[&str] = &[
+    #[cfg(all(unix, feature = "control"))]
+    "control",
+    #[cfg(feature = "overlay")]
+    "overlay",
+    #[cfg(feature = "replay")]
+    "replay",
+    #[cfg(feature = "spotify")]
+    "spotify",
+    #[cfg(feature = "webhooks")]
+    "webhooks",
+];
+
+/// Checks the bot's runtime dependencies for `musicm8 doctor`, returning
+/// a line per dependency and whether they're all usable.
+pub async fn doctor(programs: &Programs) -> (String, bool) {
+    let mut report = String::new();
+    let mut healthy = true;
+
+    for (name, program, flag) in programs.each() {
+        match program.version(flag).await {
+            Ok(version) => {
+                let _ = writeln!(report, "ok      {}: {}", name, version);
+            }
+            Err(e) => {
+                healthy = false;
+                let _ = writeln!(
+                    report,
+                    "missing {} ({}): {}",
+                    name,
+                    program.path.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    // Opus is linked in rather than run, so building an encoder is the
+    // check.
+    match Encoder::new(SampleRate::Hz48000, Channels::Stereo, Application::Audio) {
+        Ok(_) => {
+            let _ = writeln!(report, "ok      opus: encoder available");
+        }
+        Err(e) => {
+            healthy = false;
+            let _ = writeln!(report, "broken  opus: {}", e);
+        }
+    }
+
+    (report, healthy)
+}
+
+/// The overview posted by `j/stats`, for anyone wondering what this
+/// instance of the bot is up to and what it was built with.
+pub async fn stats(state: &State) -> String {
+    let features = if FEATURES.is_empty() {
+        "none".to_string()
+    } else {
+        FEATURES.join(", ")
+    };
+
+    format!(
+        "**musicm8 {}**\n\
+         Servers: {}\n\
+         Playing in: {}\n\
+         Tracks queued: {}\n\
+         Features: {}\n\
+         Schema version: {}",
+        env!("CARGO_PKG_VERSION"),
+        state.known_guilds.read().await.len(),
+        state.trackdata.read().await.len(),
+        state.queue.total(),
+        features,
+        SCHEMA_VERSION
+    )
+}
+
+/// Builds the plain-text report posted by `j/debug`.
+pub async fn report(state: &State, guild_id: GuildId) -> String {
+    let mut report = String::new();
+
+    let _ = writeln!(report, "musicm8 {}", env!("CARGO_PKG_VERSION"));
+    for (name, program, flag) in state.config.programs.each() {
+        let version = match program.version(flag).await {
+            Ok(version) => version,
+            Err(e) => format!("unavailable ({})", e),
+        };
+        let _ = writeln!(report, "{}: {}", name, version);
+    }
+    if let Some(block) = state.config.ipv6_block {
+        let _ = writeln!(report, "source addresses: {}", block);
+    }
+    let _ = writeln!(report, "features: {}", FEATURES.join(", "));
+    let _ = writeln!(report, "guild: {}", guild_id);
+
+    let _ = writeln!(report, "\n[shards]");
+
+    let mut shards = state.cluster.info().into_iter().collect::<Vec<_>>();
+    shards.sort_by_key(|(id, _)| *id);
+
+    for (id, info) in shards {
+        let lag = state.event_lag.get(id);
+        let _ = writeln!(
+            report,
+            "{}: {:?}, average latency {:?}, event lag {:?} (max {:?}), {} dropped",
+            id,
+            info.stage(),
+            info.latency().average(),
+            lag.last,
+            lag.max,
+            lag.dropped
+        );
+    }
+
+    let _ = writeln!(report, "\n[voice]");
+
+    match state.songbird.get(guild_id) {
+        Some(call_lock) => {
+            let call = call_lock.lock().await;
+
+            match call.current_connection() {
+                Some(info) => {
+                    let _ = writeln!(
+                        report,
+                        "connected to channel {:?} via {}",
+                        info.channel_id.map(|channel| channel.0),
+                        info.endpoint
+                    );
+                }
+                None => {
+                    let _ = writeln!(report, "call exists but is not connected");
+                }
+            }
+        }
+        None => {
+            let _ = writeln!(report, "no call");
+        }
+    }
+
+    let _ = writeln!(report, "\n[source]");
+
+    match state.trackdata.read().await.get(&guild_id) {
+        Some(handle) => {
+            let track = TrackInfo::of(handle);
+
+            let _ = writeln!(report, "url: {:?}", track.source_url);
+            let _ = writeln!(report, "title: {:?}", track.title);
+            let _ = writeln!(report, "duration: {:?}", track.duration);
+            let _ = writeln!(report, "seekable: {}", handle.is_seekable());
+
+            match handle.get_info().await {
+                Ok(info) => {
+                    let _ = writeln!(
+                        report,
+                        "state: {:?} at {:?}, volume {}",
+                        info.playing, info.position, info.volume
+                    );
+                }
+                Err(e) => {
+                    let _ = writeln!(report, "state: unavailable ({})", e);
+                }
+            }
+        }
+        None => {
+            let _ = writeln!(report, "nothing playing");
+        }
+    }
+
+    let _ = writeln!(report, "\n[recent logs]");
+
+    for line in state.logs.recent(guild_id) {
+        let _ = writeln!(report, "{}", line);
+    }
+
+    report
+}
+
+/// The guild's in-memory state for `j/dev state`, as the debug report
+/// leaves it out.
+pub async fn dump(state: &State, guild_id: GuildId) -> String {
+    let mut dump = String::new();
+
+    let _ = writeln!(dump, "queue: {:?}", state.queue.list(guild_id));
+    let _ = writeln!(dump, "loop: {:?}", state.loops.read().await.get(&guild_id));
+    let _ = writeln!(
+        dump,
+        "now playing: {:?}",
+        state.now_playing.read().await.get(&guild_id)
+    );
+    let _ = writeln!(
+        dump,
+        "on a break: {}",
+        state.breaks.read().await.contains(&guild_id)
+    );
+    let _ = writeln!(
+        dump,
+        "event: {:?}",
+        state.events.read().await.get(&guild_id)
+    );
+    let _ = writeln!(
+        dump,
+        "jingles: {}",
+        state
+            .jingles
+            .read()
+            .await
+            .get(&guild_id)
+            .map_or(0, Vec::len)
+    );
+    let _ = writeln!(
+        dump,
+        "text channel: {:?}",
+        state.text_channels.read().await.get(&guild_id)
+    );
+    let _ = writeln!(
+        dump,
+        "settings: {:?}",
+        state.settings.read().await.get(&guild_id)
+    );
+
+    dump
+}
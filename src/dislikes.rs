@@ -0,0 +1,79 @@
+use crate::{commands, track::TrackInfo, State};
+use std::error::Error;
+use twilight_model::id::MessageId;
+
+/// Skips the track `message_id` announced if it is still playing and
+/// enough of the listeners in the bot's channel have downvoted it.
+///
+/// Only downvotes from members currently in the call count, so a vote
+/// left behind by someone who has since left doesn't skip anything.
+pub async fn check(
+    state: &State,
+    message_id: MessageId,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let (guild_id, url, downvoters) = match state.ratings.downvoters(message_id) {
+        Some(downvotes) => downvotes,
+        None => return Ok(()),
+    };
+
+    let threshold = match state
+        .settings
+        .read()
+        .await
+        .get(&guild_id)
+        .and_then(|settings| settings.dislike_skip)
+    {
+        Some(threshold) => threshold,
+        None => return Ok(()),
+    };
+
+    let channel_id = match state.songbird.get(guild_id) {
+        Some(call_lock) => match call_lock.lock().await.current_channel() {
+            Some(channel_id) => channel_id.0,
+            None => return Ok(()),
+        },
+        None => return Ok(()),
+    };
+
+    let listening = {
+        let voice_states = state.voice_states.read().await;
+
+        downvoters
+            .iter()
+            .filter(|user_id| {
+                voice_states
+                    .get(&(guild_id, **user_id))
+                    .is_some_and(|channel| channel.0 == channel_id)
+            })
+            .count()
+    };
+
+    if listening < threshold as usize {
+        return Ok(());
+    }
+
+    let mut trackdata = state.trackdata.write().await;
+
+    let title = match trackdata.get(&guild_id).map(TrackInfo::of) {
+        Some(track) if track.source_url.as_deref() == Some(url.as_str()) => {
+            track.title.unwrap_or_else(|| url.clone())
+        }
+        _ => return Ok(()),
+    };
+
+    if let Some(handle) = trackdata.remove(&guild_id) {
+        handle.stop()?;
+    }
+    drop(trackdata);
+
+    commands::play_next(state, guild_id).await?;
+
+    let content = format!(
+        "Skipped **{}** after {} listener{} disliked it.",
+        title,
+        listening,
+        if listening == 1 { "" } else { "s" }
+    );
+
+    crate::announce(state, guild_id, &content).await
+}
@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+/// Parses a position or length typed by a user or taken from a URL:
+/// plain seconds (`90`, `90s`), YouTube's `1h2m3s` form, or a clock time
+/// (`1:30`, `1:02:03`).
+///
+/// Units must come largest first and at most once each, and a clock time
+/// has at most hours, minutes and seconds, with minutes and seconds under
+/// 60 after the first part. Values too large to represent don't parse.
+pub fn parse(value: &str) -> Option<Duration> {
+    if value.contains(':') {
+        return parse_clock(value);
+    }
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return value
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| Duration::from_secs(secs));
+    }
+
+    let mut secs: u64 = 0;
+    let mut digits = String::new();
+    let mut units = ['h', 'm', 's'].iter();
+
+    for c in value.chars() {
+        match c {
+            '0'..='9' => digits.push(c),
+            'h' | 'm' | 's' if !digits.is_empty() => {
+                // Skipping ahead to the unit also rules out repeats and
+                // units out of order.
+                units.by_ref().find(|&&unit| unit == c)?;
+                let unit = match c {
+                    'h' => 3600,
+                    'm' => 60,
+                    _ => 1,
+                };
+                secs = secs.checked_add(digits.parse::<u64>().ok()?.checked_mul(unit)?)?;
+                digits.clear();
+            }
+            _ => return None,
+        }
+    }
+
+    (!value.is_empty() && digits.is_empty()).then(|| Duration::from_secs(secs))
+}
+
+fn parse_clock(value: &str) -> Option<Duration> {
+    let parts = value.split(':').collect::<Vec<_>>();
+    if parts.len() > 3 {
+        return None;
+    }
+
+    let mut secs: u64 = 0;
+
+    for (index, part) in parts.iter().enumerate() {
+        if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let part = part.parse::<u64>().ok()?;
+        if index > 0 && part >= 60 {
+            return None;
+        }
+
+        secs = secs.checked_mul(60)?.checked_add(part)?;
+    }
+
+    Some(Duration::from_secs(secs))
+}
+
+/// A position or length as a clock time, `m:ss`, or `h:mm:ss` from an
+/// hour on.
+pub fn format(duration: Duration) -> String {
+    let secs = duration.as_secs();
+
+    if secs < 3600 {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        format_hours(duration)
+    }
+}
+
+/// Like [`format`], but always with hours, for totals where the reader
+/// expects them.
+pub fn format_hours(duration: Duration) -> String {
+    let secs = duration.as_secs();
+
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// A wait or a limit in words, e.g. `5 minutes` or `1 hour 30 minutes`,
+/// rounded down to whole minutes once it's at least one.
+pub fn humanize(duration: Duration) -> String {
+    let secs = duration.as_secs();
+
+    if secs < 60 {
+        return plural(secs, "second");
+    }
+
+    match (secs / 3600, secs / 60 % 60) {
+        (0, minutes) => plural(minutes, "minute"),
+        (hours, 0) => plural(hours, "hour"),
+        (hours, minutes) => format!("{} {}", plural(hours, "hour"), plural(minutes, "minute")),
+    }
+}
+
+fn plural(count: u64, unit: &str) -> String {
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_form() {
+        assert_eq!(parse("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse("1h2m3s"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse("1:30"), Some(Duration::from_secs(90)));
+        assert_eq!(parse("1:30:00"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse("90:00"), Some(Duration::from_secs(5400)));
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        for value in [
+            "",
+            "soon",
+            "5m3",
+            "m",
+            "1m1h",
+            "1m1m",
+            "+5",
+            "-5",
+            "1:",
+            ":30",
+            "1:60",
+            "1:2:3:4",
+            "1::2",
+            "1:+5",
+            "99999999999999999999",
+            "5124095576030432h",
+        ] {
+            assert_eq!(parse(value), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn formats_clock_times() {
+        assert_eq!(format(Duration::from_secs(0)), "0:00");
+        assert_eq!(format(Duration::from_secs(61)), "1:01");
+        assert_eq!(format(Duration::from_secs(3599)), "59:59");
+        assert_eq!(format(Duration::from_secs(3723)), "1:02:03");
+        assert_eq!(format_hours(Duration::from_secs(61)), "0:01:01");
+    }
+
+    #[test]
+    fn formats_waits_in_words() {
+        assert_eq!(humanize(Duration::from_secs(1)), "1 second");
+        assert_eq!(humanize(Duration::from_secs(45)), "45 seconds");
+        assert_eq!(humanize(Duration::from_secs(5 * 60 + 10)), "5 minutes");
+        assert_eq!(humanize(Duration::from_secs(3600)), "1 hour");
+        assert_eq!(humanize(Duration::from_secs(5400)), "1 hour 30 minutes");
+    }
+
+    #[test]
+    fn formatted_times_parse_back() {
+        for secs in [0, 59, 60, 3599, 3600, 86_399, 360_000] {
+            let duration = Duration::from_secs(secs);
+            assert_eq!(parse(&format(duration)), Some(duration));
+            assert_eq!(parse(&format_hours(duration)), Some(duration));
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::State;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::{sync::Arc, time::Duration};
+use tokio::spawn;
+use twilight_model::id::GuildId;
+
+/// A listening event announced with `j/event start`. Once it starts,
+/// requests are locked to admins until the event is stopped.
+#[derive(Clone, Copy, Debug)]
+pub struct ListeningEvent {
+    pub starts_at: DateTime<Utc>,
+    pub locked: bool,
+    /// Tells the countdown for this event apart from one for an event
+    /// that replaced it.
+    id: u64,
+}
+
+/// Schedules an event `countdown` from now, replacing any earlier one, and
+/// spawns the countdown that locks requests and announces the start.
+pub async fn schedule(state: &State, guild_id: GuildId, countdown: Duration) -> DateTime<Utc> {
+    let starts_at = state.clock.now() + ChronoDuration::from_std(countdown).unwrap();
+    let id = rand::random();
+
+    state.events.write().await.insert(
+        guild_id,
+        ListeningEvent {
+            starts_at,
+            locked: false,
+            id,
+        },
+    );
+
+    let state = Arc::clone(state);
+    spawn(async move {
+        state.clock.sleep(countdown).await;
+
+        if !start(&state, guild_id, id).await {
+            return;
+        }
+
+        let role = state
+            .settings
+            .read()
+            .await
+            .get(&guild_id)
+            .and_then(|settings| settings.event_role);
+        let content = format!(
+            "{}The listening event is starting! Requests are locked to admins until \
+             `{prefix}event stop`.",
+            role.map_or_else(String::new, |role| format!("<@&{}> ", role)),
+            prefix = state.prefix(guild_id)
+        );
+
+        if let Err(why) = crate::announce(&state, guild_id, &content).await {
+            state.hooks.error(Some(guild_id), &*why);
+        }
+    });
+
+    starts_at
+}
+
+/// Locks the event if it is still the one scheduled as `id`.
+async fn start(state: &State, guild_id: GuildId, id: u64) -> bool {
+    match state.events.write().await.get_mut(&guild_id) {
+        Some(event) if event.id == id => {
+            event.locked = true;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Ends or calls off the guild's event, returning it if there was one.
+pub async fn cancel(state: &State, guild_id: GuildId) -> Option<ListeningEvent> {
+    state.events.write().await.remove(&guild_id)
+}
+
+pub async fn is_locked(state: &State, guild_id: GuildId) -> bool {
+    state
+        .events
+        .read()
+        .await
+        .get(&guild_id)
+        .is_some_and(|event| event.locked)
+}
@@ -0,0 +1,245 @@
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream};
+#[cfg(feature = "replay")]
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server,
+};
+use serde::de::DeserializeSeed;
+use serde_json::{json, Value};
+#[cfg(feature = "replay")]
+use std::{convert::Infallible, net::SocketAddr};
+use std::{
+    convert::TryFrom,
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+use twilight_gateway::Event;
+use twilight_model::gateway::event::{DispatchEvent, DispatchEventWithTypeDeserializer};
+#[cfg(feature = "replay")]
+use twilight_model::id::UserId;
+
+/// Fields blanked out before an event is written, so a shared log can't
+/// be used to take over a voice connection or a gateway session, or to
+/// answer an interaction.
+const SECRETS: &[&str] = &["token", "session_id"];
+
+/// Records the gateway's dispatch events to a file, one JSON object per
+/// line, for `musicm8 replay` to feed back through the dispatcher.
+///
+/// Connection events such as heartbeats aren't dispatches and are left
+/// out; nothing the bot handles depends on them.
+#[derive(Debug)]
+pub struct EventLog {
+    file: Mutex<File>,
+}
+
+impl EventLog {
+    /// Opens `path` for appending, so restarts add to the same log.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, shard_id: u64, at: DateTime<Utc>, event: &Event) {
+        let entry = match entry(shard_id, at, event) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        // Written a line at a time, so a crash loses at most the last event.
+        let mut file = self.file.lock().unwrap();
+        if let Err(why) = writeln!(file, "{}", entry) {
+            tracing::warn!("failed to record a gateway event: {}", why);
+        }
+    }
+}
+
+/// The log line for `event`, unless it isn't a dispatch.
+pub fn entry(shard_id: u64, at: DateTime<Utc>, event: &Event) -> Option<Value> {
+    let dispatch = DispatchEvent::try_from(event.clone()).ok()?;
+    let name = dispatch.kind().name()?;
+    let mut data = serde_json::to_value(&dispatch).ok()?;
+    scrub(&mut data);
+
+    Some(json!({
+        "at": at.to_rfc3339(),
+        "shard": shard_id,
+        "type": name,
+        "data": data,
+    }))
+}
+
+fn scrub(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                if SECRETS.contains(&name.as_str()) && field.is_string() {
+                    *field = Value::String("scrubbed".to_string());
+                } else {
+                    scrub(field);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(scrub),
+        _ => {}
+    }
+}
+
+/// One recorded event: the shard it arrived on, when, and the event.
+pub type Recorded = (u64, DateTime<Utc>, Event);
+
+pub fn parse(line: &str) -> Result<Recorded, Box<dyn Error + Send + Sync + 'static>> {
+    let entry: Value = serde_json::from_str(line)?;
+
+    let at = DateTime::parse_from_rfc3339(entry["at"].as_str().ok_or("missing `at`")?)?;
+    let shard_id = entry["shard"].as_u64().ok_or("missing `shard`")?;
+    let name = entry["type"].as_str().ok_or("missing `type`")?;
+    let dispatch = DispatchEventWithTypeDeserializer::new(name).deserialize(&entry["data"])?;
+
+    Ok((
+        shard_id,
+        at.with_timezone(&Utc),
+        Event::from(Box::new(dispatch)),
+    ))
+}
+
+/// Reads a whole log, skipping the lines that don't parse, such as one
+/// cut short by a crash.
+pub fn read(path: &Path) -> io::Result<Vec<Recorded>> {
+    let contents = fs::read_to_string(path)?;
+
+    Ok(contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(number, line)| match parse(line) {
+            Ok(recorded) => Some(recorded),
+            Err(why) => {
+                tracing::warn!("skipping line {} of the event log: {}", number + 1, why);
+                None
+            }
+        })
+        .collect())
+}
+
+/// The recorded events as the gateway stream they came from, spaced out
+/// as they originally were so timers fire between them as they did.
+pub fn replay(recorded: Vec<Recorded>) -> impl Stream<Item = (u64, Event)> + Unpin {
+    let previous = recorded.first().map(|(_, at, _)| *at);
+
+    Box::pin(stream::unfold(
+        (recorded.into_iter(), previous),
+        |(mut recorded, previous)| async move {
+            let (shard_id, at, event) = recorded.next()?;
+            let gap = previous
+                .and_then(|previous| (at - previous).to_std().ok())
+                .unwrap_or_default();
+            tokio::time::sleep(gap).await;
+
+            Some(((shard_id, event), (recorded, Some(at))))
+        },
+    ))
+}
+
+/// Stands in for Discord's HTTP API during a replay, so nothing reaches
+/// the real guilds. Each request is logged and gets a plausible answer:
+/// posted messages come back as messages, everything else as `{}`.
+#[cfg(feature = "replay")]
+pub fn serve_stub(user_id: UserId) -> Result<SocketAddr, hyper::Error> {
+    let make_service = make_service_fn(move |_| async move {
+        Ok::<_, Infallible>(service_fn(move |request: Request<Body>| async move {
+            let method = request.method().clone();
+            let path = request.uri().path().to_string();
+            let bytes = hyper::body::to_bytes(request.into_body()).await?;
+            let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+            tracing::info!("replay: {} {} {}", method, path, body);
+
+            let response = match path.rsplit_once("/messages") {
+                Some((channel, "")) if method == Method::POST => {
+                    let channel_id = channel.rsplit('/').next().unwrap_or_default();
+                    stub_message(channel_id, user_id, &body)
+                }
+                _ => json!({}),
+            };
+
+            Ok::<_, hyper::Error>(Response::new(Body::from(response.to_string())))
+        }))
+    });
+
+    let server = Server::try_bind(&([127, 0, 0, 1], 0).into())?.serve(make_service);
+    let address = server.local_addr();
+
+    tokio::spawn(server);
+
+    Ok(address)
+}
+
+#[cfg(feature = "replay")]
+fn stub_message(channel_id: &str, user_id: UserId, body: &Value) -> Value {
+    json!({
+        "attachments": [],
+        "author": {
+            "avatar": null,
+            "discriminator": "0000",
+            "id": user_id.to_string(),
+            "username": "musicm8",
+        },
+        "channel_id": channel_id,
+        "content": body["content"].as_str().unwrap_or_default(),
+        "edited_timestamp": null,
+        "embeds": [],
+        "id": rand::random::<u32>().max(1).to_string(),
+        "type": 0,
+        "mention_everyone": false,
+        "mention_roles": [],
+        "mentions": [],
+        "pinned": false,
+        "timestamp": Utc::now().to_rfc3339(),
+        "tts": false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use twilight_model::gateway::payload::VoiceServerUpdate;
+
+    #[test]
+    fn round_trips_without_secrets() {
+        let update = serde_json::from_value::<VoiceServerUpdate>(json!({
+            "endpoint": "voice.example.com",
+            "guild_id": "1",
+            "token": "secret",
+        }))
+        .unwrap();
+        let at = Utc.ymd(2021, 1, 1).and_hms(2, 0, 0);
+
+        let line = entry(3, at, &Event::VoiceServerUpdate(update))
+            .unwrap()
+            .to_string();
+        assert!(!line.contains("secret"));
+
+        match parse(&line).unwrap() {
+            (3, parsed_at, Event::VoiceServerUpdate(update)) => {
+                assert_eq!(parsed_at, at);
+                assert_eq!(update.endpoint.as_deref(), Some("voice.example.com"));
+                assert_eq!(update.token, "scrubbed");
+            }
+            other => panic!("unexpected entry: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn skips_connection_events() {
+        assert!(entry(0, Utc::now(), &Event::GatewayHeartbeatAck).is_none());
+    }
+}
@@ -0,0 +1,17 @@
+use crate::State;
+use songbird::tracks::TrackHandle;
+use std::time::Duration;
+
+const STEPS: u32 = 20;
+pub const DURATION: Duration = Duration::from_secs(5);
+
+/// Ramps the track's volume from `from` to `to` over [`DURATION`].
+pub async fn fade(state: &State, handle: &TrackHandle, from: f32, to: f32) {
+    for step in 1..=STEPS {
+        let volume = from + (to - from) * step as f32 / STEPS as f32;
+
+        // The track may end mid-fade, which is fine to ignore.
+        let _ = handle.set_volume(volume);
+        state.clock.sleep(DURATION / STEPS).await;
+    }
+}
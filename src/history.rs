@@ -0,0 +1,113 @@
+use crate::{identify::identify, settings::MAX_NO_REPEAT_HOURS, storage::Storage};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::{collections::HashMap, error::Error, sync::Mutex};
+use twilight_model::id::GuildId;
+
+/// What a track is remembered by: its canonical address, so the same video
+/// shared with a timestamp or from a playlist still counts as a repeat.
+pub fn key(url: &str) -> String {
+    identify(url).map_or_else(|| url.to_string(), |identified| identified.canonical)
+}
+
+/// When each guild's tracks last finished playing, saved to
+/// `history.json` whenever one does, so `j/settings norepeats` holds
+/// across restarts. Plays are forgotten once they're older than the
+/// longest window that can be set.
+#[derive(Debug, Default)]
+pub struct History {
+    storage: Storage,
+    /// Finish times in seconds since the epoch, by guild and [`key`].
+    played: Mutex<HashMap<GuildId, HashMap<String, i64>>>,
+}
+
+impl History {
+    pub fn load(storage: &Storage) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(Self {
+            storage: storage.clone(),
+            played: Mutex::new(storage.load("history.json")?),
+        })
+    }
+
+    /// Notes that the track at `url` finished at `now`, and saves.
+    pub fn record(
+        &self,
+        guild_id: GuildId,
+        url: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let mut played = self.played.lock().unwrap();
+
+        let forgotten = (now - Duration::hours(i64::from(MAX_NO_REPEAT_HOURS))).timestamp();
+        for tracks in played.values_mut() {
+            tracks.retain(|_, finished| *finished > forgotten);
+        }
+        played.retain(|_, tracks| !tracks.is_empty());
+
+        played
+            .entry(guild_id)
+            .or_default()
+            .insert(key(url), now.timestamp());
+
+        self.storage.save("history.json", &*played)
+    }
+
+    /// When the track at `url` last finished in the guild, if it has.
+    pub fn last_played(&self, guild_id: GuildId, url: &str) -> Option<DateTime<Utc>> {
+        let played = self.played.lock().unwrap();
+        let finished = *played.get(&guild_id)?.get(&key(url))?;
+
+        Some(Utc.timestamp(finished, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD: GuildId = GuildId(1);
+
+    #[test]
+    fn remembers_tracks_by_canonical_address() {
+        let history = History::default();
+        let now = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+
+        history
+            .record(GUILD, "https://youtu.be/abc?t=30", now)
+            .unwrap();
+
+        assert_eq!(
+            history.last_played(GUILD, "https://www.youtube.com/watch?v=abc"),
+            Some(now)
+        );
+        assert_eq!(
+            history.last_played(GuildId(2), "https://youtu.be/abc"),
+            None
+        );
+        assert_eq!(history.last_played(GUILD, "https://youtu.be/xyz"), None);
+    }
+
+    #[test]
+    fn forgets_plays_past_the_longest_window() {
+        let history = History::default();
+        let start = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+
+        history
+            .record(GUILD, "https://example.com/a.mp3", start)
+            .unwrap();
+        history
+            .record(
+                GUILD,
+                "https://example.com/b.mp3",
+                start + Duration::hours(i64::from(MAX_NO_REPEAT_HOURS) + 1),
+            )
+            .unwrap();
+
+        assert_eq!(
+            history.last_played(GUILD, "https://example.com/a.mp3"),
+            None
+        );
+        assert!(history
+            .last_played(GUILD, "https://example.com/b.mp3")
+            .is_some());
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{commands, track::TrackInfo, State};
+use async_trait::async_trait;
+use songbird::{Event, EventContext, EventHandler};
+use std::{error::Error, fmt, sync::Arc, time::Duration};
+use twilight_model::id::GuildId;
+
+/// Observer for track transitions.
+///
+/// Every method has an empty default, so implementors only override the
+/// transitions they care about. Hooks are registered once in
+/// `StateRef::new` and are called synchronously, so anything slow should
+/// be handed off to a spawned task.
+pub trait PlaybackHook: Send + Sync {
+    /// A source was resolved and is about to be handed to the driver.
+    fn on_enqueue(&self, _guild_id: GuildId, _track: &TrackInfo) {}
+
+    /// The driver has been given the track and will start playing it.
+    fn on_track_start(&self, _guild_id: GuildId, _track: &TrackInfo) {}
+
+    /// The track finished, either naturally or because it was stopped.
+    fn on_track_end(&self, _guild_id: GuildId, _track: &TrackInfo) {}
+
+    /// A track ended with nothing queued after it, leaving the guild idle.
+    fn on_queue_empty(&self, _guild_id: GuildId) {}
+
+    /// A command handler or source resolution failed.
+    fn on_error(&self, _guild_id: Option<GuildId>, _error: &(dyn Error + Send + Sync)) {}
+}
+
+#[derive(Default)]
+pub struct Hooks {
+    hooks: Vec<Box<dyn PlaybackHook>>,
+}
+
+impl Hooks {
+    pub fn register(&mut self, hook: impl PlaybackHook + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    pub fn enqueue(&self, guild_id: GuildId, track: &TrackInfo) {
+        for hook in &self.hooks {
+            hook.on_enqueue(guild_id, track);
+        }
+    }
+
+    pub fn track_start(&self, guild_id: GuildId, track: &TrackInfo) {
+        for hook in &self.hooks {
+            hook.on_track_start(guild_id, track);
+        }
+    }
+
+    pub fn track_end(&self, guild_id: GuildId, track: &TrackInfo) {
+        for hook in &self.hooks {
+            hook.on_track_end(guild_id, track);
+        }
+    }
+
+    pub fn queue_empty(&self, guild_id: GuildId) {
+        for hook in &self.hooks {
+            hook.on_queue_empty(guild_id);
+        }
+    }
+
+    pub fn error(&self, guild_id: Option<GuildId>, error: &(dyn Error + Send + Sync)) {
+        for hook in &self.hooks {
+            hook.on_error(guild_id, error);
+        }
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("registered", &self.hooks.len())
+            .finish()
+    }
+}
+
+/// Logs every transition through `tracing`.
+pub struct TracingHook;
+
+impl PlaybackHook for TracingHook {
+    fn on_enqueue(&self, guild_id: GuildId, track: &TrackInfo) {
+        tracing::info!(guild_id = %guild_id, "enqueued {:?}", track.source_url);
+    }
+
+    fn on_track_start(&self, guild_id: GuildId, track: &TrackInfo) {
+        tracing::info!(guild_id = %guild_id, "started {:?}", track.source_url);
+    }
+
+    fn on_track_end(&self, guild_id: GuildId, track: &TrackInfo) {
+        tracing::info!(guild_id = %guild_id, "ended {:?}", track.source_url);
+    }
+
+    fn on_queue_empty(&self, guild_id: GuildId) {
+        tracing::info!(guild_id = %guild_id, "queue empty");
+    }
+
+    fn on_error(&self, guild_id: Option<GuildId>, error: &(dyn Error + Send + Sync)) {
+        match guild_id {
+            Some(guild_id) => tracing::warn!(guild_id = %guild_id, "error: {}", error),
+            None => tracing::warn!("error: {}", error),
+        }
+    }
+}
+
+/// Songbird track event handler forwarding `TrackEvent::End` to the hooks,
+/// counting the time played against the guild's quota and starting and
+/// announcing the next queued track (see [`commands::advance`] and
+/// [`commands::announce_next`]).
+///
+/// Only the guild's current track advances the queue: commands that stop
+/// a track take it out of `trackdata` first and advance (or clear) the
+/// queue themselves.
+pub struct TrackEndNotifier {
+    pub guild_id: GuildId,
+    pub state: State,
+}
+
+#[async_trait]
+impl EventHandler for TrackEndNotifier {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(tracks) = ctx {
+            let today = self.state.clock.now().date().naive_utc();
+
+            for (track, handle) in tracks.iter() {
+                self.state
+                    .quotas
+                    .record_streamed(self.guild_id, today, track.play_time);
+                let current = self
+                    .state
+                    .trackdata
+                    .read()
+                    .await
+                    .get(&self.guild_id)
+                    .map(|current| current.uuid());
+
+                let is_current = current == Some(handle.uuid());
+
+                // Who asked for a track is only kept while it's current.
+                let info = if is_current {
+                    self.state
+                        .now_playing
+                        .read()
+                        .await
+                        .get(&self.guild_id)
+                        .cloned()
+                } else {
+                    None
+                };
+                let info = info.unwrap_or_else(|| TrackInfo::of(handle));
+                self.state.hooks.track_end(self.guild_id, &info);
+
+                if is_current {
+                    if let Some(url) = &info.source_url {
+                        let now = self.state.clock.now();
+                        if let Err(why) = self.state.history.record(self.guild_id, url, now) {
+                            self.state.hooks.error(Some(self.guild_id), &*why);
+                        }
+                    }
+
+                    let state = Arc::clone(&self.state);
+                    let guild_id = self.guild_id;
+                    let finished = (*handle).clone();
+
+                    // The driver waits on event handlers, so the next track
+                    // is started outside of it.
+                    tokio::spawn(async move {
+                        let advanced = match commands::advance(&state, guild_id, &finished).await {
+                            Ok(()) => commands::announce_next(&state, guild_id).await,
+                            Err(why) => Err(why),
+                        };
+                        if let Err(why) = advanced {
+                            state.hooks.error(Some(guild_id), &*why);
+                        }
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Checked periodically on a track that's one [`Segment`] of its source,
+/// stopping it once it reaches `end`, after which it ends like any other
+/// for [`TrackEndNotifier`]. Checking the position keeps the end in place
+/// through pauses and seeks.
+///
+/// [`Segment`]: crate::track::Segment
+pub struct SegmentEnd {
+    pub end: Duration,
+}
+
+#[async_trait]
+impl EventHandler for SegmentEnd {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(tracks) = ctx {
+            for (track, handle) in tracks.iter() {
+                if track.position >= self.end {
+                    // It may have been stopped already.
+                    let _ = handle.stop();
+                    return Some(Event::Cancel);
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,477 @@
+/// Query parameters that only track where a link was shared from.
+const TRACKERS: &[&str] = &[
+    "si",
+    "feature",
+    "fbclid",
+    "gclid",
+    "igshid",
+    "ref",
+    "ref_src",
+    "ab_channel",
+    "pp",
+];
+
+/// Extensions of files the driver can play directly.
+const AUDIO_EXTENSIONS: &[&str] = &[
+    "aac", "flac", "m4a", "mp3", "oga", "ogg", "opus", "wav", "webm",
+];
+
+/// Extensions of the playlists radio stations hand out for their streams.
+const STREAM_EXTENSIONS: &[&str] = &["m3u", "m3u8", "pls", "xspf"];
+
+/// Last path segments Icecast and Shoutcast servers commonly serve a live
+/// stream from.
+const STREAM_PATHS: &[&str] = &["stream", "live", "listen", ";"];
+
+/// What kind of thing a URL points at, with the IDs that tell one of its
+/// kind from another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    YoutubeVideo {
+        id: String,
+    },
+    YoutubePlaylist {
+        id: String,
+    },
+    /// An auto-generated mix, `list=RD…`, seeded by `video`.
+    YoutubeMix {
+        video: String,
+        list: String,
+    },
+    /// A track, album, playlist, artist, episode or show.
+    Spotify {
+        kind: String,
+        id: String,
+    },
+    SoundcloudTrack {
+        user: String,
+        track: String,
+    },
+    SoundcloudSet {
+        user: String,
+        set: String,
+    },
+    BandcampTrack {
+        artist: String,
+        track: String,
+    },
+    BandcampAlbum {
+        artist: String,
+        album: String,
+    },
+    /// An audio file served as is, by its extension.
+    File {
+        extension: String,
+    },
+    /// A live radio stream, or a playlist pointing at one.
+    Stream,
+    Other,
+}
+
+/// A URL as [`identify`] understood it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identified {
+    pub source: Source,
+    /// The same thing at its canonical address, without tracking
+    /// parameters or credentials, and on the sites it knows, over HTTPS on
+    /// the main host. Two links to the same video or track have the same
+    /// canonical URL.
+    pub canonical: String,
+}
+
+impl Identified {
+    /// Whether the URL lists several tracks.
+    pub fn is_playlist(&self) -> bool {
+        matches!(
+            self.source,
+            Source::YoutubePlaylist { .. }
+                | Source::SoundcloudSet { .. }
+                | Source::BandcampAlbum { .. }
+        )
+    }
+}
+
+/// The pieces of a URL that identifying it looks at.
+struct Parts<'a> {
+    host: String,
+    path: Vec<&'a str>,
+    params: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Parts<'a> {
+    fn parse(url: &'a str) -> Option<Self> {
+        let rest = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))?;
+        let rest = rest.split('#').next().unwrap_or_default();
+        let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let (authority, path) = address.split_once('/').unwrap_or((address, ""));
+
+        // Credentials and ports don't change what's being pointed at.
+        let host = authority.rsplit('@').next().unwrap_or_default();
+        let host = host.split(':').next().unwrap_or_default().to_lowercase();
+        if host.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            host,
+            path: path
+                .split('/')
+                .filter(|segment| !segment.is_empty())
+                .collect(),
+            params: query
+                .split('&')
+                .filter(|param| !param.is_empty())
+                .map(|param| param.split_once('=').unwrap_or((param, "")))
+                .collect(),
+        })
+    }
+
+    fn is_on(&self, domain: &str) -> bool {
+        self.host == domain || self.host.ends_with(&format!(".{}", domain))
+    }
+
+    fn param(&self, name: &str) -> Option<&'a str> {
+        self.params
+            .iter()
+            .find(|(key, value)| *key == name && !value.is_empty())
+            .map(|(_, value)| *value)
+    }
+
+    fn extension(&self) -> Option<String> {
+        let (_, extension) = self.path.last()?.rsplit_once('.')?;
+        Some(extension.to_lowercase())
+    }
+
+    /// The URL with tracking parameters left out, over HTTPS unless it was
+    /// plain HTTP, which many radio streams still are.
+    fn without_trackers(&self, url: &str) -> String {
+        let scheme = if url.starts_with("http://") {
+            "http"
+        } else {
+            "https"
+        };
+        let authority = url
+            .split("://")
+            .nth(1)
+            .and_then(|rest| rest.split(&['/', '?', '#'][..]).next())
+            .and_then(|authority| authority.rsplit('@').next())
+            .unwrap_or_default();
+
+        let mut canonical = format!("{}://{}", scheme, authority.to_lowercase());
+        for segment in &self.path {
+            canonical.push('/');
+            canonical.push_str(segment);
+        }
+
+        let kept = self
+            .params
+            .iter()
+            .filter(|(key, _)| !key.starts_with("utm_") && !TRACKERS.contains(key))
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>();
+        if !kept.is_empty() {
+            canonical.push('?');
+            canonical.push_str(&kept.join("&"));
+        }
+
+        canonical
+    }
+}
+
+/// Works out what `url` points at. Anything that isn't an HTTP(S) URL, or
+/// a `spotify:` URI, gives `None`.
+pub fn identify(url: &str) -> Option<Identified> {
+    let url = url.trim();
+
+    if let Some(uri) = url.strip_prefix("spotify:") {
+        let (kind, id) = uri.split_once(':')?;
+        return Some(spotify(kind, id));
+    }
+
+    let parts = Parts::parse(url)?;
+
+    let identified = if parts.is_on("youtube.com") || parts.host == "youtu.be" {
+        youtube(&parts)
+    } else if parts.is_on("spotify.com") {
+        // Localized links carry the language first: /intl-de/track/….
+        let path = match parts.path.first() {
+            Some(segment) if segment.starts_with("intl-") => &parts.path[1..],
+            _ => &parts.path[..],
+        };
+
+        match path {
+            [kind, id, ..] => Some(spotify(kind, id)),
+            _ => None,
+        }
+    } else if parts.is_on("soundcloud.com") && parts.host != "on.soundcloud.com" {
+        soundcloud(&parts)
+    } else if parts.is_on("bandcamp.com") {
+        bandcamp(&parts)
+    } else {
+        None
+    };
+
+    if let Some(identified) = identified {
+        return Some(identified);
+    }
+
+    let extension = parts.extension();
+    let source = match extension {
+        Some(extension) if AUDIO_EXTENSIONS.contains(&extension.as_str()) => {
+            Source::File { extension }
+        }
+        Some(extension) if STREAM_EXTENSIONS.contains(&extension.as_str()) => Source::Stream,
+        _ if parts
+            .path
+            .last()
+            .is_some_and(|segment| STREAM_PATHS.contains(segment)) =>
+        {
+            Source::Stream
+        }
+        _ => Source::Other,
+    };
+
+    Some(Identified {
+        source,
+        canonical: parts.without_trackers(url),
+    })
+}
+
+fn youtube(parts: &Parts<'_>) -> Option<Identified> {
+    let video = match (parts.host.as_str(), parts.path.as_slice()) {
+        ("youtu.be", [id, ..]) => Some(*id),
+        (_, ["watch"]) => parts.param("v"),
+        (_, ["shorts" | "embed" | "live" | "v", id, ..]) => Some(*id),
+        _ => None,
+    };
+    let list = match parts.path.as_slice() {
+        ["playlist"] | ["watch"] => parts.param("list"),
+        _ => None,
+    };
+
+    let (source, canonical) = match (video, list) {
+        (Some(video), Some(list)) if list.starts_with("RD") => (
+            Source::YoutubeMix {
+                video: video.to_string(),
+                list: list.to_string(),
+            },
+            format!("https://www.youtube.com/watch?v={}&list={}", video, list),
+        ),
+        // A video opened from a playlist plays the playlist, as
+        // `j/play` has always treated it.
+        (_, Some(list)) if !list.starts_with("RD") => (
+            Source::YoutubePlaylist {
+                id: list.to_string(),
+            },
+            format!("https://www.youtube.com/playlist?list={}", list),
+        ),
+        (Some(video), _) => (
+            Source::YoutubeVideo {
+                id: video.to_string(),
+            },
+            format!("https://www.youtube.com/watch?v={}", video),
+        ),
+        _ => return None,
+    };
+
+    Some(Identified { source, canonical })
+}
+
+fn spotify(kind: &str, id: &str) -> Identified {
+    Identified {
+        canonical: format!("https://open.spotify.com/{}/{}", kind, id),
+        source: Source::Spotify {
+            kind: kind.to_string(),
+            id: id.to_string(),
+        },
+    }
+}
+
+fn soundcloud(parts: &Parts<'_>) -> Option<Identified> {
+    let (source, canonical) = match parts.path.as_slice() {
+        [user, "sets", set, ..] => (
+            Source::SoundcloudSet {
+                user: user.to_string(),
+                set: set.to_string(),
+            },
+            format!("https://soundcloud.com/{}/sets/{}", user, set),
+        ),
+        [user, track] => (
+            Source::SoundcloudTrack {
+                user: user.to_string(),
+                track: track.to_string(),
+            },
+            format!("https://soundcloud.com/{}/{}", user, track),
+        ),
+        _ => return None,
+    };
+
+    Some(Identified { source, canonical })
+}
+
+/// Every Bandcamp artist has their own subdomain.
+fn bandcamp(parts: &Parts<'_>) -> Option<Identified> {
+    let artist = parts.host.strip_suffix(".bandcamp.com")?;
+    if artist == "www" {
+        return None;
+    }
+
+    let (source, kind, slug) = match parts.path.as_slice() {
+        ["track", track] => (
+            Source::BandcampTrack {
+                artist: artist.to_string(),
+                track: track.to_string(),
+            },
+            "track",
+            track,
+        ),
+        ["album", album] => (
+            Source::BandcampAlbum {
+                artist: artist.to_string(),
+                album: album.to_string(),
+            },
+            "album",
+            album,
+        ),
+        _ => return None,
+    };
+
+    Some(Identified {
+        source,
+        canonical: format!("https://{}.bandcamp.com/{}/{}", artist, kind, slug),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(url: &str) -> String {
+        identify(url).unwrap().canonical
+    }
+
+    #[test]
+    fn youtube_links_share_one_address() {
+        for url in [
+            "https://www.youtube.com/watch?v=abc&feature=share",
+            "https://youtu.be/abc?si=XYZ",
+            "http://m.youtube.com/watch?v=abc&t=42",
+            "https://music.youtube.com/watch?v=abc",
+            "https://www.youtube.com/shorts/abc",
+            "https://YOUTUBE.com/embed/abc",
+        ] {
+            assert_eq!(
+                identify(url).unwrap(),
+                Identified {
+                    source: Source::YoutubeVideo {
+                        id: "abc".to_string()
+                    },
+                    canonical: "https://www.youtube.com/watch?v=abc".to_string(),
+                },
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn tells_youtube_playlists_from_mixes() {
+        let playlist = identify("https://www.youtube.com/watch?v=abc&list=PL1&index=2").unwrap();
+        assert!(playlist.is_playlist());
+        assert_eq!(
+            playlist.canonical,
+            "https://www.youtube.com/playlist?list=PL1"
+        );
+
+        let mix = identify("https://www.youtube.com/watch?list=RDabc&v=abc&start_radio=1").unwrap();
+        assert!(!mix.is_playlist());
+        assert_eq!(
+            mix.source,
+            Source::YoutubeMix {
+                video: "abc".to_string(),
+                list: "RDabc".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn identifies_spotify_and_soundcloud() {
+        assert_eq!(
+            canonical("https://open.spotify.com/intl-de/track/4uLU6h?si=abc"),
+            "https://open.spotify.com/track/4uLU6h"
+        );
+        assert_eq!(
+            identify("spotify:album:1A2B").unwrap().source,
+            Source::Spotify {
+                kind: "album".to_string(),
+                id: "1A2B".to_string()
+            }
+        );
+        assert_eq!(
+            canonical("https://m.soundcloud.com/artist/song?utm_source=clipboard"),
+            "https://soundcloud.com/artist/song"
+        );
+        assert!(identify("https://soundcloud.com/artist/sets/album")
+            .unwrap()
+            .is_playlist());
+    }
+
+    #[test]
+    fn identifies_bandcamp() {
+        assert_eq!(
+            identify("http://Artist.bandcamp.com/track/song?from=discover").unwrap(),
+            Identified {
+                source: Source::BandcampTrack {
+                    artist: "artist".to_string(),
+                    track: "song".to_string()
+                },
+                canonical: "https://artist.bandcamp.com/track/song".to_string(),
+            }
+        );
+        assert!(identify("https://artist.bandcamp.com/album/record")
+            .unwrap()
+            .is_playlist());
+        assert_eq!(
+            identify("https://bandcamp.com/discover").unwrap().source,
+            Source::Other
+        );
+    }
+
+    #[test]
+    fn identifies_files_and_streams() {
+        assert_eq!(
+            identify("https://example.com/music/Song.MP3?utm_campaign=x&token=1").unwrap(),
+            Identified {
+                source: Source::File {
+                    extension: "mp3".to_string()
+                },
+                canonical: "https://example.com/music/Song.MP3?token=1".to_string(),
+            }
+        );
+        assert_eq!(
+            identify("http://radio.example.com:8000/live")
+                .unwrap()
+                .source,
+            Source::Stream
+        );
+        assert_eq!(
+            identify("https://radio.example.com/station.pls")
+                .unwrap()
+                .source,
+            Source::Stream
+        );
+        assert_eq!(
+            identify("https://example.com/about").unwrap().source,
+            Source::Other
+        );
+    }
+
+    #[test]
+    fn ignores_what_isnt_a_url() {
+        assert_eq!(identify("never gonna give you up"), None);
+        assert_eq!(identify("https://"), None);
+        assert_eq!(identify("ftp://example.com/song.mp3"), None);
+    }
+}
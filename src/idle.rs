@@ -0,0 +1,186 @@
+use crate::{clock, queue, recording, State};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    time::Duration,
+};
+use twilight_model::id::{ChannelId, GuildId, UserId};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long the bot stays in a channel everyone else has left, in case
+/// they were only reconnecting.
+const EMPTY_GRACE: Duration = Duration::from_secs(60);
+
+/// Why the bot is leaving a call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Reason {
+    /// Nothing has played for the guild's idle timeout.
+    Silent,
+    /// No one but the bot, and maybe other bots, is left in the channel.
+    Empty,
+}
+
+/// Background task leaving calls where nothing has played for the guild's
+/// idle timeout, or that have had no one in them for a minute.
+pub async fn run(state: State) {
+    let mut silent_since = HashMap::new();
+    let mut empty_since = HashMap::new();
+
+    loop {
+        state.clock.sleep(CHECK_INTERVAL).await;
+
+        // Every call the bot joined through `j/join` has a text channel.
+        let guilds = state
+            .text_channels
+            .read()
+            .await
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+
+        silent_since.retain(|guild_id, _| guilds.contains(guild_id));
+        empty_since.retain(|guild_id, _| guilds.contains(guild_id));
+
+        for guild_id in guilds {
+            if !crate::in_session(&state, guild_id).await {
+                silent_since.remove(&guild_id);
+                empty_since.remove(&guild_id);
+                continue;
+            }
+
+            let now = state.clock.instant();
+
+            if has_listeners(&state, guild_id).await {
+                empty_since.remove(&guild_id);
+            } else {
+                empty_since.entry(guild_id).or_insert(now);
+            }
+
+            if is_playing(&state, guild_id).await {
+                silent_since.remove(&guild_id);
+            } else {
+                silent_since.entry(guild_id).or_insert(now);
+            }
+
+            let default = state.profile.idle_timeout();
+            let timeout = state
+                .settings
+                .read()
+                .await
+                .get(&guild_id)
+                .map_or(Some(default), |settings| settings.idle_timeout(default));
+
+            let reason = if empty_since
+                .get(&guild_id)
+                .is_some_and(|&since| clock::elapsed(&*state.clock, since) >= EMPTY_GRACE)
+            {
+                Reason::Empty
+            } else if let Some((since, timeout)) = silent_since.get(&guild_id).zip(timeout) {
+                if clock::elapsed(&*state.clock, *since) < timeout {
+                    continue;
+                }
+                Reason::Silent
+            } else {
+                continue;
+            };
+
+            silent_since.remove(&guild_id);
+            empty_since.remove(&guild_id);
+
+            if let Err(why) = leave(&state, guild_id, reason).await {
+                state.hooks.error(Some(guild_id), &*why);
+            }
+        }
+    }
+}
+
+async fn is_playing(state: &State, guild_id: GuildId) -> bool {
+    // A recording counts as activity even when nothing is playing.
+    if recording::is_recording(state, guild_id).await {
+        return true;
+    }
+
+    queue::current(state, guild_id).await.is_some()
+}
+
+async fn has_listeners(state: &State, guild_id: GuildId) -> bool {
+    let voice_states = state.voice_states.read().await;
+    let channel_id = match voice_states.get(&(guild_id, state.user_id)) {
+        Some(channel_id) => *channel_id,
+        // Until the bot's own voice state arrives there's nothing to
+        // compare against.
+        None => return true,
+    };
+
+    !listeners(
+        &voice_states,
+        &*state.bots.read().await,
+        state.user_id,
+        guild_id,
+        channel_id,
+    )
+    .is_empty()
+}
+
+/// Everyone in `channel_id` who isn't a bot, this one or any other.
+pub fn listeners(
+    voice_states: &HashMap<(GuildId, UserId), ChannelId>,
+    bots: &HashSet<UserId>,
+    bot_id: UserId,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> HashSet<UserId> {
+    voice_states
+        .iter()
+        .filter(|((guild, user_id), channel)| {
+            *guild == guild_id
+                && **channel == channel_id
+                && *user_id != bot_id
+                && !bots.contains(user_id)
+        })
+        .map(|((_, user_id), _)| *user_id)
+        .collect()
+}
+
+async fn leave(
+    state: &State,
+    guild_id: GuildId,
+    reason: Reason,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    state.queue.clear(guild_id);
+    state.trackdata.write().await.remove(&guild_id);
+    state.songbird.leave(guild_id).await?;
+
+    let message = match reason {
+        Reason::Silent => "Nothing's playing, so I left the channel.",
+        Reason::Empty => "Everyone left, so I left the channel too.",
+    };
+    let content = crate::with_recap(state, guild_id, message);
+
+    crate::announce(state, guild_id, &content).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn other_bots_are_not_listeners() {
+        let bot_id = UserId(1);
+        let mut voice_states = HashMap::new();
+        voice_states.insert((GuildId(10), bot_id), ChannelId(20));
+        voice_states.insert((GuildId(10), UserId(2)), ChannelId(20));
+        voice_states.insert((GuildId(10), UserId(3)), ChannelId(21));
+        voice_states.insert((GuildId(11), UserId(4)), ChannelId(20));
+        let bots = std::iter::once(UserId(2)).collect();
+
+        assert!(listeners(&voice_states, &bots, bot_id, GuildId(10), ChannelId(20)).is_empty());
+
+        voice_states.insert((GuildId(10), UserId(5)), ChannelId(20));
+        assert_eq!(
+            listeners(&voice_states, &bots, bot_id, GuildId(10), ChannelId(20)),
+            std::iter::once(UserId(5)).collect()
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use rand::Rng;
+use serde::Deserialize;
+use std::{convert::TryFrom, fmt, net::Ipv6Addr};
+
+/// An IPv6 subnet routed to the host, such as `2001:db8:1::/48`, set with
+/// `ipv6_block` in the config file. youtube-dl binds to a random address
+/// in it for each track, search and playlist it fetches, so YouTube sees
+/// many clients instead of one and doesn't throttle them all at once.
+///
+/// The addresses have to be usable without being assigned one by one: on
+/// Linux, route the block to `lo` as `local` and set
+/// `net.ipv6.ip_nonlocal_bind`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct Ipv6Block {
+    network: Ipv6Addr,
+    prefix: u8,
+}
+
+impl Ipv6Block {
+    /// A random address in the block.
+    pub fn random_address(&self) -> Ipv6Addr {
+        self.address(rand::thread_rng().gen())
+    }
+
+    /// The address with the block's network bits and the rest from `host`.
+    fn address(&self, host: u128) -> Ipv6Addr {
+        let mask = self.mask();
+        Ipv6Addr::from((u128::from(self.network) & mask) | (host & !mask))
+    }
+
+    fn mask(&self) -> u128 {
+        u128::MAX
+            .checked_shl(128 - u32::from(self.prefix))
+            .unwrap_or(0)
+    }
+}
+
+impl TryFrom<String> for Ipv6Block {
+    type Error = String;
+
+    fn try_from(block: String) -> Result<Self, Self::Error> {
+        let (network, prefix) = block
+            .split_once('/')
+            .ok_or_else(|| format!("{} isn't a block like 2001:db8::/48", block))?;
+        let network = network
+            .parse()
+            .map_err(|_| format!("{} isn't an IPv6 address", network))?;
+        let prefix = match prefix.parse() {
+            Ok(prefix) if prefix <= 128 => prefix,
+            _ => return Err(format!("/{} isn't a prefix length", prefix)),
+        };
+
+        Ok(Self { network, prefix })
+    }
+}
+
+impl fmt::Display for Ipv6Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}",
+            Ipv6Addr::from(u128::from(self.network) & self.mask()),
+            self.prefix
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(block: &str) -> Ipv6Block {
+        Ipv6Block::try_from(block.to_string()).unwrap()
+    }
+
+    #[test]
+    fn addresses_stay_in_the_block() {
+        let block = block("2001:db8:1::/48");
+
+        assert_eq!(
+            block.address(u128::MAX),
+            "2001:db8:1:ffff:ffff:ffff:ffff:ffff"
+                .parse::<Ipv6Addr>()
+                .unwrap()
+        );
+        assert_eq!(
+            block.address(0),
+            "2001:db8:1::".parse::<Ipv6Addr>().unwrap()
+        );
+
+        for _ in 0..100 {
+            let segments = block.random_address().segments();
+            assert_eq!(segments[..3], [0x2001, 0xdb8, 1]);
+        }
+    }
+
+    #[test]
+    fn reads_blocks() {
+        assert_eq!(block("2001:db8:1:2::/48").to_string(), "2001:db8:1::/48");
+
+        let single = block("2001:db8::1/128");
+        assert_eq!(
+            single.random_address(),
+            "2001:db8::1".parse::<Ipv6Addr>().unwrap()
+        );
+        assert_eq!(block("::/0").address(7), Ipv6Addr::from(7));
+
+        for bad in [
+            "2001:db8::",
+            "2001:db8::/129",
+            "192.0.2.0/24",
+            "2001:db8::/x",
+        ] {
+            assert!(Ipv6Block::try_from(bad.to_string()).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{queue, State};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error, sync::Arc, time::Duration};
+use tokio::spawn;
+use twilight_model::id::{ChannelId, GuildId};
+
+/// Jingles are cut off after this, so a mistaken full album doesn't hold
+/// the channel.
+pub const MAX_LENGTH: Duration = Duration::from_secs(5 * 60);
+/// How many jingles a guild may have waiting at once.
+pub const MAX_SCHEDULED: usize = 10;
+
+/// A clip an admin scheduled with `j/jingle add`, removed once it fires.
+/// Waiting jingles are saved to `jingles.json`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Jingle {
+    pub at: DateTime<Utc>,
+    pub channel_id: ChannelId,
+    pub url: String,
+    pub title: String,
+    /// Tells this jingle's timer apart from the others in the guild.
+    id: u64,
+}
+
+/// Parses `YYYY-MM-DD` and `HH:MM` as a time in `zone`.
+pub fn parse_time(date: &str, time: &str, zone: FixedOffset) -> Option<DateTime<Utc>> {
+    let local =
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").ok()?;
+
+    zone.from_local_datetime(&local)
+        .single()
+        .map(|at| at.with_timezone(&Utc))
+}
+
+/// Schedules `url` to play in `channel_id` at `at` and spawns its timer.
+pub async fn schedule(
+    state: &State,
+    guild_id: GuildId,
+    at: DateTime<Utc>,
+    channel_id: ChannelId,
+    url: String,
+    title: String,
+) {
+    let id = rand::random();
+
+    {
+        let mut jingles = state.jingles.write().await;
+        jingles.entry(guild_id).or_default().push(Jingle {
+            at,
+            channel_id,
+            url,
+            title,
+            id,
+        });
+        save(state, &jingles);
+    }
+
+    start_timer(state, guild_id, id, at);
+}
+
+/// Spawns the timers for the jingles saved before a restart. Ones whose
+/// time came while the bot was down are dropped rather than played late.
+pub async fn restore(state: &State) {
+    let now = state.clock.now();
+    let mut jingles = state.jingles.write().await;
+
+    for scheduled in jingles.values_mut() {
+        scheduled.retain(|jingle| jingle.at > now);
+    }
+    jingles.retain(|_, scheduled| !scheduled.is_empty());
+    save(state, &jingles);
+
+    for (guild_id, scheduled) in &*jingles {
+        for jingle in scheduled {
+            start_timer(state, *guild_id, jingle.id, jingle.at);
+        }
+    }
+}
+
+fn start_timer(state: &State, guild_id: GuildId, id: u64, at: DateTime<Utc>) {
+    let countdown = (at - state.clock.now()).to_std().unwrap_or_default();
+    let state = Arc::clone(state);
+
+    spawn(async move {
+        state.clock.sleep(countdown).await;
+
+        // Gone if it was cancelled in the meantime.
+        let jingle = match take(&state, guild_id, |jingle| jingle.id == id).await {
+            Some(jingle) => jingle,
+            None => return,
+        };
+
+        if let Err(why) = fire(&state, guild_id, &jingle).await {
+            state.hooks.error(Some(guild_id), &*why);
+        }
+    });
+}
+
+/// The guild's waiting jingles, soonest first.
+pub async fn list(state: &State, guild_id: GuildId) -> Vec<Jingle> {
+    let mut jingles = state
+        .jingles
+        .read()
+        .await
+        .get(&guild_id)
+        .cloned()
+        .unwrap_or_default();
+
+    jingles.sort_by_key(|jingle| jingle.at);
+    jingles
+}
+
+/// Calls off the jingle at `index` in [`list`]'s order, counting from 1.
+pub async fn cancel(state: &State, guild_id: GuildId, index: usize) -> Option<Jingle> {
+    let id = list(state, guild_id)
+        .await
+        .get(index.checked_sub(1)?)
+        .map(|jingle| jingle.id)?;
+
+    take(state, guild_id, |jingle| jingle.id == id).await
+}
+
+async fn take(
+    state: &State,
+    guild_id: GuildId,
+    matches: impl Fn(&Jingle) -> bool,
+) -> Option<Jingle> {
+    let mut jingles = state.jingles.write().await;
+    let scheduled = jingles.get_mut(&guild_id)?;
+
+    let position = scheduled.iter().position(matches)?;
+    let jingle = scheduled.remove(position);
+
+    if scheduled.is_empty() {
+        jingles.remove(&guild_id);
+    }
+    save(state, &jingles);
+
+    Some(jingle)
+}
+
+/// Saves the waiting jingles. They keep their timers either way; a failed
+/// save only means a restart would lose them.
+fn save(state: &State, jingles: &HashMap<GuildId, Vec<Jingle>>) {
+    if let Err(why) = state.storage.save("jingles.json", jingles) {
+        state.hooks.error(None, &*why);
+    }
+}
+
+/// Plays the jingle, joining its channel if the bot isn't in a call and
+/// leaving again afterwards. A call in another channel isn't interrupted.
+async fn fire(
+    state: &State,
+    guild_id: GuildId,
+    jingle: &Jingle,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let current = match state.songbird.get(guild_id) {
+        Some(call_lock) => call_lock.lock().await.current_channel(),
+        None => None,
+    };
+
+    if let Some(current) = current {
+        if current.0 != jingle.channel_id.0 {
+            let content = format!(
+                "Skipped the jingle **{}** for <#{}>, I'm busy in <#{}>.",
+                jingle.title, jingle.channel_id, current.0
+            );
+
+            return crate::announce(state, guild_id, &content).await;
+        }
+    }
+
+    let input = state.resolver.resolve(&jingle.url).await?;
+    let length = input
+        .metadata
+        .duration
+        .unwrap_or(MAX_LENGTH)
+        .min(MAX_LENGTH);
+
+    let joined = current.is_none();
+    let call_lock = if joined {
+        let (call_lock, result) = state.songbird.join(guild_id, jingle.channel_id.0).await;
+        result?;
+        call_lock
+    } else {
+        state.songbird.get(guild_id).ok_or("left the call")?
+    };
+
+    let clip = call_lock.lock().await.play_source(input);
+
+    let content = format!("🎉 Playing the scheduled jingle **{}**!", jingle.title);
+    crate::announce(state, guild_id, &content).await?;
+
+    state.clock.sleep(length).await;
+    // The clip may have ended on its own already.
+    let _ = clip.stop();
+
+    // Stay if someone started playing music while the jingle was on.
+    if joined && queue::current(state, guild_id).await.is_none() {
+        state.songbird.leave(guild_id).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_local_time() {
+        let zone = FixedOffset::east(2 * 3600);
+
+        assert_eq!(
+            parse_time("2021-12-31", "23:30", zone),
+            Some(Utc.ymd(2021, 12, 31).and_hms(21, 30, 0))
+        );
+        assert_eq!(parse_time("2021-02-30", "12:00", zone), None);
+        assert_eq!(parse_time("2021-12-31", "midnight", zone), None);
+    }
+}
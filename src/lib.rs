@@ -0,0 +1,884 @@
+mod abuse;
+mod ambience;
+pub mod args;
+mod auditlog;
+pub mod backup;
+mod chapters;
+pub mod clock;
+mod commands;
+pub mod config;
+#[cfg(all(unix, feature = "control"))]
+mod control;
+mod curfew;
+mod debug;
+mod dislikes;
+mod duration;
+mod event;
+pub mod eventlog;
+mod fade;
+mod history;
+mod hooks;
+mod identify;
+mod idle;
+pub mod ipv6;
+mod jingle;
+#[cfg(feature = "overlay")]
+mod listen;
+pub mod listenalong;
+mod logbuffer;
+pub mod migrations;
+#[cfg(feature = "overlay")]
+mod overlay;
+mod permissions;
+mod playback;
+mod playlist;
+mod prefixes;
+pub mod profile;
+pub mod programs;
+pub mod providers;
+mod queue;
+mod quota;
+mod ratings;
+mod reconnect;
+mod recording;
+mod search;
+pub mod secrets;
+mod session;
+mod settings;
+pub mod shards;
+pub mod slash;
+mod snapshot;
+pub mod sources;
+#[cfg(feature = "spotify")]
+mod spotify;
+#[cfg(feature = "spotify")]
+mod spotifylinks;
+mod storage;
+mod template;
+mod themes;
+mod tour;
+mod track;
+mod vibe;
+mod voteskip;
+#[cfg(feature = "webhooks")]
+mod webhooks;
+
+pub use debug::doctor;
+pub use logbuffer::LogBuffer;
+
+use abuse::{AbuseGuard, Offence};
+use auditlog::AuditEntry;
+use backup::BackupConfig;
+use clock::Clock;
+use commands::StoppedTrack;
+use config::Config;
+#[cfg(all(unix, feature = "control"))]
+use control::ControlConfig;
+use event::ListeningEvent;
+use eventlog::EventLog;
+use history::History;
+use hooks::{Hooks, TracingHook};
+use jingle::Jingle;
+#[cfg(feature = "overlay")]
+use overlay::OverlayConfig;
+use permissions::DjGrants;
+use prefixes::Prefixes;
+use profile::Profile;
+use providers::{LimitedResolver, ProviderLimits};
+use queue::{LoopMode, Queue};
+use quota::Quotas;
+use ratings::{Ratings, Vote};
+use reconnect::Reconnects;
+use recording::Recording;
+use session::Sessions;
+use settings::GuildSettings;
+use shards::EventLag;
+use songbird::{tracks::TrackHandle, Songbird};
+use sources::SourceResolver;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use storage::Storage;
+use themes::Themes;
+use tokio::{spawn, sync::RwLock};
+use tour::Tours;
+use tracing::{field, Instrument};
+use track::TrackInfo;
+use twilight_gateway::{Cluster, Event};
+use twilight_http::Client as HttpClient;
+use twilight_model::{
+    application::interaction::Interaction,
+    channel::{Message, Reaction},
+    id::{ChannelId, GuildId, UserId},
+    voice::VoiceState,
+};
+use twilight_standby::Standby;
+use voteskip::VoteSkips;
+#[cfg(feature = "webhooks")]
+use webhooks::{WebhookEvent, Webhooks};
+
+pub type State = Arc<StateRef>;
+
+#[derive(Debug)]
+pub struct StateRef {
+    abuse: AbuseGuard,
+    ambience: RwLock<HashMap<GuildId, TrackHandle>>,
+    clock: Arc<dyn Clock>,
+    cluster: Cluster,
+    config: Config,
+    dj_grants: DjGrants,
+    /// Other bots seen in voice, which don't count as listeners when
+    /// deciding whether a call is empty.
+    bots: RwLock<HashSet<UserId>>,
+    breaks: RwLock<HashSet<GuildId>>,
+    events: RwLock<HashMap<GuildId, ListeningEvent>>,
+    /// Guilds the bot was already in when its shards connected, or has
+    /// since been sent; any other guild it's sent has just added it.
+    known_guilds: RwLock<HashSet<GuildId>>,
+    event_lag: EventLag,
+    event_log: Option<EventLog>,
+    http: HttpClient,
+    /// When tracks last finished, for guilds' no-repeats windows.
+    history: History,
+    hooks: Hooks,
+    jingles: RwLock<HashMap<GuildId, Vec<Jingle>>>,
+    /// Members seen listening on Spotify, warned about once per session.
+    listening_along: RwLock<HashSet<(GuildId, UserId)>>,
+    logs: LogBuffer,
+    loops: RwLock<HashMap<GuildId, LoopMode>>,
+    /// Each guild's current track, with who asked for it.
+    now_playing: RwLock<HashMap<GuildId, TrackInfo>>,
+    prefixes: Prefixes,
+    profile: Profile,
+    /// Shared with the resolver, which waits on them.
+    provider_limits: Arc<ProviderLimits>,
+    queue: Queue,
+    /// The text channel each guild's queue was started from, where the
+    /// tracks after the first are announced.
+    queue_channels: RwLock<HashMap<GuildId, ChannelId>>,
+    quotas: Quotas,
+    ratings: Ratings,
+    reconnects: Reconnects,
+    recordings: RwLock<HashMap<GuildId, Arc<Recording>>>,
+    resolver: Box<dyn SourceResolver>,
+    sessions: Sessions,
+    settings: RwLock<HashMap<GuildId, GuildSettings>>,
+    trackdata: RwLock<HashMap<GuildId, TrackHandle>>,
+    voice_states: RwLock<HashMap<(GuildId, UserId), ChannelId>>,
+    vote_skips: VoteSkips,
+    songbird: Songbird,
+    #[cfg(feature = "spotify")]
+    spotify: Option<spotify::Spotify>,
+    #[cfg(feature = "spotify")]
+    spotify_links: spotifylinks::SpotifyLinks,
+    standby: Standby,
+    stopped: RwLock<HashMap<GuildId, StoppedTrack>>,
+    storage: Storage,
+    text_channels: RwLock<HashMap<GuildId, ChannelId>>,
+    themes: Themes,
+    tours: Tours,
+    user_id: UserId,
+    #[cfg(feature = "webhooks")]
+    webhooks: Webhooks,
+}
+
+impl StateRef {
+    /// Builds the shared state around an existing cluster and HTTP client.
+    ///
+    /// Nothing here connects to Discord, so tests can pass a cluster that
+    /// was never brought up, a client pointed at a mock server, a manual
+    /// clock and a fake resolver.
+    pub fn new(
+        config: Config,
+        clock: Arc<dyn Clock>,
+        cluster: Cluster,
+        http: HttpClient,
+        user_id: UserId,
+        logs: LogBuffer,
+        resolver: Box<dyn SourceResolver>,
+    ) -> Result<State, Box<dyn Error + Send + Sync + 'static>> {
+        Self::build(config, clock, cluster, http, user_id, logs, resolver, false)
+    }
+
+    /// Builds the state `musicm8 replay` runs on, which reaches nothing
+    /// outside the machine: no webhooks fire, Spotify isn't asked about
+    /// links, tracks aren't fetched, and neither the data directory nor
+    /// the event log is written to.
+    #[cfg(feature = "replay")]
+    pub fn offline(
+        config: Config,
+        clock: Arc<dyn Clock>,
+        cluster: Cluster,
+        http: HttpClient,
+        user_id: UserId,
+        logs: LogBuffer,
+    ) -> Result<State, Box<dyn Error + Send + Sync + 'static>> {
+        let config = Config {
+            data_dir: None,
+            event_log: None,
+            spotify: None,
+            ..config
+        };
+
+        Self::build(
+            config,
+            clock,
+            cluster,
+            http,
+            user_id,
+            logs,
+            Box::new(sources::OfflineResolver),
+            true,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build(
+        config: Config,
+        clock: Arc<dyn Clock>,
+        cluster: Cluster,
+        http: HttpClient,
+        user_id: UserId,
+        logs: LogBuffer,
+        resolver: Box<dyn SourceResolver>,
+        offline: bool,
+    ) -> Result<State, Box<dyn Error + Send + Sync + 'static>> {
+        let profile = config.profile;
+        let songbird =
+            Songbird::twilight_from_config(cluster.clone(), user_id, profile.songbird_config());
+
+        #[cfg(feature = "webhooks")]
+        let webhooks = if offline {
+            Webhooks::none()
+        } else {
+            Webhooks::load("webhooks.json")?
+        };
+        #[cfg(not(feature = "webhooks"))]
+        let _ = offline;
+        #[cfg(feature = "spotify")]
+        let spotify = config.spotify.clone().map(spotify::Spotify::new);
+
+        let quotas = Quotas::load("quotas.json")?;
+        let event_log = config
+            .event_log
+            .as_deref()
+            .map(EventLog::create)
+            .transpose()?;
+        let storage = Storage::new(config.data_dir.as_deref());
+        migrations::check(&storage)?;
+        let prefixes = Prefixes::load(&storage)?;
+        let history = History::load(&storage)?;
+        let tours = Tours::load(&storage)?;
+        let themes = Themes::load(&storage)?;
+        let ratings = Ratings::load(&storage)?;
+        let dj_grants = DjGrants::load(&storage, clock.now())?;
+        let jingles = storage.load("jingles.json")?;
+        let queue = Queue::load(&storage)?;
+        let settings = storage.load_settings()?;
+        // Tokens are only sealed and opened when accounts can be linked, so
+        // a missing key doesn't matter otherwise.
+        #[cfg(feature = "spotify")]
+        let spotify_links = {
+            let linkable = config
+                .spotify
+                .as_ref()
+                .is_some_and(|spotify| spotify.redirect_uri.is_some());
+            let secrets = if linkable {
+                secrets::Secrets::from_env()?
+            } else {
+                None
+            };
+            spotifylinks::SpotifyLinks::load(&storage, secrets)?
+        };
+
+        let provider_limits = Arc::new(ProviderLimits::new(config.rate_limits));
+        let resolver: Box<dyn SourceResolver> = Box::new(LimitedResolver::new(
+            resolver,
+            Arc::clone(&provider_limits),
+            Arc::clone(&clock),
+        ));
+
+        let mut hooks = Hooks::default();
+        hooks.register(TracingHook);
+        #[cfg(feature = "webhooks")]
+        hooks.register(webhooks.clone());
+
+        Ok(Arc::new(StateRef {
+            abuse: Default::default(),
+            ambience: Default::default(),
+            clock,
+            cluster,
+            config,
+            bots: Default::default(),
+            dj_grants,
+            breaks: Default::default(),
+            events: Default::default(),
+            known_guilds: Default::default(),
+            event_lag: Default::default(),
+            event_log,
+            http,
+            hooks,
+            jingles: RwLock::new(jingles),
+            listening_along: Default::default(),
+            logs,
+            loops: Default::default(),
+            now_playing: Default::default(),
+            prefixes,
+            history,
+            profile,
+            provider_limits,
+            queue,
+            queue_channels: Default::default(),
+            quotas,
+            ratings,
+            reconnects: Default::default(),
+            recordings: Default::default(),
+            resolver,
+            sessions: Default::default(),
+            settings: RwLock::new(settings),
+            trackdata: Default::default(),
+            voice_states: Default::default(),
+            vote_skips: Default::default(),
+            songbird,
+            #[cfg(feature = "spotify")]
+            spotify,
+            #[cfg(feature = "spotify")]
+            spotify_links,
+            standby: Standby::new(),
+            stopped: Default::default(),
+            storage,
+            text_channels: Default::default(),
+            themes,
+            tours,
+            user_id,
+            #[cfg(feature = "webhooks")]
+            webhooks,
+        }))
+    }
+
+    /// What the guild's typed commands start with.
+    pub fn prefix(&self, guild_id: GuildId) -> String {
+        self.prefixes
+            .get(guild_id)
+            .unwrap_or_else(|| self.config.prefix.clone())
+    }
+
+    /// `text` with every `{prefix}` in it replaced by the guild's prefix,
+    /// for replies that name commands.
+    pub fn prefixed(&self, guild_id: GuildId, text: &str) -> String {
+        text.replace("{prefix}", &self.prefix(guild_id))
+    }
+
+    /// Whether player events in the guild are POSTed to a webhook.
+    #[cfg(feature = "webhooks")]
+    pub fn has_webhook(&self, guild_id: GuildId) -> bool {
+        self.webhooks.contains(guild_id)
+    }
+
+    /// Whether nothing is currently playing in the guild.
+    pub async fn is_idle(&self, guild_id: GuildId) -> bool {
+        !self.trackdata.read().await.contains_key(&guild_id)
+    }
+}
+
+/// Leaves every voice channel, ending any recordings first, saves what
+/// lasts across restarts, and disconnects the shards.
+///
+/// Queues are saved without being cleared, so the next start picks them
+/// back up.
+pub async fn shut_down(state: &State) {
+    let guild_ids = state
+        .voice_states
+        .read()
+        .await
+        .keys()
+        .filter(|(_, user_id)| *user_id == state.user_id)
+        .map(|(guild_id, _)| *guild_id)
+        .collect::<HashSet<_>>();
+
+    for guild_id in guild_ids {
+        if let Err(why) = recording::stop(state, guild_id).await {
+            state.hooks.error(Some(guild_id), &*why);
+        }
+
+        if let Err(why) = state.songbird.leave(guild_id).await {
+            state.hooks.error(Some(guild_id), &why);
+        }
+    }
+
+    state.queue.flush();
+
+    if let Err(why) = state.storage.save_settings(&*state.settings.read().await) {
+        state.hooks.error(None, &*why);
+    }
+
+    state.cluster.down();
+    tracing::info!("shut down");
+}
+
+/// The saved settings of the guild `guild_id` in `data_dir`, as printed by
+/// `musicm8 export-guild`.
+pub fn export_guild(
+    data_dir: &Path,
+    guild_id: GuildId,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync + 'static>> {
+    let storage = Storage::new(Some(data_dir));
+    migrations::check(&storage)?;
+
+    storage.export_guild(guild_id)
+}
+
+/// Starts the tasks that run alongside the event loop: restoring saved
+/// queues and jingles, the curfew and idle checkers and, when their config files
+/// exist, the overlay server and the control socket.
+pub fn spawn_background_tasks(state: &State) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    #[cfg(feature = "overlay")]
+    if let Some(config) = OverlayConfig::load("overlay.json")? {
+        let state = Arc::clone(state);
+
+        spawn(async move {
+            if let Err(why) = overlay::serve(config, state).await {
+                tracing::warn!("overlay server stopped: {}", why);
+            }
+        });
+    }
+
+    #[cfg(all(unix, feature = "control"))]
+    if let Some(config) = ControlConfig::load("control.json")? {
+        let state = Arc::clone(state);
+
+        spawn(async move {
+            if let Err(why) = control::serve(config, state).await {
+                tracing::warn!("control socket stopped: {}", why);
+            }
+        });
+    }
+
+    queue::restore(state);
+    spawn(queue::run_saver(Arc::clone(state)));
+
+    if let (Some(data_dir), Some(config)) = (&state.config.data_dir, &state.config.backups) {
+        spawn(take_backups(
+            Arc::clone(state),
+            data_dir.clone(),
+            config.clone(),
+        ));
+    }
+
+    let restoring = Arc::clone(state);
+    spawn(async move { jingle::restore(&restoring).await });
+
+    spawn(curfew::run(Arc::clone(state)));
+
+    spawn(idle::run(Arc::clone(state)));
+
+    Ok(())
+}
+
+/// Backs the data directory up every `config.interval`, on a blocking
+/// thread since it copies every file.
+async fn take_backups(state: State, data_dir: PathBuf, config: BackupConfig) {
+    loop {
+        state.clock.sleep(config.interval()).await;
+
+        // Queue changes are written a few seconds late otherwise.
+        let flushing = Arc::clone(&state);
+        let _ = tokio::task::spawn_blocking(move || flushing.queue.flush()).await;
+
+        let (data_dir, config, now) = (data_dir.clone(), config.clone(), state.clock.now());
+        match tokio::task::spawn_blocking(move || backup::take(&data_dir, &config, now)).await {
+            Ok(Ok(backup)) => {
+                tracing::info!("backed up the data directory to {}", backup.display())
+            }
+            Ok(Err(why)) => state.hooks.error(None, &*why),
+            Err(why) => state.hooks.error(None, &why),
+        }
+    }
+}
+
+/// Routes one gateway event. Command handlers are spawned, so this
+/// returns as soon as the event has been dispatched.
+pub async fn handle_event(state: &State, event: Event) {
+    if state.config.dev_guild_id.is_some() {
+        tracing::debug!("event: {:?}", event);
+    }
+
+    state.standby.process(&event);
+    state.songbird.process(&event).await;
+
+    match &event {
+        Event::GuildCreate(guild) => {
+            let mut voice_states = state.voice_states.write().await;
+
+            for voice_state in &guild.voice_states {
+                if let Some(channel_id) = voice_state.channel_id {
+                    voice_states.insert((guild.id, voice_state.user_id), channel_id);
+                }
+            }
+
+            state.bots.write().await.extend(
+                guild
+                    .members
+                    .iter()
+                    .filter(|member| member.user.bot)
+                    .map(|member| member.user.id),
+            );
+
+            if state.known_guilds.write().await.insert(guild.id) {
+                let state = Arc::clone(state);
+                let guild = guild.clone();
+
+                spawn(async move {
+                    if let Err(why) = tour::greet(&state, &guild).await {
+                        state.hooks.error(Some(guild.id), &*why);
+                    }
+                });
+            }
+        }
+        Event::Ready(ready) => {
+            state
+                .known_guilds
+                .write()
+                .await
+                .extend(ready.guilds.iter().map(|guild| guild.id));
+        }
+        Event::VoiceStateUpdate(update) => track_voice_state(state, &update.0).await,
+        Event::ReactionAdd(reaction) => rate(state, &reaction.0, true),
+        Event::ReactionRemove(reaction) => rate(state, &reaction.0, false),
+        Event::PresenceUpdate(presence) => {
+            if let Err(why) = listenalong::presence_update(state, presence).await {
+                state.hooks.error(Some(presence.guild_id), &*why);
+            }
+        }
+        _ => {}
+    }
+
+    match event {
+        Event::MessageCreate(msg) => dispatch(state, msg.0),
+        Event::InteractionCreate(interaction) => match interaction.0 {
+            Interaction::ApplicationCommand(command) => {
+                let state = Arc::clone(state);
+
+                spawn(async move {
+                    if let Err(why) = slash::handle(&state, *command).await {
+                        state.hooks.error(None, &*why);
+                    }
+                });
+            }
+            Interaction::MessageComponent(press) if tour::is_tour_button(&press) => {
+                let state = Arc::clone(state);
+
+                spawn(async move {
+                    if let Err(why) = tour::press(&state, &press).await {
+                        state.hooks.error(press.guild_id, &*why);
+                    }
+                });
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+}
+
+/// Runs the command in `msg`, if it starts with the guild's prefix,
+/// unless its author is banned or sending too many.
+fn dispatch(state: &State, msg: Message) {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return,
+    };
+
+    let prefix = state.prefix(guild_id);
+    let command = match msg.content.strip_prefix(prefix.as_str()) {
+        Some(rest) => args::split(rest).0.to_string(),
+        // The configured prefix always reaches `prefix`, so a guild can't
+        // lock itself out with one nobody remembers.
+        None => match msg.content.strip_prefix(state.config.prefix.as_str()) {
+            Some(rest) if args::split(rest).0 == "prefix" => "prefix".to_string(),
+            _ => return,
+        },
+    };
+
+    let now = state.clock.instant();
+
+    if state.abuse.is_banned(guild_id, msg.author.id, now) {
+        return;
+    }
+
+    if let Some(offence) = state.abuse.command(guild_id, msg.author.id, now) {
+        report_abuse(state, guild_id, msg.author.id, offence);
+        return;
+    }
+
+    match command.as_str() {
+        "join" => spawn_handler(state, msg, commands::join),
+        "play" => spawn_handler(state, msg, commands::play),
+        "chapters" => spawn_handler(state, msg, commands::chapters),
+        "leave" => spawn_dj_handler(state, msg, commands::leave),
+        "stop" => spawn_dj_handler(state, msg, commands::stop),
+        "resume" => spawn_handler(state, msg, commands::resume),
+        "pause" => spawn_handler(state, msg, commands::pause),
+        "nowplaying" | "np" => spawn_handler(state, msg, commands::now_playing),
+        "loop" => spawn_dj_handler(state, msg, commands::loop_mode),
+        "volume" => spawn_dj_handler(state, msg, commands::volume),
+        "shuffle" => spawn_dj_handler(state, msg, commands::shuffle),
+        // Who may remove a track depends on who queued it.
+        "remove" => spawn_handler(state, msg, commands::remove),
+        "move" => spawn_dj_handler(state, msg, commands::move_track),
+        "dev" if state.config.dev_guild_id == Some(guild_id) => {
+            spawn_handler(state, msg, commands::dev)
+        }
+        "seek" => spawn_dj_handler(state, msg, commands::seek),
+        "settings" => spawn_handler(state, msg, commands::settings),
+        "debug" => spawn_handler(state, msg, commands::debug),
+        "stats" => spawn_handler(state, msg, commands::stats),
+        "simulate" => spawn_handler(state, msg, commands::simulate),
+        "record" => spawn_handler(state, msg, commands::record),
+        "top" => spawn_handler(state, msg, commands::top),
+        "event" => spawn_handler(state, msg, commands::event),
+        "break" => spawn_handler(state, msg, commands::intermission),
+        "ambience" => spawn_handler(state, msg, commands::ambience),
+        "vibe" => spawn_handler(state, msg, commands::vibe),
+        "theme" => spawn_handler(state, msg, commands::theme),
+        "queue" => spawn_handler(state, msg, commands::queue),
+        "skip" => spawn_dj_handler(state, msg, commands::skip),
+        "voteskip" => spawn_handler(state, msg, commands::vote_skip),
+        "jingle" => spawn_handler(state, msg, commands::jingle),
+        "handoff" => spawn_handler(state, msg, commands::handoff),
+        "admin" => spawn_handler(state, msg, commands::admin),
+        "prefix" => spawn_handler(state, msg, commands::prefix),
+        "tour" => spawn_handler(state, msg, commands::tour),
+        "setdj" => spawn_handler(state, msg, commands::set_dj),
+        "dj" => spawn_handler(state, msg, commands::dj),
+        "spotify" => spawn_handler(state, msg, commands::spotify),
+
+        _ => {}
+    }
+}
+
+/// Notifies the guild about a new ban in the background.
+fn report_abuse(state: &State, guild_id: GuildId, user_id: UserId, offence: Offence) {
+    let state = Arc::clone(state);
+
+    spawn(async move {
+        if let Err(why) = abuse::notify(&state, guild_id, user_id, offence).await {
+            state.hooks.error(Some(guild_id), &*why);
+        }
+    });
+}
+
+/// Counts a 👍/👎 reaction on a now-playing message as a vote, and
+/// checks whether a new downvote should skip the track.
+fn rate(state: &State, reaction: &Reaction, added: bool) {
+    let reaction_guild = reaction.guild_id;
+
+    if reaction.user_id == state.user_id {
+        return;
+    }
+
+    let vote = match Vote::from_emoji(&reaction.emoji) {
+        Some(vote) => vote,
+        None => return,
+    };
+
+    let counted = match state
+        .ratings
+        .vote(reaction.message_id, reaction.user_id, vote, added)
+    {
+        Ok(counted) => counted,
+        Err(why) => {
+            // The vote is counted, just not saved.
+            state.hooks.error(reaction_guild, &*why);
+            true
+        }
+    };
+
+    if counted && added && vote == Vote::Down {
+        let state = Arc::clone(state);
+        let message_id = reaction.message_id;
+
+        spawn(async move {
+            if let Err(why) = dislikes::check(&state, message_id).await {
+                state.hooks.error(reaction_guild, &*why);
+            }
+        });
+    }
+}
+
+fn spawn_handler<F, Fut>(state: &State, msg: Message, handler: F)
+where
+    F: FnOnce(Message, State) -> Fut,
+    Fut: Future<Output = Result<(), Box<dyn Error + Send + Sync + 'static>>> + Send + 'static,
+{
+    let guild_id = msg.guild_id;
+    let author_id = msg.author.id;
+
+    let span = tracing::info_span!(
+        "command",
+        guild_id = field::Empty,
+        user_id = %msg.author.id,
+        command = msg.content.split_whitespace().next().unwrap_or_default(),
+    );
+
+    if let Some(guild_id) = guild_id {
+        span.record("guild_id", &field::display(guild_id));
+    }
+
+    let handler = handler(msg, Arc::clone(state));
+    let state = Arc::clone(state);
+
+    spawn(
+        async move {
+            if let Err(why) = handler.await {
+                state.hooks.error(guild_id, &*why);
+
+                if let Some(guild_id) = guild_id {
+                    let action = format!("Command failed: {}", why);
+                    auditlog::record(
+                        &state,
+                        guild_id,
+                        AuditEntry::error(Some(author_id), &action),
+                    )
+                    .await;
+                }
+            }
+        }
+        .instrument(span),
+    );
+}
+
+/// Like [`spawn_handler`], for the commands that need a DJ once the guild
+/// has a DJ role; everyone else gets told who can use them instead.
+fn spawn_dj_handler<F, Fut>(state: &State, msg: Message, handler: F)
+where
+    F: FnOnce(Message, State) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), Box<dyn Error + Send + Sync + 'static>>> + Send + 'static,
+{
+    spawn_handler(state, msg, |msg, state| async move {
+        if permissions::is_dj(&state, &msg).await? {
+            handler(msg, state).await
+        } else {
+            commands::not_a_dj(msg, state).await
+        }
+    });
+}
+
+async fn track_voice_state(state: &State, voice_state: &VoiceState) {
+    let guild_id = match voice_state.guild_id {
+        Some(guild_id) => guild_id,
+        None => return,
+    };
+
+    if voice_state
+        .member
+        .as_ref()
+        .is_some_and(|member| member.user.bot)
+    {
+        state.bots.write().await.insert(voice_state.user_id);
+    }
+
+    let key = (guild_id, voice_state.user_id);
+    let previous = {
+        let mut voice_states = state.voice_states.write().await;
+
+        match voice_state.channel_id {
+            Some(channel_id) => voice_states.insert(key, channel_id),
+            None => voice_states.remove(&key),
+        }
+    };
+
+    let channel_id = match joined_bot_channel(state, guild_id, voice_state, previous).await {
+        Some(channel_id) => channel_id,
+        None => return,
+    };
+
+    #[cfg(feature = "webhooks")]
+    state.webhooks.send(
+        guild_id,
+        &WebhookEvent::UserJoined {
+            guild_id,
+            channel_id,
+            user_id: voice_state.user_id,
+        },
+    );
+    #[cfg(not(feature = "webhooks"))]
+    let _ = channel_id;
+
+    let state = Arc::clone(state);
+    let user_id = voice_state.user_id;
+
+    spawn(async move {
+        if let Err(why) = themes::play(&state, guild_id, user_id).await {
+            state.hooks.error(Some(guild_id), &*why);
+        }
+    });
+}
+
+/// The channel a member moved into, if it is the one the bot is in.
+async fn joined_bot_channel(
+    state: &State,
+    guild_id: GuildId,
+    voice_state: &VoiceState,
+    previous: Option<ChannelId>,
+) -> Option<ChannelId> {
+    // Mute/deafen toggles also arrive as voice state updates, so only a
+    // change of channel counts as joining.
+    let channel_id = match voice_state.channel_id {
+        Some(channel_id) if previous != Some(channel_id) => channel_id,
+        _ => return None,
+    };
+
+    if voice_state
+        .member
+        .as_ref()
+        .is_some_and(|member| member.user.bot)
+    {
+        return None;
+    }
+
+    let bot_channel = state.songbird.get(guild_id)?.lock().await.current_channel();
+
+    (bot_channel.map(|channel| channel.0) == Some(channel_id.0)).then_some(channel_id)
+}
+
+/// Whether the bot is connected to a voice channel in the guild.
+async fn in_session(state: &State, guild_id: GuildId) -> bool {
+    match state.songbird.get(guild_id) {
+        Some(call_lock) => call_lock.lock().await.current_channel().is_some(),
+        None => false,
+    }
+}
+
+/// Posts to the text channel `j/join` was last used from, if any.
+async fn announce(
+    state: &State,
+    guild_id: GuildId,
+    content: &str,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let channel_id = match state.text_channels.read().await.get(&guild_id) {
+        Some(channel_id) => *channel_id,
+        None => return Ok(()),
+    };
+
+    state
+        .http
+        .create_message(channel_id)
+        .content(content)?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+/// Appends the recap of the guild's session, if one was running, to a
+/// message announcing that the bot left.
+fn with_recap(state: &State, guild_id: GuildId, content: &str) -> String {
+    match state.sessions.end(guild_id, state.clock.instant()) {
+        Some(summary) => format!("{}\n{}", content, summary),
+        None => content.to_string(),
+    }
+}
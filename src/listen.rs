@@ -0,0 +1,167 @@
+use crate::{queue, track::TrackInfo, State};
+use hyper::body::{Bytes, Sender};
+use songbird::tracks::PlayMode;
+use std::{error::Error, io::Read, time::Duration};
+use tokio::{sync::mpsc, task};
+use twilight_model::id::GuildId;
+
+/// The relay's output format: 16kHz mono 16-bit PCM, so a listener takes
+/// about 256kbps instead of the driver's 48kHz stereo floats.
+pub const SAMPLE_RATE: u32 = 16_000;
+const DOWNSAMPLE: usize = 48_000 / SAMPLE_RATE as usize;
+
+/// Audio is sent a second at a time and paced to real time, so listeners
+/// stay close to what the call hears.
+const CHUNK: Duration = Duration::from_secs(1);
+
+/// A WAV header for a stream of unknown length; players read until the
+/// connection closes.
+pub fn wav_header() -> Vec<u8> {
+    let mut header = Vec::with_capacity(44);
+
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel.
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    header.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+
+    header
+}
+
+/// Converts interleaved 48kHz float samples to the relay's format,
+/// averaging channels and neighbouring frames.
+pub fn downmix(samples: &[f32], channels: usize) -> Vec<u8> {
+    samples
+        .chunks(channels * DOWNSAMPLE)
+        .flat_map(|frames| {
+            let mean = frames.iter().sum::<f32>() / frames.len() as f32;
+            ((mean.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes()
+        })
+        .collect()
+}
+
+/// Streams whatever the guild is playing to one listener, track after
+/// track, until nothing is playing or the listener disconnects.
+///
+/// The current track is resolved again and read from its position, like
+/// [`crate::vibe`] does, since the driver's mixed output isn't exposed.
+pub async fn relay(state: State, guild_id: GuildId, mut sender: Sender) {
+    if sender.send_data(Bytes::from(wav_header())).await.is_err() {
+        return;
+    }
+
+    while let Some(handle) = queue::current(&state, guild_id).await {
+        let (url, info) = match (TrackInfo::of(&handle).source_url, handle.get_info().await) {
+            (Some(url), Ok(info)) => (url, info),
+            _ => return,
+        };
+
+        let mut chunks = match read_from(&state, &url, info.position).await {
+            Ok(chunks) => chunks,
+            Err(why) => {
+                state.hooks.error(Some(guild_id), &*why);
+                return;
+            }
+        };
+
+        while let Some(chunk) = chunks.recv().await {
+            if sender.send_data(Bytes::from(chunk)).await.is_err() {
+                return;
+            }
+
+            state.clock.sleep(CHUNK).await;
+
+            // Hold the stream while paused; a stopped track isn't paused.
+            while matches!(handle.get_info().await, Ok(info) if info.playing == PlayMode::Pause) {
+                state.clock.sleep(CHUNK).await;
+            }
+
+            match queue::current(&state, guild_id).await {
+                Some(current) if current.uuid() == handle.uuid() => {}
+                // Skipped or stopped, so move on to whatever plays now.
+                _ => break,
+            }
+        }
+    }
+}
+
+/// Decodes the track at `url` from `position` on a blocking thread,
+/// handing back [`CHUNK`]-sized pieces in the relay's format.
+async fn read_from(
+    state: &State,
+    url: &str,
+    position: Duration,
+) -> Result<mpsc::Receiver<Vec<u8>>, Box<dyn Error + Send + Sync + 'static>> {
+    let mut input = state.resolver.resolve(url).await?;
+    let (chunks, receiver) = mpsc::channel(2);
+
+    task::spawn_blocking(move || {
+        if position > Duration::default() && input.seek_time(position).is_none() {
+            return;
+        }
+
+        let channels = if input.is_stereo() { 2 } else { 1 };
+        let mut bytes = vec![0; 48_000 * channels * CHUNK.as_secs() as usize * 4];
+
+        loop {
+            let mut read = 0;
+            while read < bytes.len() {
+                match input.read(&mut bytes[read..]) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => read += n,
+                }
+            }
+
+            if read == 0 {
+                return;
+            }
+
+            let samples = bytes[..read - read % 4]
+                .chunks_exact(4)
+                .map(|sample| f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]))
+                .collect::<Vec<_>>();
+
+            // The relay has hung up once the receiver is gone.
+            if chunks.blocking_send(downmix(&samples, channels)).is_err() {
+                return;
+            }
+        }
+    });
+
+    Ok(receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_describes_mono_pcm() {
+        let header = wav_header();
+
+        assert_eq!(header.len(), 44);
+        assert_eq!(&header[..4], b"RIFF");
+        assert_eq!(&header[22..24], &1u16.to_le_bytes());
+        assert_eq!(&header[24..28], &16_000u32.to_le_bytes());
+        assert_eq!(&header[36..40], b"data");
+    }
+
+    #[test]
+    fn downmixes_to_a_third_of_the_frames() {
+        // Three stereo frames at full scale, then three silent ones.
+        let samples = [1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+
+        assert_eq!(
+            downmix(&samples, 2),
+            [i16::MAX.to_le_bytes(), 0i16.to_le_bytes()].concat()
+        );
+    }
+}
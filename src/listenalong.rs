@@ -0,0 +1,147 @@
+use crate::{queue, State};
+use serde::Deserialize;
+use std::error::Error;
+use twilight_model::gateway::{
+    payload::PresenceUpdate,
+    presence::{Activity, ActivityType, UserOrId},
+};
+
+/// What to do when someone in the bot's voice channel starts listening on
+/// Spotify, set with `listen_along` in the config file. Presences only
+/// arrive with `presence_intent` on.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ListenAlong {
+    /// Say that there are two things playing.
+    #[default]
+    Warn,
+    /// Say so and pause the current track too.
+    Pause,
+}
+
+/// Whether any of `activities` is Spotify playing, which Listen Along
+/// sessions share: their party ID is `spotify:` and the host's user ID.
+pub fn on_spotify(activities: &[Activity]) -> bool {
+    activities.iter().any(|activity| {
+        activity.kind == ActivityType::Listening
+            && activity
+                .party
+                .as_ref()
+                .and_then(|party| party.id.as_deref())
+                .is_some_and(|id| id.starts_with("spotify:"))
+    })
+}
+
+/// Warns, or pauses, when `presence` shows someone in the bot's voice
+/// channel starting to listen on Spotify while a track is playing. Later
+/// updates about the same session say nothing more.
+pub async fn presence_update(
+    state: &State,
+    presence: &PresenceUpdate,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = presence.guild_id;
+    let user_id = match &presence.user {
+        UserOrId::User(user) => user.id,
+        UserOrId::UserId { id } => *id,
+    };
+
+    let started = {
+        let mut listening = state.listening_along.write().await;
+
+        if on_spotify(&presence.activities) {
+            listening.insert((guild_id, user_id))
+        } else {
+            listening.remove(&(guild_id, user_id));
+            false
+        }
+    };
+    if !started {
+        return Ok(());
+    }
+
+    let channel_id = state
+        .voice_states
+        .read()
+        .await
+        .get(&(guild_id, user_id))
+        .copied();
+    let bot_channel = match state.songbird.get(guild_id) {
+        Some(call_lock) => call_lock.lock().await.current_channel(),
+        None => None,
+    };
+    match (channel_id, bot_channel) {
+        (Some(channel_id), Some(bot_channel)) if channel_id.0 == bot_channel.0 => {}
+        _ => return Ok(()),
+    }
+
+    let handle = match queue::current(state, guild_id).await {
+        Some(handle) => handle,
+        None => return Ok(()),
+    };
+
+    let content = match state.config.listen_along {
+        ListenAlong::Warn => format!(
+            "<@{}> is listening along on Spotify while I'm playing, \
+             so this channel has two things playing at once.",
+            user_id
+        ),
+        ListenAlong::Pause => {
+            handle.pause()?;
+
+            state.prefixed(
+                guild_id,
+                &format!(
+                    "<@{}> is listening along on Spotify, so I've paused. \
+                     Use `{{prefix}}resume` to carry on.",
+                    user_id
+                ),
+            )
+        }
+    };
+
+    crate::announce(state, guild_id, &content).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twilight_model::gateway::presence::ActivityParty;
+
+    fn activity(kind: ActivityType, party_id: Option<&str>) -> Activity {
+        Activity {
+            application_id: None,
+            assets: None,
+            buttons: Vec::new(),
+            created_at: None,
+            details: None,
+            emoji: None,
+            flags: None,
+            id: None,
+            instance: None,
+            kind,
+            name: "Spotify".to_string(),
+            party: party_id.map(|id| ActivityParty {
+                id: Some(id.to_string()),
+                size: None,
+            }),
+            secrets: None,
+            state: None,
+            timestamps: None,
+            url: None,
+        }
+    }
+
+    #[test]
+    fn spots_spotify_sessions() {
+        assert!(on_spotify(&[activity(
+            ActivityType::Listening,
+            Some("spotify:123")
+        )]));
+        assert!(!on_spotify(&[activity(ActivityType::Listening, None)]));
+        assert!(!on_spotify(&[activity(
+            ActivityType::Playing,
+            Some("spotify:123")
+        )]));
+        assert!(!on_spotify(&[]));
+    }
+}
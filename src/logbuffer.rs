@@ -0,0 +1,115 @@
+use chrono::Utc;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{self, Write},
+    sync::{Arc, Mutex},
+};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Subscriber,
+};
+use tracing_subscriber::{
+    layer::{Context, Layer},
+    registry::LookupSpan,
+};
+use twilight_model::id::GuildId;
+
+const LINES_PER_GUILD: usize = 20;
+
+/// Tracing layer keeping the most recent log lines of every guild.
+///
+/// Only events carrying a `guild_id` field, directly or through one of
+/// their spans, are kept, and only those that pass the `RUST_LOG` filter.
+#[derive(Clone, Debug, Default)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<HashMap<GuildId, VecDeque<String>>>>,
+}
+
+impl LogBuffer {
+    pub fn recent(&self, guild_id: GuildId) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+
+        lines
+            .get(&guild_id)
+            .map(|lines| lines.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Span extension remembering the span's `guild_id` field.
+struct SpanGuild(GuildId);
+
+impl<S> Layer<S> for LogBuffer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = LineVisitor::default();
+        attrs.record(&mut visitor);
+
+        if let (Some(guild_id), Some(span)) = (visitor.guild_id, ctx.span(id)) {
+            span.extensions_mut().insert(SpanGuild(guild_id));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = LineVisitor::default();
+        values.record(&mut visitor);
+
+        if let (Some(guild_id), Some(span)) = (visitor.guild_id, ctx.span(id)) {
+            span.extensions_mut().replace(SpanGuild(guild_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = LineVisitor::default();
+        event.record(&mut visitor);
+
+        let guild_id = visitor.guild_id.or_else(|| {
+            ctx.event_scope(event)?
+                .find_map(|span| span.extensions().get::<SpanGuild>().map(|guild| guild.0))
+        });
+
+        let guild_id = match guild_id {
+            Some(guild_id) => guild_id,
+            None => return,
+        };
+
+        let line = format!(
+            "{} {} {}{}",
+            Utc::now().format("%H:%M:%S"),
+            event.metadata().level(),
+            visitor.message,
+            visitor.fields
+        );
+
+        let mut lines = self.lines.lock().unwrap();
+        let lines = lines.entry(guild_id).or_default();
+
+        if lines.len() == LINES_PER_GUILD {
+            lines.pop_front();
+        }
+
+        lines.push_back(line);
+    }
+}
+
+#[derive(Default)]
+struct LineVisitor {
+    guild_id: Option<GuildId>,
+    message: String,
+    fields: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            "guild_id" => self.guild_id = format!("{:?}", value).parse().ok().map(GuildId),
+            name => {
+                let _ = write!(self.fields, " {}={:?}", name, value);
+            }
+        }
+    }
+}
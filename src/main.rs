@@ -1,223 +1,326 @@
-use futures::StreamExt;
-use songbird::{
-    input::{Input, Restartable},
-    tracks::TrackHandle,
-    Songbird,
+#[cfg(feature = "replay")]
+use discord_music::eventlog;
+use discord_music::{
+    backup, clock::SystemClock, config::Config, sources::HostResolver, LogBuffer, StateRef,
 };
-use std::{collections::HashMap, error::Error, sync::Arc};
-use tokio::{spawn, sync::RwLock};
-use twilight_gateway::{Cluster, Event, Intents};
+#[cfg(feature = "replay")]
+use std::{convert::TryFrom, time::Duration};
+use std::{env, error::Error, io, path::Path, process, sync::Arc};
+use tokio::signal;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+#[cfg(feature = "replay")]
+use twilight_gateway::{cluster::ShardScheme, Event};
+use twilight_gateway::{Cluster, Intents};
 use twilight_http::Client as HttpClient;
-use twilight_model::{channel::Message, gateway::payload::MessageCreate, id::GuildId};
-use twilight_standby::Standby;
-
-type State = Arc<StateRef>;
-
-#[derive(Debug)]
-struct StateRef {
-    cluster: Cluster,
-    http: HttpClient,
-    trackdata: RwLock<HashMap<GuildId, TrackHandle>>,
-    songbird: Songbird,
-    standby: Standby,
+use twilight_model::id::GuildId;
+#[cfg(feature = "replay")]
+use twilight_model::id::{ApplicationId, UserId};
+
+// Builds without `replay` have no such command to advertise.
+#[cfg(feature = "replay")]
+macro_rules! replay_usage {
+    () => {
+        "\n    replay <FILE>        Feed a recorded event log through the bot, offline"
+    };
+}
+#[cfg(not(feature = "replay"))]
+macro_rules! replay_usage {
+    () => {
+        ""
+    };
 }
 
+const USAGE: &str = concat!(
+    "\
+Usage: musicm8 [COMMAND]
+
+Commands:
+    run [--no-migrate]   Connect to Discord and play (the default), first bringing
+                         the data directory up to date unless told not to
+    register-commands    Sync the bot's slash commands and exit
+    migrate-db           Bring the data directory up to date and exit
+    export-guild <ID>    Print one guild's saved settings as JSON
+    doctor               Check that ffmpeg, youtube-dl and opus are usable
+    restore <BACKUP>     Replace the data directory with a backup, once it's
+                         verified; stop the bot first",
+    replay_usage!(),
+    "
+    help                 Show this message"
+);
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
-    tracing_subscriber::fmt::init();
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+    // Taken out first so `musicm8 --no-migrate` also means running.
+    let migrate = match args.iter().position(|arg| arg == "--no-migrate") {
+        Some(index) => {
+            args.remove(index);
+            false
+        }
+        None => true,
+    };
 
-    let (mut events, state) = {
-        let token = include_str!("../.token");
+    let mut args = args.into_iter();
+    let command = args.next();
+    let argument = args.next();
+
+    if args.next().is_some()
+        || (argument.is_some()
+            != matches!(
+                command.as_deref(),
+                Some("replay" | "restore" | "export-guild")
+            ))
+        || (!migrate && !matches!(command.as_deref(), None | Some("run")))
+    {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
 
-        let http = HttpClient::new(token.to_string());
-        let user_id = http.current_user().exec().await?.model().await?.id;
+    match command.as_deref() {
+        None | Some("run") => run(Config::load("config.json")?, migrate).await,
+        #[cfg(feature = "replay")]
+        Some("replay") => replay(Config::load("config.json")?, argument.unwrap_or_default()).await,
+        Some("register-commands") => {
+            let config = Config::load("config.json")?;
+            let http = HttpClient::new(config.token);
+            let changes = discord_music::slash::sync(&http, config.dev_guild_id).await?;
+
+            if changes.is_empty() {
+                println!("Slash commands are up to date.");
+            }
+            for change in changes {
+                println!("{}", change);
+            }
 
-        let intents = Intents::GUILD_MESSAGES | Intents::GUILD_VOICE_STATES;
-        let (cluster, events) = Cluster::new(token, intents).await?;
-        cluster.up().await;
+            Ok(())
+        }
+        Some("migrate-db") => {
+            let config = Config::read("config.json")?;
+            let applied = discord_music::migrations::migrate(config.data_dir.as_deref())?;
 
-        let songbird = Songbird::twilight(cluster.clone(), user_id);
+            if applied.is_empty() {
+                println!("The data directory is up to date.");
+            }
+            for description in applied {
+                println!("Migrated: {}", description);
+            }
 
-        (
-            events,
-            Arc::new(StateRef {
-                cluster,
-                http,
-                trackdata: Default::default(),
-                songbird,
-                standby: Standby::new(),
-            }),
-        )
-    };
+            Ok(())
+        }
+        Some("export-guild") => {
+            let config = Config::read("config.json")?;
+            let data_dir = config
+                .data_dir
+                .ok_or("there's no data_dir in config.json to export from")?;
+            let guild_id = argument
+                .unwrap_or_default()
+                .parse()
+                .map(GuildId)
+                .map_err(|_| "the guild ID should be a number")?;
+
+            match discord_music::export_guild(&data_dir, guild_id)? {
+                Some(exported) => println!("{}", exported),
+                None => {
+                    eprintln!("Guild {} has no saved settings.", guild_id);
+                    process::exit(1);
+                }
+            }
 
-    while let Some((_, event)) = events.next().await {
-        state.standby.process(&event);
-        state.songbird.process(&event).await;
+            Ok(())
+        }
+        Some("doctor") => {
+            let config = Config::read("config.json")?;
+            let (report, healthy) = discord_music::doctor(&config.programs).await;
+            print!("{}", report);
 
-        if let Event::MessageCreate(msg) = event {
-            if msg.guild_id.is_none() || !msg.content.starts_with("j/") {
-                continue;
+            if !healthy {
+                process::exit(1);
             }
 
-            match msg.content.splitn(2, ' ').next() {
-                Some("j/join") => spawn(join(msg.0, Arc::clone(&state))),
-                Some("j/play") => spawn(play(msg.0, Arc::clone(&state))),
-                Some("j/leave") => spawn(leave(msg.0, Arc::clone(&state))),
-                Some("j/stop") => spawn(stop(msg.0, Arc::clone(&state))),
+            Ok(())
+        }
+        Some("restore") => {
+            let config = Config::read("config.json")?;
+            let data_dir = config
+                .data_dir
+                .ok_or("there's no data_dir in config.json to restore into")?;
+            let backup = argument.unwrap_or_default();
+
+            match backup::restore(Path::new(&backup), &data_dir, chrono::Utc::now())? {
+                Some(old) => println!(
+                    "Restored {} from {}; what it replaced is in {}.",
+                    data_dir.display(),
+                    backup,
+                    old.display()
+                ),
+                None => println!("Restored {} from {}.", data_dir.display(), backup),
+            }
 
-                _ => continue,
-            };
+            Ok(())
         }
-    }
+        Some("help" | "-h" | "--help") => {
+            println!("{}", USAGE);
 
-    Ok(())
+            Ok(())
+        }
+        Some(_) => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
 }
-async fn join(msg: Message, state: State) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
-    state
-        .http
-        .create_message(msg.channel_id)
-        .content("What's the channel ID you want me to join?")?
-        .exec()
-        .await?;
 
-    let author_id = msg.author.id;
-    let msg = state
-        .standby
-        .wait_for_message(msg.channel_id, move |new_msg: &MessageCreate| {
-            new_msg.author.id == author_id
-        })
-        .await?;
-    let channel_id = msg.content.parse::<u64>()?;
+/// Sets up logging to stderr and to the buffer debug reports read from.
+fn init_logging(config: &Config) -> Result<LogBuffer, Box<dyn Error + Send + Sync + 'static>> {
+    let logs = LogBuffer::default();
 
-    let guild_id = msg.guild_id.ok_or("Can't join a non-guild channel.")?;
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        // Developing against a test server wants every event.
+        Err(_) if config.dev_guild_id.is_some() => {
+            EnvFilter::try_new(format!("{},discord_music=debug", config.log_level))?
+        }
+        Err(_) => EnvFilter::try_new(&config.log_level)?,
+    };
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    // LOG_FORMAT=json switches to one JSON object per line, including the
+    // command span's guild_id/user_id/command fields, for log aggregation.
+    if env::var("LOG_FORMAT").as_deref() == Ok("json") {
+        subscriber.json().finish().with(logs.clone()).init();
+    } else {
+        subscriber.finish().with(logs.clone()).init();
+    }
 
-    let (_handle, success) = state.songbird.join(guild_id, channel_id).await;
+    Ok(logs)
+}
 
-    let content = match success {
-        Ok(()) => format!("Joined <#{}>!", channel_id),
-        Err(e) => format!("Failed to join <#{}>! Why: {:?}", channel_id, e),
-    };
+async fn run(config: Config, migrate: bool) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let logs = init_logging(&config)?;
+    config.programs.probe().await?;
 
-    state
-        .http
-        .create_message(msg.channel_id)
-        .content(&content)?
-        .exec()
-        .await?;
+    // Otherwise a data directory that's behind stops the bot starting.
+    if migrate {
+        for description in discord_music::migrations::migrate(config.data_dir.as_deref())? {
+            tracing::info!("migrated the data directory: {}", description);
+        }
+    }
 
-    Ok(())
-}
+    let (events, state) = {
+        let http = HttpClient::new(config.token.clone());
+        let user_id = http.current_user().exec().await?.model().await?.id;
+        // Typed commands still work without them, so don't refuse to start.
+        // A dev guild gets the commands on its own, where changes apply
+        // at once instead of after Discord's global cache catches up.
+        if let Err(why) = discord_music::slash::sync(&http, config.dev_guild_id).await {
+            tracing::warn!("couldn't register slash commands: {}", why);
+        }
 
-async fn leave(msg: Message, state: State) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
-    tracing::debug!(
-        "leave command in channel {} by {}",
-        msg.channel_id,
-        msg.author.name
-    );
+        // Slash commands and buttons arrive as interactions, which need no
+        // intent at all.
+        let mut intents = if config.message_commands {
+            Intents::GUILDS
+                | Intents::GUILD_MESSAGES
+                | Intents::GUILD_MESSAGE_REACTIONS
+                | Intents::GUILD_VOICE_STATES
+        } else {
+            Intents::GUILDS | Intents::GUILD_VOICE_STATES
+        };
+        if config.presence_intent {
+            intents |= Intents::GUILD_PRESENCES;
+        }
+        let (cluster, events) = Cluster::new(config.token.clone(), intents).await?;
+        cluster.up().await;
+        let resolver = HostResolver::new(config.programs.clone(), config.ipv6_block);
 
-    let guild_id = msg.guild_id.unwrap();
+        (
+            events,
+            StateRef::new(
+                config,
+                Arc::new(SystemClock),
+                cluster,
+                http,
+                user_id,
+                logs,
+                Box::new(resolver),
+            )?,
+        )
+    };
 
-    state.songbird.leave(guild_id).await?;
+    discord_music::spawn_background_tasks(&state)?;
 
-    state
-        .http
-        .create_message(msg.channel_id)
-        .content("Left the channel")?
-        .exec()
-        .await?;
+    tokio::select! {
+        _ = discord_music::shards::run(&state, events) => {}
+        signal = shutdown_signal() => {
+            let signal = signal?;
+            tracing::info!("received {}, shutting down", signal);
+            discord_music::shut_down(&state).await;
+        }
+    }
 
     Ok(())
 }
 
-async fn play(msg: Message, state: State) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
-    tracing::debug!(
-        "play command in channel {} by {}",
-        msg.channel_id,
-        msg.author.name
-    );
-    state
-        .http
-        .create_message(msg.channel_id)
-        .content("What's the URL of the audio to play?")?
-        .exec()
-        .await?;
-
-    let author_id = msg.author.id;
-    let msg = state
-        .standby
-        .wait_for_message(msg.channel_id, move |new_msg: &MessageCreate| {
-            new_msg.author.id == author_id
-        })
-        .await?;
+/// Waits for Ctrl-C or, on Unix, the SIGTERM service managers send, and
+/// returns which it was.
+async fn shutdown_signal() -> io::Result<&'static str> {
+    #[cfg(unix)]
+    {
+        let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())?;
 
-    let guild_id = msg.guild_id.unwrap();
-
-    match songbird::ytdl(msg.content.trim()).await {
-        Ok(song) => {
-            let input = Input::from(song);
-
-            let content = format!(
-                "Playing **{:?}** by **{:?}**",
-                input
-                    .metadata
-                    .track
-                    .as_ref()
-                    .unwrap_or(&"<UNKNOWN>".to_string()),
-                input
-                    .metadata
-                    .artist
-                    .as_ref()
-                    .unwrap_or(&"<UNKNOWN>".to_string()),
-            );
-
-            state
-                .http
-                .create_message(msg.channel_id)
-                .content(&content)?
-                .exec()
-                .await?;
-
-            if let Some(call_lock) = state.songbird.get(guild_id) {
-                let mut call = call_lock.lock().await;
-                let handle = call.play_source(input);
-
-                let mut store = state.trackdata.write().await;
-                store.insert(guild_id, handle);
-            }
-        }
-        Err(e) => {
-            state
-                .http
-                .create_message(msg.channel_id)
-                .content(&format!("error {}", e))?
-                .exec()
-                .await?;
+        tokio::select! {
+            interrupt = signal::ctrl_c() => interrupt.map(|()| "SIGINT"),
+            _ = terminate.recv() => Ok("SIGTERM"),
         }
     }
 
-    Ok(())
+    #[cfg(not(unix))]
+    signal::ctrl_c().await.map(|()| "Ctrl-C")
 }
 
-async fn stop(msg: Message, state: State) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
-    tracing::debug!(
-        "stop command in channel {} by {}",
-        msg.channel_id,
-        msg.author.name
-    );
+/// Replays a log recorded with `event_log` against a stub of Discord's
+/// API: every request the bot makes is logged instead of sent, and
+/// nothing else leaves the machine either (see [`StateRef::offline`]).
+/// The bot can't join voice or fetch tracks, so playback itself isn't
+/// reproduced.
+#[cfg(feature = "replay")]
+async fn replay(
+    config: Config,
+    path: String,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let logs = init_logging(&config)?;
+    let recorded = eventlog::read(Path::new(&path))?;
+
+    let user_id = recorded
+        .iter()
+        .find_map(|(_, _, event)| match event {
+            Event::Ready(ready) => Some(ready.user.id),
+            _ => None,
+        })
+        .unwrap_or(UserId(1));
+
+    let address = eventlog::serve_stub(user_id)?;
+    let http = HttpClient::builder()
+        .token(config.token.clone())
+        .proxy(address.to_string(), true)
+        .ratelimiter(None)
+        .build();
+    http.set_application_id(ApplicationId(user_id.0));
+
+    // Never brought up; the events come from the log instead.
+    let (cluster, _events) = Cluster::builder(config.token.clone(), Intents::empty())
+        .http_client(http.clone())
+        .shard_scheme(ShardScheme::try_from((0..=0, 1))?)
+        .build()
+        .await?;
 
-    let guild_id = msg.guild_id.unwrap();
+    let state = StateRef::offline(config, Arc::new(SystemClock), cluster, http, user_id, logs)?;
 
-    if let Some(call_lock) = state.songbird.get(guild_id) {
-        let mut call = call_lock.lock().await;
-        let _ = call.stop();
-    }
+    tracing::info!("replaying {} events from {}", recorded.len(), path);
+    discord_music::shards::run(&state, eventlog::replay(recorded)).await;
 
-    state
-        .http
-        .create_message(msg.channel_id)
-        .content("Stopped the track")?
-        .exec()
-        .await?;
+    // Handlers are spawned, so give the last ones a moment to finish.
+    tokio::time::sleep(Duration::from_secs(5)).await;
 
     Ok(())
 }
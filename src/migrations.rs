@@ -0,0 +1,144 @@
+use crate::storage::Storage;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, error::Error, path::Path};
+
+/// One change to the shape of the files in the data directory.
+struct Migration {
+    /// Logged as the migration is applied.
+    description: &'static str,
+    apply: fn(&Storage) -> Result<(), Box<dyn Error + Send + Sync + 'static>>,
+}
+
+/// Every migration, oldest first. A data directory's schema version is
+/// how many of these it has had, so new ones go at the end and old ones
+/// never change.
+const MIGRATIONS: &[Migration] = &[Migration {
+    description: "queued tracks name their address source_url",
+    apply: rename_queued_urls,
+}];
+
+/// The schema version this build reads and writes.
+pub const SCHEMA_VERSION: usize = MIGRATIONS.len();
+
+/// `schema.json`, saying which migrations the data directory has had.
+/// Directories from before it existed are at version 0.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Schema {
+    version: usize,
+}
+
+/// Brings the data directory at `data_dir` up to [`SCHEMA_VERSION`],
+/// returning the description of each migration applied.
+pub fn migrate(
+    data_dir: Option<&Path>,
+) -> Result<Vec<&'static str>, Box<dyn Error + Send + Sync + 'static>> {
+    let storage = Storage::new(data_dir);
+    let mut applied = Vec::new();
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version(&storage)?) {
+        (migration.apply)(&storage)?;
+        // Saved after each, so a failed migration is retried on its own.
+        storage.save("schema.json", &Schema { version: index + 1 })?;
+        applied.push(migration.description);
+    }
+
+    Ok(applied)
+}
+
+/// Refuses a data directory that isn't at [`SCHEMA_VERSION`], whose files
+/// may not mean what this build would read them as.
+pub(crate) fn check(storage: &Storage) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    if !storage.is_persistent() {
+        return Ok(());
+    }
+
+    match version(storage)? {
+        SCHEMA_VERSION => Ok(()),
+        version => Err(format!(
+            "the data directory is at schema version {}, but this build needs {}; \
+             start without --no-migrate, or run `musicm8 migrate-db`, to migrate it",
+            version, SCHEMA_VERSION
+        )
+        .into()),
+    }
+}
+
+fn version(storage: &Storage) -> Result<usize, Box<dyn Error + Send + Sync + 'static>> {
+    let version = storage.load::<Schema>("schema.json")?.version;
+
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "the data directory is at schema version {}, newer than this build's {}",
+            version, SCHEMA_VERSION
+        )
+        .into());
+    }
+
+    Ok(version)
+}
+
+/// Queues saved before `TrackInfo` existed call each track's address
+/// `url`.
+fn rename_queued_urls(storage: &Storage) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let mut queues: HashMap<String, Vec<Map<String, Value>>> = storage.load("queues.json")?;
+
+    for track in queues.values_mut().flatten() {
+        if let Some(url) = track.remove("url") {
+            track.entry("source_url").or_insert(url);
+        }
+    }
+
+    storage.save("queues.json", &queues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn migrates_old_directories_once() {
+        let dir = std::env::temp_dir().join(format!("musicm8-schema-{}", rand::random::<u64>()));
+        let storage = Storage::new(Some(&dir));
+        storage
+            .save(
+                "queues.json",
+                &json!({ "1": [{ "title": "Old", "url": "https://example.com/old" }] }),
+            )
+            .unwrap();
+        assert!(check(&storage).is_err());
+
+        assert_eq!(migrate(Some(&dir)).unwrap().len(), SCHEMA_VERSION);
+        assert!(migrate(Some(&dir)).unwrap().is_empty());
+        check(&storage).unwrap();
+
+        let queues: Value = storage.load("queues.json").unwrap();
+        assert_eq!(
+            queues["1"][0],
+            json!({ "title": "Old", "source_url": "https://example.com/old" })
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_directories_from_newer_builds() {
+        let dir = std::env::temp_dir().join(format!("musicm8-schema-{}", rand::random::<u64>()));
+        let storage = Storage::new(Some(&dir));
+        storage
+            .save(
+                "schema.json",
+                &Schema {
+                    version: SCHEMA_VERSION + 1,
+                },
+            )
+            .unwrap();
+
+        assert!(migrate(Some(&dir)).is_err());
+        assert!(check(&storage).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+#[cfg(feature = "spotify")]
+use crate::spotifylinks;
+use crate::{listen, queue, State};
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use songbird::tracks::PlayMode;
+use std::{
+    collections::HashMap, convert::Infallible, error::Error, fs, io::ErrorKind, net::SocketAddr,
+    sync::Arc,
+};
+use subtle::ConstantTimeEq;
+use twilight_model::id::GuildId;
+use url::form_urlencoded;
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<style>
+body { margin: 0; font-family: sans-serif; color: #fff; text-shadow: 0 0 4px #000; }
+#bar { height: 4px; background: rgba(255, 255, 255, 0.3); }
+#fill { height: 100%; width: 0; background: #fff; }
+</style>
+</head>
+<body>
+<div id="title"></div>
+<div id="artist"></div>
+<div id="bar"><div id="fill"></div></div>
+<script>
+async function refresh() {
+    const response = await fetch(location.pathname.replace(/\/?$/, "/now.json") + location.search);
+    const now = await response.json();
+    document.getElementById("title").textContent = now.title || "";
+    document.getElementById("artist").textContent = now.artist || "";
+    const progress = now.duration_secs ? now.position_secs / now.duration_secs : 0;
+    document.getElementById("fill").style.width = (progress * 100) + "%";
+}
+refresh();
+setInterval(refresh, 1000);
+</script>
+</body>
+</html>
+"#;
+
+const LISTEN_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Listen along</title>
+</head>
+<body>
+<audio controls autoplay></audio>
+<script>
+document.querySelector("audio").src =
+    location.pathname.replace(/\/listen\/?$/, "/listen.wav") + location.search;
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(feature = "spotify")]
+const LINKED_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Spotify linked</title>
+</head>
+<body>
+<p>Your Spotify account is linked. You can close this tab.</p>
+</body>
+</html>
+"#;
+
+#[cfg(feature = "spotify")]
+const LINK_FAILED_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Spotify not linked</title>
+</head>
+<body>
+<p>Your Spotify account couldn't be linked. The link may have run out or been used already; ask the bot for another.</p>
+</body>
+</html>
+"#;
+
+/// Operator configuration read from `overlay.json`.
+///
+/// Each guild that should be reachable gets its own token, which the
+/// streamer passes as `?token=` in the browser source URL.
+#[derive(Debug, Deserialize)]
+pub struct OverlayConfig {
+    pub address: SocketAddr,
+    pub tokens: HashMap<GuildId, String>,
+}
+
+impl OverlayConfig {
+    pub fn load(path: &str) -> Result<Option<Self>, Box<dyn Error + Send + Sync + 'static>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct NowPlaying {
+    title: Option<String>,
+    artist: Option<String>,
+    url: Option<String>,
+    position_secs: f64,
+    duration_secs: Option<f64>,
+    paused: bool,
+}
+
+/// Serves `/overlay/<guild_id>/` (HTML), `/overlay/<guild_id>/now.json`,
+/// and the listen-along page `/overlay/<guild_id>/listen` with its audio
+/// stream `/overlay/<guild_id>/listen.wav`. `/spotify/callback` is where
+/// Spotify sends users back to after `j/spotify link`.
+pub async fn serve(config: OverlayConfig, state: State) -> Result<(), hyper::Error> {
+    let address = config.address;
+    let tokens = Arc::new(config.tokens);
+
+    let make_service = make_service_fn(move |_| {
+        let tokens = Arc::clone(&tokens);
+        let state = Arc::clone(&state);
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let tokens = Arc::clone(&tokens);
+                let state = Arc::clone(&state);
+
+                async move { Ok::<_, Infallible>(handle(request, &tokens, &state).await) }
+            }))
+        }
+    });
+
+    tracing::info!("serving overlay on {}", address);
+
+    Server::bind(&address).serve(make_service).await
+}
+
+async fn handle(
+    request: Request<Body>,
+    tokens: &HashMap<GuildId, String>,
+    state: &State,
+) -> Response<Body> {
+    #[cfg(feature = "spotify")]
+    if request.uri().path() == "/spotify/callback" {
+        return spotify_callback(&request, state).await;
+    }
+
+    let mut segments = request.uri().path().trim_matches('/').split('/');
+
+    let (guild_id, resource) = match (segments.next(), segments.next(), segments.next()) {
+        (Some("overlay"), Some(guild_id), resource) => match guild_id.parse() {
+            Ok(guild_id) => (GuildId(guild_id), resource.unwrap_or("")),
+            Err(_) => return status(StatusCode::NOT_FOUND),
+        },
+        _ => return status(StatusCode::NOT_FOUND),
+    };
+
+    let token = request.uri().query().and_then(|query| {
+        form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == "token")
+            .map(|(_, value)| value.into_owned())
+    });
+
+    // Compared in constant time, so response times don't give away how
+    // much of a guessed token is right.
+    match (tokens.get(&guild_id), token) {
+        (Some(expected), Some(token))
+            if bool::from(expected.as_bytes().ct_eq(token.as_bytes())) => {}
+        _ => return status(StatusCode::UNAUTHORIZED),
+    }
+
+    match resource {
+        "" => Response::builder()
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(PAGE))
+            .unwrap(),
+        "now.json" => {
+            let body = serde_json::to_vec(&now_playing(state, guild_id).await).unwrap();
+
+            Response::builder()
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        }
+        "listen" => Response::builder()
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(LISTEN_PAGE))
+            .unwrap(),
+        "listen.wav" => {
+            let (sender, body) = Body::channel();
+            tokio::spawn(listen::relay(Arc::clone(state), guild_id, sender));
+
+            Response::builder()
+                .header(CONTENT_TYPE, "audio/wav")
+                .body(body)
+                .unwrap()
+        }
+        _ => status(StatusCode::NOT_FOUND),
+    }
+}
+
+async fn now_playing(state: &State, guild_id: GuildId) -> NowPlaying {
+    let store = state.trackdata.read().await;
+
+    let handle = match store.get(&guild_id) {
+        Some(handle) => handle,
+        None => return NowPlaying::default(),
+    };
+
+    let info = match handle.get_info().await {
+        Ok(info) if !info.playing.is_done() => info,
+        _ => return NowPlaying::default(),
+    };
+
+    let track = queue::current_info(state, guild_id, handle).await;
+
+    NowPlaying {
+        position_secs: track.elapsed(info.position).as_secs_f64(),
+        title: track.title,
+        artist: track.artist,
+        url: track.source_url,
+        duration_secs: track.duration.map(|duration| duration.as_secs_f64()),
+        paused: info.playing == PlayMode::Pause,
+    }
+}
+
+/// Finishes `j/spotify link` with the code and state Spotify added to the
+/// query. Someone who turned the app down comes back without a code.
+#[cfg(feature = "spotify")]
+async fn spotify_callback(request: &Request<Body>, state: &State) -> Response<Body> {
+    let query: HashMap<_, _> =
+        form_urlencoded::parse(request.uri().query().unwrap_or("").as_bytes())
+            .into_owned()
+            .collect();
+
+    let linked = match (query.get("state"), query.get("code")) {
+        (Some(oauth_state), Some(code)) => {
+            match spotifylinks::callback(state, oauth_state, code).await {
+                Ok(_) => true,
+                Err(why) => {
+                    state.hooks.error(None, &*why);
+                    false
+                }
+            }
+        }
+        _ => false,
+    };
+
+    let (code, page) = if linked {
+        (StatusCode::OK, LINKED_PAGE)
+    } else {
+        (StatusCode::BAD_REQUEST, LINK_FAILED_PAGE)
+    };
+
+    Response::builder()
+        .status(code)
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(page))
+        .unwrap()
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(code)
+        .body(Body::empty())
+        .unwrap()
+}
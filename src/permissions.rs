@@ -0,0 +1,289 @@
+use crate::{storage::Storage, State};
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, error::Error, sync::Mutex};
+use twilight_model::{
+    channel::Message,
+    guild::Permissions,
+    id::{GuildId, RoleId, UserId},
+};
+
+/// Whether the author of a guild message may change the bot's settings
+/// for that guild: the owner, anyone with Administrator or Manage Server
+/// through one of their roles, or one of the bot's configured owners.
+pub async fn is_admin(
+    state: &State,
+    msg: &Message,
+) -> Result<bool, Box<dyn Error + Send + Sync + 'static>> {
+    Ok(permissions(state, msg)
+        .await?
+        .intersects(Permissions::ADMINISTRATOR | Permissions::MANAGE_GUILD))
+}
+
+/// Whether the author may use the commands that change playback for
+/// everyone: anyone when the guild has no DJ role set with `j/setdj`,
+/// otherwise members with the role, anyone with Manage Channels, and
+/// admins.
+pub async fn is_dj(
+    state: &State,
+    msg: &Message,
+) -> Result<bool, Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.ok_or("Not a guild message.")?;
+
+    if dj_role(state, guild_id).await.is_none() {
+        return Ok(true);
+    }
+
+    has_dj_rights(state, msg).await
+}
+
+/// Whether the author is a DJ in their own right, even where everyone may
+/// use the DJ commands: a member with the DJ role if there is one, one
+/// granted the DJ commands for now with `j/dj grant`, anyone with Manage
+/// Channels, or an admin.
+pub async fn has_dj_rights(
+    state: &State,
+    msg: &Message,
+) -> Result<bool, Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.ok_or("Not a guild message.")?;
+
+    if state
+        .dj_grants
+        .is_granted(guild_id, msg.author.id, state.clock.now())
+    {
+        return Ok(true);
+    }
+
+    can_grant_dj(state, msg).await
+}
+
+/// Whether the author is a DJ other than through a grant, and so may
+/// grant the DJ commands to others: a member with the DJ role, anyone
+/// with Manage Channels, or an admin.
+pub async fn can_grant_dj(
+    state: &State,
+    msg: &Message,
+) -> Result<bool, Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.ok_or("Not a guild message.")?;
+
+    if let Some(dj_role) = dj_role(state, guild_id).await {
+        if roles(msg).contains(&dj_role) {
+            return Ok(true);
+        }
+    }
+
+    Ok(permissions(state, msg).await?.intersects(
+        Permissions::ADMINISTRATOR | Permissions::MANAGE_GUILD | Permissions::MANAGE_CHANNELS,
+    ))
+}
+
+/// Members given the DJ commands for a while with `j/dj grant`, with when
+/// each grant runs out, saved to `grants.json` whenever one changes.
+///
+/// Grants are checked against the time, so ones picked back up after a
+/// restart still end on schedule without their expiry timers.
+#[derive(Debug, Default)]
+pub struct DjGrants {
+    storage: Storage,
+    grants: Mutex<HashMap<GuildId, HashMap<UserId, DateTime<Utc>>>>,
+}
+
+impl DjGrants {
+    /// Loads the saved grants, dropping any that ran out before `now`.
+    pub fn load(
+        storage: &Storage,
+        now: DateTime<Utc>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        let mut grants: HashMap<GuildId, HashMap<UserId, DateTime<Utc>>> =
+            storage.load("grants.json")?;
+        for guild in grants.values_mut() {
+            guild.retain(|_, until| *until > now);
+        }
+        grants.retain(|_, guild| !guild.is_empty());
+
+        Ok(Self {
+            storage: storage.clone(),
+            grants: Mutex::new(grants),
+        })
+    }
+
+    /// Makes `user_id` a DJ until `until`, replacing any grant before, and
+    /// saves.
+    pub fn grant(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        until: DateTime<Utc>,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let mut grants = self.grants.lock().unwrap();
+        grants.entry(guild_id).or_default().insert(user_id, until);
+
+        self.storage.save("grants.json", &*grants)
+    }
+
+    /// Ends the grant that runs out at `until`, unless it has been
+    /// renewed since, and saves.
+    pub fn expire(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        until: DateTime<Utc>,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let mut grants = self.grants.lock().unwrap();
+
+        if let Some(guild) = grants.get_mut(&guild_id) {
+            if guild.get(&user_id) == Some(&until) {
+                guild.remove(&user_id);
+            }
+            if guild.is_empty() {
+                grants.remove(&guild_id);
+            }
+        }
+
+        self.storage.save("grants.json", &*grants)
+    }
+
+    pub fn is_granted(&self, guild_id: GuildId, user_id: UserId, now: DateTime<Utc>) -> bool {
+        self.grants
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .and_then(|guild| guild.get(&user_id))
+            .is_some_and(|until| *until > now)
+    }
+
+    /// The guild's grants that haven't run out, soonest to end first.
+    pub fn list(&self, guild_id: GuildId, now: DateTime<Utc>) -> Vec<(UserId, DateTime<Utc>)> {
+        let mut grants = self
+            .grants
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .map(|guild| {
+                guild
+                    .iter()
+                    .filter(|(_, until)| **until > now)
+                    .map(|(user_id, until)| (*user_id, *until))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        grants.sort_by_key(|(user_id, until)| (*until, user_id.0));
+
+        grants
+    }
+}
+
+/// The loudest the author may set `j/volume`, going by the highest of
+/// their roles' caps from `j/settings volumecap`. Admins have none.
+pub async fn volume_cap(
+    state: &State,
+    msg: &Message,
+) -> Result<Option<u32>, Box<dyn Error + Send + Sync + 'static>> {
+    let guild_id = msg.guild_id.ok_or("Not a guild message.")?;
+
+    let cap = state
+        .settings
+        .read()
+        .await
+        .get(&guild_id)
+        .and_then(|settings| {
+            roles(msg)
+                .iter()
+                .filter_map(|role_id| settings.volume_caps.get(role_id).copied())
+                .max()
+        });
+
+    if cap.is_none() || is_admin(state, msg).await? {
+        return Ok(None);
+    }
+
+    Ok(cap)
+}
+
+pub async fn dj_role(state: &State, guild_id: GuildId) -> Option<RoleId> {
+    state
+        .settings
+        .read()
+        .await
+        .get(&guild_id)
+        .and_then(|settings| settings.dj_role)
+}
+
+fn roles(msg: &Message) -> &[RoleId] {
+    msg.member
+        .as_ref()
+        .map(|member| member.roles.as_slice())
+        .unwrap_or_default()
+}
+
+/// The author's guild-wide permissions, with everything for the guild
+/// owner and the bot's configured owners.
+async fn permissions(
+    state: &State,
+    msg: &Message,
+) -> Result<Permissions, Box<dyn Error + Send + Sync + 'static>> {
+    if state.config.owner_ids.contains(&msg.author.id) {
+        return Ok(Permissions::all());
+    }
+
+    let guild_id = msg.guild_id.ok_or("Not a guild message.")?;
+    let guild = state.http.guild(guild_id).exec().await?.model().await?;
+
+    if guild.owner_id == msg.author.id {
+        return Ok(Permissions::all());
+    }
+
+    let member_roles = roles(msg);
+
+    // The @everyone role shares its ID with the guild.
+    Ok(guild
+        .roles
+        .iter()
+        .filter(|role| role.id.0 == guild_id.0 || member_roles.contains(&role.id))
+        .fold(Permissions::empty(), |acc, role| acc | role.permissions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Memory;
+    use chrono::{Duration, TimeZone};
+
+    const GUILD: GuildId = GuildId(1);
+    const USER: UserId = UserId(2);
+
+    #[test]
+    fn renewed_grants_outlast_the_first_expiry() {
+        let grants = DjGrants::default();
+        let now = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+        let first = now + Duration::hours(1);
+        let renewed = now + Duration::hours(2);
+
+        grants.grant(GUILD, USER, first).unwrap();
+        grants.grant(GUILD, USER, renewed).unwrap();
+        grants.expire(GUILD, USER, first).unwrap();
+        assert!(grants.is_granted(GUILD, USER, first));
+
+        grants.expire(GUILD, USER, renewed).unwrap();
+        assert!(!grants.is_granted(GUILD, USER, now));
+        assert!(grants.list(GUILD, now).is_empty());
+    }
+
+    #[test]
+    fn grants_that_ran_out_are_dropped_on_load() {
+        let storage = Storage::with_backend(Memory::default());
+        let now = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+
+        let grants = DjGrants::load(&storage, now).unwrap();
+        grants.grant(GUILD, USER, now + Duration::hours(1)).unwrap();
+        grants
+            .grant(GUILD, UserId(3), now + Duration::hours(3))
+            .unwrap();
+
+        let later = now + Duration::hours(2);
+        let reloaded = DjGrants::load(&storage, later).unwrap();
+        assert_eq!(
+            reloaded.list(GUILD, later),
+            [(UserId(3), now + Duration::hours(3))]
+        );
+    }
+}
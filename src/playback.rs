@@ -0,0 +1,84 @@
+use crate::{
+    commands,
+    queue::{self, QueuedTrack},
+    track::TrackInfo,
+    State,
+};
+use songbird::tracks::{LoopState, TrackHandle};
+use std::{error::Error, time::Duration};
+use twilight_model::id::GuildId;
+
+/// How long to wait on the driver for where a track is before giving up
+/// and starting it over.
+const DRIVER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Where a playing track had got to, so it can carry on in a new call:
+/// after `j/stop` and `j/resume`, when the voice connection comes back and
+/// when it's handed off to another server.
+#[derive(Clone, Debug)]
+pub struct PlaybackSnapshot {
+    /// Always has a source URL; see [`PlaybackSnapshot::capture`].
+    pub info: TrackInfo,
+    pub position: Duration,
+    /// What it was playing at, or `None` to use what the guild plays at.
+    pub volume: Option<f32>,
+    pub looping: bool,
+}
+
+impl PlaybackSnapshot {
+    /// Takes down where the guild's current track, `handle`, is. `None` if
+    /// it can't be resolved again.
+    ///
+    /// A driver that doesn't answer leaves the track to start over, at the
+    /// guild's volume.
+    pub async fn capture(state: &State, guild_id: GuildId, handle: &TrackHandle) -> Option<Self> {
+        let info = queue::current_info(state, guild_id, handle).await;
+        info.source_url.as_ref()?;
+
+        let snapshot = match tokio::time::timeout(DRIVER_TIMEOUT, handle.get_info()).await {
+            Ok(Ok(playing)) => Self {
+                info,
+                position: playing.position,
+                volume: Some(playing.volume),
+                looping: playing.loops != LoopState::Finite(0),
+            },
+            _ => Self {
+                info,
+                position: Duration::default(),
+                volume: None,
+                looping: false,
+            },
+        };
+
+        Some(snapshot)
+    }
+
+    /// Resolves the track again and plays it from where it was in
+    /// `guild_id`'s call.
+    pub async fn restore(
+        &self,
+        state: &State,
+        guild_id: GuildId,
+    ) -> Result<TrackHandle, Box<dyn Error + Send + Sync + 'static>> {
+        if state.songbird.get(guild_id).is_none() {
+            return Err("Not in a call.".into());
+        }
+
+        let input = state.resolver.resolve(self.info.source_url()).await?;
+        let mut track = QueuedTrack::resolved(input, self.info.clone());
+        track.start = Some(self.position);
+
+        let handle = commands::start_queued(state, guild_id, track)
+            .await?
+            .ok_or("Not in a call.")?;
+
+        if let Some(volume) = self.volume {
+            handle.set_volume(volume)?;
+        }
+        if self.looping {
+            handle.enable_loop()?;
+        }
+
+        Ok(handle)
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{
+    programs::Program,
+    search::{self, SearchResult},
+    sources,
+};
+use std::error::Error;
+
+/// Longer playlists are cut off here, so one request can't resolve and
+/// queue hundreds of tracks.
+pub const MAX_TRACKS: usize = 50;
+
+/// Whether `url` is a YouTube playlist (including a video played from
+/// one) or a SoundCloud set, which `j/play` expands into the queue.
+///
+/// Mixes are handled by [`crate::sources::mix_video`] before this is
+/// asked.
+pub fn is_playlist(url: &str) -> bool {
+    sources::identify(url).is_some_and(|identified| identified.is_playlist())
+}
+
+/// Lists the playlist's entries through youtube-dl, without resolving
+/// each one.
+pub async fn ytdl(
+    youtube_dl: &Program,
+    url: &str,
+) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+    search::flat_entries(youtube_dl, url).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_playlists() {
+        assert!(is_playlist("https://www.youtube.com/playlist?list=PLabc"));
+        assert!(is_playlist(
+            "https://www.youtube.com/watch?v=abc&list=PLabc&index=2"
+        ));
+        assert!(is_playlist("https://soundcloud.com/artist/sets/album"));
+    }
+
+    #[test]
+    fn single_tracks_are_not_playlists() {
+        assert!(!is_playlist("https://www.youtube.com/watch?v=abc"));
+        assert!(!is_playlist(
+            "https://www.youtube.com/watch?v=abc&list=RDabc"
+        ));
+        assert!(!is_playlist("https://soundcloud.com/artist/track"));
+        assert!(!is_playlist("https://example.com/playlist?list=PLabc"));
+    }
+}
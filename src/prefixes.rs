@@ -0,0 +1,98 @@
+use crate::storage::Storage;
+use std::{collections::HashMap, error::Error, sync::Mutex};
+use twilight_model::id::GuildId;
+
+/// Longest prefix `j/prefix` accepts.
+pub const MAX_LENGTH: usize = 8;
+
+/// Command prefixes guilds chose over the configured one, saved to
+/// `prefixes.json` whenever one changes.
+#[derive(Debug, Default)]
+pub struct Prefixes {
+    storage: Storage,
+    prefixes: Mutex<HashMap<GuildId, String>>,
+}
+
+impl Prefixes {
+    pub fn load(storage: &Storage) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(Self {
+            storage: storage.clone(),
+            prefixes: Mutex::new(storage.load("prefixes.json")?),
+        })
+    }
+
+    pub fn get(&self, guild_id: GuildId) -> Option<String> {
+        self.prefixes.lock().unwrap().get(&guild_id).cloned()
+    }
+
+    /// Sets the guild's prefix, or goes back to the configured one with
+    /// `None`, and saves the change.
+    pub fn set(
+        &self,
+        guild_id: GuildId,
+        prefix: Option<String>,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let mut prefixes = self.prefixes.lock().unwrap();
+
+        match prefix {
+            Some(prefix) => prefixes.insert(guild_id, prefix),
+            None => prefixes.remove(&guild_id),
+        };
+
+        self.storage.save("prefixes.json", &*prefixes)
+    }
+}
+
+/// Why a prefix can't be used, if it can't.
+pub fn check(prefix: &str) -> Result<(), String> {
+    if prefix.is_empty() || prefix.contains(char::is_whitespace) {
+        return Err("A prefix can't be empty or contain spaces.".to_string());
+    }
+
+    if prefix.chars().count() > MAX_LENGTH {
+        return Err(format!(
+            "Keep the prefix to {} characters or fewer.",
+            MAX_LENGTH
+        ));
+    }
+
+    // Mentions and channel links would make every ping look like a
+    // command.
+    if prefix.starts_with('<') || prefix.starts_with('@') || prefix.starts_with('#') {
+        return Err("A prefix can't start with <, @ or #.".to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_prefixes() {
+        assert!(check("!").is_ok());
+        assert!(check("m8.").is_ok());
+        assert!(check("").is_err());
+        assert!(check("a b").is_err());
+        assert!(check("waytoolong").is_err());
+        assert!(check("<@1>").is_err());
+    }
+
+    #[test]
+    fn saves_and_reloads() {
+        let dir = std::env::temp_dir().join(format!("musicm8-prefixes-{}", rand::random::<u64>()));
+        let storage = Storage::new(Some(&dir));
+        let prefixes = Prefixes::load(&storage).unwrap();
+
+        prefixes.set(GuildId(1), Some("!".to_string())).unwrap();
+        prefixes.set(GuildId(2), Some("?".to_string())).unwrap();
+        prefixes.set(GuildId(2), None).unwrap();
+
+        let reloaded = Prefixes::load(&storage).unwrap();
+        assert_eq!(reloaded.get(GuildId(1)).as_deref(), Some("!"));
+        assert_eq!(reloaded.get(GuildId(2)), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
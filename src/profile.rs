@@ -0,0 +1,63 @@
+use serde::Deserialize;
+use songbird::{
+    driver::{Bitrate, DecodeMode},
+    Config,
+};
+use std::{env, time::Duration};
+
+/// Playback tuning, chosen by the operator with `profile` in the config
+/// file or `PLAYBACK_PROFILE`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Profile {
+    #[default]
+    Standard,
+    /// For Raspberry Pi class hosts: a lower bitrate, no decryption of
+    /// incoming voice packets, and leaving the channel soon after playback
+    /// stops so an idle driver isn't competing for the CPU.
+    LowResource,
+}
+
+impl Profile {
+    /// The profile `PLAYBACK_PROFILE` asks for, if it is set.
+    pub fn from_env() -> Option<Self> {
+        match env::var("PLAYBACK_PROFILE").as_deref() {
+            Ok("low-resource") => Some(Profile::LowResource),
+            Ok("standard") => Some(Profile::Standard),
+            Err(_) => None,
+            Ok(other) => {
+                tracing::warn!("unknown PLAYBACK_PROFILE {:?}, using standard", other);
+                Some(Profile::Standard)
+            }
+        }
+    }
+
+    pub fn songbird_config(self) -> Config {
+        match self {
+            Profile::Standard => Config::default(),
+            // Only `j/record` needs what members say as PCM, and
+            // `recording::start` switches its call to `DecodeMode::Decode`
+            // itself. The overlay's `listen` relay resolves the track again
+            // rather than reading the call, so other calls can pass
+            // received audio along undecoded.
+            Profile::LowResource => Config::default().decode_mode(DecodeMode::Pass),
+        }
+    }
+
+    /// Encoder bitrate to set on every call, if not songbird's default.
+    pub fn bitrate(self) -> Option<Bitrate> {
+        match self {
+            Profile::Standard => None,
+            Profile::LowResource => Some(Bitrate::BitsPerSecond(64_000)),
+        }
+    }
+
+    /// How long a call may sit with nothing playing before the bot
+    /// leaves, unless the guild has set its own time.
+    pub fn idle_timeout(self) -> Duration {
+        match self {
+            Profile::Standard => Duration::from_secs(5 * 60),
+            Profile::LowResource => Duration::from_secs(2 * 60),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use serde::Deserialize;
+use std::{error::Error, io, path::PathBuf, process};
+use tokio::process::Command;
+
+/// An external program playback runs: where to find it, and arguments
+/// added to every run, such as a proxy or a user agent.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Program {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl Program {
+    /// The program called `name`, looked up on `PATH`.
+    pub fn named(name: &str) -> Self {
+        Self {
+            path: PathBuf::from(name),
+            args: Vec::new(),
+        }
+    }
+
+    /// A command running the program with its extra arguments, ready for
+    /// the rest.
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.path);
+        command.args(&self.args);
+        command
+    }
+
+    /// Like [`command`](Self::command), for pipelines the driver reads from
+    /// synchronously.
+    pub fn std_command(&self) -> process::Command {
+        let mut command = process::Command::new(&self.path);
+        command.args(&self.args);
+        command
+    }
+
+    /// Runs the program with `flag` and returns the first line it prints,
+    /// which for both programs is the version. The extra arguments are
+    /// left out, since they're meant for real runs.
+    pub async fn version(&self, flag: &str) -> io::Result<String> {
+        let output = Command::new(&self.path).arg(flag).output().await?;
+
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "`{}` exited with {}",
+                flag, output.status
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .unwrap_or("unknown")
+            .to_string())
+    }
+}
+
+/// The programs playback depends on, set with `programs` in the config
+/// file. Either can be left out to use the one on `PATH`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Programs {
+    /// youtube-dl, or a compatible fork such as yt-dlp. Its extra
+    /// arguments go before the URL.
+    pub youtube_dl: Program,
+    /// Its extra arguments go before the input, so they apply to reading
+    /// it.
+    pub ffmpeg: Program,
+}
+
+impl Default for Programs {
+    fn default() -> Self {
+        Self {
+            youtube_dl: Program::named("youtube-dl"),
+            ffmpeg: Program::named("ffmpeg"),
+        }
+    }
+}
+
+impl Programs {
+    /// Each program with its name and the flag that prints its version.
+    pub fn each(&self) -> [(&'static str, &Program, &'static str); 2] {
+        [
+            ("youtube-dl", &self.youtube_dl, "--version"),
+            ("ffmpeg", &self.ffmpeg, "-version"),
+        ]
+    }
+
+    /// Checks that every program can be run, so a bad path shows up at
+    /// startup rather than as the first `j/play` failing.
+    pub async fn probe(&self) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        for (name, program, flag) in self.each() {
+            match program.version(flag).await {
+                Ok(version) => tracing::info!("using {} {}", name, version),
+                Err(e) => {
+                    return Err(format!("can't run {} at {:?}: {}", name, program.path, e).into())
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
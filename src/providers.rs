@@ -0,0 +1,303 @@
+use crate::{
+    chapters::Chapter,
+    clock::Clock,
+    search::SearchResult,
+    sources::{identify, Source, SourceResolver},
+};
+use async_trait::async_trait;
+use serde::Deserialize;
+use songbird::input::Input;
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The window each provider's limit counts lookups over.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Who a query is looked up with. Keywords are searched for on YouTube.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Provider {
+    Youtube,
+    Soundcloud,
+    Bandcamp,
+    Other,
+}
+
+impl Provider {
+    pub fn of(query: &str) -> Self {
+        if query.starts_with("ytsearch") {
+            return Self::Youtube;
+        }
+
+        match identify(query).map(|identified| identified.source) {
+            None
+            | Some(Source::YoutubeVideo { .. })
+            | Some(Source::YoutubePlaylist { .. })
+            | Some(Source::YoutubeMix { .. }) => Self::Youtube,
+            Some(Source::SoundcloudTrack { .. }) | Some(Source::SoundcloudSet { .. }) => {
+                Self::Soundcloud
+            }
+            Some(Source::BandcampTrack { .. }) | Some(Source::BandcampAlbum { .. }) => {
+                Self::Bandcamp
+            }
+            Some(_) => Self::Other,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Youtube => "YouTube",
+            Self::Soundcloud => "SoundCloud",
+            Self::Bandcamp => "Bandcamp",
+            Self::Other => "The site",
+        }
+    }
+}
+
+/// How many lookups a minute each provider gets across every guild, set
+/// with `rate_limits` in the config file so a playlist import storm doesn't
+/// get the bot's address blocked. `null` leaves a provider unlimited.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimits {
+    pub youtube: Option<u32>,
+    pub soundcloud: Option<u32>,
+    pub bandcamp: Option<u32>,
+    /// Every other site, and direct links to files and streams.
+    pub other: Option<u32>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            youtube: Some(30),
+            soundcloud: Some(30),
+            bandcamp: Some(30),
+            other: None,
+        }
+    }
+}
+
+impl RateLimits {
+    pub fn per_minute(&self, provider: Provider) -> Option<u32> {
+        match provider {
+            Provider::Youtube => self.youtube,
+            Provider::Soundcloud => self.soundcloud,
+            Provider::Bandcamp => self.bandcamp,
+            Provider::Other => self.other,
+        }
+    }
+
+    /// Each limit with the name it has in the config file.
+    pub fn each(&self) -> [(&'static str, Option<u32>); 4] {
+        [
+            ("youtube", self.youtube),
+            ("soundcloud", self.soundcloud),
+            ("bandcamp", self.bandcamp),
+            ("other", self.other),
+        ]
+    }
+}
+
+/// The lookups booked with each provider over the last minute, and any
+/// waiting for a slot. Lookups are let through in the order they asked.
+#[derive(Debug, Default)]
+pub struct ProviderLimits {
+    limits: RateLimits,
+    booked: Mutex<HashMap<Provider, VecDeque<Instant>>>,
+}
+
+impl ProviderLimits {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            booked: Default::default(),
+        }
+    }
+
+    /// Books the next free slot with `provider`, returning how long to
+    /// wait from `now` until it comes.
+    pub fn reserve(&self, provider: Provider, now: Instant) -> Duration {
+        let limit = match self.limits.per_minute(provider) {
+            Some(limit) => limit as usize,
+            None => return Duration::default(),
+        };
+
+        let mut booked = self.booked.lock().unwrap();
+        let booked = booked.entry(provider).or_default();
+        while booked.front().is_some_and(|&at| at + WINDOW <= now) {
+            booked.pop_front();
+        }
+
+        let at = next_slot(booked, limit, now);
+        booked.push_back(at);
+        at - now
+    }
+
+    /// How long until the last of `count` more lookups with `provider`
+    /// would go through, without booking them.
+    pub fn expected_wait(&self, provider: Provider, count: usize, now: Instant) -> Duration {
+        let limit = match self.limits.per_minute(provider) {
+            Some(limit) => limit as usize,
+            None => return Duration::default(),
+        };
+
+        let mut booked = self
+            .booked
+            .lock()
+            .unwrap()
+            .get(&provider)
+            .cloned()
+            .unwrap_or_default();
+
+        let mut last = now;
+        for _ in 0..count {
+            last = next_slot(&booked, limit, now);
+            booked.push_back(last);
+        }
+        last - now
+    }
+}
+
+/// The earliest a lookup can go through, with no more than `limit` in any
+/// minute. `booked` is in order, as slots are handed out.
+fn next_slot(booked: &VecDeque<Instant>, limit: usize, now: Instant) -> Instant {
+    match booked.len().checked_sub(limit) {
+        Some(index) => now.max(booked[index] + WINDOW),
+        None => now,
+    }
+}
+
+/// A resolver that waits its turn with each provider before every lookup.
+#[derive(Debug)]
+pub struct LimitedResolver {
+    inner: Box<dyn SourceResolver>,
+    limits: Arc<ProviderLimits>,
+    clock: Arc<dyn Clock>,
+}
+
+impl LimitedResolver {
+    pub fn new(
+        inner: Box<dyn SourceResolver>,
+        limits: Arc<ProviderLimits>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            inner,
+            limits,
+            clock,
+        }
+    }
+
+    async fn wait_for(&self, query: &str) {
+        let provider = Provider::of(query);
+        let wait = self.limits.reserve(provider, self.clock.instant());
+
+        if wait > Duration::default() {
+            tracing::debug!("waiting {:?} for a {} slot", wait, provider.name());
+            self.clock.sleep(wait).await;
+        }
+    }
+}
+
+#[async_trait]
+impl SourceResolver for LimitedResolver {
+    async fn resolve(&self, query: &str) -> Result<Input, Box<dyn Error + Send + Sync + 'static>> {
+        self.wait_for(query).await;
+        self.inner.resolve(query).await
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+        // Searches go to YouTube whatever they say.
+        self.wait_for("ytsearch").await;
+        self.inner.search(query, limit).await
+    }
+
+    async fn playlist(
+        &self,
+        url: &str,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+        self.wait_for(url).await;
+        self.inner.playlist(url).await
+    }
+
+    async fn chapters(
+        &self,
+        url: &str,
+    ) -> Result<Vec<Chapter>, Box<dyn Error + Send + Sync + 'static>> {
+        self.wait_for(url).await;
+        self.inner.chapters(url).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_providers_apart() {
+        assert_eq!(Provider::of("https://youtu.be/abc"), Provider::Youtube);
+        assert_eq!(Provider::of("ytsearch1:Artist - Song"), Provider::Youtube);
+        assert_eq!(Provider::of("never gonna give you up"), Provider::Youtube);
+        assert_eq!(
+            Provider::of("https://soundcloud.com/artist/song"),
+            Provider::Soundcloud
+        );
+        assert_eq!(
+            Provider::of("https://artist.bandcamp.com/album/record"),
+            Provider::Bandcamp
+        );
+        assert_eq!(
+            Provider::of("https://example.com/song.mp3"),
+            Provider::Other
+        );
+    }
+
+    #[test]
+    fn lookups_wait_for_a_slot_in_order() {
+        let limits = ProviderLimits::new(RateLimits {
+            youtube: Some(2),
+            ..RateLimits::default()
+        });
+        let start = Instant::now();
+        let secs = Duration::from_secs;
+
+        assert_eq!(limits.reserve(Provider::Youtube, start), secs(0));
+        assert_eq!(limits.reserve(Provider::Youtube, start + secs(10)), secs(0));
+        assert_eq!(
+            limits.expected_wait(Provider::Youtube, 3, start + secs(20)),
+            secs(100)
+        );
+
+        // The third waits for the first to leave the window, the fourth for
+        // the second.
+        assert_eq!(
+            limits.reserve(Provider::Youtube, start + secs(20)),
+            secs(40)
+        );
+        assert_eq!(
+            limits.reserve(Provider::Youtube, start + secs(20)),
+            secs(50)
+        );
+
+        // Slots that have come and gone free up again.
+        assert_eq!(
+            limits.reserve(Provider::Youtube, start + secs(200)),
+            secs(0)
+        );
+
+        // Other providers are counted apart, and unlimited ones never wait.
+        assert_eq!(
+            limits.reserve(Provider::Soundcloud, start + secs(20)),
+            secs(0)
+        );
+        assert_eq!(limits.expected_wait(Provider::Other, 1000, start), secs(0));
+    }
+}
@@ -0,0 +1,805 @@
+use crate::{duration, storage::Storage, track::TrackInfo, State};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use songbird::{input::Input, tracks::TrackHandle};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    error::Error,
+    fmt::Write as _,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use twilight_model::id::{GuildId, UserId};
+
+/// How many tracks at the front of a queue are resolved ahead of time.
+/// Tracks behind them wait as what their playlist said about them, and
+/// [`warm`] resolves each in the background as it nears the front.
+pub const WARM_AHEAD: usize = 3;
+
+/// How many upcoming tracks a page of `j/queue` lists, keeping it within a
+/// message.
+pub const PAGE_SIZE: usize = 10;
+
+/// A track waiting for the one before it to end.
+///
+/// Sources are lazy, so holding the input doesn't download anything until
+/// the track is handed to the driver.
+#[derive(Debug)]
+pub struct QueuedTrack {
+    /// `None` until the track is resolved, which only fetches its metadata.
+    pub input: Option<Input>,
+    /// Always has a source URL and requester; see [`QueuedTrack::new`].
+    pub info: TrackInfo,
+    /// Where playback starts, from the requested URL.
+    pub start: Option<Duration>,
+    /// Tells the track apart from copies of it while it's resolved in the
+    /// background.
+    id: u64,
+}
+
+impl QueuedTrack {
+    /// Queues `input`, resolved from `url` for `requester`.
+    pub fn new(input: Input, url: &str, requester: UserId) -> Self {
+        let mut info = TrackInfo::from_metadata(&input.metadata).requested_by(requester);
+        info.source_url = Some(url.to_string());
+
+        Self::resolved(input, info)
+    }
+
+    pub fn resolved(input: Input, info: TrackInfo) -> Self {
+        Self {
+            input: Some(input),
+            info,
+            start: None,
+            id: next_id(),
+        }
+    }
+
+    /// Queues a track known only by `info`, to be resolved later.
+    pub fn pending(info: TrackInfo) -> Self {
+        Self {
+            input: None,
+            info,
+            start: None,
+            id: next_id(),
+        }
+    }
+
+    /// Takes the input the track resolved to, with what resolving it found
+    /// out. Who asked for it and any start position stay, and so do a
+    /// chapter's own title and length.
+    pub fn fill(&mut self, input: Input) {
+        let mut found = TrackInfo::from_metadata(&input.metadata);
+        if self.info.segment.is_some() {
+            found.title = self.info.title.take();
+            found.duration = self.info.duration;
+        }
+
+        self.info = TrackInfo {
+            title: found.title.or_else(|| self.info.title.take()),
+            artist: found.artist.or_else(|| self.info.artist.take()),
+            duration: found.duration,
+            thumbnail: found.thumbnail,
+            // A search stands in for some tracks, and this is what it found.
+            source_url: found.source_url.or_else(|| self.info.source_url.take()),
+            live: found.live,
+            ..self.info.clone()
+        };
+        self.input = Some(input);
+    }
+
+    pub fn title(&self) -> &str {
+        self.info.title()
+    }
+
+    pub fn url(&self) -> &str {
+        self.info.source_url()
+    }
+}
+
+fn next_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// What happens when the guild's current track ends, set with `j/loop`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoopMode {
+    /// The next queued track plays.
+    #[default]
+    Off,
+    /// The same track plays again.
+    Track,
+    /// The track goes to the back of the queue, so the queue repeats.
+    Queue,
+}
+
+impl LoopMode {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "off" => Some(LoopMode::Off),
+            "track" => Some(LoopMode::Track),
+            "queue" => Some(LoopMode::Queue),
+            _ => None,
+        }
+    }
+}
+
+/// What's kept of a queued track in `queues.json`, enough to resolve it
+/// again after a restart.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SavedTrack {
+    #[serde(flatten)]
+    pub info: TrackInfo,
+    #[serde(default)]
+    pub start: Option<Duration>,
+}
+
+/// How often [`run_saver`] writes out queues that changed.
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Per-guild tracks lined up behind the one in `trackdata`, next first.
+///
+/// Changes are written to `queues.json` by [`run_saver`], so the queues
+/// [`Queue::load`] finds there are lined up again when the bot comes back.
+#[derive(Debug, Default)]
+pub struct Queue {
+    storage: Storage,
+    queues: Mutex<HashMap<GuildId, VecDeque<QueuedTrack>>>,
+    /// Tracks [`warm`] is resolving, so they're only resolved once.
+    warming: Mutex<HashSet<u64>>,
+    /// Whether a queue changed since they were last written out.
+    dirty: AtomicBool,
+    /// Held while `queues.json` is written, so an older copy can't land
+    /// after a newer one.
+    writing: Mutex<()>,
+}
+
+impl Queue {
+    /// Lines up the tracks saved by the last run again, in their old
+    /// order. None of them are resolved yet; see [`restore`].
+    ///
+    /// The tracks that were playing when the bot stopped aren't saved,
+    /// only the ones behind them.
+    pub fn load(storage: &Storage) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        let saved: HashMap<GuildId, Vec<SavedTrack>> = storage.load("queues.json")?;
+
+        let queues = saved
+            .into_iter()
+            .filter(|(_, tracks)| !tracks.is_empty())
+            .map(|(guild_id, tracks)| {
+                let queue = tracks
+                    .into_iter()
+                    .map(|track| QueuedTrack {
+                        start: track.start,
+                        ..QueuedTrack::pending(track.info)
+                    })
+                    .collect();
+
+                (guild_id, queue)
+            })
+            .collect();
+
+        Ok(Self {
+            storage: storage.clone(),
+            queues: Mutex::new(queues),
+            ..Self::default()
+        })
+    }
+
+    /// Adds a track to the back, returning its position counting from 1.
+    pub fn push(&self, guild_id: GuildId, track: QueuedTrack) -> usize {
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.entry(guild_id).or_default();
+
+        queue.push_back(track);
+        let position = queue.len();
+
+        self.changed();
+        position
+    }
+
+    /// Puts `tracks` at the front, in the order given, ahead of whatever
+    /// is waiting.
+    pub fn push_front(&self, guild_id: GuildId, tracks: Vec<QueuedTrack>) {
+        if tracks.is_empty() {
+            return;
+        }
+
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.entry(guild_id).or_default();
+
+        for track in tracks.into_iter().rev() {
+            queue.push_front(track);
+        }
+
+        self.changed();
+    }
+
+    pub fn pop(&self, guild_id: GuildId) -> Option<QueuedTrack> {
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.get_mut(&guild_id)?;
+        let track = queue.pop_front();
+
+        if queue.is_empty() {
+            queues.remove(&guild_id);
+        }
+
+        self.changed();
+        track
+    }
+
+    pub fn is_empty(&self, guild_id: GuildId) -> bool {
+        !self.queues.lock().unwrap().contains_key(&guild_id)
+    }
+
+    /// Takes `requester`'s tracks out of the guild's queue, keeping their
+    /// order, and leaves everyone else's in place.
+    pub fn take_requested_by(&self, guild_id: GuildId, requester: UserId) -> Vec<QueuedTrack> {
+        let mut queues = self.queues.lock().unwrap();
+        let queue = match queues.remove(&guild_id) {
+            Some(queue) => queue,
+            None => return Vec::new(),
+        };
+
+        let (taken, kept): (VecDeque<_>, VecDeque<_>) = queue
+            .into_iter()
+            .partition(|track| track.info.requester == Some(requester));
+
+        if !kept.is_empty() {
+            queues.insert(guild_id, kept);
+        }
+
+        self.changed();
+        taken.into()
+    }
+
+    /// Takes the track at `position` out of the queue, counting from 1, if
+    /// `allowed` says it may go. Otherwise it stays, and its info comes
+    /// back in its place.
+    pub fn remove(
+        &self,
+        guild_id: GuildId,
+        position: usize,
+        allowed: impl FnOnce(&TrackInfo) -> bool,
+    ) -> Option<Result<QueuedTrack, TrackInfo>> {
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.get_mut(&guild_id)?;
+
+        let index = position.checked_sub(1)?;
+        let info = &queue.get(index)?.info;
+        if !allowed(info) {
+            return Some(Err(info.clone()));
+        }
+        let track = queue.remove(index)?;
+
+        if queue.is_empty() {
+            queues.remove(&guild_id);
+        }
+
+        self.changed();
+        Some(Ok(track))
+    }
+
+    /// Moves the track at `from` to `to`, both counting from 1, returning
+    /// its title. Nothing moves unless both positions are in the queue.
+    pub fn move_track(&self, guild_id: GuildId, from: usize, to: usize) -> Option<String> {
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.get_mut(&guild_id)?;
+
+        if !(1..=queue.len()).contains(&from) || !(1..=queue.len()).contains(&to) {
+            return None;
+        }
+
+        let track = queue.remove(from - 1)?;
+        let title = track.title().to_string();
+        queue.insert(to - 1, track);
+
+        self.changed();
+        Some(title)
+    }
+
+    /// Puts the guild's waiting tracks in a random order, returning how many
+    /// there are.
+    pub fn shuffle(&self, guild_id: GuildId) -> usize {
+        let mut queues = self.queues.lock().unwrap();
+        let shuffled = match queues.get_mut(&guild_id) {
+            Some(queue) => {
+                queue.make_contiguous().shuffle(&mut rand::thread_rng());
+                queue.len()
+            }
+            None => return 0,
+        };
+
+        self.changed();
+        shuffled
+    }
+
+    /// Drops every waiting track, returning how many there were.
+    pub fn clear(&self, guild_id: GuildId) -> usize {
+        let mut queues = self.queues.lock().unwrap();
+        let cleared = queues.remove(&guild_id).map_or(0, |queue| queue.len());
+
+        if cleared > 0 {
+            self.changed();
+        }
+
+        cleared
+    }
+
+    /// What's known about each waiting track, next first.
+    pub fn list(&self, guild_id: GuildId) -> Vec<TrackInfo> {
+        self.queues
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .map(|queue| queue.iter().map(|track| track.info.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// The IDs and URLs of the unresolved tracks among the first
+    /// [`WARM_AHEAD`], which are now [`warm`]'s to resolve.
+    fn claim_unresolved(&self, guild_id: GuildId) -> Vec<(u64, String)> {
+        let queues = self.queues.lock().unwrap();
+        let mut warming = self.warming.lock().unwrap();
+
+        queues
+            .get(&guild_id)
+            .into_iter()
+            .flatten()
+            .take(WARM_AHEAD)
+            .filter(|track| track.input.is_none() && warming.insert(track.id))
+            .map(|track| (track.id, track.url().to_string()))
+            .collect()
+    }
+
+    /// Fills in the queued track `id` with the input it resolved to, or
+    /// takes it out of the queue with `None`. Tracks that have left the
+    /// queue since are left alone.
+    fn settle(&self, guild_id: GuildId, id: u64, input: Option<Input>) {
+        self.warming.lock().unwrap().remove(&id);
+
+        let mut queues = self.queues.lock().unwrap();
+        let queue = match queues.get_mut(&guild_id) {
+            Some(queue) => queue,
+            None => return,
+        };
+        let index = match queue.iter().position(|track| track.id == id) {
+            Some(index) => index,
+            None => return,
+        };
+
+        match input {
+            Some(input) => queue[index].fill(input),
+            None => {
+                queue.remove(index);
+                if queue.is_empty() {
+                    queues.remove(&guild_id);
+                }
+            }
+        }
+
+        self.changed();
+    }
+
+    /// How many tracks are waiting across every guild.
+    pub fn total(&self) -> usize {
+        self.queues
+            .lock()
+            .unwrap()
+            .values()
+            .map(VecDeque::len)
+            .sum()
+    }
+
+    /// The guilds with tracks waiting.
+    pub fn guilds(&self) -> Vec<GuildId> {
+        self.queues.lock().unwrap().keys().copied().collect()
+    }
+
+    /// Writes every queue as it stands, for shutdown. This blocks on the
+    /// disk, so [`run_saver`] calls it on a blocking thread.
+    pub fn flush(&self) {
+        let _writing = self.writing.lock().unwrap();
+
+        self.dirty.store(false, Ordering::Relaxed);
+        let saved = saved(&self.queues.lock().unwrap());
+
+        // A failure only means the last changes may be forgotten by a
+        // restart; it's logged rather than failing the commands that
+        // made them.
+        if let Err(why) = self.storage.save("queues.json", &saved) {
+            tracing::warn!("failed to save queues: {}", why);
+        }
+    }
+
+    /// Marks the queues for [`run_saver`] to write out.
+    fn changed(&self) {
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+}
+
+fn saved(queues: &HashMap<GuildId, VecDeque<QueuedTrack>>) -> HashMap<GuildId, Vec<SavedTrack>> {
+    queues
+        .iter()
+        .map(|(guild_id, queue)| {
+            let tracks = queue
+                .iter()
+                .map(|track| SavedTrack {
+                    info: track.info.clone(),
+                    start: track.start,
+                })
+                .collect::<Vec<_>>();
+
+            (*guild_id, tracks)
+        })
+        .collect()
+}
+
+/// Writes the queues out every [`SAVE_INTERVAL`] if they changed, so
+/// pushing or popping a track never waits on the disk.
+pub async fn run_saver(state: State) {
+    loop {
+        state.clock.sleep(SAVE_INTERVAL).await;
+
+        if state.queue.dirty.load(Ordering::Relaxed) {
+            let state = Arc::clone(&state);
+            let _ = tokio::task::spawn_blocking(move || state.queue.flush()).await;
+        }
+    }
+}
+
+/// Resolves the metadata of the guild's next few unresolved tracks in the
+/// background, so `j/queue` shows what they really are before they play.
+/// Tracks that no longer resolve or are over the length limit are dropped.
+pub fn warm(state: &State, guild_id: GuildId) {
+    let claimed = state.queue.claim_unresolved(guild_id);
+    if claimed.is_empty() {
+        return;
+    }
+
+    let state = Arc::clone(state);
+    tokio::spawn(async move {
+        for (id, url) in claimed {
+            let input = match state.resolver.resolve(&url).await {
+                Ok(input) if state.quotas.check_length(input.metadata.duration).is_ok() => {
+                    Some(input)
+                }
+                Ok(_) => {
+                    tracing::info!(guild_id = %guild_id, "dropping overlong queued track {}", url);
+                    None
+                }
+                Err(why) => {
+                    tracing::warn!(guild_id = %guild_id, "dropping queued track {}: {}", url, why);
+                    None
+                }
+            };
+
+            state.queue.settle(guild_id, id, input);
+        }
+    });
+}
+
+/// Starts resolving the front of every queue [`Queue::load`] picked back
+/// up. The rest are left to [`warm`] as they near the front, and restored
+/// queues wait for someone to `j/join`.
+pub fn restore(state: &State) {
+    for guild_id in state.queue.guilds() {
+        tracing::info!("restored queued tracks in {}", guild_id);
+        warm(state, guild_id);
+    }
+}
+
+/// How long [`current`] waits for the driver to say how a track is doing.
+/// A connected driver answers within a mixing cycle, but one whose
+/// connection is down doesn't answer until it's back.
+const DRIVER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The guild's current track, unless it has already ended. A track the
+/// driver doesn't answer for is still the current one, waiting for the
+/// connection to come back.
+pub async fn current(state: &State, guild_id: GuildId) -> Option<TrackHandle> {
+    let handle = state.trackdata.read().await.get(&guild_id).cloned()?;
+
+    match tokio::time::timeout(DRIVER_TIMEOUT, handle.get_info()).await {
+        Ok(Ok(info)) if !info.playing.is_done() => Some(handle),
+        Ok(_) => None,
+        Err(_) => Some(handle),
+    }
+}
+
+/// What's known about `handle`, the guild's current track: who asked for
+/// it and, for a chapter, its own title and length, on top of what the
+/// driver was given.
+pub async fn current_info(state: &State, guild_id: GuildId, handle: &TrackHandle) -> TrackInfo {
+    state
+        .now_playing
+        .read()
+        .await
+        .get(&guild_id)
+        .cloned()
+        .unwrap_or_else(|| TrackInfo::of(handle))
+}
+
+/// How many pages of `j/queue` `len` upcoming tracks take. An empty queue
+/// still has the one page saying so.
+pub fn page_count(len: usize) -> usize {
+    len.div_ceil(PAGE_SIZE).max(1)
+}
+
+/// Page `page` of `upcoming`, counting from 1: each track's position, title,
+/// length and who asked for it.
+pub fn render_page(upcoming: &[TrackInfo], page: usize) -> String {
+    if upcoming.is_empty() {
+        return "The queue is empty.".to_string();
+    }
+
+    let pages = page_count(upcoming.len());
+    let mut content = if pages == 1 {
+        "Up next:".to_string()
+    } else {
+        format!("Up next, page {} of {}:", page, pages)
+    };
+
+    let shown = upcoming
+        .iter()
+        .enumerate()
+        .skip((page - 1) * PAGE_SIZE)
+        .take(PAGE_SIZE);
+    for (position, track) in shown {
+        let _ = write!(content, "\n{}. **{}**", position + 1, track.title());
+        if let Some(length) = track.duration.filter(|_| !track.live) {
+            let _ = write!(content, " ({})", duration::format(length));
+        }
+        let _ = write!(content, " <{}>", track.source_url());
+        if let Some(requester) = track.requester {
+            let _ = write!(content, " for <@{}>", requester);
+        }
+    }
+
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use songbird::input::reader::Reader;
+
+    const GUILD: GuildId = GuildId(1);
+
+    fn track(title: &str) -> QueuedTrack {
+        requested(title, UserId(1))
+    }
+
+    fn requested(title: &str, requester: UserId) -> QueuedTrack {
+        let mut track = QueuedTrack::new(
+            Input::float_pcm(true, Reader::from_memory(Vec::new())),
+            &format!("https://example.com/{}", title),
+            requester,
+        );
+        track.info.title = Some(title.to_string());
+        track
+    }
+
+    #[test]
+    fn plays_in_order() {
+        let queue = Queue::default();
+
+        assert_eq!(queue.push(GUILD, track("a")), 1);
+        assert_eq!(queue.push(GUILD, track("b")), 2);
+
+        assert_eq!(queue.pop(GUILD).unwrap().title(), "a");
+        assert_eq!(queue.pop(GUILD).unwrap().title(), "b");
+        assert!(queue.pop(GUILD).is_none());
+        assert!(queue.is_empty(GUILD));
+    }
+
+    #[test]
+    fn guilds_are_separate() {
+        let queue = Queue::default();
+
+        queue.push(GUILD, track("a"));
+        queue.push(GuildId(2), track("b"));
+
+        assert_eq!(queue.clear(GUILD), 1);
+        assert!(queue.is_empty(GUILD));
+        assert_eq!(
+            queue
+                .list(GuildId(2))
+                .iter()
+                .map(|info| (info.title(), info.source_url()))
+                .collect::<Vec<_>>(),
+            [("b", "https://example.com/b")]
+        );
+    }
+
+    #[test]
+    fn takes_one_requesters_tracks() {
+        let queue = Queue::default();
+
+        queue.push(GUILD, requested("a", UserId(1)));
+        queue.push(GUILD, requested("b", UserId(2)));
+        queue.push(GUILD, requested("c", UserId(1)));
+
+        let taken = queue.take_requested_by(GUILD, UserId(1));
+        assert_eq!(
+            taken.iter().map(QueuedTrack::title).collect::<Vec<_>>(),
+            ["a", "c"]
+        );
+        assert_eq!(queue.pop(GUILD).unwrap().title(), "b");
+
+        assert!(queue.take_requested_by(GUILD, UserId(1)).is_empty());
+    }
+
+    #[test]
+    fn puts_tracks_back_at_the_front() {
+        let queue = Queue::default();
+
+        queue.push(GUILD, track("c"));
+        queue.push_front(GUILD, vec![track("a"), track("b")]);
+
+        let titles = queue
+            .list(GUILD)
+            .into_iter()
+            .map(|info| info.title().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn removes_and_moves_tracks() {
+        let queue = Queue::default();
+
+        for title in ["a", "b", "c", "d"] {
+            queue.push(GUILD, track(title));
+        }
+
+        assert!(queue.remove(GUILD, 0, |_| true).is_none());
+        assert!(queue.remove(GUILD, 5, |_| true).is_none());
+        assert_eq!(
+            queue.remove(GUILD, 2, |_| true).unwrap().unwrap().title(),
+            "b"
+        );
+        assert_eq!(
+            queue
+                .remove(GUILD, 1, |info| info.requester == Some(UserId(2)))
+                .unwrap()
+                .unwrap_err()
+                .title(),
+            "a"
+        );
+
+        assert_eq!(queue.move_track(GUILD, 3, 1).as_deref(), Some("d"));
+        assert!(queue.move_track(GUILD, 1, 4).is_none());
+
+        let titles = queue
+            .list(GUILD)
+            .into_iter()
+            .map(|info| info.title().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, ["d", "a", "c"]);
+    }
+
+    #[test]
+    fn shuffles_the_waiting_tracks() {
+        let queue = Queue::default();
+
+        for title in ["a", "b", "c", "d"] {
+            queue.push(GUILD, track(title));
+        }
+
+        assert_eq!(queue.shuffle(GUILD), 4);
+        let mut titles = queue
+            .list(GUILD)
+            .into_iter()
+            .map(|info| info.title().to_string())
+            .collect::<Vec<_>>();
+        titles.sort();
+        assert_eq!(titles, ["a", "b", "c", "d"]);
+
+        assert_eq!(queue.shuffle(GuildId(2)), 0);
+    }
+
+    #[test]
+    fn resolves_the_front_of_the_queue() {
+        let queue = Queue::default();
+        let pending = |title: &str| {
+            QueuedTrack::pending(TrackInfo {
+                title: Some(title.to_string()),
+                source_url: Some(format!("ytsearch1:{}", title)),
+                ..Default::default()
+            })
+        };
+
+        queue.push(GUILD, track("a"));
+        for title in ["b", "c", "d"] {
+            queue.push(GUILD, pending(title));
+        }
+
+        let claimed = queue.claim_unresolved(GUILD);
+        assert_eq!(
+            claimed
+                .iter()
+                .map(|(_, url)| url.as_str())
+                .collect::<Vec<_>>(),
+            ["ytsearch1:b", "ytsearch1:c"]
+        );
+        assert!(queue.claim_unresolved(GUILD).is_empty());
+
+        let mut input = Input::float_pcm(true, Reader::from_memory(Vec::new()));
+        input.metadata.title = Some("B, for real".to_string());
+        input.metadata.source_url = Some("https://example.com/b".to_string());
+        input.metadata.duration = Some(Duration::from_secs(60));
+        queue.settle(GUILD, claimed[0].0, Some(input));
+        queue.settle(GUILD, claimed[1].0, None);
+
+        let listed = queue.list(GUILD);
+        assert_eq!(
+            listed.iter().map(TrackInfo::title).collect::<Vec<_>>(),
+            ["a", "B, for real", "d"]
+        );
+        assert_eq!(listed[1].source_url(), "https://example.com/b");
+        assert_eq!(listed[1].duration, Some(Duration::from_secs(60)));
+
+        // With one dropped, the next moves up into reach.
+        assert_eq!(queue.claim_unresolved(GUILD).len(), 1);
+    }
+
+    #[test]
+    fn lists_the_queue_a_page_at_a_time() {
+        let upcoming = (1..=12)
+            .map(|n| TrackInfo {
+                title: Some(format!("Track {}", n)),
+                duration: Some(Duration::from_secs(61)),
+                source_url: Some(format!("https://example.com/{}", n)),
+                requester: Some(UserId(5)),
+                live: n == 12,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(page_count(0), 1);
+        assert_eq!(page_count(10), 1);
+        assert_eq!(page_count(upcoming.len()), 2);
+
+        let first = render_page(&upcoming, 1);
+        assert!(first.starts_with(
+            "Up next, page 1 of 2:\n1. **Track 1** (1:01) <https://example.com/1> for <@5>\n"
+        ));
+        assert_eq!(first.lines().count(), 1 + PAGE_SIZE);
+
+        assert_eq!(
+            render_page(&upcoming, 2),
+            "Up next, page 2 of 2:\n\
+             11. **Track 11** (1:01) <https://example.com/11> for <@5>\n\
+             12. **Track 12** <https://example.com/12> for <@5>"
+        );
+        assert_eq!(render_page(&[], 1), "The queue is empty.");
+    }
+
+    #[test]
+    fn saves_waiting_tracks() {
+        let dir = std::env::temp_dir().join(format!("musicm8-queue-{}", rand::random::<u64>()));
+        let storage = Storage::new(Some(&dir));
+        let queue = Queue::load(&storage).unwrap();
+
+        queue.push(GUILD, requested("a", UserId(1)));
+        queue.push(GUILD, requested("b", UserId(2)));
+        queue.pop(GUILD);
+        queue.flush();
+
+        let saved: HashMap<GuildId, Vec<SavedTrack>> = storage.load("queues.json").unwrap();
+        assert_eq!(saved[&GUILD].len(), 1);
+        assert_eq!(saved[&GUILD][0].info.title(), "b");
+        assert_eq!(saved[&GUILD][0].info.requester, Some(UserId(2)));
+
+        let reloaded = Queue::load(&storage).unwrap();
+        assert_eq!(reloaded.guilds(), [GUILD]);
+        assert_eq!(reloaded.claim_unresolved(GUILD).len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
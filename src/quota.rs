@@ -0,0 +1,297 @@
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::{
+    collections::HashMap, error::Error, fmt, fs, io::ErrorKind, sync::Mutex, time::Duration,
+};
+use twilight_model::id::GuildId;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub struct Limits {
+    /// Tracks resolved per UTC day.
+    #[serde(default)]
+    pub tracks: Option<u32>,
+    /// Minutes of audio streamed per UTC day.
+    #[serde(default)]
+    pub minutes: Option<u64>,
+}
+
+/// Operator configuration read from `quotas.json`. Without the file
+/// only the track length limit applies.
+#[derive(Debug, Deserialize)]
+pub struct QuotaConfig {
+    #[serde(default)]
+    pub per_guild: Limits,
+    #[serde(default)]
+    pub global: Limits,
+    /// Longest track that may be played; `null` allows any length.
+    #[serde(default = "default_max_track_minutes")]
+    pub max_track_minutes: Option<u64>,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            per_guild: Limits::default(),
+            global: Limits::default(),
+            max_track_minutes: default_max_track_minutes(),
+        }
+    }
+}
+
+fn default_max_track_minutes() -> Option<u64> {
+    Some(3 * 60)
+}
+
+/// Which limit stopped a new track.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Exceeded {
+    GuildTracks(u32),
+    GuildMinutes(u64),
+    GlobalTracks,
+    GlobalMinutes,
+}
+
+impl fmt::Display for Exceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exceeded::GuildTracks(limit) => {
+                write!(f, "This server has played its {} tracks for today", limit)?
+            }
+            Exceeded::GuildMinutes(limit) => write!(
+                f,
+                "This server has used its {} minutes of playback for today",
+                limit
+            )?,
+            Exceeded::GlobalTracks | Exceeded::GlobalMinutes => {
+                f.write_str("I've hit my playback limit for today across all servers")?
+            }
+        }
+
+        f.write_str(". Quotas reset at midnight UTC, sorry!")
+    }
+}
+
+/// A track over the length limit, in minutes.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TooLong(pub u64);
+
+impl fmt::Display for TooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "That track is longer than the {} minute limit, so I won't play it.",
+            self.0
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Usage {
+    tracks: u32,
+    streamed: Duration,
+}
+
+impl Usage {
+    fn exceeds(&self, limits: Limits) -> (bool, bool) {
+        (
+            limits.tracks.is_some_and(|limit| self.tracks >= limit),
+            limits
+                .minutes
+                .is_some_and(|limit| self.streamed.as_secs() >= limit * 60),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Day {
+    date: Option<NaiveDate>,
+    global: Usage,
+    guilds: HashMap<GuildId, Usage>,
+}
+
+impl Day {
+    /// Starts over once the UTC date changes.
+    fn roll(&mut self, today: NaiveDate) -> &mut Self {
+        if self.date != Some(today) {
+            *self = Day {
+                date: Some(today),
+                ..Default::default()
+            };
+        }
+
+        self
+    }
+}
+
+/// Daily usage counters, kept in memory, so a restart resets them.
+#[derive(Debug, Default)]
+pub struct Quotas {
+    config: QuotaConfig,
+    day: Mutex<Day>,
+}
+
+impl Quotas {
+    pub fn new(config: QuotaConfig) -> Self {
+        Self {
+            config,
+            day: Default::default(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Self::new(serde_json::from_str(&contents)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Whether the guild may start another track today.
+    pub fn check(&self, guild_id: GuildId, today: NaiveDate) -> Result<(), Exceeded> {
+        let mut day = self.day.lock().unwrap();
+        let day = day.roll(today);
+
+        match day.global.exceeds(self.config.global) {
+            (true, _) => return Err(Exceeded::GlobalTracks),
+            (_, true) => return Err(Exceeded::GlobalMinutes),
+            _ => {}
+        }
+
+        let usage = day.guilds.get(&guild_id).copied().unwrap_or_default();
+        let limits = self.config.per_guild;
+
+        match (usage.exceeds(limits), limits.tracks, limits.minutes) {
+            ((true, _), Some(limit), _) => Err(Exceeded::GuildTracks(limit)),
+            ((_, true), _, Some(limit)) => Err(Exceeded::GuildMinutes(limit)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a track's length from its metadata, before any audio is
+    /// fetched. Tracks of unknown length, like streams, are allowed.
+    pub fn check_length(&self, duration: Option<Duration>) -> Result<(), TooLong> {
+        match (self.config.max_track_minutes, duration) {
+            (Some(limit), Some(duration)) if duration.as_secs() > limit * 60 => Err(TooLong(limit)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn record_track(&self, guild_id: GuildId, today: NaiveDate) {
+        let mut day = self.day.lock().unwrap();
+        let day = day.roll(today);
+
+        day.global.tracks += 1;
+        day.guilds.entry(guild_id).or_default().tracks += 1;
+    }
+
+    pub fn record_streamed(&self, guild_id: GuildId, today: NaiveDate, streamed: Duration) {
+        let mut day = self.day.lock().unwrap();
+        let day = day.roll(today);
+
+        day.global.streamed += streamed;
+        day.guilds.entry(guild_id).or_default().streamed += streamed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quotas(per_guild: Limits, global: Limits) -> Quotas {
+        Quotas::new(QuotaConfig {
+            per_guild,
+            global,
+            ..Default::default()
+        })
+    }
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2021, 1, day)
+    }
+
+    #[test]
+    fn unlimited_by_default() {
+        let quotas = Quotas::default();
+
+        for _ in 0..1000 {
+            quotas.record_track(GuildId(1), day(1));
+        }
+
+        assert_eq!(quotas.check(GuildId(1), day(1)), Ok(()));
+    }
+
+    #[test]
+    fn guild_track_limit() {
+        let limits = Limits {
+            tracks: Some(2),
+            minutes: None,
+        };
+        let quotas = quotas(limits, Limits::default());
+
+        quotas.record_track(GuildId(1), day(1));
+        assert_eq!(quotas.check(GuildId(1), day(1)), Ok(()));
+
+        quotas.record_track(GuildId(1), day(1));
+        assert_eq!(
+            quotas.check(GuildId(1), day(1)),
+            Err(Exceeded::GuildTracks(2))
+        );
+        assert_eq!(quotas.check(GuildId(2), day(1)), Ok(()));
+    }
+
+    #[test]
+    fn global_minute_limit() {
+        let limits = Limits {
+            tracks: None,
+            minutes: Some(10),
+        };
+        let quotas = quotas(Limits::default(), limits);
+
+        quotas.record_streamed(GuildId(1), day(1), Duration::from_secs(9 * 60));
+        assert_eq!(quotas.check(GuildId(2), day(1)), Ok(()));
+
+        quotas.record_streamed(GuildId(2), day(1), Duration::from_secs(60));
+        assert_eq!(
+            quotas.check(GuildId(3), day(1)),
+            Err(Exceeded::GlobalMinutes)
+        );
+    }
+
+    #[test]
+    fn resets_on_a_new_day() {
+        let limits = Limits {
+            tracks: Some(1),
+            minutes: None,
+        };
+        let quotas = quotas(limits, Limits::default());
+
+        quotas.record_track(GuildId(1), day(1));
+        assert!(quotas.check(GuildId(1), day(1)).is_err());
+        assert_eq!(quotas.check(GuildId(1), day(2)), Ok(()));
+    }
+
+    #[test]
+    fn length_limit() {
+        let quotas = Quotas::default();
+
+        assert_eq!(quotas.check_length(None), Ok(()));
+        assert_eq!(
+            quotas.check_length(Some(Duration::from_secs(3 * 3600))),
+            Ok(())
+        );
+        assert_eq!(
+            quotas.check_length(Some(Duration::from_secs(12 * 3600))),
+            Err(TooLong(180))
+        );
+    }
+
+    #[test]
+    fn length_limit_can_be_disabled() {
+        let config: QuotaConfig = serde_json::from_str(r#"{"max_track_minutes": null}"#).unwrap();
+
+        assert_eq!(
+            Quotas::new(config).check_length(Some(Duration::from_secs(12 * 3600))),
+            Ok(())
+        );
+    }
+}
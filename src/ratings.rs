@@ -0,0 +1,314 @@
+use crate::storage::Storage;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+    error::Error,
+    sync::Mutex,
+};
+use twilight_model::{
+    channel::ReactionType,
+    id::{GuildId, MessageId, UserId},
+};
+
+pub const UPVOTE: &str = "👍";
+pub const DOWNVOTE: &str = "👎";
+
+/// How many now-playing messages stay open for votes.
+const TRACKED_MESSAGES: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Vote {
+    Up,
+    Down,
+}
+
+impl Vote {
+    pub fn from_emoji(emoji: &ReactionType) -> Option<Self> {
+        match emoji {
+            ReactionType::Unicode { name } if name == UPVOTE => Some(Vote::Up),
+            ReactionType::Unicode { name } if name == DOWNVOTE => Some(Vote::Down),
+            _ => None,
+        }
+    }
+}
+
+/// One line of a guild's chart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rated {
+    pub title: String,
+    pub url: String,
+    pub up: usize,
+    pub down: usize,
+}
+
+impl Rated {
+    pub fn score(&self) -> i64 {
+        self.up as i64 - self.down as i64
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct TrackVotes {
+    title: String,
+    up: HashSet<UserId>,
+    down: HashSet<UserId>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Inner {
+    /// Now-playing messages, oldest first, and the track each announced.
+    messages: VecDeque<(MessageId, GuildId, String)>,
+    /// Votes by guild and track URL.
+    tracks: HashMap<GuildId, HashMap<String, TrackVotes>>,
+}
+
+/// 👍/👎 votes on now-playing messages, kept per guild and per track URL
+/// so a track keeps its votes across plays. Saved to `ratings.json`
+/// whenever a vote or an open message changes, so charts and dislikes
+/// hold across restarts.
+#[derive(Debug, Default)]
+pub struct Ratings {
+    storage: Storage,
+    inner: Mutex<Inner>,
+}
+
+impl Ratings {
+    pub fn load(storage: &Storage) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(Self {
+            storage: storage.clone(),
+            inner: Mutex::new(storage.load("ratings.json")?),
+        })
+    }
+
+    /// Opens `message_id` for votes on the track at `url`, and saves.
+    pub fn track_message(
+        &self,
+        message_id: MessageId,
+        guild_id: GuildId,
+        url: &str,
+        title: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.messages.len() == TRACKED_MESSAGES {
+            inner.messages.pop_front();
+        }
+
+        inner
+            .messages
+            .push_back((message_id, guild_id, url.to_string()));
+        inner
+            .tracks
+            .entry(guild_id)
+            .or_default()
+            .entry(url.to_string())
+            .or_default()
+            .title = title.to_string();
+
+        self.storage.save("ratings.json", &*inner)
+    }
+
+    /// Adds or withdraws a vote and saves, returning whether the message
+    /// was one open for votes.
+    pub fn vote(
+        &self,
+        message_id: MessageId,
+        user_id: UserId,
+        vote: Vote,
+        added: bool,
+    ) -> Result<bool, Box<dyn Error + Send + Sync + 'static>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let (guild_id, url) = match inner.messages.iter().find(|(id, _, _)| *id == message_id) {
+            Some((_, guild_id, url)) => (*guild_id, url.clone()),
+            None => return Ok(false),
+        };
+
+        let votes = inner
+            .tracks
+            .entry(guild_id)
+            .or_default()
+            .entry(url)
+            .or_default();
+        let voters = match vote {
+            Vote::Up => &mut votes.up,
+            Vote::Down => &mut votes.down,
+        };
+
+        if added {
+            voters.insert(user_id);
+        } else {
+            voters.remove(&user_id);
+        }
+
+        self.storage.save("ratings.json", &*inner)?;
+        Ok(true)
+    }
+
+    /// The track `message_id` announced, with everyone currently
+    /// downvoting it.
+    pub fn downvoters(&self, message_id: MessageId) -> Option<(GuildId, String, Vec<UserId>)> {
+        let inner = self.inner.lock().unwrap();
+
+        let (_, guild_id, url) = inner.messages.iter().find(|(id, _, _)| *id == message_id)?;
+        let votes = inner.tracks.get(guild_id)?.get(url)?;
+
+        Some((*guild_id, url.clone(), votes.down.iter().copied().collect()))
+    }
+
+    /// The guild's best rated tracks with a positive score, best first.
+    pub fn top(&self, guild_id: GuildId, limit: usize) -> Vec<Rated> {
+        let inner = self.inner.lock().unwrap();
+
+        let mut rated = inner
+            .tracks
+            .get(&guild_id)
+            .into_iter()
+            .flatten()
+            .map(|(url, votes)| Rated {
+                title: votes.title.clone(),
+                url: url.clone(),
+                up: votes.up.len(),
+                down: votes.down.len(),
+            })
+            .filter(|rated| rated.score() > 0)
+            .collect::<Vec<_>>();
+
+        rated.sort_by_key(|rated| {
+            (
+                Reverse(rated.score()),
+                Reverse(rated.up),
+                rated.title.clone(),
+            )
+        });
+        rated.truncate(limit);
+        rated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Memory;
+
+    const GUILD: GuildId = GuildId(1);
+
+    fn ratings() -> Ratings {
+        let ratings = Ratings::default();
+        ratings
+            .track_message(MessageId(10), GUILD, "https://example.com/a", "A")
+            .unwrap();
+        ratings
+            .track_message(MessageId(11), GUILD, "https://example.com/b", "B")
+            .unwrap();
+        ratings
+    }
+
+    #[test]
+    fn ranks_by_score() {
+        let ratings = ratings();
+
+        ratings
+            .vote(MessageId(10), UserId(1), Vote::Up, true)
+            .unwrap();
+        ratings
+            .vote(MessageId(11), UserId(1), Vote::Up, true)
+            .unwrap();
+        ratings
+            .vote(MessageId(11), UserId(2), Vote::Up, true)
+            .unwrap();
+
+        let top = ratings.top(GUILD, 10);
+        assert_eq!(
+            top.iter()
+                .map(|rated| rated.title.as_str())
+                .collect::<Vec<_>>(),
+            ["B", "A"]
+        );
+    }
+
+    #[test]
+    fn withdrawn_and_negative_votes_drop_out() {
+        let ratings = ratings();
+
+        ratings
+            .vote(MessageId(10), UserId(1), Vote::Up, true)
+            .unwrap();
+        ratings
+            .vote(MessageId(10), UserId(1), Vote::Up, false)
+            .unwrap();
+        ratings
+            .vote(MessageId(11), UserId(1), Vote::Up, true)
+            .unwrap();
+        ratings
+            .vote(MessageId(11), UserId(2), Vote::Down, true)
+            .unwrap();
+        ratings
+            .vote(MessageId(11), UserId(3), Vote::Down, true)
+            .unwrap();
+
+        assert!(ratings.top(GUILD, 10).is_empty());
+    }
+
+    #[test]
+    fn votes_follow_track_across_messages() {
+        let ratings = ratings();
+        ratings
+            .track_message(MessageId(12), GUILD, "https://example.com/a", "A")
+            .unwrap();
+
+        ratings
+            .vote(MessageId(10), UserId(1), Vote::Up, true)
+            .unwrap();
+        ratings
+            .vote(MessageId(12), UserId(2), Vote::Up, true)
+            .unwrap();
+
+        assert_eq!(ratings.top(GUILD, 10)[0].up, 2);
+    }
+
+    #[test]
+    fn lists_downvoters_of_announced_track() {
+        let ratings = ratings();
+
+        ratings
+            .vote(MessageId(11), UserId(1), Vote::Down, true)
+            .unwrap();
+        ratings
+            .vote(MessageId(11), UserId(2), Vote::Up, true)
+            .unwrap();
+
+        assert_eq!(
+            ratings.downvoters(MessageId(11)),
+            Some((GUILD, "https://example.com/b".to_string(), vec![UserId(1)]))
+        );
+        assert_eq!(ratings.downvoters(MessageId(99)), None);
+    }
+
+    #[test]
+    fn ignores_other_messages() {
+        assert!(!ratings()
+            .vote(MessageId(99), UserId(1), Vote::Up, true)
+            .unwrap());
+    }
+
+    #[test]
+    fn votes_survive_a_reload() {
+        let storage = Storage::with_backend(Memory::default());
+
+        let ratings = Ratings::load(&storage).unwrap();
+        ratings
+            .track_message(MessageId(10), GUILD, "https://example.com/a", "A")
+            .unwrap();
+        ratings
+            .vote(MessageId(10), UserId(1), Vote::Up, true)
+            .unwrap();
+
+        let reloaded = Ratings::load(&storage).unwrap();
+        assert_eq!(reloaded.top(GUILD, 10)[0].up, 1);
+        assert!(reloaded
+            .vote(MessageId(10), UserId(2), Vote::Down, true)
+            .unwrap());
+    }
+}
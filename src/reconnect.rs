@@ -0,0 +1,227 @@
+use crate::{commands, duration, playback::PlaybackSnapshot, queue, State};
+use async_trait::async_trait;
+use songbird::{
+    events::context_data::DisconnectReason, model::CloseCode, Call, CoreEvent, Event, EventContext,
+    EventHandler,
+};
+use std::{
+    collections::HashSet,
+    error::Error,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use twilight_model::id::{ChannelId, GuildId};
+
+/// How long to wait before each attempt to get back into the channel.
+const RETRY_DELAYS: &[Duration] = &[
+    Duration::from_secs(2),
+    Duration::from_secs(10),
+    Duration::from_secs(30),
+];
+
+/// Gets the bot back into voice after the connection drops, picking the
+/// interrupted track up where it left off.
+///
+/// Songbird retries a dropped connection itself; its `DriverDisconnect`
+/// event only fires once it has given up, and then nothing plays again
+/// until someone runs `j/join`.
+#[derive(Debug, Default)]
+pub struct Reconnects {
+    /// Guilds whose call already reports to the supervisor. Songbird keeps
+    /// a call's event handlers across joins, so each registers only once.
+    watched: Mutex<HashSet<GuildId>>,
+    recovering: Mutex<HashSet<GuildId>>,
+}
+
+/// Has the guild's call report dropped connections, once per call.
+pub fn watch(state: &State, guild_id: GuildId, call: &mut Call) {
+    if state.reconnects.watched.lock().unwrap().insert(guild_id) {
+        call.add_global_event(
+            CoreEvent::DriverDisconnect.into(),
+            Disconnects {
+                guild_id,
+                state: Arc::clone(state),
+            },
+        );
+    }
+}
+
+struct Disconnects {
+    guild_id: GuildId,
+    state: State,
+}
+
+#[async_trait]
+impl EventHandler for Disconnects {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::DriverDisconnect(data) = ctx {
+            tracing::warn!(
+                guild_id = %self.guild_id,
+                "voice connection ended ({:?}): {:?}",
+                data.kind,
+                data.reason
+            );
+
+            // Leaving or moving channels on purpose comes without a reason.
+            if data.reason.as_ref().is_some_and(worth_rejoining) {
+                let state = Arc::clone(&self.state);
+                let guild_id = self.guild_id;
+                let channel_id = data.channel_id.map(|channel_id| ChannelId(channel_id.0));
+
+                // The driver waits on event handlers, so rejoining happens
+                // outside of it.
+                tokio::spawn(async move { recover(&state, guild_id, channel_id).await });
+            }
+        }
+
+        None
+    }
+}
+
+/// Whether a connection that ended for `reason` should be rejoined. A
+/// discarded attempt was replaced by a newer join, and `Disconnected`
+/// means the bot was kicked or the channel deleted.
+fn worth_rejoining(reason: &DisconnectReason) -> bool {
+    !matches!(
+        reason,
+        DisconnectReason::AttemptDiscarded
+            | DisconnectReason::WsClosed(Some(CloseCode::Disconnected))
+    )
+}
+
+async fn recover(state: &State, guild_id: GuildId, channel_id: Option<ChannelId>) {
+    if !state.reconnects.recovering.lock().unwrap().insert(guild_id) {
+        return;
+    }
+
+    let channel_id = match channel_id {
+        Some(channel_id) => Some(channel_id),
+        None => state
+            .voice_states
+            .read()
+            .await
+            .get(&(guild_id, state.user_id))
+            .copied(),
+    };
+
+    let result = match channel_id {
+        Some(channel_id) => rejoin(state, guild_id, channel_id)
+            .await
+            .map(|resumed| (channel_id, resumed)),
+        None => Err("the channel it was in isn't known".into()),
+    };
+
+    state
+        .reconnects
+        .recovering
+        .lock()
+        .unwrap()
+        .remove(&guild_id);
+
+    let content = match result {
+        Ok((channel_id, Some((title, position)))) => format!(
+            "The voice connection dropped, so I rejoined <#{}> and picked up **{}** from {}.",
+            channel_id,
+            title,
+            duration::format(position)
+        ),
+        Ok((channel_id, None)) => format!(
+            "The voice connection dropped, so I rejoined <#{}>.",
+            channel_id
+        ),
+        Err(why) => {
+            state.hooks.error(Some(guild_id), &*why);
+
+            // A call left half-connected would otherwise look joined.
+            state.reconnects.watched.lock().unwrap().remove(&guild_id);
+            state.trackdata.write().await.remove(&guild_id);
+            if let Err(why) = state.songbird.remove(guild_id).await {
+                tracing::debug!(guild_id = %guild_id, "no call to remove: {}", why);
+            }
+
+            state.prefixed(
+                guild_id,
+                "The voice connection dropped and I couldn't get back in. \
+                 Use `{prefix}join` to bring me back; the queue is still there.",
+            )
+        }
+    };
+
+    if let Err(why) = crate::announce(state, guild_id, &content).await {
+        state.hooks.error(Some(guild_id), &*why);
+    }
+}
+
+/// Rejoins `channel_id`, returning the interrupted track's title and the
+/// position it carried on from, if one was playing.
+async fn rejoin(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<Option<(String, Duration)>, Box<dyn Error + Send + Sync + 'static>> {
+    // Taken out of `trackdata` before it's stopped, so its end doesn't
+    // advance the queue.
+    let interrupted = match queue::current(state, guild_id).await {
+        Some(handle) => {
+            let snapshot = PlaybackSnapshot::capture(state, guild_id, &handle).await;
+            state.trackdata.write().await.remove(&guild_id);
+            let _ = handle.stop();
+
+            snapshot
+        }
+        None => None,
+    };
+
+    let mut last_error = None;
+
+    for delay in RETRY_DELAYS {
+        state.clock.sleep(*delay).await;
+
+        let (_, joined) = state.songbird.join(guild_id, channel_id.0).await;
+        match joined {
+            Ok(()) => {
+                last_error = None;
+                break;
+            }
+            Err(why) => {
+                tracing::warn!(guild_id = %guild_id, "rejoining failed: {}", why);
+                last_error = Some(why);
+            }
+        }
+    }
+
+    if let Some(why) = last_error {
+        return Err(why.into());
+    }
+
+    match interrupted {
+        Some(snapshot) => {
+            snapshot.restore(state, guild_id).await?;
+            Ok(Some((snapshot.info.title().to_string(), snapshot.position)))
+        }
+        None => {
+            commands::play_next(state, guild_id).await?;
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejoins_unless_kicked_or_replaced() {
+        assert!(worth_rejoining(&DisconnectReason::TimedOut));
+        assert!(worth_rejoining(&DisconnectReason::Io));
+        assert!(worth_rejoining(&DisconnectReason::WsClosed(Some(
+            CloseCode::SessionTimeout
+        ))));
+        assert!(worth_rejoining(&DisconnectReason::WsClosed(None)));
+
+        assert!(!worth_rejoining(&DisconnectReason::AttemptDiscarded));
+        assert!(!worth_rejoining(&DisconnectReason::WsClosed(Some(
+            CloseCode::Disconnected
+        ))));
+    }
+}
@@ -0,0 +1,402 @@
+use crate::{clock, State};
+use async_trait::async_trait;
+use songbird::{driver::DecodeMode, CoreEvent, Event, EventContext, EventHandler};
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use twilight_model::id::GuildId;
+
+pub const DIRECTORY: &str = "recordings";
+
+const SAMPLE_RATE: u32 = 48_000;
+const CHANNELS: u16 = 2;
+const FRAME_DURATION: Duration = Duration::from_millis(20);
+/// Interleaved samples in one 20ms stereo frame.
+const FRAME_SAMPLES: usize = 960 * CHANNELS as usize;
+/// How far behind the newest packet frames are kept before being written,
+/// so late packets from other speakers can still be mixed in.
+const MIX_LATENCY: u64 = 50;
+/// A speaker's packet arriving further than this from where their stream
+/// left off starts a new talk spurt at its arrival time.
+const RESYNC_FRAMES: u64 = 10;
+/// WAV sizes are 32-bit, which at 48kHz stereo runs out after six hours.
+pub const MAX_LENGTH: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Sums decoded voice packets from every speaker into one timeline.
+///
+/// Packets are placed by arrival time, except that consecutive packets
+/// from one speaker stay back to back, so network jitter doesn't leave
+/// gaps or overlaps in their audio.
+#[derive(Debug, Default)]
+struct Mixer {
+    pending: VecDeque<Vec<i32>>,
+    /// Frame index of `pending[0]`.
+    base: u64,
+    /// The frame each speaker's next packet would continue at.
+    cursors: HashMap<u32, u64>,
+}
+
+impl Mixer {
+    fn add(&mut self, ssrc: u32, arrival: u64, audio: &[i16]) {
+        let frame = match self.cursors.get(&ssrc) {
+            Some(&next) if arrival.abs_diff(next) <= RESYNC_FRAMES => next,
+            _ => arrival,
+        }
+        .max(self.base);
+
+        self.cursors.insert(ssrc, frame + 1);
+
+        let index = (frame - self.base) as usize;
+        while self.pending.len() <= index {
+            self.pending.push_back(vec![0; FRAME_SAMPLES]);
+        }
+
+        for (mixed, sample) in self.pending[index].iter_mut().zip(audio) {
+            *mixed += i32::from(*sample);
+        }
+    }
+
+    /// Takes the mixed samples of every frame before `frame`.
+    fn drain_before(&mut self, frame: u64) -> Vec<i16> {
+        let mut samples = Vec::new();
+
+        while self.base < frame {
+            match self.pending.pop_front() {
+                Some(mixed) => samples.extend(
+                    mixed
+                        .into_iter()
+                        .map(|sample| sample.clamp(i16::MIN.into(), i16::MAX.into()) as i16),
+                ),
+                None => samples.resize(samples.len() + FRAME_SAMPLES, 0),
+            }
+
+            self.base += 1;
+        }
+
+        samples
+    }
+
+    fn len(&self) -> u64 {
+        self.base + self.pending.len() as u64
+    }
+}
+
+/// 16-bit stereo PCM WAV output; the sizes in the header are filled in
+/// by [`finish`](Self::finish).
+struct WavWriter<W: Write + Seek> {
+    out: W,
+    data_bytes: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    fn new(mut out: W) -> io::Result<Self> {
+        let block_align = CHANNELS * 2;
+
+        out.write_all(b"RIFF")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?;
+        out.write_all(&CHANNELS.to_le_bytes())?;
+        out.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        out.write_all(&(SAMPLE_RATE * u32::from(block_align)).to_le_bytes())?;
+        out.write_all(&block_align.to_le_bytes())?;
+        out.write_all(&16u16.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self { out, data_bytes: 0 })
+    }
+
+    fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        for sample in samples {
+            self.out.write_all(&sample.to_le_bytes())?;
+        }
+
+        self.data_bytes += samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out.write_all(&(36 + self.data_bytes).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&self.data_bytes.to_le_bytes())?;
+        self.out.flush()?;
+
+        Ok(self.out)
+    }
+}
+
+struct Output {
+    mixer: Mixer,
+    wav: Option<WavWriter<BufWriter<File>>>,
+    error: Option<io::Error>,
+}
+
+/// One guild's recording in progress.
+pub struct Recording {
+    pub path: PathBuf,
+    started: Instant,
+    stopped: AtomicBool,
+    output: Mutex<Output>,
+}
+
+impl fmt::Debug for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recording")
+            .field("path", &self.path)
+            .field("started", &self.started)
+            .finish()
+    }
+}
+
+impl Recording {
+    fn frame_at(&self, state: &State) -> u64 {
+        (clock::elapsed(&*state.clock, self.started).as_millis() / FRAME_DURATION.as_millis())
+            as u64
+    }
+
+    fn receive(&self, arrival: u64, ssrc: u32, audio: &[i16]) {
+        if arrival >= MAX_LENGTH.as_millis() as u64 / FRAME_DURATION.as_millis() as u64 {
+            return;
+        }
+
+        let mut output = self.output.lock().unwrap();
+        output.mixer.add(ssrc, arrival, audio);
+
+        let samples = output
+            .mixer
+            .drain_before(arrival.saturating_sub(MIX_LATENCY));
+        output.write(&samples);
+    }
+}
+
+impl Output {
+    fn write(&mut self, samples: &[i16]) {
+        if samples.is_empty() || self.error.is_some() {
+            return;
+        }
+
+        if let Some(wav) = &mut self.wav {
+            if let Err(why) = wav.write(samples) {
+                self.error = Some(why);
+            }
+        }
+    }
+}
+
+/// Songbird handler feeding received voice into a [`Recording`]. The bot
+/// never receives its own audio, so only the other members are mixed.
+struct Receiver {
+    state: State,
+    recording: Arc<Recording>,
+}
+
+#[async_trait]
+impl EventHandler for Receiver {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if self.recording.stopped.load(Ordering::SeqCst) {
+            return Some(Event::Cancel);
+        }
+
+        if let EventContext::VoicePacket(data) = ctx {
+            if let Some(audio) = data.audio {
+                let arrival = self.recording.frame_at(&self.state);
+                self.recording.receive(arrival, data.packet.ssrc, audio);
+            }
+        }
+
+        None
+    }
+}
+
+/// Starts recording the guild's call into a new WAV file under
+/// [`DIRECTORY`]. Fails if the bot isn't in a call or a recording is
+/// already running.
+pub async fn start(
+    state: &State,
+    guild_id: GuildId,
+) -> Result<Arc<Recording>, Box<dyn Error + Send + Sync + 'static>> {
+    let call_lock = state
+        .songbird
+        .get(guild_id)
+        .ok_or("I'm not in a voice channel.")?;
+
+    let mut recordings = state.recordings.write().await;
+
+    if recordings.contains_key(&guild_id) {
+        return Err("I'm already recording.".into());
+    }
+
+    fs::create_dir_all(DIRECTORY)?;
+
+    let path = PathBuf::from(DIRECTORY).join(format!(
+        "{}-{}.wav",
+        guild_id,
+        state.clock.now().format("%Y%m%d-%H%M%S")
+    ));
+    let wav = WavWriter::new(BufWriter::new(File::create(&path)?))?;
+
+    let recording = Arc::new(Recording {
+        path,
+        started: state.clock.instant(),
+        stopped: AtomicBool::new(false),
+        output: Mutex::new(Output {
+            mixer: Mixer::default(),
+            wav: Some(wav),
+            error: None,
+        }),
+    });
+
+    let mut call = call_lock.lock().await;
+
+    // Received audio is only decrypted by default; mixing needs PCM.
+    let config = call.config().clone().decode_mode(DecodeMode::Decode);
+    call.set_config(config);
+    call.add_global_event(
+        CoreEvent::VoicePacket.into(),
+        Receiver {
+            state: Arc::clone(state),
+            recording: Arc::clone(&recording),
+        },
+    );
+
+    recordings.insert(guild_id, Arc::clone(&recording));
+
+    Ok(recording)
+}
+
+/// Ends the guild's recording, if any, and returns where it was saved and
+/// how long it is.
+pub async fn stop(
+    state: &State,
+    guild_id: GuildId,
+) -> Result<Option<(PathBuf, Duration)>, Box<dyn Error + Send + Sync + 'static>> {
+    let recording = match state.recordings.write().await.remove(&guild_id) {
+        Some(recording) => recording,
+        None => return Ok(None),
+    };
+
+    recording.stopped.store(true, Ordering::SeqCst);
+
+    if let Some(call_lock) = state.songbird.get(guild_id) {
+        let mut call = call_lock.lock().await;
+        let config = call
+            .config()
+            .clone()
+            .decode_mode(state.profile.songbird_config().decode_mode);
+        call.set_config(config);
+    }
+
+    let mut output = recording.output.lock().unwrap();
+    let end = output.mixer.len();
+    let samples = output.mixer.drain_before(end);
+    output.write(&samples);
+
+    if let Some(why) = output.error.take() {
+        return Err(why.into());
+    }
+
+    if let Some(wav) = output.wav.take() {
+        wav.finish()?;
+    }
+
+    let length = FRAME_DURATION * end as u32;
+
+    Ok(Some((recording.path.clone(), length)))
+}
+
+pub async fn is_recording(state: &State, guild_id: GuildId) -> bool {
+    state.recordings.read().await.contains_key(&guild_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{convert::TryInto, io::Cursor};
+
+    fn frame(value: i16) -> Vec<i16> {
+        vec![value; FRAME_SAMPLES]
+    }
+
+    #[test]
+    fn mixes_simultaneous_speakers() {
+        let mut mixer = Mixer::default();
+
+        mixer.add(1, 0, &frame(100));
+        mixer.add(2, 0, &frame(-30));
+        mixer.add(3, 0, &frame(i16::MAX));
+
+        let samples = mixer.drain_before(1);
+        assert_eq!(samples.len(), FRAME_SAMPLES);
+        assert!(samples.iter().all(|&sample| sample == i16::MAX));
+    }
+
+    #[test]
+    fn keeps_jittery_packets_back_to_back() {
+        let mut mixer = Mixer::default();
+
+        // The second packet arrives late and the third early.
+        mixer.add(1, 0, &frame(1));
+        mixer.add(1, 3, &frame(2));
+        mixer.add(1, 3, &frame(3));
+
+        let samples = mixer.drain_before(4);
+        let firsts = samples
+            .chunks(FRAME_SAMPLES)
+            .map(|frame| frame[0])
+            .collect::<Vec<_>>();
+        assert_eq!(firsts, [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn resyncs_after_silence() {
+        let mut mixer = Mixer::default();
+
+        mixer.add(1, 0, &frame(1));
+        mixer.add(1, 100, &frame(2));
+
+        let samples = mixer.drain_before(101);
+        assert_eq!(samples[FRAME_SAMPLES], 0);
+        assert_eq!(samples[100 * FRAME_SAMPLES], 2);
+    }
+
+    #[test]
+    fn late_packets_for_written_frames_are_appended() {
+        let mut mixer = Mixer::default();
+
+        mixer.add(1, 5, &frame(1));
+        let _ = mixer.drain_before(6);
+
+        mixer.add(2, 2, &frame(7));
+        assert_eq!(mixer.drain_before(7)[0], 7);
+    }
+
+    #[test]
+    fn wav_header_records_sizes() {
+        let mut wav = WavWriter::new(Cursor::new(Vec::new())).unwrap();
+        wav.write(&[1, -1, 2, -2]).unwrap();
+        let bytes = wav.finish().unwrap().into_inner();
+
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 44);
+        assert_eq!(
+            u32::from_le_bytes(bytes[24..28].try_into().unwrap()),
+            48_000
+        );
+        assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 8);
+        assert_eq!(&bytes[44..46], &1i16.to_le_bytes());
+    }
+}
@@ -0,0 +1,128 @@
+use crate::programs::Program;
+use serde::Deserialize;
+use std::{error::Error, time::Duration};
+
+/// How many results `j/play` offers to pick from.
+pub const RESULTS: usize = 5;
+
+/// One hit for a keyword search or entry in a playlist, before it is
+/// resolved for playback.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub duration: Option<Duration>,
+}
+
+/// Whether `query` should be played as is rather than searched for.
+pub fn is_url(query: &str) -> bool {
+    query.starts_with("https://") || query.starts_with("http://")
+}
+
+/// A line of `youtube-dl --flat-playlist -j` output. Flat YouTube entries
+/// may only carry the video ID in `url`, not a full URL.
+#[derive(Deserialize)]
+struct Entry {
+    id: String,
+    url: Option<String>,
+    title: Option<String>,
+    duration: Option<f64>,
+}
+
+/// Parses youtube-dl's one-object-per-line output, skipping lines that
+/// aren't entries.
+pub fn parse_ytdl(output: &str) -> Vec<SearchResult> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Entry>(line).ok())
+        .map(|entry| SearchResult {
+            url: match entry.url {
+                Some(url) if is_url(&url) => url,
+                _ => format!("https://www.youtube.com/watch?v={}", entry.id),
+            },
+            title: entry.title.unwrap_or(entry.id),
+            duration: entry
+                .duration
+                .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                .map(Duration::from_secs_f64),
+        })
+        .collect()
+}
+
+/// Searches YouTube through youtube-dl's `ytsearch` prefix.
+pub async fn ytdl(
+    youtube_dl: &Program,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+    flat_entries(youtube_dl, &format!("ytsearch{}:{}", limit, query)).await
+}
+
+/// Lists what youtube-dl finds at `target` without resolving each entry.
+pub async fn flat_entries(
+    youtube_dl: &Program,
+    target: &str,
+) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+    let output = youtube_dl
+        .command()
+        .arg("--flat-playlist")
+        .arg("-j")
+        .arg(target)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "youtube-dl failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(parse_ytdl(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_flat_entries() {
+        let output = concat!(
+            r#"{"_type": "url", "id": "dQw4w9WgXcQ", "title": "Never Gonna Give You Up", "duration": 212.0}"#,
+            "\n",
+            r#"{"_type": "url", "id": "abc", "url": "abc", "title": null, "duration": null}"#,
+            "\n",
+            r#"{"_type": "url", "id": "123", "url": "https://soundcloud.com/a/b", "title": "B"}"#,
+            "\n",
+            "WARNING: not json\n",
+        );
+
+        assert_eq!(
+            parse_ytdl(output),
+            [
+                SearchResult {
+                    title: "Never Gonna Give You Up".to_string(),
+                    url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+                    duration: Some(Duration::from_secs(212)),
+                },
+                SearchResult {
+                    title: "abc".to_string(),
+                    url: "https://www.youtube.com/watch?v=abc".to_string(),
+                    duration: None,
+                },
+                SearchResult {
+                    title: "B".to_string(),
+                    url: "https://soundcloud.com/a/b".to_string(),
+                    duration: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn urls_are_not_searched() {
+        assert!(is_url("https://example.com/song"));
+        assert!(!is_url("never gonna give you up"));
+    }
+}
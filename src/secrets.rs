@@ -0,0 +1,119 @@
+use rand::rngs::OsRng;
+use std::{env, error::Error};
+use xsalsa20poly1305::{
+    aead::{Aead, NewAead},
+    Key, Nonce, XSalsa20Poly1305,
+};
+
+const NONCE_LEN: usize = 24;
+
+/// Encryption at rest for secrets users hand the bot, such as third-party
+/// session tokens. Every secret must be sealed before it is persisted.
+///
+/// The key is 32 bytes, base64-encoded in `SECRETS_KEY`, and never leaves
+/// the host's environment.
+pub struct Secrets {
+    cipher: XSalsa20Poly1305,
+}
+
+impl Secrets {
+    /// Returns `None` if `SECRETS_KEY` is unset, in which case features that
+    /// need to store user secrets should stay disabled.
+    pub fn from_env() -> Result<Option<Self>, Box<dyn Error + Send + Sync + 'static>> {
+        match env::var("SECRETS_KEY") {
+            Ok(key) => Ok(Some(Self::new(&base64::decode(key.trim())?)?)),
+            Err(env::VarError::NotPresent) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn new(key: &[u8]) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        if key.len() != 32 {
+            return Err("SECRETS_KEY must be 32 bytes".into());
+        }
+
+        Ok(Self {
+            cipher: XSalsa20Poly1305::new(Key::from_slice(key)),
+        })
+    }
+
+    /// Encrypts `secret` under a fresh nonce, returning base64 text that
+    /// can be stored alongside ordinary settings.
+    pub fn seal(&self, secret: &str) -> String {
+        let nonce = xsalsa20poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, secret.as_bytes())
+            .expect("encrypting into a Vec can't fail");
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+
+        base64::encode(sealed)
+    }
+
+    /// Decrypts a value produced by [`seal`](Self::seal). Fails if it was
+    /// sealed under another key or has been tampered with.
+    pub fn open(&self, sealed: &str) -> Result<String, Box<dyn Error + Send + Sync + 'static>> {
+        let sealed = base64::decode(sealed)?;
+
+        if sealed.len() < NONCE_LEN {
+            return Err("sealed secret is truncated".into());
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "sealed secret failed to decrypt")?;
+
+        Ok(String::from_utf8(plaintext)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets(byte: u8) -> Secrets {
+        Secrets::new(&[byte; 32]).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let secrets = secrets(1);
+        let sealed = secrets.seal("session-token");
+
+        assert!(!sealed.contains("session-token"));
+        assert_eq!(secrets.open(&sealed).unwrap(), "session-token");
+    }
+
+    #[test]
+    fn nonces_differ() {
+        let secrets = secrets(1);
+
+        assert_ne!(secrets.seal("same"), secrets.seal("same"));
+    }
+
+    #[test]
+    fn wrong_key_fails() {
+        let sealed = secrets(1).seal("session-token");
+
+        assert!(secrets(2).open(&sealed).is_err());
+    }
+
+    #[test]
+    fn tampering_fails() {
+        let secrets = secrets(1);
+        let mut sealed = base64::decode(secrets.seal("session-token")).unwrap();
+        *sealed.last_mut().unwrap() ^= 1;
+
+        assert!(secrets.open(&base64::encode(sealed)).is_err());
+        assert!(secrets.open("AAAA").is_err());
+    }
+
+    #[test]
+    fn rejects_short_keys() {
+        assert!(Secrets::new(&[0; 16]).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use crate::duration;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use twilight_model::id::{GuildId, UserId};
+
+#[derive(Debug)]
+struct Session {
+    started: Instant,
+    requests: HashMap<UserId, u32>,
+}
+
+/// What happened between the bot joining and leaving a call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub length: Duration,
+    pub tracks: u32,
+    /// Whoever requested the most tracks, with how many.
+    pub top_requester: Option<(UserId, u32)>,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Session recap: {} in voice, {} track{}",
+            duration::format_hours(self.length),
+            self.tracks,
+            if self.tracks == 1 { "" } else { "s" }
+        )?;
+
+        if let Some((user_id, count)) = self.top_requester {
+            write!(f, ", most requested by <@{}> ({})", user_id, count)?;
+        }
+
+        write!(f, ".")
+    }
+}
+
+/// Per-guild listening sessions, from `j/join` until the bot leaves.
+#[derive(Debug, Default)]
+pub struct Sessions {
+    sessions: Mutex<HashMap<GuildId, Session>>,
+}
+
+impl Sessions {
+    /// Starts a session, unless one is already running because the bot
+    /// only moved channels.
+    pub fn start(&self, guild_id: GuildId, now: Instant) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .entry(guild_id)
+            .or_insert_with(|| Session {
+                started: now,
+                requests: HashMap::new(),
+            });
+    }
+
+    pub fn record_track(&self, guild_id: GuildId, requester: UserId) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(&guild_id) {
+            *session.requests.entry(requester).or_default() += 1;
+        }
+    }
+
+    /// Ends the guild's session, if one was running.
+    pub fn end(&self, guild_id: GuildId, now: Instant) -> Option<Summary> {
+        let session = self.sessions.lock().unwrap().remove(&guild_id)?;
+
+        // Ties go to the lowest user ID so the recap is deterministic.
+        let top_requester = session
+            .requests
+            .iter()
+            .max_by(|(a_id, a), (b_id, b)| a.cmp(b).then(b_id.cmp(a_id)))
+            .map(|(user_id, count)| (*user_id, *count));
+
+        Some(Summary {
+            length: now.saturating_duration_since(session.started),
+            tracks: session.requests.values().sum(),
+            top_requester,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD: GuildId = GuildId(1);
+
+    #[test]
+    fn summarises_session() {
+        let sessions = Sessions::default();
+        let start = Instant::now();
+
+        sessions.start(GUILD, start);
+        sessions.record_track(GUILD, UserId(20));
+        sessions.record_track(GUILD, UserId(10));
+        sessions.record_track(GUILD, UserId(20));
+
+        let summary = sessions
+            .end(GUILD, start + Duration::from_secs(3725))
+            .unwrap();
+
+        assert_eq!(
+            summary.to_string(),
+            "Session recap: 1:02:05 in voice, 3 tracks, most requested by <@20> (2)."
+        );
+        assert_eq!(sessions.end(GUILD, start), None);
+    }
+
+    #[test]
+    fn ties_go_to_lowest_id() {
+        let sessions = Sessions::default();
+        let start = Instant::now();
+
+        sessions.start(GUILD, start);
+        sessions.record_track(GUILD, UserId(20));
+        sessions.record_track(GUILD, UserId(10));
+
+        assert_eq!(
+            sessions.end(GUILD, start).unwrap().top_requester,
+            Some((UserId(10), 1))
+        );
+    }
+
+    #[test]
+    fn moving_channels_keeps_session() {
+        let sessions = Sessions::default();
+        let start = Instant::now();
+
+        sessions.start(GUILD, start);
+        sessions.start(GUILD, start + Duration::from_secs(60));
+
+        assert_eq!(
+            sessions
+                .end(GUILD, start + Duration::from_secs(61))
+                .unwrap()
+                .to_string(),
+            "Session recap: 0:01:01 in voice, 0 tracks."
+        );
+    }
+
+    #[test]
+    fn tracks_outside_sessions_are_ignored() {
+        let sessions = Sessions::default();
+
+        sessions.record_track(GUILD, UserId(10));
+
+        assert_eq!(sessions.end(GUILD, Instant::now()), None);
+    }
+}
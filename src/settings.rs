@@ -0,0 +1,283 @@
+use crate::{template::Template, voteskip};
+use chrono::{DateTime, Duration, FixedOffset, NaiveTime, Utc};
+use std::{collections::HashMap, fmt};
+use twilight_model::id::{ChannelId, RoleId};
+
+/// Placeholders available to the now playing template.
+pub const NOW_PLAYING_FIELDS: &[&str] = &["title", "artist", "url"];
+
+pub const DEFAULT_NOW_PLAYING: &str = "Playing **{title}** by **{artist}**";
+
+/// The longest idle timeout `j/settings idle` takes, a day.
+pub const MAX_IDLE_MINUTES: u32 = 24 * 60;
+
+/// The longest no-repeats window `j/settings norepeats` takes, a week.
+pub const MAX_NO_REPEAT_HOURS: u32 = 7 * 24;
+
+/// The loudest `j/volume` goes, and the highest cap `j/settings volumecap`
+/// takes, as `default_volume` is limited to 2.
+pub const MAX_VOLUME_PERCENT: u32 = 200;
+
+/// Volume applied to tracks started during quiet hours.
+pub const QUIET_HOURS_VOLUME: f32 = 0.3;
+
+#[derive(Clone, Debug, Default)]
+pub struct GuildSettings {
+    pub quiet_hours: Option<QuietHours>,
+    pub curfew: Option<Curfew>,
+    pub log_channel: Option<ChannelId>,
+    pub timezone: Option<FixedOffset>,
+    pub announcements: Announcements,
+    /// Replaces the "Playing ..." announcement; see [`NOW_PLAYING_FIELDS`].
+    pub now_playing_template: Option<Template>,
+    /// Whether `j/record` may be used; recording is opt-in per guild.
+    pub recording: bool,
+    /// How many listeners must dislike a track before it is skipped.
+    pub dislike_skip: Option<u32>,
+    /// The percentage of listeners `j/voteskip` needs, if not the default.
+    pub vote_skip: Option<u32>,
+    /// Pinged when a listening event is announced and when it starts.
+    pub event_role: Option<RoleId>,
+    /// Members who may use the DJ commands; see [`crate::permissions`].
+    pub dj_role: Option<RoleId>,
+    /// Whether members' `j/theme` clips play when they join.
+    pub themes: bool,
+    /// Minutes a call may sit with nothing playing before the bot leaves,
+    /// if not the profile's default; `0` stays until told to leave.
+    pub idle_minutes: Option<u32>,
+    /// Keeps tracks that finished recently from being queued again.
+    pub no_repeats: Option<NoRepeats>,
+    /// Whether `j/remove` is left to each track's requester and DJs, rather
+    /// than to DJs alone.
+    pub requester_removals: bool,
+    /// The loudest members with each role may set `j/volume`, in percent.
+    pub volume_caps: HashMap<RoleId, u32>,
+    /// The volume set with `j/volume`, in percent, in place of the
+    /// configured default.
+    pub volume: Option<u32>,
+}
+
+impl GuildSettings {
+    /// The guild's UTC offset, used by schedules set without their own.
+    pub fn zone(&self) -> FixedOffset {
+        self.timezone.unwrap_or_else(|| FixedOffset::east(0))
+    }
+
+    pub fn vote_skip(&self) -> u32 {
+        self.vote_skip.unwrap_or(voteskip::DEFAULT_PERCENT)
+    }
+
+    /// How long the bot waits in a silent call before leaving, if at all.
+    pub fn idle_timeout(&self, default: std::time::Duration) -> Option<std::time::Duration> {
+        match self.idle_minutes {
+            Some(0) => None,
+            Some(minutes) => Some(std::time::Duration::from_secs(u64::from(minutes) * 60)),
+            None => Some(default),
+        }
+    }
+
+    pub fn now_playing_template(&self) -> Template {
+        self.now_playing_template.clone().unwrap_or_else(|| {
+            Template::parse(DEFAULT_NOW_PLAYING, NOW_PLAYING_FIELDS)
+                .expect("default template is valid")
+        })
+    }
+}
+
+/// How loudly the bot announces each track it starts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Announcements {
+    /// No per-track message at all.
+    Off,
+    /// A reaction on the message that requested the track.
+    Minimal,
+    /// The now playing message.
+    #[default]
+    Full,
+}
+
+impl Announcements {
+    pub fn parse(level: &str) -> Option<Self> {
+        match level {
+            "off" => Some(Announcements::Off),
+            "minimal" => Some(Announcements::Minimal),
+            "full" => Some(Announcements::Full),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Announcements {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Announcements::Off => "off",
+            Announcements::Minimal => "minimal",
+            Announcements::Full => "full",
+        })
+    }
+}
+
+/// How long after a track finishes it can't be queued again, checked
+/// against [`crate::history`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoRepeats {
+    pub hours: u32,
+    /// Whether DJs may queue a repeat anyway. Otherwise nobody can.
+    pub dj_override: bool,
+}
+
+impl NoRepeats {
+    /// Parses a number of hours, then `dj` to let DJs override it or
+    /// `strict` (the default) not to.
+    pub fn parse(hours: &str, mode: Option<&str>) -> Option<Self> {
+        let hours = match hours.parse() {
+            Ok(hours) if (1..=MAX_NO_REPEAT_HOURS).contains(&hours) => hours,
+            _ => return None,
+        };
+
+        let dj_override = match mode {
+            None | Some("strict") => false,
+            Some("dj") => true,
+            Some(_) => return None,
+        };
+
+        Some(Self { hours, dj_override })
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::hours(i64::from(self.hours))
+    }
+}
+
+impl fmt::Display for NoRepeats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            self.hours,
+            if self.dj_override { "dj" } else { "strict" }
+        )
+    }
+}
+
+/// A daily window during which the bot refuses to join and caps the
+/// volume of anything it starts.
+///
+/// Without an offset of its own it follows the guild's time zone.
+#[derive(Clone, Copy, Debug)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub offset: Option<FixedOffset>,
+}
+
+impl QuietHours {
+    /// Parses `HH:MM-HH:MM` with an optional `+HH:MM`/`-HH:MM` offset.
+    pub fn parse(range: &str, offset: Option<&str>) -> Option<Self> {
+        let (start, end) = range.split_once('-')?;
+        let start = NaiveTime::parse_from_str(start, "%H:%M").ok()?;
+        let end = NaiveTime::parse_from_str(end, "%H:%M").ok()?;
+
+        let offset = match offset {
+            Some(offset) => Some(parse_offset(offset)?),
+            None => None,
+        };
+
+        Some(Self { start, end, offset })
+    }
+
+    pub fn contains(&self, now: DateTime<Utc>, zone: FixedOffset) -> bool {
+        let local = now.with_timezone(&self.offset.unwrap_or(zone)).time();
+
+        if self.start <= self.end {
+            self.start <= local && local < self.end
+        } else {
+            // The window wraps around midnight, e.g. 23:00-07:00.
+            local >= self.start || local < self.end
+        }
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{} ({})",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+            ZoneName(self.offset)
+        )
+    }
+}
+
+/// A daily time at which any active session is faded out and
+/// disconnected.
+///
+/// Without an offset of its own it follows the guild's time zone.
+#[derive(Clone, Copy, Debug)]
+pub struct Curfew {
+    pub time: NaiveTime,
+    pub offset: Option<FixedOffset>,
+}
+
+impl Curfew {
+    /// Parses `HH:MM` with an optional `+HH:MM`/`-HH:MM` offset.
+    pub fn parse(time: &str, offset: Option<&str>) -> Option<Self> {
+        let time = NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+
+        let offset = match offset {
+            Some(offset) => Some(parse_offset(offset)?),
+            None => None,
+        };
+
+        Some(Self { time, offset })
+    }
+
+    /// Time left until the next curfew, always less than a day.
+    pub fn until(&self, now: DateTime<Utc>, zone: FixedOffset) -> Duration {
+        let local = now.with_timezone(&self.offset.unwrap_or(zone)).time();
+        let until = self.time - local;
+
+        if until < Duration::zero() {
+            until + Duration::days(1)
+        } else {
+            until
+        }
+    }
+}
+
+impl fmt::Display for Curfew {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({})",
+            self.time.format("%H:%M"),
+            ZoneName(self.offset)
+        )
+    }
+}
+
+/// A schedule's own offset, or a pointer to the guild's time zone.
+struct ZoneName(Option<FixedOffset>);
+
+impl fmt::Display for ZoneName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(offset) => write!(f, "UTC{}", offset),
+            None => f.write_str("server time zone"),
+        }
+    }
+}
+
+/// Parses a `+HH:MM`/`-HH:MM` UTC offset; the minutes may be left out.
+pub fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let (sign, rest) = match offset.strip_prefix('+') {
+        Some(rest) => (1, rest),
+        None => (-1, offset.strip_prefix('-')?),
+    };
+
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+
+    FixedOffset::east_opt(sign * seconds)
+}
@@ -0,0 +1,202 @@
+use crate::State;
+use futures::{Stream, StreamExt};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    spawn,
+    sync::mpsc::{self, error::TrySendError},
+};
+use twilight_gateway::Event;
+
+/// Events a shard may have waiting before floods start being shed.
+pub const QUEUE_CAPACITY: usize = 256;
+/// Handling an event this long after it arrived gets a warning.
+pub const LAG_WARNING: Duration = Duration::from_secs(1);
+
+/// How far behind each shard's worker is, for `j/debug`.
+#[derive(Debug, Default)]
+pub struct EventLag {
+    shards: Mutex<HashMap<u64, ShardLag>>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShardLag {
+    /// Between the gateway handing over the last event and it being
+    /// handled.
+    pub last: Duration,
+    pub max: Duration,
+    /// Events shed because the shard's queue was full.
+    pub dropped: u64,
+}
+
+impl EventLag {
+    pub fn record(&self, shard_id: u64, lag: Duration) {
+        let mut shards = self.shards.lock().unwrap();
+        let shard = shards.entry(shard_id).or_default();
+
+        shard.last = lag;
+        shard.max = shard.max.max(lag);
+    }
+
+    pub fn record_drop(&self, shard_id: u64) {
+        self.shards
+            .lock()
+            .unwrap()
+            .entry(shard_id)
+            .or_default()
+            .dropped += 1;
+    }
+
+    pub fn get(&self, shard_id: u64) -> ShardLag {
+        self.shards
+            .lock()
+            .unwrap()
+            .get(&shard_id)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Whether an event can be shed when its shard is flooded. Chat and
+/// reactions can; voice, guild and connection events can't, since
+/// missing one leaves the bot's view of the world wrong. For those the
+/// reader waits instead.
+pub fn droppable(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::MessageCreate(_)
+            | Event::MessageUpdate(_)
+            | Event::ReactionAdd(_)
+            | Event::ReactionRemove(_)
+            | Event::TypingStart(_)
+            | Event::PresenceUpdate(_)
+    )
+}
+
+/// Which of a shard's workers handles an event.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Lane {
+    /// Voice state and server updates, which songbird needs promptly to
+    /// finish joining or reconnecting. They never wait behind commands.
+    Voice,
+    General,
+}
+
+impl Lane {
+    pub fn of(event: &Event) -> Self {
+        match event {
+            Event::VoiceStateUpdate(_) | Event::VoiceServerUpdate(_) => Lane::Voice,
+            _ => Lane::General,
+        }
+    }
+}
+
+/// Hands each shard's events to workers of its own, one per [`Lane`], so
+/// neither a busy shard nor a burst of messages can hold up voice events,
+/// and returns once the stream ends.
+pub async fn run(state: &State, mut events: impl Stream<Item = (u64, Event)> + Unpin) {
+    let mut workers = HashMap::new();
+
+    while let Some((shard_id, event)) = events.next().await {
+        if let Some(log) = &state.event_log {
+            log.record(shard_id, state.clock.now(), &event);
+        }
+
+        let lane = Lane::of(&event);
+        let worker = workers
+            .entry((shard_id, lane))
+            .or_insert_with(|| spawn_worker(state, shard_id));
+        let queued = (state.clock.instant(), event);
+
+        match worker.try_send(queued) {
+            Ok(()) => {}
+            Err(TrySendError::Full((_, event))) if droppable(&event) => {
+                state.event_lag.record_drop(shard_id);
+                tracing::warn!(shard_id, "shard queue is full, dropped {:?}", event.kind());
+            }
+            Err(TrySendError::Full(queued)) => {
+                let _ = worker.send(queued).await;
+            }
+            // Only if the worker panicked; a new one takes over.
+            Err(TrySendError::Closed(queued)) => {
+                let worker = spawn_worker(state, shard_id);
+                let _ = worker.send(queued).await;
+                workers.insert((shard_id, lane), worker);
+            }
+        }
+    }
+}
+
+fn spawn_worker(state: &State, shard_id: u64) -> mpsc::Sender<(Instant, Event)> {
+    let (sender, mut receiver) = mpsc::channel::<(Instant, Event)>(QUEUE_CAPACITY);
+    let state = Arc::clone(state);
+
+    spawn(async move {
+        while let Some((received_at, event)) = receiver.recv().await {
+            let lag = state.clock.instant().saturating_duration_since(received_at);
+            state.event_lag.record(shard_id, lag);
+
+            if lag >= LAG_WARNING {
+                tracing::warn!(shard_id, "handling events {:?} late", lag);
+            }
+
+            crate::handle_event(&state, event).await;
+        }
+    });
+
+    sender
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twilight_model::gateway::payload::{TypingStart, VoiceServerUpdate};
+
+    #[test]
+    fn voice_updates_get_their_own_lane() {
+        let update = serde_json::from_value::<VoiceServerUpdate>(serde_json::json!({
+            "endpoint": "voice.example.com",
+            "guild_id": "1",
+            "token": "token",
+        }))
+        .unwrap();
+
+        assert_eq!(Lane::of(&Event::VoiceServerUpdate(update)), Lane::Voice);
+        assert_eq!(Lane::of(&Event::GatewayReconnect), Lane::General);
+    }
+
+    #[test]
+    fn keeps_the_worst_lag() {
+        let lag = EventLag::default();
+
+        lag.record(0, Duration::from_millis(300));
+        lag.record(0, Duration::from_millis(20));
+        lag.record_drop(0);
+
+        assert_eq!(
+            lag.get(0),
+            ShardLag {
+                last: Duration::from_millis(20),
+                max: Duration::from_millis(300),
+                dropped: 1,
+            }
+        );
+        assert_eq!(lag.get(1), ShardLag::default());
+    }
+
+    #[test]
+    fn only_chatter_is_droppable() {
+        let typing = serde_json::from_value::<TypingStart>(serde_json::json!({
+            "channel_id": "1",
+            "timestamp": 0,
+            "user_id": "2",
+        }))
+        .unwrap();
+
+        assert!(droppable(&Event::TypingStart(Box::new(typing))));
+        assert!(!droppable(&Event::GatewayReconnect));
+    }
+}
@@ -0,0 +1,423 @@
+use crate::State;
+use std::error::Error;
+use twilight_http::Client as HttpClient;
+use twilight_model::{
+    application::{
+        callback::{CallbackData, InteractionResponse},
+        command::{ChoiceCommandOptionData, Command, CommandOption},
+        interaction::application_command::{ApplicationCommand, CommandDataOption},
+    },
+    id::{CommandId, GuildId},
+};
+
+/// A string option's name and description.
+type StringOption = (&'static str, &'static str);
+
+/// The slash commands registered next to the typed prefix: name,
+/// description, and the string option (if any) handed to the handler as
+/// the command's arguments.
+const COMMANDS: &[(&str, &str, Option<StringOption>)] = &[
+    (
+        "join",
+        "Join a voice channel",
+        Some(("channel", "ID of the channel to join")),
+    ),
+    (
+        "play",
+        "Play a track, or queue it behind the current one",
+        Some(("url", "URL of the audio to play, or keywords to search for")),
+    ),
+    ("leave", "Leave the voice channel", None),
+    ("stop", "Stop playback and clear the queue", None),
+    ("skip", "Skip to the next track in the queue", None),
+    ("voteskip", "Vote to skip the current track", None),
+    ("pause", "Pause the current track", None),
+    ("resume", "Resume a paused or recently stopped track", None),
+    (
+        "seek",
+        "Jump to a point in the current track",
+        Some(("position", "Where to jump to, as mm:ss")),
+    ),
+    (
+        "queue",
+        "List the upcoming tracks",
+        Some(("page", "Which page of the queue to show")),
+    ),
+    (
+        "chapters",
+        "Queue each chapter of a long upload as its own track",
+        Some(("url", "The upload to split")),
+    ),
+    ("shuffle", "Put the upcoming tracks in a random order", None),
+    (
+        "remove",
+        "Take a track out of the queue",
+        Some(("position", "The track's position in the queue")),
+    ),
+    (
+        "move",
+        "Move a track to another place in the queue",
+        Some(("positions", "<from> <to>, as positions in the queue")),
+    ),
+    ("nowplaying", "Show the current track", None),
+    (
+        "loop",
+        "Repeat the current track or the whole queue",
+        Some(("mode", "track, queue or off")),
+    ),
+    (
+        "volume",
+        "Show or set the playback volume",
+        Some(("percent", "The new volume, from 0 to 200")),
+    ),
+    (
+        "handoff",
+        "Take your queued tracks to another server",
+        Some(("server", "ID of the server to take them to")),
+    ),
+    (
+        "theme",
+        "Set, clear or show your join theme",
+        Some(("arguments", "set <url> [start] [seconds], or clear")),
+    ),
+    ("vibe", "Draw the current track", None),
+    (
+        "ambience",
+        "Play background ambience",
+        Some(("name", "The ambience to play, or off")),
+    ),
+    (
+        "break",
+        "Pause the music for a while",
+        Some(("minutes", "How long the break lasts")),
+    ),
+    (
+        "event",
+        "Start, stop or show a listening event",
+        Some(("arguments", "start <minutes>, or stop")),
+    ),
+    ("top", "Show the best rated tracks", Some(("list", "rated"))),
+    (
+        "record",
+        "Start or stop recording the call",
+        Some(("action", "start or stop")),
+    ),
+    (
+        "jingle",
+        "Schedule, list or cancel jingles",
+        Some((
+            "arguments",
+            "add <YYYY-MM-DD> <HH:MM> <#voice channel> <url>, or cancel <number>",
+        )),
+    ),
+    (
+        "settings",
+        "Show or change the server's settings",
+        Some((
+            "arguments",
+            "The setting and its value, as for the typed command",
+        )),
+    ),
+    (
+        "prefix",
+        "Show or change the typed command prefix",
+        Some(("prefix", "The new prefix, or reset")),
+    ),
+    (
+        "simulate",
+        "Try a command without touching the call",
+        Some(("arguments", "The command to simulate and its arguments")),
+    ),
+    (
+        "setdj",
+        "Show or set the role that can use DJ commands",
+        Some(("role", "The DJ role, or off")),
+    ),
+    (
+        "dj",
+        "Let someone use the DJ commands for a while, or list who can",
+        Some(("arguments", "grant <@user> <duration>, or list")),
+    ),
+    (
+        "spotify",
+        "Link a Spotify account to queue its private playlists and Liked Songs",
+        Some(("arguments", "link, liked, playlists or unlink")),
+    ),
+    ("tour", "Take a quick tour of what the bot can do", None),
+    ("debug", "Post a diagnostics report", None),
+    (
+        "stats",
+        "Show what the bot is up to and its build features",
+        None,
+    ),
+    (
+        "admin",
+        "Moderate the bot's users",
+        Some(("arguments", "unban <@user>")),
+    ),
+];
+
+pub fn commands() -> Vec<Command> {
+    COMMANDS
+        .iter()
+        .map(|(name, description, option)| Command {
+            application_id: None,
+            guild_id: None,
+            name: name.to_string(),
+            default_permission: None,
+            description: description.to_string(),
+            id: None,
+            options: option
+                .iter()
+                .map(|(name, description)| {
+                    CommandOption::String(ChoiceCommandOptionData {
+                        choices: Vec::new(),
+                        description: description.to_string(),
+                        name: name.to_string(),
+                        required: false,
+                    })
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// One step towards making the registered commands match [`commands`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change<'a> {
+    Create(&'a Command),
+    Update(CommandId, &'a Command),
+    Delete(CommandId, String),
+}
+
+impl Change<'_> {
+    fn describe(&self) -> String {
+        match self {
+            Change::Create(command) => format!("created /{}", command.name),
+            Change::Update(_, command) => format!("updated /{}", command.name),
+            Change::Delete(_, name) => format!("deleted /{}", name),
+        }
+    }
+}
+
+/// What it takes to get from the `registered` commands to the `wanted`
+/// ones, matching them up by name. Commands that haven't changed are left
+/// alone, so their IDs and permissions survive.
+pub fn diff<'a>(registered: &[Command], wanted: &'a [Command]) -> Vec<Change<'a>> {
+    let mut changes = Vec::new();
+
+    for command in wanted {
+        match registered.iter().find(|old| old.name == command.name) {
+            None => changes.push(Change::Create(command)),
+            Some(old)
+                if old.description != command.description || old.options != command.options =>
+            {
+                if let Some(id) = old.id {
+                    changes.push(Change::Update(id, command));
+                }
+            }
+            Some(_) => {}
+        }
+    }
+
+    for old in registered {
+        if let (Some(id), false) = (
+            old.id,
+            wanted.iter().any(|command| command.name == old.name),
+        ) {
+            changes.push(Change::Delete(id, old.name.clone()));
+        }
+    }
+
+    changes
+}
+
+/// Looks up the bot's application and brings its commands in line with
+/// [`commands`]: the global ones, or those of `guild_id` if given. Returns
+/// what changed, which is also logged.
+pub async fn sync(
+    http: &HttpClient,
+    guild_id: Option<GuildId>,
+) -> Result<Vec<String>, Box<dyn Error + Send + Sync + 'static>> {
+    let application = http
+        .current_user_application()
+        .exec()
+        .await?
+        .model()
+        .await?;
+    http.set_application_id(application.id);
+
+    let registered = match guild_id {
+        Some(guild_id) => http.get_guild_commands(guild_id)?.exec().await?,
+        None => http.get_global_commands()?.exec().await?,
+    }
+    .models()
+    .await?;
+    let wanted = commands();
+
+    let mut changed = Vec::new();
+
+    for change in diff(&registered, &wanted) {
+        match (&change, guild_id) {
+            (Change::Create(command), Some(guild_id)) => {
+                http.create_guild_command(guild_id, &command.name, &command.description)?
+                    .command_options(&command.options)?
+                    .exec()
+                    .await?;
+            }
+            (Change::Create(command), None) => {
+                http.create_global_command(&command.name, &command.description)?
+                    .command_options(&command.options)?
+                    .exec()
+                    .await?;
+            }
+            (Change::Update(id, command), Some(guild_id)) => {
+                http.update_guild_command(guild_id, *id)?
+                    .description(&command.description)
+                    .command_options(&command.options)
+                    .exec()
+                    .await?;
+            }
+            (Change::Update(id, command), None) => {
+                http.update_global_command(*id)?
+                    .description(&command.description)
+                    .command_options(&command.options)
+                    .exec()
+                    .await?;
+            }
+            (Change::Delete(id, _), Some(guild_id)) => {
+                http.delete_guild_command(guild_id, *id)?.exec().await?;
+            }
+            (Change::Delete(id, _), None) => {
+                http.delete_global_command(*id)?.exec().await?;
+            }
+        }
+
+        let description = change.describe();
+        tracing::info!("slash commands: {}", description);
+        changed.push(description);
+    }
+
+    Ok(changed)
+}
+
+/// The typed command line the slash command stands for.
+pub fn command_line(prefix: &str, command: &ApplicationCommand) -> String {
+    let mut line = format!("{}{}", prefix, command.data.name);
+
+    for option in &command.data.options {
+        if let CommandDataOption::String { value, .. } = option {
+            line.push(' ');
+            line.push_str(value);
+        }
+    }
+
+    line
+}
+
+/// Acknowledges the interaction with a message showing the command, then
+/// runs that message through the same dispatcher as typed commands, as if
+/// the invoking user had sent it.
+///
+/// Handlers reply in the channel as usual; the acknowledgement gives them
+/// a real message to react to.
+pub async fn handle(
+    state: &State,
+    command: ApplicationCommand,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let user = command
+        .member
+        .as_ref()
+        .and_then(|member| member.user.clone())
+        .filter(|_| command.guild_id.is_some());
+
+    let prefix = match command.guild_id {
+        Some(guild_id) => state.prefix(guild_id),
+        None => state.config.prefix.clone(),
+    };
+    let line = command_line(&prefix, &command);
+    let content = if user.is_some() {
+        format!("`{}`", line)
+    } else {
+        "Commands only work in servers.".to_string()
+    };
+
+    let response = InteractionResponse::ChannelMessageWithSource(CallbackData {
+        allowed_mentions: None,
+        components: None,
+        content: Some(content),
+        embeds: Vec::new(),
+        flags: None,
+        tts: None,
+    });
+
+    state
+        .http
+        .interaction_callback(command.id, &command.token, &response)
+        .exec()
+        .await?;
+
+    let user = match user {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let mut msg = state
+        .http
+        .get_interaction_original(&command.token)?
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    msg.author = user;
+    msg.member = command.member;
+    msg.content = line;
+    msg.guild_id = command.guild_id;
+    msg.channel_id = command.channel_id;
+
+    crate::dispatch(state, msg);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twilight_model::application::command::CommandOptionType;
+
+    #[test]
+    fn commands_match_the_table() {
+        let commands = commands();
+
+        assert_eq!(commands.len(), COMMANDS.len());
+        assert_eq!(commands[1].name, "play");
+        assert!(commands[1].options[0].kind() == CommandOptionType::String);
+        assert!(!commands[1].options[0].is_required());
+        assert!(commands[2].options.is_empty());
+    }
+
+    #[test]
+    fn diffs_registered_commands() {
+        let wanted = commands();
+
+        let mut registered = wanted.clone();
+        for (id, command) in registered.iter_mut().enumerate() {
+            command.id = Some(CommandId(id as u64 + 1));
+        }
+        assert!(diff(&registered, &wanted).is_empty());
+
+        // One command renamed away, one reworded.
+        registered[0].name = "summon".to_string();
+        registered[2].description = "Go away".to_string();
+
+        assert_eq!(
+            diff(&registered, &wanted),
+            [
+                Change::Create(&wanted[0]),
+                Change::Update(CommandId(3), &wanted[2]),
+                Change::Delete(CommandId(1), "summon".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use crate::{
+    prefixes,
+    settings::{
+        self, Announcements, Curfew, GuildSettings, NoRepeats, QuietHours, NOW_PLAYING_FIELDS,
+    },
+    template::Template,
+};
+use serde::{Deserialize, Serialize};
+
+/// The portable part of a guild's settings, as exported by
+/// `j/settings export` and applied by `j/settings import`.
+///
+/// Everything is kept in the same text form the settings commands take,
+/// so snapshots stay readable and can be edited by hand. The log channel,
+/// event role, DJ role and per-role volume caps are left out: channel and
+/// role IDs belong to one guild, and a copy would point at nothing in
+/// another. So is permission to record, which each guild has to grant
+/// itself.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Snapshot {
+    /// The guild's own command prefix. It's saved in `prefixes.json`
+    /// rather than with the other settings, so [`Snapshot::export`] leaves
+    /// it for the caller to fill in and [`Snapshot::apply`] only checks it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub quiet_hours: Option<Schedule>,
+    #[serde(default)]
+    pub curfew: Option<Schedule>,
+    #[serde(default)]
+    pub announcements: Option<String>,
+    #[serde(default)]
+    pub now_playing_template: Option<String>,
+    #[serde(default)]
+    pub dislike_skip: Option<u32>,
+    #[serde(default)]
+    pub vote_skip: Option<u32>,
+    #[serde(default)]
+    pub themes: Option<bool>,
+    #[serde(default)]
+    pub idle_minutes: Option<u32>,
+    /// Hours, then `dj` or `strict`, as `j/settings norepeats` takes them.
+    #[serde(default)]
+    pub no_repeats: Option<String>,
+    #[serde(default)]
+    pub requester_removals: Option<bool>,
+    /// In percent, as `j/volume` takes it.
+    #[serde(default)]
+    pub volume: Option<u32>,
+}
+
+/// Times as `HH:MM` (quiet hours as `HH:MM-HH:MM`), plus an optional
+/// `+HH:MM` offset; without one the schedule follows the guild time zone.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Schedule {
+    pub time: String,
+    #[serde(default)]
+    pub offset: Option<String>,
+}
+
+impl Snapshot {
+    pub fn export(settings: &GuildSettings) -> Self {
+        Self {
+            prefix: None,
+            timezone: settings.timezone.map(|offset| offset.to_string()),
+            quiet_hours: settings.quiet_hours.map(|quiet_hours| Schedule {
+                time: format!(
+                    "{}-{}",
+                    quiet_hours.start.format("%H:%M"),
+                    quiet_hours.end.format("%H:%M")
+                ),
+                offset: quiet_hours.offset.map(|offset| offset.to_string()),
+            }),
+            curfew: settings.curfew.map(|curfew| Schedule {
+                time: curfew.time.format("%H:%M").to_string(),
+                offset: curfew.offset.map(|offset| offset.to_string()),
+            }),
+            announcements: Some(settings.announcements.to_string()),
+            now_playing_template: settings
+                .now_playing_template
+                .as_ref()
+                .map(|template| template.to_string()),
+            dislike_skip: settings.dislike_skip,
+            vote_skip: settings.vote_skip,
+            idle_minutes: settings.idle_minutes,
+            themes: Some(settings.themes),
+            no_repeats: settings.no_repeats.map(|no_repeats| no_repeats.to_string()),
+            requester_removals: Some(settings.requester_removals),
+            volume: settings.volume,
+        }
+    }
+
+    /// Applies the snapshot on top of `settings`, replacing every portable
+    /// setting. Nothing is changed unless the whole snapshot is valid.
+    pub fn apply(&self, settings: &mut GuildSettings) -> Result<(), String> {
+        if let Some(prefix) = &self.prefix {
+            prefixes::check(prefix)?;
+        }
+
+        let timezone = match &self.timezone {
+            Some(offset) => Some(
+                settings::parse_offset(offset)
+                    .ok_or_else(|| format!("Invalid time zone `{}`.", offset))?,
+            ),
+            None => None,
+        };
+
+        let quiet_hours = match &self.quiet_hours {
+            Some(schedule) => Some(
+                QuietHours::parse(&schedule.time, schedule.offset.as_deref())
+                    .ok_or_else(|| format!("Invalid quiet hours `{}`.", schedule.time))?,
+            ),
+            None => None,
+        };
+
+        let curfew = match &self.curfew {
+            Some(schedule) => Some(
+                Curfew::parse(&schedule.time, schedule.offset.as_deref())
+                    .ok_or_else(|| format!("Invalid curfew `{}`.", schedule.time))?,
+            ),
+            None => None,
+        };
+
+        let announcements = match &self.announcements {
+            Some(level) => Announcements::parse(level)
+                .ok_or_else(|| format!("Invalid announcements level `{}`.", level))?,
+            None => Announcements::default(),
+        };
+
+        let now_playing_template = match &self.now_playing_template {
+            Some(text) => Some(Template::parse(text, NOW_PLAYING_FIELDS)?),
+            None => None,
+        };
+
+        let no_repeats = match &self.no_repeats {
+            Some(text) => {
+                let mut words = text.split_whitespace();
+                Some(
+                    words
+                        .next()
+                        .and_then(|hours| NoRepeats::parse(hours, words.next()))
+                        .ok_or_else(|| format!("Invalid no-repeats window `{}`.", text))?,
+                )
+            }
+            None => None,
+        };
+
+        let dislike_skip = match self.dislike_skip {
+            Some(0) => return Err("Invalid dislike threshold `0`.".to_string()),
+            dislike_skip => dislike_skip,
+        };
+
+        let idle_minutes = match self.idle_minutes {
+            Some(minutes) if minutes > settings::MAX_IDLE_MINUTES => {
+                return Err(format!("Invalid idle timeout `{}` minutes.", minutes))
+            }
+            idle_minutes => idle_minutes,
+        };
+
+        let vote_skip = match self.vote_skip {
+            Some(percent) if !(1..=100).contains(&percent) => {
+                return Err(format!("Invalid vote-skip percentage `{}`.", percent))
+            }
+            vote_skip => vote_skip,
+        };
+
+        let volume = match self.volume {
+            Some(percent) if percent > settings::MAX_VOLUME_PERCENT => {
+                return Err(format!("Invalid volume `{}%`.", percent))
+            }
+            volume => volume,
+        };
+
+        settings.timezone = timezone;
+        settings.quiet_hours = quiet_hours;
+        settings.curfew = curfew;
+        settings.announcements = announcements;
+        settings.now_playing_template = now_playing_template;
+        settings.dislike_skip = dislike_skip;
+        settings.vote_skip = vote_skip;
+        settings.themes = self.themes.unwrap_or_default();
+        settings.idle_minutes = idle_minutes;
+        settings.no_repeats = no_repeats;
+        settings.requester_removals = self.requester_removals.unwrap_or_default();
+        settings.volume = volume;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twilight_model::id::ChannelId;
+
+    #[test]
+    fn round_trip() {
+        let mut original = GuildSettings::default();
+        Snapshot {
+            prefix: Some("!".to_string()),
+            timezone: Some("+02:00".to_string()),
+            quiet_hours: Some(Schedule {
+                time: "22:00-07:00".to_string(),
+                offset: None,
+            }),
+            curfew: Some(Schedule {
+                time: "23:30".to_string(),
+                offset: Some("-05:00".to_string()),
+            }),
+            announcements: Some("minimal".to_string()),
+            now_playing_template: Some("🎶 {title}".to_string()),
+            dislike_skip: Some(3),
+            vote_skip: Some(75),
+            themes: Some(true),
+            idle_minutes: Some(30),
+            no_repeats: Some("6 dj".to_string()),
+            requester_removals: Some(true),
+            volume: Some(60),
+        }
+        .apply(&mut original)
+        .unwrap();
+
+        let json = serde_json::to_string(&Snapshot::export(&original)).unwrap();
+        let mut copy = GuildSettings::default();
+        serde_json::from_str::<Snapshot>(&json)
+            .unwrap()
+            .apply(&mut copy)
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(Snapshot::export(&copy)).unwrap(),
+            serde_json::to_value(Snapshot::export(&original)).unwrap()
+        );
+        assert_eq!(copy.curfew.unwrap().to_string(), "23:30 (UTC-05:00)");
+        assert_eq!(copy.dislike_skip, Some(3));
+        assert_eq!(copy.vote_skip, Some(75));
+        assert!(copy.themes);
+        assert!(copy.requester_removals);
+        assert_eq!(copy.idle_minutes, Some(30));
+        assert_eq!(copy.volume, Some(60));
+        assert_eq!(
+            copy.no_repeats,
+            Some(NoRepeats {
+                hours: 6,
+                dj_override: true
+            })
+        );
+    }
+
+    #[test]
+    fn keeps_log_channel() {
+        let mut settings = GuildSettings {
+            log_channel: Some(ChannelId(1)),
+            ..Default::default()
+        };
+
+        Snapshot::default().apply(&mut settings).unwrap();
+
+        assert_eq!(settings.log_channel, Some(ChannelId(1)));
+    }
+
+    #[test]
+    fn invalid_snapshot_changes_nothing() {
+        let mut settings = GuildSettings::default();
+        let snapshot: Snapshot =
+            serde_json::from_str(r#"{"timezone": "+01:00", "curfew": {"time": "25:00"}}"#).unwrap();
+
+        assert_eq!(
+            snapshot.apply(&mut settings),
+            Err("Invalid curfew `25:00`.".to_string())
+        );
+        assert_eq!(settings.timezone, None);
+    }
+
+    #[test]
+    fn out_of_range_vote_skip_is_refused() {
+        let mut settings = GuildSettings::default();
+        let snapshot: Snapshot =
+            serde_json::from_str(r#"{"themes": true, "vote_skip": 150}"#).unwrap();
+
+        assert_eq!(
+            snapshot.apply(&mut settings),
+            Err("Invalid vote-skip percentage `150`.".to_string())
+        );
+        assert!(!settings.themes);
+    }
+
+    #[test]
+    fn zero_dislikes_and_overlong_idle_timeouts_are_refused() {
+        let mut settings = GuildSettings::default();
+
+        let snapshot: Snapshot = serde_json::from_str(r#"{"dislike_skip": 0}"#).unwrap();
+        assert_eq!(
+            snapshot.apply(&mut settings),
+            Err("Invalid dislike threshold `0`.".to_string())
+        );
+
+        let snapshot: Snapshot = serde_json::from_str(r#"{"idle_minutes": 5000}"#).unwrap();
+        assert_eq!(
+            snapshot.apply(&mut settings),
+            Err("Invalid idle timeout `5000` minutes.".to_string())
+        );
+
+        let snapshot: Snapshot = serde_json::from_str(r#"{"volume": 300}"#).unwrap();
+        assert_eq!(
+            snapshot.apply(&mut settings),
+            Err("Invalid volume `300%`.".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_prefix_is_refused() {
+        let mut settings = GuildSettings::default();
+        let snapshot: Snapshot =
+            serde_json::from_str(r#"{"themes": true, "prefix": "<@1>"}"#).unwrap();
+
+        assert_eq!(
+            snapshot.apply(&mut settings),
+            Err("A prefix can't start with <, @ or #.".to_string())
+        );
+        assert!(!settings.themes);
+    }
+}
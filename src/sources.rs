@@ -0,0 +1,833 @@
+use crate::{
+    chapters::{self, Chapter},
+    duration,
+    ipv6::Ipv6Block,
+    playlist,
+    programs::{Program, Programs},
+    search,
+};
+use async_trait::async_trait;
+use songbird::input::{
+    error::{Error as InputError, Result as InputResult},
+    reader::Reader,
+    restartable::Restart,
+    Codec, Container, Input, Metadata, Restartable,
+};
+use std::{collections::HashMap, error::Error, fmt, process::Stdio, time::Duration};
+
+pub use crate::{
+    identify::{identify, Identified, Source},
+    search::SearchResult,
+    track::TrackInfo,
+};
+
+/// Turns what a user asked for into a source the driver can play.
+///
+/// Every source comes back seekable, so `j/resume` can restart it at an
+/// offset.
+#[async_trait]
+pub trait SourceResolver: fmt::Debug + Send + Sync {
+    async fn resolve(&self, query: &str) -> Result<Input, Box<dyn Error + Send + Sync + 'static>>;
+
+    /// Finds up to `limit` tracks matching the keywords in `query`.
+    async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>>;
+
+    /// Lists the tracks in the playlist at `url`, in order.
+    async fn playlist(
+        &self,
+        url: &str,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>>;
+
+    /// Lists the chapters of the track at `url`, in order; none if it
+    /// isn't divided into any.
+    async fn chapters(
+        &self,
+        url: &str,
+    ) -> Result<Vec<Chapter>, Box<dyn Error + Send + Sync + 'static>>;
+}
+
+/// For a YouTube watch URL that is part of an auto-generated mix
+/// (`list=RD…`), the same URL with the mix parameters removed.
+///
+/// youtube-dl expands a mix into its first few dozen videos, which a
+/// single-track source can't play, so only the primary video is kept.
+pub fn mix_video(query: &str) -> Option<String> {
+    let (base, params) = query.split_once('?')?;
+    let host = base
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split('/')
+        .next()?;
+
+    if !(host == "youtube.com" || host.ends_with(".youtube.com")) || !base.ends_with("/watch") {
+        return None;
+    }
+
+    let params = params.split('&').collect::<Vec<_>>();
+
+    if !params.iter().any(|param| param.starts_with("list=RD")) {
+        return None;
+    }
+
+    let kept = params
+        .into_iter()
+        .filter(|param| {
+            let key = param.split('=').next().unwrap_or_default();
+            !matches!(key, "list" | "index" | "start_radio" | "pp")
+        })
+        .collect::<Vec<_>>();
+
+    if !kept.iter().any(|param| param.starts_with("v=")) {
+        return None;
+    }
+
+    Some(format!("{}?{}", base, kept.join("&")))
+}
+
+/// Where playback of `query` should start, from a `t=`/`start=` query
+/// parameter or a `#t=` fragment.
+///
+/// Accepts the forms [`duration::parse`] does.
+pub fn start_time(query: &str) -> Option<Duration> {
+    let (rest, fragment) = match query.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (query, None),
+    };
+
+    let from_params = rest.split_once('?').and_then(|(_, params)| {
+        params
+            .split('&')
+            .find_map(|param| match param.split_once('=') {
+                Some(("t" | "start", value)) => duration::parse(value),
+                _ => None,
+            })
+    });
+
+    from_params.or_else(|| duration::parse(fragment?.strip_prefix("t=")?))
+}
+
+/// Resolves URLs through `youtube-dl`, piped through `ffmpeg`, run as
+/// the config file says.
+#[derive(Debug, Default)]
+pub struct YtdlResolver {
+    programs: Programs,
+    ipv6_block: Option<Ipv6Block>,
+}
+
+impl YtdlResolver {
+    pub fn new(programs: Programs, ipv6_block: Option<Ipv6Block>) -> Self {
+        Self {
+            programs,
+            ipv6_block,
+        }
+    }
+
+    /// youtube-dl for one track, search or playlist, bound to its own
+    /// address from the IPv6 block if there is one.
+    fn youtube_dl(&self) -> Program {
+        let mut youtube_dl = self.programs.youtube_dl.clone();
+        if let Some(block) = self.ipv6_block {
+            youtube_dl.args.push("--source-address".to_string());
+            youtube_dl.args.push(block.random_address().to_string());
+        }
+        youtube_dl
+    }
+}
+
+#[async_trait]
+impl SourceResolver for YtdlResolver {
+    async fn resolve(&self, query: &str) -> Result<Input, Box<dyn Error + Send + Sync + 'static>> {
+        let source = YtdlSource {
+            url: query.to_string(),
+            programs: Programs {
+                youtube_dl: self.youtube_dl(),
+                ffmpeg: self.programs.ffmpeg.clone(),
+            },
+        };
+
+        // A lazy source only fetches metadata until the driver first reads
+        // from it, and can seek by relaunching ytdl at an offset.
+        Ok(Restartable::new(source, true).await?.into())
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+        search::ytdl(&self.youtube_dl(), query, limit).await
+    }
+
+    async fn playlist(
+        &self,
+        url: &str,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+        playlist::ytdl(&self.youtube_dl(), url).await
+    }
+
+    async fn chapters(
+        &self,
+        url: &str,
+    ) -> Result<Vec<Chapter>, Box<dyn Error + Send + Sync + 'static>> {
+        chapters::ytdl(&self.youtube_dl(), url).await
+    }
+}
+
+/// Sends each URL to what plays it best: audio files and radio streams go
+/// straight to ffmpeg, and every site, SoundCloud and Bandcamp included,
+/// goes through youtube-dl. Searches are always youtube-dl's.
+#[derive(Debug, Default)]
+pub struct HostResolver {
+    youtube_dl: YtdlResolver,
+    direct: DirectResolver,
+}
+
+impl HostResolver {
+    pub fn new(programs: Programs, ipv6_block: Option<Ipv6Block>) -> Self {
+        Self {
+            direct: DirectResolver {
+                ffmpeg: programs.ffmpeg.clone(),
+            },
+            youtube_dl: YtdlResolver::new(programs, ipv6_block),
+        }
+    }
+
+    fn route(&self, url: &str) -> &dyn SourceResolver {
+        match identify(url).map(|identified| identified.source) {
+            Some(Source::File { .. }) | Some(Source::Stream) => &self.direct,
+            _ => &self.youtube_dl,
+        }
+    }
+}
+
+#[async_trait]
+impl SourceResolver for HostResolver {
+    async fn resolve(&self, query: &str) -> Result<Input, Box<dyn Error + Send + Sync + 'static>> {
+        self.route(query).resolve(query).await
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+        self.youtube_dl.search(query, limit).await
+    }
+
+    async fn playlist(
+        &self,
+        url: &str,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+        self.route(url).playlist(url).await
+    }
+
+    async fn chapters(
+        &self,
+        url: &str,
+    ) -> Result<Vec<Chapter>, Box<dyn Error + Send + Sync + 'static>> {
+        self.route(url).chapters(url).await
+    }
+}
+
+/// Plays a URL with ffmpeg alone, reading the file's or the stream's own
+/// tags. youtube-dl's generic extractor only knows a file's name.
+#[derive(Debug)]
+pub struct DirectResolver {
+    ffmpeg: Program,
+}
+
+impl Default for DirectResolver {
+    fn default() -> Self {
+        Self {
+            ffmpeg: Programs::default().ffmpeg,
+        }
+    }
+}
+
+#[async_trait]
+impl SourceResolver for DirectResolver {
+    async fn resolve(&self, query: &str) -> Result<Input, Box<dyn Error + Send + Sync + 'static>> {
+        let source = DirectSource {
+            url: query.to_string(),
+            ffmpeg: self.ffmpeg.clone(),
+        };
+
+        Ok(Restartable::new(source, true).await?.into())
+    }
+
+    async fn search(
+        &self,
+        _query: &str,
+        _limit: usize,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+        Err("direct links can't be searched".into())
+    }
+
+    async fn playlist(
+        &self,
+        url: &str,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+        Err(format!("{} is a single file or stream, not a playlist", url).into())
+    }
+
+    async fn chapters(
+        &self,
+        _url: &str,
+    ) -> Result<Vec<Chapter>, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(Vec::new())
+    }
+}
+
+/// youtube-dl's options for picking and fetching a single track's audio,
+/// as songbird's own ytdl source uses.
+const YTDL_ARGS: &[&str] = &[
+    "-f",
+    "webm[abr>0]/bestaudio/best",
+    "-R",
+    "infinite",
+    "--no-playlist",
+    "--ignore-config",
+    "--no-warnings",
+];
+
+/// ffmpeg's options for writing the float PCM the driver mixes to its
+/// stdout.
+const PCM_OUTPUT_ARGS: &[&str] = &[
+    "-f",
+    "s16le",
+    "-ac",
+    "2",
+    "-ar",
+    "48000",
+    "-acodec",
+    "pcm_f32le",
+    "-",
+];
+
+/// ffmpeg's options for reading from HTTP, so a dropped radio stream picks
+/// back up.
+const RECONNECT_ARGS: &[&str] = &[
+    "-reconnect",
+    "1",
+    "-reconnect_streamed",
+    "1",
+    "-reconnect_delay_max",
+    "5",
+];
+
+/// Songbird's ytdl source with the configured programs in place of the
+/// ones on `PATH`: youtube-dl downloads to its stdout, and ffmpeg turns
+/// that into the float PCM the driver mixes.
+struct YtdlSource {
+    url: String,
+    programs: Programs,
+}
+
+#[async_trait]
+impl Restart for YtdlSource {
+    async fn call_restart(&mut self, time: Option<Duration>) -> InputResult<Input> {
+        let mut youtube_dl = self
+            .programs
+            .youtube_dl
+            .std_command()
+            .args(YTDL_ARGS)
+            .arg(&self.url)
+            .args(["-o", "-"])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let downloaded = youtube_dl.stdout.take().ok_or(InputError::Stdout)?;
+
+        let mut ffmpeg = self.programs.ffmpeg.std_command();
+        if let Some(time) = time {
+            ffmpeg.args(["-ss", &format!("{:.3}", time.as_secs_f64())]);
+        }
+        let ffmpeg = ffmpeg
+            .args(["-i", "-"])
+            .args(PCM_OUTPUT_ARGS)
+            .stdin(downloaded)
+            .stderr(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        Ok(Input::new(
+            true,
+            Reader::from(vec![youtube_dl, ffmpeg]),
+            Codec::FloatPcm,
+            Container::Raw,
+            None,
+        ))
+    }
+
+    async fn lazy_init(&mut self) -> InputResult<(Option<Metadata>, Codec, Container)> {
+        let output = self
+            .programs
+            .youtube_dl
+            .command()
+            .arg("-j")
+            .args(YTDL_ARGS)
+            .arg(&self.url)
+            .stdin(Stdio::null())
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(InputError::YouTubeDlRun(output));
+        }
+
+        let line = output
+            .stdout
+            .split(|&b| b == b'\n')
+            .next()
+            .unwrap_or_default();
+        let value = serde_json::from_slice(line).map_err(|error| InputError::Json {
+            error,
+            parsed_text: String::from_utf8_lossy(line).into_owned(),
+        })?;
+
+        Ok((
+            Some(Metadata::from_ytdl_output(value)),
+            Codec::FloatPcm,
+            Container::Raw,
+        ))
+    }
+}
+
+struct DirectSource {
+    url: String,
+    ffmpeg: Program,
+}
+
+#[async_trait]
+impl Restart for DirectSource {
+    async fn call_restart(&mut self, time: Option<Duration>) -> InputResult<Input> {
+        let mut ffmpeg = self.ffmpeg.std_command();
+        ffmpeg.args(RECONNECT_ARGS);
+        if let Some(time) = time {
+            ffmpeg.args(["-ss", &format!("{:.3}", time.as_secs_f64())]);
+        }
+        let ffmpeg = ffmpeg
+            .args(["-i", &self.url])
+            .args(PCM_OUTPUT_ARGS)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        Ok(Input::new(
+            true,
+            Reader::from(vec![ffmpeg]),
+            Codec::FloatPcm,
+            Container::Raw,
+            None,
+        ))
+    }
+
+    async fn lazy_init(&mut self) -> InputResult<(Option<Metadata>, Codec, Container)> {
+        // Without an output ffmpeg only describes the input, and exits
+        // unhappy that there's nothing to write.
+        let output = self
+            .ffmpeg
+            .command()
+            .arg("-hide_banner")
+            .args(RECONNECT_ARGS)
+            .args(["-i", &self.url])
+            .stdin(Stdio::null())
+            .output()
+            .await?;
+
+        let metadata = probe_metadata(&String::from_utf8_lossy(&output.stderr), &self.url)
+            .ok_or(InputError::Metadata)?;
+
+        Ok((Some(metadata), Codec::FloatPcm, Container::Raw))
+    }
+}
+
+/// Reads the tags and length ffmpeg printed for the input at `url`, or
+/// `None` if it couldn't open it. Radio streams go by their station name.
+fn probe_metadata(description: &str, url: &str) -> Option<Metadata> {
+    let mut lines = description
+        .lines()
+        .skip_while(|line| !line.starts_with("Input #"));
+    lines.next()?;
+
+    let mut tags = HashMap::new();
+    let mut duration = None;
+
+    for line in lines.map(str::trim) {
+        // Streams have tags of their own, which the container's win over.
+        if line.starts_with("Stream #") {
+            break;
+        }
+
+        if let Some(rest) = line.strip_prefix("Duration: ") {
+            duration = rest.split(',').next().and_then(probe_duration);
+        } else if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            if !value.is_empty() {
+                tags.entry(key.trim().to_lowercase())
+                    .or_insert_with(|| value.to_string());
+            }
+        }
+    }
+
+    let file_name = url
+        .split(&['?', '#'][..])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .map(|name| percent_decode(name.rsplit_once('.').map_or(name, |(stem, _)| stem)));
+
+    let title = tags
+        .remove("title")
+        .or_else(|| tags.remove("icy-name"))
+        .or(file_name);
+    let artist = tags
+        .remove("artist")
+        .or_else(|| tags.remove("album_artist"));
+
+    Some(Metadata {
+        track: title.clone(),
+        title,
+        artist,
+        duration,
+        source_url: Some(url.to_string()),
+        ..Default::default()
+    })
+}
+
+/// `value` with its `%20`-style escapes undone.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A length as ffmpeg prints it, `01:02:03.45`. Streams have `N/A`.
+fn probe_duration(value: &str) -> Option<Duration> {
+    let (clock, fraction) = value.split_once('.').unwrap_or((value, "0"));
+    let fraction = format!("0.{}", fraction).parse::<f64>().ok()?;
+
+    Some(duration::parse(clock)? + Duration::from_secs_f64(fraction))
+}
+
+/// Turns every lookup away, for `musicm8 replay`, which mustn't fetch
+/// anything from the sites a recorded session played from.
+#[cfg(feature = "replay")]
+#[derive(Debug, Default)]
+pub struct OfflineResolver;
+
+#[cfg(feature = "replay")]
+#[async_trait]
+impl SourceResolver for OfflineResolver {
+    async fn resolve(&self, query: &str) -> Result<Input, Box<dyn Error + Send + Sync + 'static>> {
+        Err(format!("not fetching {:?} during a replay", query).into())
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        _limit: usize,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+        Err(format!("not searching for {:?} during a replay", query).into())
+    }
+
+    async fn playlist(
+        &self,
+        url: &str,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+        Err(format!("not fetching {:?} during a replay", url).into())
+    }
+
+    async fn chapters(
+        &self,
+        url: &str,
+    ) -> Result<Vec<Chapter>, Box<dyn Error + Send + Sync + 'static>> {
+        Err(format!("not fetching {:?} during a replay", url).into())
+    }
+}
+
+/// Serves silence for a fixed set of queries, for tests that must not
+/// touch the network or external binaries.
+///
+/// The reported duration is whatever the test registered, but at most
+/// [`FAKE_AUDIO_LIMIT`] of audio is actually generated, so long tracks
+/// don't have to be held in memory.
+pub const FAKE_AUDIO_LIMIT: Duration = Duration::from_secs(90);
+
+#[derive(Debug, Default)]
+pub struct FakeResolver {
+    tracks: HashMap<String, Metadata>,
+    playlists: HashMap<String, Vec<String>>,
+    chapters: HashMap<String, Vec<Chapter>>,
+}
+
+impl FakeResolver {
+    /// Registers a track; its audio is silence lasting `duration`, up to
+    /// [`FAKE_AUDIO_LIMIT`].
+    pub fn with_track(
+        mut self,
+        query: &str,
+        title: &str,
+        artist: &str,
+        duration: Duration,
+    ) -> Self {
+        let metadata = Metadata {
+            track: Some(title.to_string()),
+            title: Some(title.to_string()),
+            artist: Some(artist.to_string()),
+            duration: Some(duration),
+            source_url: Some(query.to_string()),
+            ..Default::default()
+        };
+
+        self.tracks.insert(query.to_string(), metadata);
+        self
+    }
+
+    /// Registers a playlist of URLs; entries that weren't registered with
+    /// [`with_track`](Self::with_track) fail to resolve.
+    pub fn with_playlist(mut self, url: &str, entries: &[&str]) -> Self {
+        self.playlists.insert(
+            url.to_string(),
+            entries.iter().map(|entry| entry.to_string()).collect(),
+        );
+        self
+    }
+
+    /// Divides the track registered as `url` into chapters, each a title
+    /// with where it starts and ends.
+    pub fn with_chapters(mut self, url: &str, chapters: &[(&str, Duration, Duration)]) -> Self {
+        self.chapters.insert(
+            url.to_string(),
+            chapters
+                .iter()
+                .map(|(title, start, end)| Chapter {
+                    title: title.to_string(),
+                    start: *start,
+                    end: *end,
+                })
+                .collect(),
+        );
+        self
+    }
+}
+
+#[async_trait]
+impl SourceResolver for FakeResolver {
+    async fn resolve(&self, query: &str) -> Result<Input, Box<dyn Error + Send + Sync + 'static>> {
+        let metadata = self
+            .tracks
+            .get(query)
+            .ok_or_else(|| format!("no fake track for {:?}", query))?;
+
+        // Stereo 32-bit float PCM at 48kHz.
+        let duration = metadata.duration.unwrap_or_default().min(FAKE_AUDIO_LIMIT);
+        let samples = duration.as_millis() as usize * 48 * 2;
+        let reader = Reader::from_memory(vec![0; samples * 4]);
+
+        let mut input = Input::float_pcm(true, reader);
+        input.metadata = Box::new(metadata.clone());
+
+        Ok(input)
+    }
+
+    /// Matches registered titles containing `query`, ignoring case, in
+    /// title order.
+    async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+        let query = query.to_lowercase();
+
+        let mut results = self
+            .tracks
+            .iter()
+            .filter_map(|(url, metadata)| {
+                let title = metadata.title.as_ref()?;
+
+                title.to_lowercase().contains(&query).then(|| SearchResult {
+                    title: title.clone(),
+                    url: url.clone(),
+                    duration: metadata.duration,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        results.sort_by(|a, b| a.title.cmp(&b.title));
+        results.truncate(limit);
+
+        Ok(results)
+    }
+
+    async fn playlist(
+        &self,
+        url: &str,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error + Send + Sync + 'static>> {
+        let entries = self
+            .playlists
+            .get(url)
+            .ok_or_else(|| format!("no fake playlist for {:?}", url))?;
+
+        Ok(entries
+            .iter()
+            .map(|entry| {
+                let metadata = self.tracks.get(entry);
+
+                SearchResult {
+                    title: metadata
+                        .and_then(|metadata| metadata.title.clone())
+                        .unwrap_or_else(|| entry.clone()),
+                    url: entry.clone(),
+                    duration: metadata.and_then(|metadata| metadata.duration),
+                }
+            })
+            .collect())
+    }
+    async fn chapters(
+        &self,
+        url: &str,
+    ) -> Result<Vec<Chapter>, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(self.chapters.get(url).cloned().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_time_from_parameters() {
+        assert_eq!(
+            start_time("https://youtu.be/abc?t=90"),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(
+            start_time("https://www.youtube.com/watch?v=abc&t=1h2m3s"),
+            Some(Duration::from_secs(3723))
+        );
+        assert_eq!(
+            start_time("https://www.youtube.com/embed/abc?start=45"),
+            Some(Duration::from_secs(45))
+        );
+    }
+
+    #[test]
+    fn start_time_from_fragment() {
+        assert_eq!(
+            start_time("https://example.com/song.mp3#t=1:30"),
+            Some(Duration::from_secs(90))
+        );
+    }
+
+    #[test]
+    fn start_time_ignores_other_values() {
+        assert_eq!(start_time("https://www.youtube.com/watch?v=abc"), None);
+        assert_eq!(start_time("https://example.com/?t=soon"), None);
+        assert_eq!(start_time("https://example.com/?t=5m3"), None);
+        assert_eq!(start_time("https://example.com/#top"), None);
+    }
+
+    #[test]
+    fn mix_keeps_primary_video() {
+        assert_eq!(
+            mix_video("https://www.youtube.com/watch?v=abc123&list=RDabc123&start_radio=1&index=2")
+                .as_deref(),
+            Some("https://www.youtube.com/watch?v=abc123")
+        );
+    }
+
+    #[test]
+    fn mix_keeps_other_parameters() {
+        assert_eq!(
+            mix_video("https://music.youtube.com/watch?list=RDAMVMabc&v=abc&t=42").as_deref(),
+            Some("https://music.youtube.com/watch?v=abc&t=42")
+        );
+    }
+
+    #[test]
+    fn ignores_regular_playlists_and_other_sites() {
+        assert_eq!(
+            mix_video("https://www.youtube.com/watch?v=abc&list=PL123"),
+            None
+        );
+        assert_eq!(
+            mix_video("https://example.com/watch?v=abc&list=RDabc"),
+            None
+        );
+        assert_eq!(
+            mix_video("https://www.youtube.com/playlist?list=RDabc"),
+            None
+        );
+    }
+
+    #[test]
+    fn probes_file_tags_and_length() {
+        let description = "\
+Input #0, mp3, from 'https://example.com/music/song.mp3':
+  Metadata:
+    title           : Song
+    artist          : Artist
+  Duration: 00:03:25.50, start: 0.025057, bitrate: 320 kb/s
+  Stream #0:0: Audio: mp3, 44100 Hz, stereo, fltp, 320 kb/s
+    Metadata:
+      title           : Cover
+At least one output file must be specified
+";
+        let metadata = probe_metadata(description, "https://example.com/music/song.mp3").unwrap();
+
+        assert_eq!(metadata.title.as_deref(), Some("Song"));
+        assert_eq!(metadata.artist.as_deref(), Some("Artist"));
+        assert_eq!(metadata.duration, Some(Duration::from_millis(205_500)));
+    }
+
+    #[test]
+    fn probes_streams_and_untagged_files() {
+        let stream = probe_metadata(
+            "Input #0, mp3, from 'http://radio.example.com/live':\n  Metadata:\n    icy-name        : Radio\n  Duration: N/A, start: 0.000000, bitrate: 128 kb/s\n",
+            "http://radio.example.com/live",
+        )
+        .unwrap();
+        assert_eq!(stream.title.as_deref(), Some("Radio"));
+        assert_eq!(stream.duration, None);
+
+        let untagged = probe_metadata(
+            "Input #0, wav, from 'x':\n  Duration: 00:00:01.00, bitrate: 1411 kb/s\n",
+            "https://example.com/Take%201%.wav?download=1",
+        )
+        .unwrap();
+        assert_eq!(untagged.title.as_deref(), Some("Take 1%"));
+
+        assert!(probe_metadata(
+            "https://example.com/gone.mp3: Server returned 404 Not Found",
+            ""
+        )
+        .is_none());
+    }
+}
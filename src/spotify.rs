@@ -0,0 +1,405 @@
+use crate::{config::SpotifyCredentials, playlist, search::SearchResult};
+use hyper::{
+    body,
+    client::HttpConnector,
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Body, Client, Request,
+};
+use hyper_rustls::HttpsConnector;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use url::form_urlencoded;
+
+const AUTHORIZE_URL: &str = "https://accounts.spotify.com/authorize";
+const TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+const API_URL: &str = "https://api.spotify.com/v1";
+
+/// What a linked account lets the bot read: private and collaborative
+/// playlists, and Liked Songs.
+const SCOPES: &str = "playlist-read-private playlist-read-collaborative user-library-read";
+
+/// How long before Spotify says a token expires it's replaced, so it
+/// doesn't run out between fetching a playlist's pages.
+const TOKEN_MARGIN: Duration = Duration::from_secs(60);
+
+/// A track as the Web API describes it, with what it takes to find it
+/// elsewhere.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct SpotifyTrack {
+    pub name: String,
+    #[serde(default)]
+    pub artists: Vec<Artist>,
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Artist {
+    pub name: String,
+}
+
+impl SpotifyTrack {
+    /// A YouTube search for the track, which youtube-dl resolves like any
+    /// other URL.
+    pub fn entry(&self) -> SearchResult {
+        let title = match self.artists.first() {
+            Some(artist) => format!("{} - {}", artist.name, self.name),
+            None => self.name.clone(),
+        };
+
+        SearchResult {
+            url: format!("ytsearch1:{}", title),
+            title,
+            duration: self.duration_ms.map(Duration::from_millis),
+        }
+    }
+}
+
+/// The tracks behind a Spotify link, taken up to
+/// [`playlist::MAX_TRACKS`].
+#[derive(Debug, PartialEq)]
+pub struct Tracks {
+    pub tracks: Vec<SpotifyTrack>,
+    /// How many there are in all, including any left out.
+    pub total: usize,
+}
+
+/// One of a linked account's playlists, as `j/spotify playlists` lists
+/// it.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct SpotifyPlaylist {
+    pub id: String,
+    pub name: String,
+}
+
+/// An access token fetched from the accounts service.
+#[derive(Debug, Deserialize)]
+pub struct Token {
+    pub access_token: String,
+    pub expires_in: u64,
+    /// Only handed out for a user's tokens: once when they link their
+    /// account, and sometimes again when the old one is refreshed.
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+}
+
+impl Token {
+    /// How long the token can be used for, leaving [`TOKEN_MARGIN`].
+    pub fn lifetime(&self) -> Duration {
+        Duration::from_secs(self.expires_in).saturating_sub(TOKEN_MARGIN)
+    }
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    items: Vec<T>,
+    next: Option<String>,
+    total: usize,
+}
+
+/// A playlist entry or a saved track. Its track is missing for local
+/// files and episodes that are no longer available.
+#[derive(Deserialize)]
+struct PlaylistItem {
+    track: Option<SpotifyTrack>,
+}
+
+/// A Web API client, authorized with the operator's app credentials from
+/// `spotify` in the config file. The app's own token reads public catalog
+/// data; private playlists and Liked Songs take the token of a user who
+/// linked their account through [`authorize_url`](Self::authorize_url).
+#[derive(Debug)]
+pub struct Spotify {
+    client: Client<HttpsConnector<HttpConnector>>,
+    credentials: SpotifyCredentials,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl Spotify {
+    pub fn new(credentials: SpotifyCredentials) -> Self {
+        Self {
+            client: Client::builder().build(HttpsConnector::with_native_roots()),
+            credentials,
+            token: Mutex::new(None),
+        }
+    }
+
+    /// The tracks behind a link of `kind` with `id`, or `None` for kinds
+    /// that aren't music, such as artists and shows. A linked user's
+    /// `user_token` also reaches their private playlists.
+    pub async fn tracks(
+        &self,
+        kind: &str,
+        id: &str,
+        user_token: Option<&str>,
+    ) -> Result<Option<Tracks>, Box<dyn Error + Send + Sync + 'static>> {
+        let token = match user_token {
+            Some(token) => token.to_string(),
+            None => self.access_token().await?,
+        };
+
+        let tracks = match kind {
+            "track" => {
+                let track = self
+                    .get(&format!("{}/tracks/{}", API_URL, id), &token)
+                    .await?;
+                Tracks {
+                    tracks: vec![track],
+                    total: 1,
+                }
+            }
+            "album" => {
+                let url = format!("{}/albums/{}/tracks?limit=50", API_URL, id);
+                self.pages(url, &token, Some).await?
+            }
+            "playlist" => {
+                let url = format!("{}/playlists/{}/tracks?limit=100", API_URL, id);
+                self.pages(url, &token, |item: PlaylistItem| item.track)
+                    .await?
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(tracks))
+    }
+
+    /// The Liked Songs of the user whose token `user_token` is, newest
+    /// first.
+    pub async fn liked(
+        &self,
+        user_token: &str,
+    ) -> Result<Tracks, Box<dyn Error + Send + Sync + 'static>> {
+        let url = format!("{}/me/tracks?limit=50", API_URL);
+        self.pages(url, user_token, |item: PlaylistItem| item.track)
+            .await
+    }
+
+    /// The first 10 playlists the user whose token `user_token` is made or
+    /// follows, and how many there are in all.
+    pub async fn playlists(
+        &self,
+        user_token: &str,
+    ) -> Result<(Vec<SpotifyPlaylist>, usize), Box<dyn Error + Send + Sync + 'static>> {
+        let url = format!("{}/me/playlists?limit=10", API_URL);
+        let page: Page<SpotifyPlaylist> = self.get(&url, user_token).await?;
+
+        Ok((page.items, page.total))
+    }
+
+    /// Where to send a user to let the bot read their playlists, or `None`
+    /// if the app has no `redirect_uri` to bring them back to. `state`
+    /// comes back with them, so the callback knows who they are.
+    pub fn authorize_url(&self, state: &str) -> Option<String> {
+        let redirect_uri = self.credentials.redirect_uri.as_deref()?;
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("client_id", &self.credentials.client_id)
+            .append_pair("response_type", "code")
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("scope", SCOPES)
+            .append_pair("state", state)
+            .finish();
+
+        Some(format!("{}?{}", AUTHORIZE_URL, query))
+    }
+
+    /// Trades the code the callback was given for the user's tokens.
+    #[cfg(feature = "overlay")]
+    pub async fn authorize(
+        &self,
+        code: &str,
+    ) -> Result<Token, Box<dyn Error + Send + Sync + 'static>> {
+        let redirect_uri = self
+            .credentials
+            .redirect_uri
+            .as_deref()
+            .ok_or("spotify has no redirect_uri")?;
+        let form = form_urlencoded::Serializer::new(String::new())
+            .append_pair("grant_type", "authorization_code")
+            .append_pair("code", code)
+            .append_pair("redirect_uri", redirect_uri)
+            .finish();
+
+        self.request_token(form).await
+    }
+
+    /// A fresh access token for the user whose `refresh_token` it is.
+    pub async fn refresh(
+        &self,
+        refresh_token: &str,
+    ) -> Result<Token, Box<dyn Error + Send + Sync + 'static>> {
+        let form = form_urlencoded::Serializer::new(String::new())
+            .append_pair("grant_type", "refresh_token")
+            .append_pair("refresh_token", refresh_token)
+            .finish();
+
+        self.request_token(form).await
+    }
+
+    /// Follows a list's pages until it ends or there are enough tracks.
+    async fn pages<T: DeserializeOwned>(
+        &self,
+        url: String,
+        token: &str,
+        track: impl Fn(T) -> Option<SpotifyTrack>,
+    ) -> Result<Tracks, Box<dyn Error + Send + Sync + 'static>> {
+        let mut tracks = Vec::new();
+        let mut total = 0;
+        let mut next = Some(url);
+
+        while let Some(url) = next {
+            let page: Page<T> = self.get(&url, token).await?;
+            total = page.total;
+            tracks.extend(page.items.into_iter().filter_map(&track));
+            next = page.next.filter(|_| tracks.len() < playlist::MAX_TRACKS);
+        }
+
+        tracks.truncate(playlist::MAX_TRACKS);
+
+        Ok(Tracks { tracks, total })
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        token: &str,
+    ) -> Result<T, Box<dyn Error + Send + Sync + 'static>> {
+        let request = Request::get(url)
+            .header(AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())?;
+
+        let response = self.client.request(request).await?;
+        let status = response.status();
+        let body = body::to_bytes(response.into_body()).await?;
+
+        if !status.is_success() {
+            return Err(format!("Spotify responded with {}", status).into());
+        }
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// The current access token, fetched again once it's about to expire.
+    async fn access_token(&self) -> Result<String, Box<dyn Error + Send + Sync + 'static>> {
+        let mut token = self.token.lock().await;
+
+        if let Some((access_token, expires)) = &*token {
+            if Instant::now() < *expires {
+                return Ok(access_token.clone());
+            }
+        }
+
+        let fetched = self
+            .request_token("grant_type=client_credentials".to_string())
+            .await?;
+        *token = Some((
+            fetched.access_token.clone(),
+            Instant::now() + fetched.lifetime(),
+        ));
+
+        Ok(fetched.access_token)
+    }
+
+    /// Asks the accounts service for a token, as the app.
+    async fn request_token(
+        &self,
+        form: String,
+    ) -> Result<Token, Box<dyn Error + Send + Sync + 'static>> {
+        let credentials = base64::encode(format!(
+            "{}:{}",
+            self.credentials.client_id, self.credentials.client_secret
+        ));
+        let request = Request::post(TOKEN_URL)
+            .header(AUTHORIZATION, format!("Basic {}", credentials))
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form))?;
+
+        let response = self.client.request(request).await?;
+        let status = response.status();
+        let body = body::to_bytes(response.into_body()).await?;
+
+        if !status.is_success() {
+            return Err(format!("Spotify refused the credentials with {}", status).into());
+        }
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn searches_for_the_first_artist() {
+        let track: SpotifyTrack = serde_json::from_str(
+            r#"{"name": "Song", "artists": [{"name": "Artist"}, {"name": "Guest"}], "duration_ms": 200000}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            track.entry(),
+            SearchResult {
+                title: "Artist - Song".to_string(),
+                url: "ytsearch1:Artist - Song".to_string(),
+                duration: Some(Duration::from_secs(200)),
+            }
+        );
+    }
+
+    #[test]
+    fn authorize_urls_carry_the_state() {
+        let spotify = Spotify::new(SpotifyCredentials {
+            client_id: "id".to_string(),
+            client_secret: "secret".to_string(),
+            redirect_uri: Some("https://bot.example.com/spotify/callback".to_string()),
+        });
+
+        let url = spotify.authorize_url("abc").unwrap();
+        let query = url
+            .strip_prefix("https://accounts.spotify.com/authorize?")
+            .unwrap();
+        let pairs: Vec<_> = form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect();
+
+        assert!(pairs.contains(&("state".to_string(), "abc".to_string())));
+        assert!(pairs.contains(&(
+            "redirect_uri".to_string(),
+            "https://bot.example.com/spotify/callback".to_string()
+        )));
+        assert!(!url.contains("secret"));
+
+        let unlinkable = Spotify::new(SpotifyCredentials {
+            redirect_uri: None,
+            ..spotify.credentials.clone()
+        });
+        assert_eq!(unlinkable.authorize_url("abc"), None);
+    }
+
+    #[test]
+    fn skips_playlist_entries_without_a_track() {
+        let page: Page<PlaylistItem> = serde_json::from_str(
+            r#"{
+                "items": [{"track": {"name": "Song", "artists": []}}, {"track": null}],
+                "next": "https://api.spotify.com/v1/playlists/1/tracks?offset=100",
+                "total": 150
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(page.total, 150);
+        assert!(page.next.is_some());
+        assert_eq!(
+            page.items
+                .into_iter()
+                .filter_map(|item| item.track)
+                .map(|track| track.entry().url)
+                .collect::<Vec<_>>(),
+            ["ytsearch1:Song"]
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{secrets::Secrets, storage::Storage, State};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use twilight_model::id::UserId;
+
+/// How long the URL `j/spotify link` sends stays good for.
+pub const LINK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Spotify accounts users have linked with `j/spotify link`, so the bot
+/// can read their private playlists and Liked Songs.
+///
+/// Each account's refresh token is sealed with [`Secrets`] and saved to
+/// `spotify_links.json`; the access tokens fetched with them are only
+/// kept in memory. Without `SECRETS_KEY` nothing can be linked.
+#[derive(Default)]
+pub struct SpotifyLinks {
+    storage: Storage,
+    secrets: Option<Secrets>,
+    /// Sealed refresh tokens, by who linked them.
+    refresh_tokens: Mutex<HashMap<UserId, String>>,
+    /// Access tokens, with when each stops working.
+    access_tokens: Mutex<HashMap<UserId, (String, Instant)>>,
+    /// The `state` each outstanding authorization URL carries, with who
+    /// it was sent to and when.
+    pending: Mutex<HashMap<String, (UserId, Instant)>>,
+}
+
+// Written out so no token, sealed or not, ends up in a log line.
+impl fmt::Debug for SpotifyLinks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpotifyLinks")
+            .field("enabled", &self.secrets.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SpotifyLinks {
+    pub fn load(
+        storage: &Storage,
+        secrets: Option<Secrets>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(Self {
+            storage: storage.clone(),
+            secrets,
+            refresh_tokens: Mutex::new(storage.load("spotify_links.json")?),
+            access_tokens: Default::default(),
+            pending: Default::default(),
+        })
+    }
+
+    /// Whether there's a key to seal tokens with.
+    pub fn is_enabled(&self) -> bool {
+        self.secrets.is_some()
+    }
+
+    /// A new `state` for an authorization URL sent to `user_id`, which
+    /// [`finish`](Self::finish) takes back once. Any that ran out are
+    /// dropped.
+    pub fn begin(&self, user_id: UserId, now: Instant) -> String {
+        let oauth_state = format!("{:032x}", rand::random::<u128>());
+        let mut pending = self.pending.lock().unwrap();
+
+        pending.retain(|_, (_, sent)| now.saturating_duration_since(*sent) < LINK_TIMEOUT);
+        pending.insert(oauth_state.clone(), (user_id, now));
+
+        oauth_state
+    }
+
+    /// Who the authorization URL carrying `oauth_state` was sent to, if it
+    /// hasn't been used or run out.
+    #[cfg(feature = "overlay")]
+    pub fn finish(&self, oauth_state: &str, now: Instant) -> Option<UserId> {
+        let (user_id, sent) = self.pending.lock().unwrap().remove(oauth_state)?;
+
+        if now.saturating_duration_since(sent) < LINK_TIMEOUT {
+            Some(user_id)
+        } else {
+            None
+        }
+    }
+
+    /// Seals and saves `refresh_token` as `user_id`'s, replacing any they
+    /// had.
+    pub fn link(
+        &self,
+        user_id: UserId,
+        refresh_token: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let secrets = self.secrets.as_ref().ok_or("SECRETS_KEY is not set")?;
+        let mut refresh_tokens = self.refresh_tokens.lock().unwrap();
+
+        refresh_tokens.insert(user_id, secrets.seal(refresh_token));
+
+        self.storage.save("spotify_links.json", &*refresh_tokens)
+    }
+
+    /// Forgets `user_id`'s account, returning whether they had linked one.
+    pub fn unlink(&self, user_id: UserId) -> Result<bool, Box<dyn Error + Send + Sync + 'static>> {
+        self.access_tokens.lock().unwrap().remove(&user_id);
+        let mut refresh_tokens = self.refresh_tokens.lock().unwrap();
+
+        if refresh_tokens.remove(&user_id).is_none() {
+            return Ok(false);
+        }
+
+        self.storage.save("spotify_links.json", &*refresh_tokens)?;
+
+        Ok(true)
+    }
+
+    /// `user_id`'s refresh token, opened again, or `None` if they haven't
+    /// linked an account.
+    pub fn refresh_token(
+        &self,
+        user_id: UserId,
+    ) -> Result<Option<String>, Box<dyn Error + Send + Sync + 'static>> {
+        let sealed = match self.refresh_tokens.lock().unwrap().get(&user_id) {
+            Some(sealed) => sealed.clone(),
+            None => return Ok(None),
+        };
+        let secrets = self.secrets.as_ref().ok_or("SECRETS_KEY is not set")?;
+
+        Ok(Some(secrets.open(&sealed)?))
+    }
+
+    fn cached(&self, user_id: UserId, now: Instant) -> Option<String> {
+        match self.access_tokens.lock().unwrap().get(&user_id) {
+            Some((token, expires)) if now < *expires => Some(token.clone()),
+            _ => None,
+        }
+    }
+
+    fn cache(&self, user_id: UserId, token: String, expires: Instant) {
+        self.access_tokens
+            .lock()
+            .unwrap()
+            .insert(user_id, (token, expires));
+    }
+}
+
+/// An access token for `user_id`'s linked account, refreshed once the last
+/// one runs out, or `None` if they haven't linked one.
+pub async fn access_token(
+    state: &State,
+    user_id: UserId,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync + 'static>> {
+    let links = &state.spotify_links;
+
+    if let Some(token) = links.cached(user_id, state.clock.instant()) {
+        return Ok(Some(token));
+    }
+
+    let (spotify, refresh_token) = match (&state.spotify, links.refresh_token(user_id)?) {
+        (Some(spotify), Some(refresh_token)) => (spotify, refresh_token),
+        _ => return Ok(None),
+    };
+
+    let fetched = spotify.refresh(&refresh_token).await?;
+    // Spotify may hand out a new refresh token in place of the old one.
+    if let Some(refresh_token) = &fetched.refresh_token {
+        links.link(user_id, refresh_token)?;
+    }
+    links.cache(
+        user_id,
+        fetched.access_token.clone(),
+        state.clock.instant() + fetched.lifetime(),
+    );
+
+    Ok(Some(fetched.access_token))
+}
+
+/// Links the account of whoever the authorization URL carrying
+/// `oauth_state` was sent to, with the `code` Spotify sent them back with.
+#[cfg(feature = "overlay")]
+pub async fn callback(
+    state: &State,
+    oauth_state: &str,
+    code: &str,
+) -> Result<UserId, Box<dyn Error + Send + Sync + 'static>> {
+    let links = &state.spotify_links;
+    let spotify = state.spotify.as_ref().ok_or("no Spotify app is set up")?;
+    let user_id = links
+        .finish(oauth_state, state.clock.instant())
+        .ok_or("the link was already used or has run out")?;
+
+    let fetched = spotify.authorize(code).await?;
+    let refresh_token = fetched
+        .refresh_token
+        .as_deref()
+        .ok_or("Spotify sent no refresh token")?;
+    links.link(user_id, refresh_token)?;
+    links.cache(
+        user_id,
+        fetched.access_token.clone(),
+        state.clock.instant() + fetched.lifetime(),
+    );
+
+    Ok(user_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Memory;
+
+    const USER: UserId = UserId(1);
+
+    fn links(storage: &Storage) -> SpotifyLinks {
+        SpotifyLinks::load(storage, Some(Secrets::new(&[1; 32]).unwrap())).unwrap()
+    }
+
+    #[cfg(feature = "overlay")]
+    #[test]
+    fn authorization_urls_work_once() {
+        let links = links(&Storage::default());
+        let now = Instant::now();
+
+        let oauth_state = links.begin(USER, now);
+        assert_eq!(links.finish("guessed", now), None);
+        assert_eq!(links.finish(&oauth_state, now), Some(USER));
+        assert_eq!(links.finish(&oauth_state, now), None);
+
+        let late = links.begin(USER, now);
+        assert_eq!(links.finish(&late, now + LINK_TIMEOUT), None);
+    }
+
+    #[test]
+    fn refresh_tokens_are_saved_sealed() {
+        let storage = Storage::with_backend(Memory::default());
+        links(&storage).link(USER, "refresh-token").unwrap();
+
+        let saved: HashMap<UserId, String> = storage.load("spotify_links.json").unwrap();
+        assert!(!saved[&USER].contains("refresh-token"));
+
+        let reloaded = links(&storage);
+        assert_eq!(
+            reloaded.refresh_token(USER).unwrap().as_deref(),
+            Some("refresh-token")
+        );
+        assert!(reloaded.unlink(USER).unwrap());
+        assert!(!reloaded.unlink(USER).unwrap());
+        assert_eq!(links(&storage).refresh_token(USER).unwrap(), None);
+    }
+
+    #[test]
+    fn nothing_links_without_a_key() {
+        let links = SpotifyLinks::load(&Storage::default(), None).unwrap();
+
+        assert!(!links.is_enabled());
+        assert!(links.link(USER, "refresh-token").is_err());
+    }
+}
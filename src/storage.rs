@@ -0,0 +1,330 @@
+use crate::{settings::GuildSettings, snapshot::Snapshot};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use twilight_model::id::{ChannelId, GuildId, RoleId};
+
+/// Where [`Storage`] keeps its files. Each is read and written whole, so a
+/// backend only has to store named blobs of JSON.
+pub trait Backend: fmt::Debug + Send + Sync {
+    /// What was last written as `name`, or `None` if nothing has been.
+    fn read(&self, name: &str) -> Result<Option<String>, Box<dyn Error + Send + Sync + 'static>>;
+
+    /// Replaces `name` with `contents`, all at once, so a crash can't
+    /// leave half of it behind.
+    fn write(
+        &self,
+        name: &str,
+        contents: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>>;
+}
+
+/// Files in the data directory, the default.
+#[derive(Debug)]
+pub struct Files {
+    dir: PathBuf,
+}
+
+impl Files {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+}
+
+impl Backend for Files {
+    fn read(&self, name: &str) -> Result<Option<String>, Box<dyn Error + Send + Sync + 'static>> {
+        match fs::read_to_string(self.dir.join(name)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(
+        &self,
+        name: &str,
+        contents: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        fs::create_dir_all(&self.dir)?;
+
+        // Written aside and renamed over, so a crash can't leave half a file.
+        let path = self.dir.join(name);
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, contents)?;
+        fs::rename(&temporary, path)?;
+
+        Ok(())
+    }
+}
+
+/// Files kept in memory, for tests that want saved state to read back
+/// without a directory to clean up.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct Memory {
+    files: std::sync::Mutex<HashMap<String, String>>,
+}
+
+#[cfg(test)]
+impl Backend for Memory {
+    fn read(&self, name: &str) -> Result<Option<String>, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(self.files.lock().unwrap().get(name).cloned())
+    }
+
+    fn write(
+        &self,
+        name: &str,
+        contents: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), contents.to_string());
+
+        Ok(())
+    }
+}
+
+/// The JSON files that carry guild state across restarts, one per kind of
+/// state, kept by a [`Backend`].
+///
+/// Without a backend nothing is read or written, and everything only
+/// lasts until the bot stops.
+#[derive(Clone, Debug, Default)]
+pub struct Storage {
+    backend: Option<Arc<dyn Backend>>,
+}
+
+impl Storage {
+    /// Keeps files in `dir`, if there is one.
+    pub fn new(dir: Option<&Path>) -> Self {
+        Self {
+            backend: dir.map(|dir| Arc::new(Files::new(dir)) as Arc<dyn Backend>),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_backend(backend: impl Backend + 'static) -> Self {
+        Self {
+            backend: Some(Arc::new(backend)),
+        }
+    }
+
+    /// Whether anything saved outlives the process.
+    pub fn is_persistent(&self) -> bool {
+        self.backend.is_some()
+    }
+
+    /// Reads `name`, or the default if it hasn't been saved yet.
+    pub fn load<T: DeserializeOwned + Default>(
+        &self,
+        name: &str,
+    ) -> Result<T, Box<dyn Error + Send + Sync + 'static>> {
+        let backend = match &self.backend {
+            Some(backend) => backend,
+            None => return Ok(T::default()),
+        };
+
+        match backend.read(name)? {
+            Some(contents) => Ok(serde_json::from_str(&contents)?),
+            None => Ok(T::default()),
+        }
+    }
+
+    /// Replaces `name` with `value`.
+    pub fn save<T: Serialize>(
+        &self,
+        name: &str,
+        value: &T,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        match &self.backend {
+            Some(backend) => backend.write(name, &serde_json::to_string_pretty(value)?),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads `settings.json`, skipping guilds whose saved settings no
+    /// longer parse rather than refusing to start.
+    pub fn load_settings(
+        &self,
+    ) -> Result<HashMap<GuildId, GuildSettings>, Box<dyn Error + Send + Sync + 'static>> {
+        let saved: HashMap<GuildId, SavedSettings> = self.load("settings.json")?;
+        let mut loaded = HashMap::new();
+
+        for (guild_id, saved) in saved {
+            let mut settings = GuildSettings {
+                log_channel: saved.log_channel,
+                event_role: saved.event_role,
+                dj_role: saved.dj_role,
+                recording: saved.recording,
+                volume_caps: saved.volume_caps,
+                ..GuildSettings::default()
+            };
+
+            match saved.snapshot.apply(&mut settings) {
+                Ok(()) => {
+                    loaded.insert(guild_id, settings);
+                }
+                Err(why) => tracing::warn!("dropping saved settings for {}: {}", guild_id, why),
+            }
+        }
+
+        Ok(loaded)
+    }
+
+    pub fn save_settings(
+        &self,
+        settings: &HashMap<GuildId, GuildSettings>,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let saved = settings
+            .iter()
+            .map(|(guild_id, settings)| {
+                let saved = SavedSettings {
+                    snapshot: Snapshot::export(settings),
+                    log_channel: settings.log_channel,
+                    event_role: settings.event_role,
+                    dj_role: settings.dj_role,
+                    recording: settings.recording,
+                    volume_caps: settings.volume_caps.clone(),
+                };
+
+                (*guild_id, saved)
+            })
+            .collect::<HashMap<_, _>>();
+
+        self.save("settings.json", &saved)
+    }
+
+    /// One guild's settings as they're saved, with its prefix, as JSON for
+    /// `musicm8 export-guild`. `None` if it has none saved.
+    pub fn export_guild(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Option<String>, Box<dyn Error + Send + Sync + 'static>> {
+        let settings = match self.load_settings()?.remove(&guild_id) {
+            Some(settings) => settings,
+            None => return Ok(None),
+        };
+        let prefixes: HashMap<GuildId, String> = self.load("prefixes.json")?;
+
+        let saved = SavedSettings {
+            snapshot: Snapshot {
+                prefix: prefixes.get(&guild_id).cloned(),
+                ..Snapshot::export(&settings)
+            },
+            log_channel: settings.log_channel,
+            event_role: settings.event_role,
+            dj_role: settings.dj_role,
+            recording: settings.recording,
+            volume_caps: settings.volume_caps,
+        };
+
+        Ok(Some(serde_json::to_string_pretty(&saved)?))
+    }
+}
+
+/// A guild's settings as saved: the [`Snapshot`] form, plus what exports
+/// leave out because it only makes sense in the guild itself.
+#[derive(Deserialize, Serialize)]
+struct SavedSettings {
+    #[serde(flatten)]
+    snapshot: Snapshot,
+    #[serde(default)]
+    log_channel: Option<ChannelId>,
+    #[serde(default)]
+    event_role: Option<RoleId>,
+    #[serde(default)]
+    dj_role: Option<RoleId>,
+    #[serde(default)]
+    recording: bool,
+    #[serde(default)]
+    volume_caps: HashMap<RoleId, u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Announcements;
+
+    #[test]
+    fn saves_and_reloads_settings() {
+        let dir = std::env::temp_dir().join(format!("musicm8-storage-{}", rand::random::<u64>()));
+        let storage = Storage::new(Some(&dir));
+
+        let mut settings = HashMap::new();
+        settings.insert(
+            GuildId(1),
+            GuildSettings {
+                log_channel: Some(ChannelId(2)),
+                dj_role: Some(RoleId(3)),
+                recording: true,
+                announcements: Announcements::Minimal,
+                dislike_skip: Some(3),
+                volume: Some(40),
+                ..GuildSettings::default()
+            },
+        );
+        storage.save_settings(&settings).unwrap();
+
+        let reloaded = storage.load_settings().unwrap();
+        let guild = &reloaded[&GuildId(1)];
+        assert_eq!(guild.log_channel, Some(ChannelId(2)));
+        assert_eq!(guild.dj_role, Some(RoleId(3)));
+        assert!(guild.recording);
+        assert_eq!(guild.announcements, Announcements::Minimal);
+        assert_eq!(guild.dislike_skip, Some(3));
+        assert_eq!(guild.volume, Some(40));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn exports_one_guild() {
+        let storage = Storage::with_backend(Memory::default());
+        let mut settings = HashMap::new();
+        settings.insert(
+            GuildId(1),
+            GuildSettings {
+                dj_role: Some(RoleId(3)),
+                dislike_skip: Some(3),
+                ..GuildSettings::default()
+            },
+        );
+        storage.save_settings(&settings).unwrap();
+        let mut prefixes = HashMap::new();
+        prefixes.insert(GuildId(1), "!");
+        storage.save("prefixes.json", &prefixes).unwrap();
+
+        let exported: serde_json::Value =
+            serde_json::from_str(&storage.export_guild(GuildId(1)).unwrap().unwrap()).unwrap();
+        assert_eq!(exported["prefix"], "!");
+        assert_eq!(exported["dj_role"], "3");
+        assert_eq!(exported["dislike_skip"], 3);
+        assert!(storage.export_guild(GuildId(2)).unwrap().is_none());
+    }
+
+    #[test]
+    fn nothing_is_kept_without_a_directory() {
+        let storage = Storage::default();
+
+        storage.save("queues.json", &vec![1, 2, 3]).unwrap();
+        assert!(storage.load::<Vec<u8>>("queues.json").unwrap().is_empty());
+    }
+
+    #[test]
+    fn memory_keeps_what_was_saved() {
+        let storage = Storage::with_backend(Memory::default());
+
+        storage.save("queues.json", &vec![1, 2, 3]).unwrap();
+        assert_eq!(storage.load::<Vec<u8>>("queues.json").unwrap(), [1, 2, 3]);
+        assert!(storage.load::<Vec<u8>>("history.json").unwrap().is_empty());
+    }
+}
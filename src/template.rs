@@ -0,0 +1,119 @@
+use std::fmt;
+
+/// An announcement with `{placeholder}` fields, customisable per guild.
+///
+/// Placeholders are checked when the template is set, so a typo is
+/// reported to the admin instead of showing up verbatim in chat.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template(String);
+
+impl Template {
+    /// Validates `text` against the placeholders the announcement supports.
+    pub fn parse(text: &str, placeholders: &[&str]) -> Result<Self, String> {
+        let mut rest = text;
+
+        while let Some(start) = rest.find(['{', '}']) {
+            if rest[start..].starts_with('}') {
+                return Err("Unmatched `}` in template.".to_string());
+            }
+
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| "Unmatched `{` in template.".to_string())?;
+            let name = &rest[start + 1..start + end];
+
+            if !placeholders.contains(&name) {
+                return Err(format!(
+                    "Unknown placeholder `{{{}}}`, expected one of: {}.",
+                    name,
+                    placeholders
+                        .iter()
+                        .map(|name| format!("`{{{}}}`", name))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+
+            rest = &rest[start + end + 1..];
+        }
+
+        Ok(Self(text.to_string()))
+    }
+
+    /// Fills in every placeholder in one pass, so braces inside values are
+    /// left alone.
+    pub fn render(&self, values: &[(&str, &str)]) -> String {
+        let mut rendered = String::new();
+        let mut rest = self.0.as_str();
+
+        while let Some(start) = rest.find('{') {
+            // `parse` guarantees every `{` is closed.
+            let end = start + rest[start..].find('}').unwrap();
+            let name = &rest[start + 1..end];
+
+            rendered.push_str(&rest[..start]);
+            rendered.push_str(
+                values
+                    .iter()
+                    .find(|(key, _)| *key == name)
+                    .map_or("", |(_, value)| value),
+            );
+
+            rest = &rest[end + 1..];
+        }
+
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLACEHOLDERS: &[&str] = &["title", "artist"];
+
+    #[test]
+    fn renders_placeholders() {
+        let template =
+            Template::parse("🎶 Now spinning: {title} by {artist}", PLACEHOLDERS).unwrap();
+
+        assert_eq!(
+            template.render(&[("title", "Song"), ("artist", "Artist")]),
+            "🎶 Now spinning: Song by Artist"
+        );
+    }
+
+    #[test]
+    fn values_are_not_expanded_again() {
+        let template = Template::parse("{title} by {artist}", PLACEHOLDERS).unwrap();
+
+        assert_eq!(
+            template.render(&[("title", "{artist}"), ("artist", "Artist")]),
+            "{artist} by Artist"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        assert_eq!(
+            Template::parse("Now: {titel}", PLACEHOLDERS),
+            Err(
+                "Unknown placeholder `{titel}`, expected one of: `{title}`, `{artist}`."
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_unbalanced_braces() {
+        assert!(Template::parse("Now: {title", PLACEHOLDERS).is_err());
+        assert!(Template::parse("Now: title}", PLACEHOLDERS).is_err());
+    }
+}
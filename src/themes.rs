@@ -0,0 +1,242 @@
+use crate::{storage::Storage, State};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use twilight_model::id::{GuildId, UserId};
+
+pub const DEFAULT_LENGTH: Duration = Duration::from_secs(5);
+pub const MAX_LENGTH: Duration = Duration::from_secs(10);
+/// How long after a theme plays before the same user's plays again.
+pub const COOLDOWN: Duration = Duration::from_secs(10 * 60);
+/// The main track's volume is scaled by this while a theme plays.
+const DUCK: f32 = 0.3;
+
+/// A clip played when its owner joins the bot's voice channel.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Theme {
+    pub url: String,
+    pub start: Duration,
+    pub length: Duration,
+}
+
+/// Members' themes, saved to `themes.json` whenever one changes.
+#[derive(Debug, Default)]
+pub struct Themes {
+    storage: Storage,
+    themes: Mutex<HashMap<GuildId, HashMap<UserId, Theme>>>,
+    last_played: Mutex<HashMap<(GuildId, UserId), Instant>>,
+    /// Guilds with a theme playing right now; only one plays at a time so
+    /// ducks don't stack.
+    playing: Mutex<HashSet<GuildId>>,
+}
+
+impl Themes {
+    pub fn load(storage: &Storage) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(Self {
+            storage: storage.clone(),
+            themes: Mutex::new(storage.load("themes.json")?),
+            ..Self::default()
+        })
+    }
+
+    pub fn set(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        theme: Theme,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let mut themes = self.themes.lock().unwrap();
+        themes.entry(guild_id).or_default().insert(user_id, theme);
+
+        self.storage.save("themes.json", &*themes)
+    }
+
+    /// Clears the user's theme, returning whether they had one.
+    pub fn clear(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<bool, Box<dyn Error + Send + Sync + 'static>> {
+        let mut themes = self.themes.lock().unwrap();
+
+        let cleared = match themes.get_mut(&guild_id) {
+            Some(guild) => guild.remove(&user_id).is_some(),
+            None => false,
+        };
+        if !cleared {
+            return Ok(false);
+        }
+        themes.retain(|_, guild| !guild.is_empty());
+
+        self.storage.save("themes.json", &*themes)?;
+        Ok(true)
+    }
+
+    pub fn get(&self, guild_id: GuildId, user_id: UserId) -> Option<Theme> {
+        self.themes
+            .lock()
+            .unwrap()
+            .get(&guild_id)?
+            .get(&user_id)
+            .cloned()
+    }
+
+    /// The user's theme if it may play now, marking the guild as playing
+    /// one and starting the user's cooldown.
+    pub fn claim(&self, guild_id: GuildId, user_id: UserId, now: Instant) -> Option<Theme> {
+        let theme = self.get(guild_id, user_id)?;
+
+        let mut last_played = self.last_played.lock().unwrap();
+        if let Some(last) = last_played.get(&(guild_id, user_id)) {
+            if now.saturating_duration_since(*last) < COOLDOWN {
+                return None;
+            }
+        }
+
+        if !self.playing.lock().unwrap().insert(guild_id) {
+            return None;
+        }
+
+        last_played.insert((guild_id, user_id), now);
+        Some(theme)
+    }
+
+    pub fn finished(&self, guild_id: GuildId) {
+        self.playing.lock().unwrap().remove(&guild_id);
+    }
+}
+
+/// Plays the user's theme over the current track, if themes are enabled
+/// in the guild and the user's isn't cooling down.
+pub async fn play(
+    state: &State,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let enabled = state
+        .settings
+        .read()
+        .await
+        .get(&guild_id)
+        .is_some_and(|settings| settings.themes);
+
+    if !enabled {
+        return Ok(());
+    }
+
+    let theme = match state.themes.claim(guild_id, user_id, state.clock.instant()) {
+        Some(theme) => theme,
+        None => return Ok(()),
+    };
+
+    let result = duck_under(state, guild_id, &theme).await;
+    state.themes.finished(guild_id);
+
+    result
+}
+
+async fn duck_under(
+    state: &State,
+    guild_id: GuildId,
+    theme: &Theme,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let call_lock = match state.songbird.get(guild_id) {
+        Some(call_lock) => call_lock,
+        None => return Ok(()),
+    };
+
+    let input = state.resolver.resolve(&theme.url).await?;
+
+    let main = state.trackdata.read().await.get(&guild_id).cloned();
+    let main_volume = match &main {
+        Some(main) => Some(main.get_info().await?.volume),
+        None => None,
+    };
+
+    let clip = call_lock.lock().await.play_source(input);
+    if theme.start > Duration::default() {
+        clip.seek_time(theme.start)?;
+    }
+
+    if let (Some(main), Some(volume)) = (&main, main_volume) {
+        main.set_volume(volume * DUCK)?;
+    }
+
+    state.clock.sleep(theme.length).await;
+
+    // The clip may have been shorter than its length.
+    let _ = clip.stop();
+
+    if let (Some(main), Some(volume)) = (&main, main_volume) {
+        // Ignored if the main track ended while the theme played.
+        let _ = main.set_volume(volume);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD: GuildId = GuildId(1);
+
+    fn themes() -> Themes {
+        let themes = Themes::default();
+        for user in 1..=2 {
+            themes
+                .set(
+                    GUILD,
+                    UserId(user),
+                    Theme {
+                        url: "https://example.com/theme".to_string(),
+                        start: Duration::default(),
+                        length: DEFAULT_LENGTH,
+                    },
+                )
+                .unwrap();
+        }
+        themes
+    }
+
+    #[test]
+    fn cooldown_per_user() {
+        let themes = themes();
+        let now = Instant::now();
+
+        assert!(themes.claim(GUILD, UserId(1), now).is_some());
+        themes.finished(GUILD);
+
+        assert!(themes.claim(GUILD, UserId(1), now + COOLDOWN / 2).is_none());
+        assert!(themes.claim(GUILD, UserId(2), now + COOLDOWN / 2).is_some());
+        themes.finished(GUILD);
+
+        assert!(themes.claim(GUILD, UserId(1), now + COOLDOWN).is_some());
+    }
+
+    #[test]
+    fn one_theme_at_a_time() {
+        let themes = themes();
+        let now = Instant::now();
+
+        assert!(themes.claim(GUILD, UserId(1), now).is_some());
+        assert!(themes.claim(GUILD, UserId(2), now).is_none());
+
+        // The refused user isn't put on cooldown.
+        themes.finished(GUILD);
+        assert!(themes.claim(GUILD, UserId(2), now).is_some());
+    }
+
+    #[test]
+    fn users_without_themes() {
+        let themes = themes();
+
+        assert!(themes.clear(GUILD, UserId(1)).unwrap());
+        assert!(!themes.clear(GUILD, UserId(1)).unwrap());
+        assert!(themes.claim(GUILD, UserId(1), Instant::now()).is_none());
+    }
+}
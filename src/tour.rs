@@ -0,0 +1,234 @@
+use crate::{storage::Storage, State};
+use std::{collections::HashSet, error::Error, sync::Mutex};
+use twilight_model::{
+    application::{
+        callback::{CallbackData, InteractionResponse},
+        component::{button::ButtonStyle, ActionRow, Button, Component},
+        interaction::MessageComponentInteraction,
+    },
+    channel::message::MessageFlags,
+    guild::Guild,
+    id::UserId,
+};
+
+/// The tour, a step at a time. Each is shown only to whoever is taking it.
+const STEPS: &[&str] = &[
+    "**Playing music**\n\
+     Join a voice channel and try queuing a song now with `{prefix}play <url or search>`. \
+     YouTube, SoundCloud, Bandcamp and Spotify links work, and so does an audio file \
+     attached to the command.",
+    "**The queue**\n\
+     `{prefix}queue` lists what's coming up. `{prefix}voteskip` asks the channel to skip a track, \
+     and `{prefix}loop`, `{prefix}shuffle`, `{prefix}remove` and `{prefix}move` rearrange what's left.",
+    "**Rating tracks**\n\
+     React 👍 or 👎 on a now playing message to rate the track. `{prefix}top` shows the \
+     server's favourites.",
+    "**Making it yours**\n\
+     `{prefix}theme set <url>` plays a clip when you join a call. Admins can change how I \
+     behave here with `{prefix}settings` and `{prefix}prefix`.",
+];
+
+const FINISHED: &str = "That's the tour! Run `{prefix}tour` whenever you want to see it again.";
+
+const ALREADY_TAKEN: &str =
+    "You've taken the tour already. Run `{prefix}tour` if you'd like to see it again.";
+
+/// The button `j/tour` and the join greeting post to start the tour.
+/// `j/tour`'s starts it `again` for anyone; the greeting's turns away
+/// members who have finished it, so it's only ever taken once from there.
+pub fn start_button(again: bool) -> Component {
+    Component::ActionRow(ActionRow {
+        components: vec![button(
+            if again { "tour:again" } else { "tour:start" },
+            "Take the tour",
+            ButtonStyle::Primary,
+        )],
+    })
+}
+
+fn button(custom_id: &str, label: &str, style: ButtonStyle) -> Component {
+    Component::Button(Button {
+        custom_id: Some(custom_id.to_string()),
+        disabled: false,
+        emoji: None,
+        label: Some(label.to_string()),
+        style,
+        url: None,
+    })
+}
+
+/// Members who have finished the tour, saved to `tours.json` whenever one
+/// does.
+#[derive(Debug, Default)]
+pub struct Tours {
+    storage: Storage,
+    finished: Mutex<HashSet<UserId>>,
+}
+
+impl Tours {
+    pub fn load(storage: &Storage) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(Self {
+            storage: storage.clone(),
+            finished: Mutex::new(storage.load("tours.json")?),
+        })
+    }
+
+    pub fn has_finished(&self, user_id: UserId) -> bool {
+        self.finished.lock().unwrap().contains(&user_id)
+    }
+
+    fn finish(&self, user_id: UserId) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let mut finished = self.finished.lock().unwrap();
+
+        if finished.insert(user_id) {
+            self.storage.save("tours.json", &*finished)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a button press belongs to the tour, which handles it whenever
+/// it comes rather than waiting for it.
+pub fn is_tour_button(press: &MessageComponentInteraction) -> bool {
+    press.data.custom_id.starts_with("tour:")
+}
+
+/// Answers a press of one of the tour's buttons: starting it in a message
+/// only the presser sees, moving it on a step, or finishing it.
+pub async fn press(
+    state: &State,
+    press: &MessageComponentInteraction,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let user_id = match press.author_id() {
+        Some(user_id) => user_id,
+        None => return Ok(()),
+    };
+
+    // The tour names commands with the prefix of the guild it's taken in.
+    let prefix = press.guild_id.map_or_else(
+        || state.config.prefix.clone(),
+        |guild_id| state.prefix(guild_id),
+    );
+
+    let (content, components, update) = match press.data.custom_id.as_str() {
+        "tour:start" if state.tours.has_finished(user_id) => (
+            ALREADY_TAKEN.replace("{prefix}", &prefix),
+            Vec::new(),
+            false,
+        ),
+        "tour:start" | "tour:again" => (step_content(0, &prefix), step_buttons(0), false),
+        "tour:done" => {
+            state.tours.finish(user_id)?;
+            (FINISHED.replace("{prefix}", &prefix), Vec::new(), true)
+        }
+        custom_id => match custom_id
+            .strip_prefix("tour:")
+            .and_then(|step| step.parse::<usize>().ok())
+            .filter(|&step| step < STEPS.len())
+        {
+            Some(step) => (step_content(step, &prefix), step_buttons(step), true),
+            None => return Ok(()),
+        },
+    };
+
+    let data = CallbackData {
+        allowed_mentions: None,
+        components: Some(components),
+        content: Some(content),
+        embeds: Vec::new(),
+        flags: Some(MessageFlags::EPHEMERAL),
+        tts: None,
+    };
+    // Later steps replace the one before in the same private message.
+    let response = if update {
+        InteractionResponse::UpdateMessage(data)
+    } else {
+        InteractionResponse::ChannelMessageWithSource(data)
+    };
+
+    state
+        .http
+        .interaction_callback(press.id, &press.token, &response)
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+fn step_content(step: usize, prefix: &str) -> String {
+    format!(
+        "Tour, step {} of {}\n\n{}",
+        step + 1,
+        STEPS.len(),
+        STEPS[step].replace("{prefix}", prefix)
+    )
+}
+
+/// The button on to the step after `step`, or to finish after the last.
+fn step_buttons(step: usize) -> Vec<Component> {
+    let next = if step + 1 == STEPS.len() {
+        button("tour:done", "Finish", ButtonStyle::Success)
+    } else {
+        button(&format!("tour:{}", step + 1), "Next", ButtonStyle::Primary)
+    };
+
+    vec![Component::ActionRow(ActionRow {
+        components: vec![next],
+    })]
+}
+
+/// Says hello in a guild the bot has just been added to, in its system
+/// channel if it has one, offering the tour.
+pub async fn greet(
+    state: &State,
+    guild: &Guild,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let channel_id = match guild.system_channel_id {
+        Some(channel_id) => channel_id,
+        None => return Ok(()),
+    };
+
+    let content = format!(
+        "Thanks for having me! Join a voice channel and use `{}play <url or search>` to \
+         get the music going. New here? Take the tour to see what else I can do.",
+        state.prefix(guild.id)
+    );
+
+    state
+        .http
+        .create_message(channel_id)
+        .content(&content)?
+        .components(&[start_button(false)])?
+        .exec()
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn next_id(step: usize) -> Option<String> {
+        match step_buttons(step).pop()? {
+            Component::ActionRow(mut row) => match row.components.pop()? {
+                Component::Button(button) => button.custom_id,
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn steps_lead_on_to_the_finish() {
+        let custom_ids = (0..STEPS.len()).map(next_id).collect::<Vec<_>>();
+
+        assert_eq!(
+            custom_ids,
+            ["tour:1", "tour:2", "tour:3", "tour:done"].map(|id| Some(id.to_string()))
+        );
+        assert!(step_content(1, "!").starts_with("Tour, step 2 of 4\n\n**The queue**"));
+        assert!(step_content(1, "!").contains("`!queue` lists what's coming up."));
+    }
+}
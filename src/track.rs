@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use songbird::{input::Metadata, tracks::TrackHandle};
+use std::time::Duration;
+use twilight_model::id::UserId;
+
+/// Shown in place of a title or artist the source didn't report.
+pub const UNKNOWN: &str = "<UNKNOWN>";
+
+/// What's known about a track, whichever resolver it came from.
+///
+/// Songbird's [`Metadata`] is read in [`from_metadata`](Self::from_metadata)
+/// and nowhere else, so announcements, the queue, hooks and webhooks all
+/// agree on what a track is called.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TrackInfo {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub duration: Option<Duration>,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    /// Where the track can be resolved from again.
+    #[serde(default)]
+    pub source_url: Option<String>,
+    #[serde(default)]
+    pub requester: Option<UserId>,
+    /// Has no known end, as with radio streams and livestreams.
+    #[serde(default)]
+    pub live: bool,
+    /// The part of the source that is this track, when it's one chapter
+    /// of a longer upload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segment: Option<Segment>,
+}
+
+/// Where a track starts and stops in its source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Segment {
+    pub start: Duration,
+    pub end: Duration,
+}
+
+impl TrackInfo {
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        Self {
+            // youtube-dl reports a music video's song as `track`, but the
+            // video title is what people searched for.
+            title: metadata.title.clone().or_else(|| metadata.track.clone()),
+            artist: metadata.artist.clone(),
+            duration: metadata.duration,
+            thumbnail: metadata.thumbnail.clone(),
+            source_url: metadata.source_url.clone(),
+            requester: None,
+            live: metadata.duration.is_none(),
+            segment: None,
+        }
+    }
+
+    /// The info the driver was given for `handle`. Who requested it isn't
+    /// part of that; see `StateRef::now_playing`.
+    pub fn of(handle: &TrackHandle) -> Self {
+        Self::from_metadata(handle.metadata())
+    }
+
+    pub fn requested_by(mut self, requester: UserId) -> Self {
+        self.requester = Some(requester);
+        self
+    }
+
+    pub fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or(UNKNOWN)
+    }
+
+    pub fn artist(&self) -> &str {
+        self.artist.as_deref().unwrap_or(UNKNOWN)
+    }
+
+    pub fn source_url(&self) -> &str {
+        self.source_url.as_deref().unwrap_or_default()
+    }
+
+    /// How far into the track `position`, the driver's position in the
+    /// source, is.
+    pub fn elapsed(&self, position: Duration) -> Duration {
+        match self.segment {
+            Some(segment) => position.saturating_sub(segment.start),
+            None => position,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_songbird_metadata() {
+        let info = TrackInfo::from_metadata(&Metadata {
+            track: Some("Song".to_string()),
+            artist: Some("Artist".to_string()),
+            duration: Some(Duration::from_secs(200)),
+            source_url: Some("https://example.com/song".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(info.title(), "Song");
+        assert_eq!(info.artist(), "Artist");
+        assert_eq!(info.source_url(), "https://example.com/song");
+        assert!(!info.live);
+
+        let stream = TrackInfo::from_metadata(&Metadata::default());
+        assert_eq!(stream.title(), UNKNOWN);
+        assert!(stream.live);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::State;
+use flate2::{write::ZlibEncoder, Compression};
+use std::{
+    error::Error,
+    io::{Read, Write},
+    time::Duration,
+};
+use tokio::task;
+
+pub const WIDTH: u32 = 480;
+pub const HEIGHT: u32 = 120;
+/// How much of the track after the current position is drawn.
+pub const SEGMENT: Duration = Duration::from_secs(6);
+
+const BACKGROUND: [u8; 3] = [0x20, 0x22, 0x25];
+const WAVE: [u8; 3] = [0x58, 0x65, 0xf2];
+
+/// Decodes [`SEGMENT`] of the track at `url` from `position` and renders
+/// its waveform as a PNG.
+///
+/// The track is resolved again rather than tapped from the driver, so the
+/// playing track isn't disturbed; reading happens on a blocking thread
+/// since sources read from child processes.
+pub async fn snapshot(
+    state: &State,
+    url: &str,
+    position: Duration,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync + 'static>> {
+    let mut input = state.resolver.resolve(url).await?;
+
+    task::spawn_blocking(move || {
+        if position > Duration::default() {
+            input
+                .seek_time(position)
+                .ok_or("couldn't seek in the track")?;
+        }
+
+        let channels = if input.is_stereo() { 2 } else { 1 };
+        let mut bytes = vec![0; 48_000 * channels * SEGMENT.as_secs() as usize * 4];
+        let mut read = 0;
+
+        while read < bytes.len() {
+            match input.read(&mut bytes[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+
+        let samples = bytes[..read]
+            .chunks_exact(4 * channels)
+            .map(|frame| {
+                frame
+                    .chunks_exact(4)
+                    .map(|sample| f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]))
+                    .sum::<f32>()
+                    / channels as f32
+            })
+            .collect::<Vec<_>>();
+
+        Ok(waveform_png(&samples))
+    })
+    .await?
+}
+
+/// Draws the peak envelope of mono `samples` as a [`WIDTH`]×[`HEIGHT`]
+/// RGB PNG.
+pub fn waveform_png(samples: &[f32]) -> Vec<u8> {
+    let per_column = (samples.len() as f32 / WIDTH as f32).max(1.0);
+    let middle = HEIGHT as f32 / 2.0;
+
+    let columns = (0..WIDTH)
+        .map(|x| {
+            let start = (x as f32 * per_column) as usize;
+            let end = (((x + 1) as f32 * per_column) as usize).min(samples.len());
+            let peak = samples
+                .get(start..end)
+                .unwrap_or_default()
+                .iter()
+                .fold(0.0f32, |peak, sample| peak.max(sample.abs()))
+                .min(1.0);
+
+            // Always at least a line, so silence still shows something.
+            (peak * middle).max(0.5)
+        })
+        .collect::<Vec<_>>();
+
+    let mut pixels = Vec::with_capacity(((WIDTH * 3 + 1) * HEIGHT) as usize);
+
+    for y in 0..HEIGHT {
+        // Every scanline starts with filter type 0 (none).
+        pixels.push(0);
+
+        let distance = (y as f32 + 0.5 - middle).abs();
+        for peak in &columns {
+            pixels.extend_from_slice(if distance <= *peak {
+                &WAVE
+            } else {
+                &BACKGROUND
+            });
+        }
+    }
+
+    encode_png(WIDTH, HEIGHT, &pixels)
+}
+
+/// Wraps filtered RGB scanlines in a minimal PNG.
+fn encode_png(width: u32, height: u32, scanlines: &[u8]) -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8-bit truecolour, default compression and filtering, no interlace.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    chunk(&mut png, b"IHDR", &header);
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(scanlines)
+        .expect("writing to a Vec can't fail");
+    chunk(
+        &mut png,
+        b"IDAT",
+        &encoder.finish().expect("writing to a Vec can't fail"),
+    );
+
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::ZlibDecoder;
+
+    /// The chunks of a PNG as (type, data), checking each CRC.
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let kind = [rest[4], rest[5], rest[6], rest[7]];
+            let data = rest[8..8 + len].to_vec();
+            let crc = &rest[8 + len..12 + len];
+
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(&kind);
+            hasher.update(&data);
+            assert_eq!(crc, hasher.finalize().to_be_bytes());
+
+            chunks.push((kind, data));
+            rest = &rest[12 + len..];
+        }
+
+        chunks
+    }
+
+    fn decode(png: &[u8]) -> Vec<u8> {
+        let chunks = chunks(png);
+        assert_eq!(&chunks[0].0, b"IHDR");
+        assert_eq!(&chunks[0].1[..8], &[0, 0, 1, 0xe0, 0, 0, 0, 120]);
+        assert_eq!(&chunks[2].0, b"IEND");
+
+        let mut scanlines = Vec::new();
+        ZlibDecoder::new(&chunks[1].1[..])
+            .read_to_end(&mut scanlines)
+            .unwrap();
+        scanlines
+    }
+
+    fn pixel(scanlines: &[u8], x: u32, y: u32) -> [u8; 3] {
+        let offset = (y * (WIDTH * 3 + 1) + 1 + x * 3) as usize;
+        [
+            scanlines[offset],
+            scanlines[offset + 1],
+            scanlines[offset + 2],
+        ]
+    }
+
+    #[test]
+    fn silence_is_a_flat_line() {
+        let scanlines = decode(&waveform_png(&[0.0; 48_000]));
+
+        assert_eq!(scanlines.len(), ((WIDTH * 3 + 1) * HEIGHT) as usize);
+        assert_eq!(pixel(&scanlines, 10, HEIGHT / 2), WAVE);
+        assert_eq!(pixel(&scanlines, 10, HEIGHT / 2 - 2), BACKGROUND);
+        assert_eq!(pixel(&scanlines, 10, 0), BACKGROUND);
+    }
+
+    #[test]
+    fn loud_samples_fill_the_column() {
+        let mut samples = vec![0.0; WIDTH as usize * 10];
+        samples[5] = -1.0;
+
+        let scanlines = decode(&waveform_png(&samples));
+
+        assert_eq!(pixel(&scanlines, 0, 0), WAVE);
+        assert_eq!(pixel(&scanlines, 0, HEIGHT - 1), WAVE);
+        assert_eq!(pixel(&scanlines, 1, 0), BACKGROUND);
+    }
+}
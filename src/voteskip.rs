@@ -0,0 +1,66 @@
+use songbird::tracks::TrackHandle;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+use twilight_model::id::{GuildId, UserId};
+
+/// The share of listeners that must vote when a guild hasn't set one.
+pub const DEFAULT_PERCENT: u32 = 50;
+
+/// Who has voted with `j/voteskip` to skip each guild's current track.
+///
+/// Votes belong to the track they were cast against, so they start over
+/// as soon as another track is playing.
+#[derive(Debug, Default)]
+pub struct VoteSkips {
+    votes: Mutex<HashMap<GuildId, (TrackHandle, HashSet<UserId>)>>,
+}
+
+impl VoteSkips {
+    /// Adds `voter`'s vote against `track`, returning whether it's a new
+    /// one and everyone who has voted against it so far.
+    pub fn vote(
+        &self,
+        guild_id: GuildId,
+        track: &TrackHandle,
+        voter: UserId,
+    ) -> (bool, HashSet<UserId>) {
+        let mut votes = self.votes.lock().unwrap();
+        let entry = votes
+            .entry(guild_id)
+            .or_insert_with(|| (track.clone(), HashSet::new()));
+
+        if entry.0.uuid() != track.uuid() {
+            *entry = (track.clone(), HashSet::new());
+        }
+
+        let added = entry.1.insert(voter);
+        (added, entry.1.clone())
+    }
+
+    pub fn clear(&self, guild_id: GuildId) {
+        self.votes.lock().unwrap().remove(&guild_id);
+    }
+}
+
+/// How many of `listeners` must vote for a skip at `percent`, rounding up
+/// and never fewer than one.
+pub fn needed(listeners: usize, percent: u32) -> usize {
+    (listeners * percent as usize).div_ceil(100).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_up_to_a_whole_listener() {
+        assert_eq!(needed(4, 50), 2);
+        assert_eq!(needed(3, 50), 2);
+        assert_eq!(needed(1, 50), 1);
+        assert_eq!(needed(5, 100), 5);
+        assert_eq!(needed(0, 50), 1);
+        assert_eq!(needed(10, 1), 1);
+    }
+}
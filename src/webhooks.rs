@@ -0,0 +1,224 @@
+use crate::{hooks::PlaybackHook, track::TrackInfo};
+use hyper::{client::HttpConnector, header::CONTENT_TYPE, Body, Client, Request};
+use hyper_rustls::HttpsConnector;
+use serde::Serialize;
+use std::{collections::HashMap, error::Error, fs, io::ErrorKind, sync::Arc};
+use twilight_model::id::{ChannelId, GuildId, UserId};
+
+/// Player events POSTed as JSON to a guild's webhook.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent<'a> {
+    TrackStarted {
+        guild_id: GuildId,
+        title: Option<&'a str>,
+        artist: Option<&'a str>,
+        url: Option<&'a str>,
+    },
+    TrackEnded {
+        guild_id: GuildId,
+        title: Option<&'a str>,
+        artist: Option<&'a str>,
+        url: Option<&'a str>,
+    },
+    /// Sent once the last queued track has ended, after its `track_ended`.
+    QueueEmpty { guild_id: GuildId },
+    UserJoined {
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        user_id: UserId,
+    },
+}
+
+/// Outgoing webhooks, configured per guild by the operator in
+/// `webhooks.json` as a map of guild ID to URL.
+#[derive(Clone, Debug)]
+pub struct Webhooks {
+    client: Client<HttpsConnector<HttpConnector>>,
+    urls: Arc<HashMap<GuildId, String>>,
+}
+
+impl Webhooks {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        let urls = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            client: Client::builder().build(HttpsConnector::with_native_roots()),
+            urls: Arc::new(urls),
+        })
+    }
+
+    /// Webhooks for no guild at all, for runs that mustn't reach anyone.
+    pub fn none() -> Self {
+        Self {
+            client: Client::builder().build(HttpsConnector::with_native_roots()),
+            urls: Default::default(),
+        }
+    }
+
+    pub fn contains(&self, guild_id: GuildId) -> bool {
+        self.urls.contains_key(&guild_id)
+    }
+
+    /// Sends the event in the background if the guild has a webhook.
+    pub fn send(&self, guild_id: GuildId, event: &WebhookEvent<'_>) {
+        let url = match self.urls.get(&guild_id) {
+            Some(url) => url.clone(),
+            None => return,
+        };
+
+        let body = match serde_json::to_vec(event) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!("failed to serialize webhook event: {}", e);
+                return;
+            }
+        };
+
+        let client = self.client.clone();
+
+        tokio::spawn(async move {
+            let request = match Request::post(&url)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+            {
+                Ok(request) => request,
+                Err(e) => {
+                    tracing::warn!("invalid webhook url for guild {}: {}", guild_id, e);
+                    return;
+                }
+            };
+
+            match client.request(request).await {
+                Ok(response) if !response.status().is_success() => {
+                    tracing::warn!(
+                        "webhook for guild {} responded with {}",
+                        guild_id,
+                        response.status()
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("webhook for guild {} failed: {}", guild_id, e),
+            }
+        });
+    }
+}
+
+impl PlaybackHook for Webhooks {
+    fn on_track_start(&self, guild_id: GuildId, track: &TrackInfo) {
+        self.send(
+            guild_id,
+            &WebhookEvent::TrackStarted {
+                guild_id,
+                title: track.title.as_deref(),
+                artist: track.artist.as_deref(),
+                url: track.source_url.as_deref(),
+            },
+        );
+    }
+
+    fn on_track_end(&self, guild_id: GuildId, track: &TrackInfo) {
+        self.send(
+            guild_id,
+            &WebhookEvent::TrackEnded {
+                guild_id,
+                title: track.title.as_deref(),
+                artist: track.artist.as_deref(),
+                url: track.source_url.as_deref(),
+            },
+        );
+    }
+
+    fn on_queue_empty(&self, guild_id: GuildId) {
+        self.send(guild_id, &WebhookEvent::QueueEmpty { guild_id });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hooks::Hooks;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Response, Server,
+    };
+    use serde_json::Value;
+    use std::{convert::Infallible, time::Duration};
+    use tokio::{sync::mpsc, time};
+
+    const GUILD: GuildId = GuildId(1);
+
+    /// Webhooks for [`GUILD`] pointed at a local server, and the events it
+    /// receives.
+    fn listen() -> (Webhooks, mpsc::UnboundedReceiver<Value>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let make_service = make_service_fn(move |_| {
+            let tx = tx.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let tx = tx.clone();
+
+                    async move {
+                        let bytes = hyper::body::to_bytes(request.into_body()).await?;
+                        tx.send(serde_json::from_slice(&bytes).unwrap()).unwrap();
+
+                        Ok::<_, hyper::Error>(Response::new(Body::empty()))
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}/hook", server.local_addr());
+        tokio::spawn(server);
+
+        let mut urls = HashMap::new();
+        urls.insert(GUILD, url);
+        let webhooks = Webhooks {
+            urls: Arc::new(urls),
+            ..Webhooks::none()
+        };
+
+        (webhooks, rx)
+    }
+
+    fn track(url: &str) -> TrackInfo {
+        TrackInfo {
+            source_url: Some(url.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn two_queued_tracks_empty_the_queue_once() {
+        let (webhooks, mut events) = listen();
+        let mut hooks = Hooks::default();
+        hooks.register(webhooks);
+
+        // What the player reports as a queue of two plays out.
+        for url in &["https://example.com/a.mp3", "https://example.com/b.mp3"] {
+            hooks.track_start(GUILD, &track(url));
+            hooks.track_end(GUILD, &track(url));
+        }
+        hooks.queue_empty(GUILD);
+
+        let mut received = Vec::new();
+        for _ in 0..5 {
+            let event = time::timeout(Duration::from_secs(5), events.recv()).await;
+            received.push(event.unwrap().unwrap()["event"].clone());
+        }
+        assert!(time::timeout(Duration::from_millis(200), events.recv())
+            .await
+            .is_err());
+
+        let count = |name: &str| received.iter().filter(|event| *event == name).count();
+        assert_eq!(count("track_started"), 2);
+        assert_eq!(count("track_ended"), 2);
+        assert_eq!(count("queue_empty"), 1);
+    }
+}
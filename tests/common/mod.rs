@@ -0,0 +1,511 @@
+//! Test harness: a mock Discord REST API and a bot state wired up to it.
+//!
+//! The cluster is built against a dummy gateway URL and never brought up,
+//! so tests drive the dispatcher by handing it synthetic events directly.
+
+use chrono::{TimeZone, Utc};
+use discord_music::{
+    clock::ManualClock, config::Config, sources::FakeResolver, LogBuffer, State, StateRef,
+};
+use hyper::{
+    body,
+    service::{make_service_fn, service_fn},
+    Body, Method, Response, Server,
+};
+use serde_json::{json, Value};
+use std::{
+    convert::Infallible,
+    convert::TryFrom,
+    env, fs,
+    net::SocketAddr,
+    path::PathBuf,
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::mpsc, time};
+use twilight_gateway::{cluster::ShardScheme, Cluster, Event, Intents};
+use twilight_http::Client as HttpClient;
+use twilight_model::{
+    channel::{Message, Reaction, ReactionType},
+    gateway::payload::{GuildCreate, InteractionCreate, MessageCreate, ReactionAdd},
+    id::{ApplicationId, ChannelId, GuildId, MessageId, UserId},
+};
+
+pub const GUILD_ID: u64 = 100;
+pub const CHANNEL_ID: u64 = 200;
+pub const BOT_ID: u64 = 300;
+pub const OWNER_ID: u64 = 400;
+pub const MEMBER_ID: u64 = 500;
+pub const DM_CHANNEL_ID: u64 = 600;
+/// The ID of every message the bot posts.
+pub const BOT_MESSAGE_ID: u64 = 700;
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request the bot made against the mock API.
+#[derive(Debug)]
+pub struct Recorded {
+    pub method: Method,
+    pub path: String,
+    pub body: Value,
+}
+
+pub struct Harness {
+    pub state: State,
+    /// Starts at noon UTC on 2021-01-01 and only moves when advanced.
+    pub clock: Arc<ManualClock>,
+    requests: mpsc::UnboundedReceiver<Recorded>,
+    /// Removed once the test is done.
+    data_dir: Option<PathBuf>,
+}
+
+impl Harness {
+    pub async fn new() -> Self {
+        Self::with_resolver(FakeResolver::default()).await
+    }
+
+    pub async fn with_resolver(resolver: FakeResolver) -> Self {
+        Self::build(resolver, None).await
+    }
+
+    /// Starts from a data directory of its own holding `saved`, as a name
+    /// and JSON contents for each file, as after a restart.
+    pub async fn with_saved(resolver: FakeResolver, saved: &[(&str, Value)]) -> Self {
+        static DIRS: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = env::temp_dir().join(format!(
+            "musicm8-test-{}-{}",
+            process::id(),
+            DIRS.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in saved {
+            fs::write(dir.join(name), contents.to_string()).unwrap();
+        }
+        // As `musicm8 run` does before starting.
+        discord_music::migrations::migrate(Some(&dir)).unwrap();
+
+        Self::build(resolver, Some(dir)).await
+    }
+
+    async fn build(resolver: FakeResolver, data_dir: Option<PathBuf>) -> Self {
+        let (tx, requests) = mpsc::unbounded_channel();
+        let address = serve(tx).await;
+
+        let http = HttpClient::builder()
+            .token("Bot test".to_string())
+            .proxy(address.to_string(), true)
+            .ratelimiter(None)
+            .build();
+        http.set_application_id(ApplicationId(BOT_ID));
+
+        let (cluster, _events) = Cluster::builder("Bot test", Intents::empty())
+            .http_client(http.clone())
+            .gateway_url(Some("ws://127.0.0.1:1".to_string()))
+            .shard_scheme(ShardScheme::try_from((0..=0, 1)).unwrap())
+            .build()
+            .await
+            .unwrap();
+
+        let clock = Arc::new(ManualClock::new(Utc.ymd(2021, 1, 1).and_hms(12, 0, 0)));
+
+        let state = StateRef::new(
+            Config {
+                data_dir: data_dir.clone(),
+                ..Config::default()
+            },
+            clock.clone(),
+            cluster,
+            http,
+            UserId(BOT_ID),
+            LogBuffer::default(),
+            Box::new(resolver),
+        )
+        .unwrap();
+
+        Self {
+            state,
+            clock,
+            requests,
+            data_dir,
+        }
+    }
+
+    /// Dispatches a guild message from `author_id` in the test channel.
+    pub async fn send(&self, author_id: u64, content: &str) {
+        let event = Event::MessageCreate(Box::new(MessageCreate(message(author_id, content))));
+
+        discord_music::handle_event(&self.state, event).await;
+    }
+
+    /// Has the bot join a voice channel, as `j/join` does, so playback
+    /// commands go ahead.
+    ///
+    /// The gateway never comes up, so the join itself fails, but songbird
+    /// keeps the channel it was joining, which is all they check for.
+    pub async fn join_call(&mut self) {
+        self.send(OWNER_ID, "j/join 800").await;
+        self.next_message().await;
+    }
+
+    /// Like [`send`](Self::send), from a member with `role_id`.
+    pub async fn send_with_role(&self, author_id: u64, role_id: u64, content: &str) {
+        let mut json = message_json(1, author_id, content);
+        json["member"] = member_json(author_id);
+        json["member"]["roles"] = json!([role_id.to_string()]);
+        let message = serde_json::from_value(json).unwrap();
+
+        discord_music::handle_event(
+            &self.state,
+            Event::MessageCreate(Box::new(MessageCreate(message))),
+        )
+        .await;
+    }
+
+    /// Like [`send`](Self::send), with a file of `size` bytes attached.
+    pub async fn send_with_attachment(
+        &self,
+        author_id: u64,
+        content: &str,
+        filename: &str,
+        size: u64,
+    ) {
+        let mut json = message_json(1, author_id, content);
+        json["attachments"] = json!([{
+            "filename": filename,
+            "id": "4",
+            "proxy_url": attachment_url(filename),
+            "size": size,
+            "url": attachment_url(filename),
+        }]);
+        let message = serde_json::from_value(json).unwrap();
+
+        discord_music::handle_event(
+            &self.state,
+            Event::MessageCreate(Box::new(MessageCreate(message))),
+        )
+        .await;
+    }
+
+    /// Dispatches the test guild arriving, as when the bot is added to it,
+    /// with the test channel as its system channel.
+    pub async fn join_guild(&self) {
+        let mut guild = guild();
+        guild["system_channel_id"] = json!(CHANNEL_ID.to_string());
+        guild["channels"] = json!([]);
+        guild["members"] = json!([]);
+        guild["presences"] = json!([]);
+        guild["voice_states"] = json!([]);
+
+        discord_music::handle_event(
+            &self.state,
+            Event::GuildCreate(Box::new(GuildCreate(
+                serde_json::from_value(guild).unwrap(),
+            ))),
+        )
+        .await;
+    }
+
+    /// Dispatches `user_id` pressing the button `custom_id` on the bot's
+    /// last message.
+    pub async fn press(&self, user_id: u64, custom_id: &str) {
+        let interaction = serde_json::from_value(json!({
+            "application_id": BOT_ID.to_string(),
+            "channel_id": CHANNEL_ID.to_string(),
+            "data": {
+                "component_type": 2,
+                "custom_id": custom_id,
+            },
+            "guild_id": GUILD_ID.to_string(),
+            "id": "3",
+            "member": member_json(user_id),
+            "message": message_json(BOT_MESSAGE_ID, BOT_ID, ""),
+            "token": "button-token",
+            "type": 3,
+        }))
+        .unwrap();
+
+        discord_music::handle_event(
+            &self.state,
+            Event::InteractionCreate(Box::new(InteractionCreate(interaction))),
+        )
+        .await;
+    }
+
+    /// Dispatches `user_id` running the slash command `name` in the test
+    /// channel, with `options` as its string options.
+    pub async fn slash(&self, user_id: u64, name: &str, options: &[(&str, &str)]) {
+        let options = options
+            .iter()
+            .map(|(name, value)| json!({ "name": name, "type": 3, "value": value }))
+            .collect::<Vec<_>>();
+
+        let interaction = serde_json::from_value(json!({
+            "application_id": BOT_ID.to_string(),
+            "channel_id": CHANNEL_ID.to_string(),
+            "data": {
+                "id": "1",
+                "name": name,
+                "options": options,
+            },
+            "guild_id": GUILD_ID.to_string(),
+            "id": "2",
+            "member": member_json(user_id),
+            "token": "interaction-token",
+            "type": 2,
+        }))
+        .unwrap();
+
+        discord_music::handle_event(
+            &self.state,
+            Event::InteractionCreate(Box::new(InteractionCreate(interaction))),
+        )
+        .await;
+    }
+
+    /// Dispatches `user_id` reacting to `message_id` with a Unicode emoji.
+    pub async fn react(&self, user_id: u64, message_id: u64, emoji: &str) {
+        let reaction = Reaction {
+            channel_id: ChannelId(CHANNEL_ID),
+            emoji: ReactionType::Unicode {
+                name: emoji.to_string(),
+            },
+            guild_id: Some(GuildId(GUILD_ID)),
+            member: None,
+            message_id: MessageId(message_id),
+            user_id: UserId(user_id),
+        };
+
+        discord_music::handle_event(
+            &self.state,
+            Event::ReactionAdd(Box::new(ReactionAdd(reaction))),
+        )
+        .await;
+    }
+
+    /// The next request the bot made, skipping the guild lookups done by
+    /// permission checks.
+    pub async fn next_request(&mut self) -> Recorded {
+        loop {
+            let request = time::timeout(RESPONSE_TIMEOUT, self.requests.recv())
+                .await
+                .expect("timed out waiting for a request")
+                .expect("mock server stopped");
+
+            if request.method != Method::GET {
+                return request;
+            }
+        }
+    }
+
+    /// The content of the next message the bot posted.
+    pub async fn next_message(&mut self) -> String {
+        self.next_message_in(CHANNEL_ID).await
+    }
+
+    /// The content of the next message the bot posted, which must be in
+    /// `channel_id`.
+    pub async fn next_message_in(&mut self, channel_id: u64) -> String {
+        let request = self.next_request().await;
+
+        assert_eq!(request.method, Method::POST);
+        assert_eq!(request.path, format!("/channels/{}/messages", channel_id));
+
+        request.body["content"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Gives a spawned handler time to reach its next `standby` wait after
+    /// the request the test just observed.
+    pub async fn settle(&self) {
+        time::sleep(Duration::from_millis(50)).await;
+    }
+
+    /// Asserts that the bot makes no further requests for a short while.
+    pub async fn assert_silent(&mut self) {
+        if let Ok(Some(request)) =
+            time::timeout(Duration::from_millis(200), self.requests.recv()).await
+        {
+            panic!("unexpected request: {:?}", request);
+        }
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.data_dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+async fn serve(tx: mpsc::UnboundedSender<Recorded>) -> SocketAddr {
+    let make_service = make_service_fn(move |_| {
+        let tx = tx.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let tx = tx.clone();
+
+                async move {
+                    let method = request.method().clone();
+                    // Drop the `/api/v*` prefix so tests don't depend on the API version.
+                    let path = request
+                        .uri()
+                        .path()
+                        .splitn(4, '/')
+                        .nth(3)
+                        .unwrap_or_default();
+                    let path = format!("/{}", path);
+                    let bytes = body::to_bytes(request.into_body()).await.unwrap();
+                    let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+                    let response =
+                        if method == Method::GET && path == format!("/guilds/{}", GUILD_ID) {
+                            guild()
+                        } else if method == Method::GET && path.starts_with("/channels/") {
+                            voice_channel(&path["/channels/".len()..])
+                        } else if method == Method::POST && path == "/users/@me/channels" {
+                            dm_channel()
+                        } else if method == Method::POST && path.ends_with("/messages") {
+                            posted_message(&body)
+                        } else if method == Method::GET && path.ends_with("/messages/@original") {
+                            // The slash command acknowledgement.
+                            message_json(BOT_MESSAGE_ID, BOT_ID, "")
+                        } else {
+                            json!({})
+                        };
+
+                    let _ = tx.send(Recorded { method, path, body });
+
+                    Ok::<_, Infallible>(Response::new(Body::from(response.to_string())))
+                }
+            }))
+        }
+    });
+
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let address = server.local_addr();
+
+    tokio::spawn(server);
+
+    address
+}
+
+fn guild() -> Value {
+    json!({
+        "afk_channel_id": null,
+        "afk_timeout": 300,
+        "application_id": null,
+        "banner": null,
+        "default_message_notifications": 0,
+        "description": null,
+        "discovery_splash": null,
+        "emojis": [],
+        "explicit_content_filter": 0,
+        "features": [],
+        "icon": null,
+        "id": GUILD_ID.to_string(),
+        "large": false,
+        "mfa_level": 0,
+        "name": "test",
+        "nsfw_level": 0,
+        "owner_id": OWNER_ID.to_string(),
+        "preferred_locale": "en-US",
+        "roles": [],
+        "rules_channel_id": null,
+        "splash": null,
+        "system_channel_flags": 0,
+        "system_channel_id": null,
+        "vanity_url_code": null,
+        "verification_level": 0,
+    })
+}
+
+/// Every channel the bot looks up is a voice channel with no user limit.
+fn voice_channel(id: &str) -> Value {
+    json!({
+        "bitrate": 64000,
+        "guild_id": GUILD_ID.to_string(),
+        "id": id,
+        "name": "voice",
+        "parent_id": null,
+        "permission_overwrites": [],
+        "position": 0,
+        "rtc_region": null,
+        "type": 2,
+        "user_limit": 0,
+    })
+}
+
+fn dm_channel() -> Value {
+    json!({
+        "id": DM_CHANNEL_ID.to_string(),
+        "type": 1,
+        "recipients": [],
+    })
+}
+
+fn posted_message(body: &Value) -> Value {
+    message_json(
+        BOT_MESSAGE_ID,
+        BOT_ID,
+        body["content"].as_str().unwrap_or_default(),
+    )
+}
+
+/// Where the CDN serves an attachment called `filename` from.
+pub fn attachment_url(filename: &str) -> String {
+    format!(
+        "https://cdn.discordapp.com/attachments/{}/4/{}",
+        CHANNEL_ID, filename
+    )
+}
+
+fn message(author_id: u64, content: &str) -> Message {
+    serde_json::from_value(message_json(1, author_id, content)).unwrap()
+}
+
+/// The guild member an interaction from `user_id` carries.
+fn member_json(user_id: u64) -> Value {
+    json!({
+        "deaf": false,
+        "joined_at": null,
+        "mute": false,
+        "nick": null,
+        "permissions": "0",
+        "roles": [],
+        "user": message_json(1, user_id, "")["author"],
+    })
+}
+
+fn message_json(id: u64, author_id: u64, content: &str) -> Value {
+    json!({
+        "attachments": [],
+        "author": {
+            "avatar": null,
+            "discriminator": "0001",
+            "id": author_id.to_string(),
+            "username": format!("user{}", author_id),
+        },
+        "channel_id": CHANNEL_ID.to_string(),
+        "content": content,
+        "edited_timestamp": null,
+        "embeds": [],
+        "guild_id": GuildId(GUILD_ID).to_string(),
+        "id": id.to_string(),
+        "type": 0,
+        "mention_everyone": false,
+        "mention_roles": [],
+        "mentions": [],
+        "pinned": false,
+        "timestamp": "2021-01-01T00:00:00+00:00",
+        "tts": false,
+    })
+}
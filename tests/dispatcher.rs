@@ -0,0 +1,1590 @@
+mod common;
+
+use common::{
+    attachment_url, Harness, BOT_ID, BOT_MESSAGE_ID, DM_CHANNEL_ID, GUILD_ID, MEMBER_ID, OWNER_ID,
+};
+use discord_music::sources::FakeResolver;
+use hyper::Method;
+use serde_json::json;
+use std::time::Duration;
+use twilight_model::id::GuildId;
+
+#[tokio::test]
+async fn ignores_messages_without_prefix() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "hello there").await;
+    harness.send(MEMBER_ID, "j/unknown").await;
+    // Only routed in the dev guild, and the harness has none.
+    harness.send(OWNER_ID, "j/dev state").await;
+
+    harness.assert_silent().await;
+}
+
+#[tokio::test]
+async fn resume_without_stopped_track() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/resume").await;
+
+    assert_eq!(
+        harness.next_message().await,
+        "There's no recently stopped track to resume."
+    );
+}
+
+#[tokio::test]
+async fn stop_then_resume_has_nothing_to_restart() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/stop").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("Stopped the track."));
+
+    // Nothing was playing, so nothing was saved for j/resume.
+    harness.send(MEMBER_ID, "j/resume").await;
+    assert_eq!(
+        harness.next_message().await,
+        "There's no recently stopped track to resume."
+    );
+}
+
+#[tokio::test]
+async fn play_prompts_for_url_and_reports_bad_input() {
+    let mut harness = Harness::new().await;
+    harness.join_call().await;
+
+    harness.send(MEMBER_ID, "j/play").await;
+    assert_eq!(
+        harness.next_message().await,
+        "What's the URL of the audio to play?"
+    );
+
+    harness.settle().await;
+    harness.send(MEMBER_ID, "https://example.com/missing").await;
+    assert_eq!(
+        harness.next_message().await,
+        "error no fake track for \"https://example.com/missing\""
+    );
+    assert!(harness.state.is_idle(GuildId(common::GUILD_ID)).await);
+}
+
+#[tokio::test]
+async fn settings_require_admin() {
+    let mut harness = Harness::new().await;
+
+    harness
+        .send(MEMBER_ID, "j/settings quiet 22:00-07:00")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Only server admins can change my settings."
+    );
+}
+
+#[tokio::test]
+async fn owner_can_change_settings() {
+    let mut harness = Harness::new().await;
+
+    harness.send(OWNER_ID, "j/settings quiet 22:00-07:00").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("Quiet hours set to 22:00"));
+}
+
+#[tokio::test]
+async fn play_announces_resolved_track() {
+    let resolver = FakeResolver::default().with_track(
+        "https://example.com/song",
+        "Song",
+        "Artist",
+        Duration::from_secs(1),
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness.send(MEMBER_ID, "j/play").await;
+    harness.next_message().await;
+
+    harness.settle().await;
+    harness.send(MEMBER_ID, "https://example.com/song").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Playing **Song** by **Artist**"
+    );
+}
+
+#[tokio::test]
+async fn play_uses_the_guild_template() {
+    let resolver = FakeResolver::default().with_track(
+        "https://example.com/song",
+        "Song",
+        "Artist",
+        Duration::from_secs(1),
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness
+        .send(
+            OWNER_ID,
+            "j/settings template nowplaying 🎶 Now spinning: {titel}",
+        )
+        .await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("Unknown placeholder `{titel}`"));
+
+    harness
+        .send(
+            OWNER_ID,
+            "j/settings template nowplaying 🎶 Now spinning: {title}",
+        )
+        .await;
+    harness.next_message().await;
+
+    harness.send(MEMBER_ID, "j/play").await;
+    harness.next_message().await;
+
+    harness.settle().await;
+    harness.send(MEMBER_ID, "https://example.com/song").await;
+    assert_eq!(harness.next_message().await, "🎶 Now spinning: Song");
+}
+
+#[tokio::test]
+async fn simulate_play_reports_metadata() {
+    let resolver = FakeResolver::default().with_track(
+        "https://example.com/song",
+        "Song",
+        "Artist",
+        Duration::from_secs(90),
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+
+    harness
+        .send(OWNER_ID, "j/simulate play https://example.com/song")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Dry run, nothing was changed:\n\
+         • I'm not in a voice channel, so nothing would play.\n\
+         • Would play **Song** by **Artist** (1:30)."
+    );
+}
+
+#[tokio::test]
+async fn simulate_play_refuses_recent_repeats() {
+    let resolver = FakeResolver::default().with_track(
+        "https://example.com/song",
+        "Song",
+        "Artist",
+        Duration::from_secs(90),
+    );
+    // It finished an hour before the harness clock's noon.
+    let history = json!({ GUILD_ID.to_string(): { "https://example.com/song": 1_609_498_800 } });
+    let mut harness = Harness::with_saved(resolver, &[("history.json", history)]).await;
+
+    harness.send(OWNER_ID, "j/settings norepeats 6").await;
+    harness.next_message().await;
+
+    harness
+        .send(OWNER_ID, "j/simulate play https://example.com/song")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Dry run, nothing was changed:\n\
+         • I'm not in a voice channel, so nothing would play.\n\
+         • Would play **Song** by **Artist** (1:30).\n\
+         • It would then be refused: **Song** finished 1 hour ago, and this server doesn't \
+         repeat tracks within 6 hours."
+    );
+}
+
+#[tokio::test]
+async fn join_follows_the_clock_through_quiet_hours() {
+    let mut harness = Harness::new().await;
+
+    harness.send(OWNER_ID, "j/settings quiet 12:30-13:00").await;
+    harness.next_message().await;
+
+    harness.send(MEMBER_ID, "j/join").await;
+    assert_eq!(
+        harness.next_message().await,
+        "What's the channel ID you want me to join?"
+    );
+
+    harness.clock.advance(Duration::from_secs(45 * 60));
+
+    harness.send(MEMBER_ID, "j/join").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("It's quiet hours here"));
+}
+
+#[tokio::test]
+async fn join_takes_a_channel_mention_or_id() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/join general").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Usage: `j/join <#channel>`, or the channel's ID"
+    );
+
+    // The gateway never comes up, so joining is as far as it gets.
+    harness.send(MEMBER_ID, "j/join <#800>").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("Failed to join <#800>!"));
+
+    harness.send(MEMBER_ID, "j/join 800").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("Failed to join <#800>!"));
+}
+
+#[tokio::test]
+async fn quiet_hours_follow_the_server_time_zone() {
+    let mut harness = Harness::new().await;
+
+    // Noon UTC is 15:00 at UTC+03:00.
+    harness.send(OWNER_ID, "j/settings quiet 14:30-15:30").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Quiet hours set to 14:30-15:30 (server time zone)."
+    );
+
+    harness.send(OWNER_ID, "j/settings timezone +03:00").await;
+    assert_eq!(harness.next_message().await, "Time zone set to UTC+03:00.");
+
+    harness.send(MEMBER_ID, "j/join").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("It's quiet hours here"));
+}
+
+#[tokio::test]
+async fn announcement_levels() {
+    let resolver = FakeResolver::default().with_track(
+        "https://example.com/song",
+        "Song",
+        "Artist",
+        Duration::from_secs(1),
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness
+        .send(OWNER_ID, "j/settings announcements minimal")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Announcements set to minimal."
+    );
+
+    harness.send(MEMBER_ID, "j/play").await;
+    harness.next_message().await;
+    harness.settle().await;
+    harness.send(MEMBER_ID, "https://example.com/song").await;
+
+    let reaction = harness.next_request().await;
+    assert_eq!(reaction.method, Method::PUT);
+    assert!(reaction
+        .path
+        .starts_with("/channels/200/messages/1/reactions/"));
+
+    harness.send(OWNER_ID, "j/settings announcements off").await;
+    harness.next_message().await;
+
+    harness.send(MEMBER_ID, "j/play").await;
+    harness.next_message().await;
+    harness.settle().await;
+    harness.send(MEMBER_ID, "https://example.com/song").await;
+    harness.assert_silent().await;
+}
+
+#[tokio::test]
+async fn settings_export_and_import() {
+    let mut harness = Harness::new().await;
+
+    harness
+        .send(
+            OWNER_ID,
+            "j/settings import ```json\n{\"prefix\": \"!\", \"timezone\": \"+02:00\", \"announcements\": \"off\"}\n```",
+        )
+        .await;
+    assert_eq!(harness.next_message().await, "Settings imported.");
+
+    harness.send(OWNER_ID, "!settings export").await;
+    let export = harness.next_message().await;
+    assert!(export.starts_with("```json\n"));
+    assert!(export.contains("\"prefix\": \"!\""));
+    assert!(export.contains("\"timezone\": \"+02:00\""));
+    assert!(export.contains("\"announcements\": \"off\""));
+
+    harness
+        .send(OWNER_ID, "!settings import {\"timezone\": \"soon\"}")
+        .await;
+    assert_eq!(harness.next_message().await, "Invalid time zone `soon`.");
+}
+
+#[tokio::test]
+async fn no_repeats_window_is_configurable() {
+    let mut harness = Harness::new().await;
+
+    harness.send(OWNER_ID, "j/settings norepeats 6 dj").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Tracks that finished within 6 hours can't be queued again, except by DJs."
+    );
+
+    harness.send(OWNER_ID, "j/settings").await;
+    assert!(harness
+        .next_message()
+        .await
+        .contains("\nNo repeats: within 6 hours, unless a DJ queues it\n"));
+
+    for bad in ["j/settings norepeats 0", "j/settings norepeats 6 sometimes"] {
+        harness.send(OWNER_ID, bad).await;
+        assert!(harness.next_message().await.starts_with("Usage:"));
+    }
+
+    harness.send(OWNER_ID, "j/settings norepeats off").await;
+    harness.next_message().await;
+    harness.send(OWNER_ID, "j/settings").await;
+    assert!(harness.next_message().await.contains("\nNo repeats: off\n"));
+}
+
+#[tokio::test]
+async fn idle_timeout_is_configurable() {
+    let mut harness = Harness::new().await;
+
+    harness.send(OWNER_ID, "j/settings").await;
+    assert!(harness
+        .next_message()
+        .await
+        .ends_with("Idle timeout: 5 minutes"));
+
+    harness.send(OWNER_ID, "j/settings idle 90").await;
+    assert_eq!(
+        harness.next_message().await,
+        "I'll leave after 1 hour 30 minutes with nothing playing."
+    );
+
+    harness.send(OWNER_ID, "j/settings idle 0").await;
+    assert!(harness.next_message().await.starts_with("Usage:"));
+
+    harness.send(OWNER_ID, "j/settings idle off").await;
+    harness.next_message().await;
+    harness.send(OWNER_ID, "j/settings").await;
+    assert!(harness.next_message().await.ends_with("Idle timeout: off"));
+}
+
+#[tokio::test]
+async fn spammers_are_ignored_until_unbanned() {
+    let mut harness = Harness::new().await;
+
+    for _ in 0..8 {
+        harness.send(MEMBER_ID, "j/resume").await;
+        harness.next_message().await;
+    }
+
+    harness.send(MEMBER_ID, "j/resume").await;
+
+    let dm_channel = harness.next_request().await;
+    assert_eq!(dm_channel.path, "/users/@me/channels");
+    assert_eq!(dm_channel.body["recipient_id"], OWNER_ID.to_string());
+    assert!(harness
+        .next_message_in(DM_CHANNEL_ID)
+        .await
+        .starts_with("In **test**: Ignoring <@500> for 15 minutes"));
+
+    harness.send(MEMBER_ID, "j/resume").await;
+    harness.assert_silent().await;
+
+    harness.send(OWNER_ID, "j/admin unban <@!500>").await;
+    assert_eq!(harness.next_message().await, "<@500> is no longer ignored.");
+
+    harness.send(MEMBER_ID, "j/resume").await;
+    harness.next_message().await;
+}
+
+#[tokio::test]
+async fn refuses_overlong_tracks_before_playing() {
+    let resolver = FakeResolver::default().with_track(
+        "https://example.com/marathon",
+        "Marathon",
+        "Artist",
+        Duration::from_secs(12 * 3600),
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness.send(MEMBER_ID, "j/play").await;
+    harness.next_message().await;
+
+    harness.settle().await;
+    harness
+        .send(MEMBER_ID, "https://example.com/marathon")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "That track is longer than the 180 minute limit, so I won't play it."
+    );
+    assert!(harness.state.is_idle(GuildId(common::GUILD_ID)).await);
+}
+
+#[tokio::test]
+async fn recording_is_opt_in() {
+    let mut harness = Harness::new().await;
+
+    harness.send(OWNER_ID, "j/record start").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("Recording isn't enabled on this server."));
+
+    harness.send(OWNER_ID, "j/settings recording on").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Recording allowed with `j/record start`."
+    );
+
+    harness.send(OWNER_ID, "j/record start").await;
+    assert_eq!(harness.next_message().await, "I'm not in a voice channel.");
+
+    harness.send(MEMBER_ID, "j/record stop").await;
+    assert_eq!(harness.next_message().await, "I'm not recording.");
+}
+
+#[tokio::test]
+async fn votes_on_announcements_build_chart() {
+    let resolver = FakeResolver::default().with_track(
+        "https://example.com/song",
+        "Song",
+        "Artist",
+        Duration::from_secs(1),
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness.send(MEMBER_ID, "j/play").await;
+    harness.next_message().await;
+
+    harness.settle().await;
+    harness.send(MEMBER_ID, "https://example.com/song").await;
+    harness.next_message().await;
+
+    for _ in 0..2 {
+        let seeded = harness.next_request().await;
+        assert_eq!(seeded.method, Method::PUT);
+        assert!(seeded
+            .path
+            .contains(&format!("/messages/{}/reactions/", BOT_MESSAGE_ID)));
+    }
+
+    // The bot's own seed reactions don't count.
+    harness.react(BOT_ID, BOT_MESSAGE_ID, "👍").await;
+    harness.send(MEMBER_ID, "j/top rated").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("No tracks have been rated yet."));
+
+    harness.react(OWNER_ID, BOT_MESSAGE_ID, "👍").await;
+    harness.send(MEMBER_ID, "j/top rated").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Top rated tracks:\n1. **Song** (+1) <https://example.com/song>"
+    );
+}
+
+#[tokio::test]
+async fn listening_event_locks_requests_when_it_starts() {
+    let mut harness = Harness::new().await;
+    harness.join_call().await;
+
+    harness.send(OWNER_ID, "j/settings eventrole <@&800>").await;
+    harness.next_message().await;
+
+    harness.send(OWNER_ID, "j/event start 10").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("<@&800> Listening event <t:"));
+
+    harness.settle().await;
+    harness.clock.advance(Duration::from_secs(10 * 60));
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("<@&800> The listening event is starting!"));
+
+    harness.send(MEMBER_ID, "j/play").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Requests are locked for the listening event."
+    );
+
+    harness.send(OWNER_ID, "j/event stop").await;
+    assert_eq!(
+        harness.next_message().await,
+        "The listening event is over, requests are open again."
+    );
+
+    harness.send(MEMBER_ID, "j/play").await;
+    assert_eq!(
+        harness.next_message().await,
+        "What's the URL of the audio to play?"
+    );
+}
+
+#[tokio::test]
+async fn break_needs_a_track_and_length() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/break").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Usage: `j/break <minutes>`, up to two hours"
+    );
+
+    harness.send(MEMBER_ID, "j/break 5").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Nothing's playing, so there's nothing to take a break from."
+    );
+}
+
+#[tokio::test]
+async fn ambience_needs_known_name_and_call() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/ambience whales").await;
+    assert_eq!(
+        harness.next_message().await,
+        "I don't know that ambience. Try one of: rain, crackle, noise."
+    );
+
+    harness.send(MEMBER_ID, "j/ambience rain").await;
+    assert_eq!(harness.next_message().await, "I'm not in a voice channel.");
+
+    harness.send(MEMBER_ID, "j/ambience off").await;
+    assert_eq!(harness.next_message().await, "There's no ambience playing.");
+}
+
+#[tokio::test]
+async fn vibe_needs_a_track() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/vibe").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Nothing's playing, so there's nothing to draw."
+    );
+}
+
+#[tokio::test]
+async fn theme_is_set_shown_and_cleared() {
+    let resolver = FakeResolver::default().with_track(
+        "https://example.com/anthem",
+        "Anthem",
+        "Band",
+        Duration::from_secs(60),
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+
+    harness
+        .send(MEMBER_ID, "j/theme set https://example.com/anthem 1:30")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "That clip ends before the start you gave."
+    );
+
+    harness
+        .send(MEMBER_ID, "j/theme set https://example.com/anthem 0:15 30")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Your theme is set: **Anthem** from 0:15 for 10 seconds."
+    );
+
+    harness.send(MEMBER_ID, "j/theme").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Your theme is <https://example.com/anthem> from 0:15 for 10 seconds."
+    );
+
+    harness.send(MEMBER_ID, "j/theme clear").await;
+    assert_eq!(harness.next_message().await, "Your theme is cleared.");
+}
+
+#[tokio::test]
+async fn skip_and_queue_when_idle() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/queue").await;
+    assert_eq!(harness.next_message().await, "The queue is empty.");
+
+    harness.send(MEMBER_ID, "j/skip").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Nothing's playing, so there's nothing to skip."
+    );
+}
+
+#[tokio::test]
+async fn dj_role_limits_playback_commands() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/setdj <@&900>").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Only server admins can change the DJ role."
+    );
+
+    harness.send(OWNER_ID, "j/setdj <@&900>").await;
+    assert_eq!(
+        harness.next_message().await,
+        "DJ commands such as `j/skip` and `j/stop` now need <@&900> or Manage Channels."
+    );
+
+    harness.send(MEMBER_ID, "j/skip").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Only members with <@&900> or Manage Channels can do that here. \
+         `j/voteskip` is open to everyone."
+    );
+
+    harness.send(MEMBER_ID, "j/queue").await;
+    assert_eq!(harness.next_message().await, "The queue is empty.");
+
+    harness.send(OWNER_ID, "j/skip").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Nothing's playing, so there's nothing to skip."
+    );
+
+    harness.send(OWNER_ID, "j/setdj off").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Anyone can use DJ commands again."
+    );
+
+    harness.send(MEMBER_ID, "j/skip").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Nothing's playing, so there's nothing to skip."
+    );
+}
+
+#[tokio::test]
+async fn djs_can_make_others_djs_for_a_while() {
+    let mut harness = Harness::new().await;
+
+    harness.send(OWNER_ID, "j/setdj <@&900>").await;
+    harness.next_message().await;
+
+    harness
+        .send(MEMBER_ID, &format!("j/dj grant <@{}> 1h", MEMBER_ID))
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Only DJs and admins can make someone a DJ."
+    );
+
+    harness.send(OWNER_ID, "j/dj grant <@500> 2d").await;
+    assert!(harness.next_message().await.starts_with("Usage:"));
+
+    harness
+        .send(OWNER_ID, &format!("j/dj grant <@{}> 1h", MEMBER_ID))
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "<@500> can use the DJ commands for the next 1 hour."
+    );
+
+    harness.send(MEMBER_ID, "j/volume 50").await;
+    assert_eq!(harness.next_message().await, "🔊 Volume set to 50%.");
+
+    // An hour after the harness clock's noon.
+    harness.send(MEMBER_ID, "j/dj list").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Made DJs for a while:\n<@500>, until <t:1609506000:R>"
+    );
+
+    harness.clock.advance(Duration::from_secs(60 * 60));
+    harness.settle().await;
+
+    harness.send(MEMBER_ID, "j/volume 60").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("Only members with <@&900> or Manage Channels can do that here."));
+
+    harness.send(MEMBER_ID, "j/dj list").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Nobody has been made a DJ for a while."
+    );
+}
+
+#[tokio::test]
+async fn jingles_are_confirmed_listed_and_cancelled() {
+    let resolver = FakeResolver::default().with_track(
+        "https://example.com/jingle",
+        "Jingle",
+        "Band",
+        Duration::from_secs(10),
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+
+    harness
+        .send(
+            MEMBER_ID,
+            "j/jingle add 2021-01-01 13:00 <#900> https://example.com/jingle",
+        )
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Only server admins can schedule jingles."
+    );
+
+    harness
+        .send(
+            OWNER_ID,
+            "j/jingle add 2021-01-01 11:00 <#900> https://example.com/jingle",
+        )
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "That time has already passed."
+    );
+
+    harness
+        .send(
+            OWNER_ID,
+            "j/jingle add 2021-01-01 13:00 <#900> https://example.com/jingle",
+        )
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Play **Jingle** in <#900> on <t:1609506000:F>? Reply `yes` to schedule it."
+    );
+
+    harness.settle().await;
+    harness.send(OWNER_ID, "yes").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Scheduled **Jingle** for <t:1609506000:F>."
+    );
+
+    harness.send(OWNER_ID, "j/jingle").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Scheduled jingles:\n1. **Jingle** in <#900> <t:1609506000:F>"
+    );
+
+    harness.send(OWNER_ID, "j/jingle cancel 1").await;
+    assert_eq!(harness.next_message().await, "Cancelled **Jingle**.");
+
+    harness.send(OWNER_ID, "j/jingle").await;
+    assert_eq!(harness.next_message().await, "No jingles are scheduled.");
+}
+
+#[tokio::test]
+async fn loop_mode_can_be_set_and_shown() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/loop").await;
+    assert_eq!(harness.next_message().await, "Looping is off.");
+
+    harness.send(MEMBER_ID, "j/loop queue").await;
+    assert_eq!(harness.next_message().await, "🔁 Looping the queue.");
+
+    harness.send(MEMBER_ID, "j/loop").await;
+    assert_eq!(harness.next_message().await, "Looping the queue.");
+
+    harness.send(MEMBER_ID, "j/loop forever").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Usage: `j/loop [track|queue|off]`"
+    );
+
+    harness.send(MEMBER_ID, "j/loop off").await;
+    assert_eq!(harness.next_message().await, "Looping is off.");
+}
+
+#[tokio::test]
+async fn volume_can_be_set_by_djs() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/volume").await;
+    assert_eq!(harness.next_message().await, "The volume is 100%.");
+
+    harness.send(MEMBER_ID, "j/volume 50%").await;
+    assert_eq!(harness.next_message().await, "🔊 Volume set to 50%.");
+
+    harness.send(MEMBER_ID, "j/volume 250").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Usage: `j/volume [percent]`, from 0 to 200"
+    );
+
+    harness.send(OWNER_ID, "j/setdj <@&900>").await;
+    harness.next_message().await;
+
+    harness.send(MEMBER_ID, "j/volume 150").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("Only members with <@&900> or Manage Channels can do that here."));
+
+    harness.send(OWNER_ID, "j/volume").await;
+    assert_eq!(harness.next_message().await, "The volume is 50%.");
+}
+
+#[tokio::test]
+async fn volume_caps_limit_members_with_a_role() {
+    const ROLE_ID: u64 = 901;
+
+    let mut harness = Harness::new().await;
+
+    harness
+        .send(OWNER_ID, "j/settings volumecap <@&901> 80%")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "<@&901> can set the volume up to 80%."
+    );
+
+    harness.send(OWNER_ID, "j/settings").await;
+    assert!(harness
+        .next_message()
+        .await
+        .contains("\nVolume caps: <@&901> 80%\n"));
+
+    harness
+        .send_with_role(MEMBER_ID, ROLE_ID, "j/volume 120")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Your roles can set the volume up to 80%."
+    );
+
+    harness
+        .send_with_role(MEMBER_ID, ROLE_ID, "j/volume 80")
+        .await;
+    assert_eq!(harness.next_message().await, "🔊 Volume set to 80%.");
+
+    harness.send(MEMBER_ID, "j/volume 120").await;
+    assert_eq!(harness.next_message().await, "🔊 Volume set to 120%.");
+
+    harness
+        .send(OWNER_ID, "j/settings volumecap <@&901> off")
+        .await;
+    assert_eq!(harness.next_message().await, "<@&901> can set any volume.");
+
+    harness
+        .send_with_role(MEMBER_ID, ROLE_ID, "j/volume 150")
+        .await;
+    assert_eq!(harness.next_message().await, "🔊 Volume set to 150%.");
+}
+
+#[tokio::test]
+async fn pause_and_now_playing_need_a_track() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/pause").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Nothing's playing, so there's nothing to pause."
+    );
+
+    harness.send(MEMBER_ID, "j/nowplaying").await;
+    assert_eq!(harness.next_message().await, "Nothing's playing.");
+
+    harness.send(MEMBER_ID, "j/np").await;
+    assert_eq!(harness.next_message().await, "Nothing's playing.");
+
+    harness.send(MEMBER_ID, "j/voteskip").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Nothing's playing, so there's nothing to skip."
+    );
+
+    harness.send(MEMBER_ID, "j/seek 1:30").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Nothing's playing, so there's nothing to seek."
+    );
+
+    harness.send(MEMBER_ID, "j/seek later").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("Usage: `j/seek <mm:ss>`"));
+}
+
+#[tokio::test]
+async fn handoff_needs_the_user_in_voice_there() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/handoff").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("Usage: `j/handoff <server ID>`"));
+
+    harness.send(MEMBER_ID, "j/handoff 101").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Join a voice channel on that server first, then hand off."
+    );
+}
+
+#[tokio::test]
+async fn slash_commands_run_like_typed_ones() {
+    let mut harness = Harness::new().await;
+
+    harness.slash(MEMBER_ID, "skip", &[]).await;
+
+    let acknowledgement = harness.next_request().await;
+    assert_eq!(
+        acknowledgement.path,
+        "/interactions/2/interaction-token/callback"
+    );
+    assert_eq!(acknowledgement.body["data"]["content"], "`j/skip`");
+
+    assert_eq!(
+        harness.next_message().await,
+        "Nothing's playing, so there's nothing to skip."
+    );
+}
+
+#[tokio::test]
+async fn play_takes_the_url_from_the_command() {
+    let resolver = FakeResolver::default().with_track(
+        "https://example.com/song",
+        "Song",
+        "Artist",
+        Duration::from_secs(1),
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness
+        .send(MEMBER_ID, "j/play https://example.com/song")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Playing **Song** by **Artist**"
+    );
+}
+
+#[tokio::test]
+async fn play_searches_keywords_and_plays_the_pick() {
+    let resolver = FakeResolver::default()
+        .with_track(
+            "https://example.com/song",
+            "Song",
+            "Artist",
+            Duration::from_secs(61),
+        )
+        .with_track(
+            "https://example.com/other",
+            "Other Song",
+            "Artist",
+            Duration::from_secs(1),
+        );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness.send(MEMBER_ID, "j/play song").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Pick a track by replying with its number:\n\
+         1. **Other Song** (0:01)\n\
+         2. **Song** (1:01)"
+    );
+
+    harness.settle().await;
+    harness.send(MEMBER_ID, "2").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Playing **Song** by **Artist**"
+    );
+
+    harness.send(MEMBER_ID, "j/play nothing like it").await;
+    assert_eq!(
+        harness.next_message().await,
+        "I couldn't find anything for that."
+    );
+}
+
+#[tokio::test]
+async fn play_queues_every_track_in_a_playlist() {
+    let resolver = FakeResolver::default()
+        .with_track(
+            "https://example.com/one",
+            "One",
+            "Artist",
+            Duration::from_secs(1),
+        )
+        .with_track(
+            "https://example.com/two",
+            "Two",
+            "Artist",
+            Duration::from_secs(1),
+        )
+        .with_playlist(
+            "https://www.youtube.com/playlist?list=PLtest",
+            &[
+                "https://example.com/one",
+                "https://example.com/missing",
+                "https://example.com/two",
+            ],
+        );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness
+        .send(
+            MEMBER_ID,
+            "j/play https://www.youtube.com/playlist?list=PLtest",
+        )
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Added 2 tracks from the playlist to the queue. 1 couldn't be played."
+    );
+
+    // Nothing was playing, so the first track was started straight away.
+    harness.send(MEMBER_ID, "j/queue").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Now playing: **One** for <@500>\nUp next:\n1. **Two** (0:01) <https://example.com/two> for <@500>"
+    );
+}
+
+#[tokio::test]
+async fn chapters_queue_as_their_own_tracks() {
+    let resolver = FakeResolver::default()
+        .with_track(
+            "https://example.com/album",
+            "Album",
+            "Artist",
+            Duration::from_secs(45 * 60),
+        )
+        .with_track(
+            "https://example.com/single",
+            "Single",
+            "Artist",
+            Duration::from_secs(1),
+        )
+        .with_chapters(
+            "https://example.com/album",
+            &[
+                ("Intro", Duration::ZERO, Duration::from_secs(60)),
+                ("Second", Duration::from_secs(60), Duration::from_secs(150)),
+            ],
+        );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness
+        .send(MEMBER_ID, "j/chapters https://example.com/single")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "That doesn't have any chapters to split it into."
+    );
+
+    harness
+        .send(MEMBER_ID, "j/chapters https://example.com/album")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Added the 2 chapters of **Album** to the queue."
+    );
+
+    harness.send(MEMBER_ID, "j/queue").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Now playing: **Intro** for <@500>\nUp next:\n1. **Second** (1:30) <https://example.com/album> for <@500>"
+    );
+}
+
+#[tokio::test]
+async fn queue_pages_through_long_queues() {
+    let urls = (1..=12)
+        .map(|n| format!("https://example.com/{}", n))
+        .collect::<Vec<_>>();
+    let mut resolver = FakeResolver::default();
+    for (n, url) in urls.iter().enumerate() {
+        resolver = resolver.with_track(
+            url,
+            &format!("Track {}", n + 1),
+            "Artist",
+            Duration::from_secs(1),
+        );
+    }
+    let entries = urls.iter().map(String::as_str).collect::<Vec<_>>();
+    let resolver = resolver.with_playlist("https://www.youtube.com/playlist?list=PLlong", &entries);
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness
+        .send(
+            MEMBER_ID,
+            "j/play https://www.youtube.com/playlist?list=PLlong",
+        )
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Added 12 tracks from the playlist to the queue."
+    );
+
+    harness.send(MEMBER_ID, "j/queue 3").await;
+    assert_eq!(harness.next_message().await, "The queue only has 2 pages.");
+
+    // The first track started, so eleven are left.
+    harness.send(MEMBER_ID, "j/queue").await;
+    let listing = harness.next_request().await;
+    let content = listing.body["content"].as_str().unwrap();
+    assert!(content.starts_with(
+        "Now playing: **Track 1** for <@500>\nUp next, page 1 of 2:\n1. **Track 2** (0:01) <https://example.com/2> for <@500>\n"
+    ));
+    assert_eq!(
+        listing.body["allowed_mentions"]["parse"]
+            .as_array()
+            .unwrap()
+            .len(),
+        0
+    );
+    let buttons = &listing.body["components"][0]["components"];
+    assert_eq!(buttons[0]["disabled"], true);
+    assert_eq!(buttons[1]["custom_id"], "queue:2");
+
+    harness.settle().await;
+    harness.press(MEMBER_ID, "queue:2").await;
+    let update = harness.next_request().await;
+    assert_eq!(update.path, "/interactions/3/button-token/callback");
+    assert_eq!(update.body["type"], 7);
+    assert_eq!(
+        update.body["data"]["content"],
+        "Now playing: **Track 1** for <@500>\nUp next, page 2 of 2:\n11. **Track 12** (0:01) <https://example.com/12> for <@500>"
+    );
+    assert_eq!(
+        update.body["data"]["components"][0]["components"][1]["disabled"],
+        true
+    );
+
+    harness.send(MEMBER_ID, "j/queue 2").await;
+    assert!(harness.next_request().await.body["content"]
+        .as_str()
+        .unwrap()
+        .starts_with("Now playing: **Track 1** for <@500>\nUp next, page 2 of 2:"));
+}
+
+#[tokio::test]
+async fn play_takes_an_attached_file() {
+    let resolver = FakeResolver::default().with_track(
+        &attachment_url("song.mp3"),
+        "Song",
+        "Artist",
+        Duration::from_secs(1),
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness
+        .send_with_attachment(MEMBER_ID, "j/play", "cover.png", 1024)
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "I can only play attached audio files: mp3, ogg, wav, flac, m4a, opus."
+    );
+
+    harness
+        .send_with_attachment(MEMBER_ID, "j/play", "album.wav", 200 * 1024 * 1024)
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "That file is over 50 MB, which is more than I'll play."
+    );
+
+    harness
+        .send_with_attachment(MEMBER_ID, "j/play", "song.mp3", 1024)
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Playing **Song** by **Artist**"
+    );
+}
+
+#[tokio::test]
+async fn spotify_links_need_an_app() {
+    let mut harness = Harness::new().await;
+    harness.join_call().await;
+
+    harness
+        .send(MEMBER_ID, "j/play https://open.spotify.com/track/4uLU6h")
+        .await;
+
+    let expected = if cfg!(feature = "spotify") {
+        "I can't play Spotify links: no Spotify app is set up."
+    } else {
+        "I can't play Spotify links: this build leaves them out."
+    };
+    assert_eq!(harness.next_message().await, expected);
+}
+
+#[tokio::test]
+async fn spotify_accounts_need_an_app() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/spotify link").await;
+
+    let expected = if cfg!(feature = "spotify") {
+        "I can't reach Spotify: no Spotify app is set up."
+    } else {
+        "I can't reach Spotify: this build leaves it out."
+    };
+    assert_eq!(harness.next_message().await, expected);
+}
+
+#[tokio::test]
+async fn shuffle_reorders_the_queue() {
+    let mut resolver = FakeResolver::default();
+    for title in ["One", "Two", "Three"] {
+        resolver = resolver.with_track(
+            &format!("https://example.com/{}", title),
+            title,
+            "Artist",
+            Duration::from_secs(1),
+        );
+    }
+    let resolver = resolver.with_playlist(
+        "https://www.youtube.com/playlist?list=PLtest",
+        &[
+            "https://example.com/One",
+            "https://example.com/Two",
+            "https://example.com/Three",
+        ],
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness.send(MEMBER_ID, "j/shuffle").await;
+    assert_eq!(
+        harness.next_message().await,
+        "The queue is empty, so there's nothing to shuffle."
+    );
+
+    harness
+        .send(
+            MEMBER_ID,
+            "j/play https://www.youtube.com/playlist?list=PLtest",
+        )
+        .await;
+    harness.next_message().await;
+
+    harness.send(MEMBER_ID, "j/shuffle").await;
+    let shuffled = harness.next_message().await;
+    assert!(shuffled.starts_with("🔀 Shuffled 2 tracks. Up next:\n1. **"));
+    assert!(shuffled.contains("**Two**") && shuffled.contains("**Three**"));
+}
+
+#[tokio::test]
+async fn remove_and_move_rearrange_the_queue() {
+    let mut resolver = FakeResolver::default();
+    for title in ["One", "Two", "Three"] {
+        resolver = resolver.with_track(
+            &format!("https://example.com/{}", title),
+            title,
+            "Artist",
+            Duration::from_secs(1),
+        );
+    }
+    let resolver = resolver.with_playlist(
+        "https://www.youtube.com/playlist?list=PLtest",
+        &[
+            "https://example.com/One",
+            "https://example.com/Two",
+            "https://example.com/Three",
+        ],
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness
+        .send(
+            MEMBER_ID,
+            "j/play https://www.youtube.com/playlist?list=PLtest",
+        )
+        .await;
+    harness.next_message().await;
+
+    harness.send(MEMBER_ID, "j/move 2 1").await;
+    assert_eq!(harness.next_message().await, "Moved **Three** to #1.");
+
+    harness.send(MEMBER_ID, "j/move 1").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Usage: `j/move <from> <to>`, with positions from `j/queue`"
+    );
+
+    harness.send(MEMBER_ID, "j/remove 3").await;
+    assert_eq!(
+        harness.next_message().await,
+        "There's no #3 in the queue, it goes from #1 to #2."
+    );
+
+    harness.send(MEMBER_ID, "j/remove 1").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("Removed **Three**"));
+    harness.send(MEMBER_ID, "j/remove 1").await;
+    assert!(harness.next_message().await.starts_with("Removed **Two**"));
+
+    harness.send(MEMBER_ID, "j/remove 1").await;
+    assert_eq!(harness.next_message().await, "The queue is empty.");
+}
+
+#[tokio::test]
+async fn removals_can_be_left_to_requesters() {
+    const OTHER_ID: u64 = 501;
+
+    let resolver = FakeResolver::default()
+        .with_track(
+            "https://example.com/One",
+            "One",
+            "Artist",
+            Duration::from_secs(1),
+        )
+        .with_track(
+            "https://example.com/Two",
+            "Two",
+            "Artist",
+            Duration::from_secs(1),
+        )
+        .with_track(
+            "https://example.com/Three",
+            "Three",
+            "Artist",
+            Duration::from_secs(1),
+        )
+        .with_playlist(
+            "https://www.youtube.com/playlist?list=PLtest",
+            &[
+                "https://example.com/One",
+                "https://example.com/Two",
+                "https://example.com/Three",
+            ],
+        );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness
+        .send(OWNER_ID, "j/settings removals requester")
+        .await;
+    assert_eq!(
+        harness.next_message().await,
+        "Members can remove the tracks they queued, and DJs can remove any."
+    );
+
+    harness
+        .send(
+            MEMBER_ID,
+            "j/play https://www.youtube.com/playlist?list=PLtest",
+        )
+        .await;
+    harness.next_message().await;
+
+    harness.send(OTHER_ID, "j/remove 1").await;
+    let refusal = harness.next_request().await;
+    assert_eq!(
+        refusal.body["content"],
+        "**Two** was queued by <@500>, so only they or a DJ can remove it."
+    );
+    assert_eq!(
+        refusal.body["allowed_mentions"]["parse"]
+            .as_array()
+            .unwrap()
+            .len(),
+        0
+    );
+
+    harness.send(MEMBER_ID, "j/remove 1").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Removed **Two** from the queue."
+    );
+
+    // Admins count as DJs, so they can remove anyone's.
+    harness.send(OWNER_ID, "j/remove 1").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Removed **Three** from the queue."
+    );
+}
+
+#[tokio::test]
+async fn tour_walks_through_the_features_once() {
+    let mut harness = Harness::new().await;
+
+    harness.join_guild().await;
+    let greeting = harness.next_request().await;
+    assert!(greeting.body["content"]
+        .as_str()
+        .unwrap()
+        .starts_with("Thanks for having me!"));
+    assert_eq!(
+        greeting.body["components"][0]["components"][0]["custom_id"],
+        "tour:start"
+    );
+
+    // Only a guild that's new to the bot is greeted.
+    harness.join_guild().await;
+    harness.assert_silent().await;
+
+    harness.press(MEMBER_ID, "tour:start").await;
+    let first = harness.next_request().await;
+    assert_eq!(first.path, "/interactions/3/button-token/callback");
+    assert_eq!(first.body["type"], 4);
+    assert_eq!(first.body["data"]["flags"], 64);
+    assert!(first.body["data"]["content"]
+        .as_str()
+        .unwrap()
+        .starts_with("Tour, step 1 of 4\n\n**Playing music**"));
+
+    for step in ["tour:1", "tour:2", "tour:3"] {
+        harness.press(MEMBER_ID, step).await;
+        assert_eq!(harness.next_request().await.body["type"], 7);
+    }
+    harness.press(MEMBER_ID, "tour:done").await;
+    assert!(harness.next_request().await.body["data"]["content"]
+        .as_str()
+        .unwrap()
+        .starts_with("That's the tour!"));
+
+    // The greeting's button isn't offered twice, but `j/tour` still works.
+    harness.press(MEMBER_ID, "tour:start").await;
+    assert!(harness.next_request().await.body["data"]["content"]
+        .as_str()
+        .unwrap()
+        .starts_with("You've taken the tour already."));
+
+    harness.send(MEMBER_ID, "j/tour").await;
+    let offer = harness.next_request().await;
+    assert_eq!(
+        offer.body["components"][0]["components"][0]["custom_id"],
+        "tour:again"
+    );
+    harness.press(MEMBER_ID, "tour:again").await;
+    assert!(harness.next_request().await.body["data"]["content"]
+        .as_str()
+        .unwrap()
+        .starts_with("Tour, step 1 of 4"));
+}
+
+#[tokio::test]
+async fn stats_report_the_build_features() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/stats").await;
+    let stats = harness.next_message().await;
+    assert!(stats.starts_with("**musicm8 "));
+    assert!(stats.contains("\nTracks queued: 0\n"));
+    assert!(stats.ends_with(&format!(
+        "\nSchema version: {}",
+        discord_music::migrations::SCHEMA_VERSION
+    )));
+    #[cfg(feature = "spotify")]
+    assert!(stats.contains("spotify"));
+}
+
+#[tokio::test]
+async fn admins_can_change_the_prefix() {
+    let mut harness = Harness::new().await;
+
+    harness.send(MEMBER_ID, "j/prefix !").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Only server admins can change the prefix."
+    );
+
+    harness.send(OWNER_ID, "j/prefix !").await;
+    assert!(harness
+        .next_message()
+        .await
+        .starts_with("Commands here now start with `!`"));
+
+    harness.send(MEMBER_ID, "!skip").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Nothing's playing, so there's nothing to skip."
+    );
+
+    harness.send(MEMBER_ID, "j/skip").await;
+    harness.assert_silent().await;
+
+    // Hints name commands the way they're typed here now.
+    harness.send(MEMBER_ID, "!volume loud").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Usage: `!volume [percent]`, from 0 to 200"
+    );
+
+    harness.send(OWNER_ID, "j/prefix reset").await;
+    assert_eq!(
+        harness.next_message().await,
+        "Back to the default prefix `j/`."
+    );
+}
+
+#[tokio::test]
+async fn questions_can_be_answered_with_buttons() {
+    let resolver = FakeResolver::default().with_track(
+        "https://example.com/song",
+        "Song",
+        "Artist",
+        Duration::from_secs(1),
+    );
+    let mut harness = Harness::with_resolver(resolver).await;
+    harness.join_call().await;
+
+    harness.send(MEMBER_ID, "j/play song").await;
+    let prompt = harness.next_request().await;
+    assert_eq!(
+        prompt.body["components"][0]["components"][0]["custom_id"],
+        "choice:0"
+    );
+
+    harness.settle().await;
+    harness.press(MEMBER_ID, "choice:0").await;
+
+    let acknowledgement = harness.next_request().await;
+    assert_eq!(
+        acknowledgement.path,
+        "/interactions/3/button-token/callback"
+    );
+    assert_eq!(acknowledgement.body["type"], 6);
+
+    assert_eq!(
+        harness.next_message().await,
+        "Playing **Song** by **Artist**"
+    );
+}
@@ -0,0 +1,80 @@
+//! `musicm8 replay`'s state, which must reach nothing outside the machine.
+//!
+//! Webhooks are read from the working directory, so this runs on its own
+//! rather than alongside the dispatcher tests.
+
+#![cfg(all(feature = "replay", feature = "webhooks"))]
+
+use discord_music::{
+    clock::SystemClock, config::Config, sources::FakeResolver, LogBuffer, StateRef,
+};
+use std::{convert::TryFrom, env, fs, process, sync::Arc};
+use twilight_gateway::{cluster::ShardScheme, Cluster, Intents};
+use twilight_http::Client as HttpClient;
+use twilight_model::id::{GuildId, UserId};
+
+const GUILD_ID: GuildId = GuildId(100);
+const BOT_ID: UserId = UserId(300);
+
+async fn parts() -> (Cluster, HttpClient) {
+    // Nothing is sent, so neither needs anything listening.
+    let http = HttpClient::builder()
+        .token("Bot test".to_string())
+        .proxy("127.0.0.1:1".to_string(), true)
+        .ratelimiter(None)
+        .build();
+
+    let (cluster, _events) = Cluster::builder("Bot test", Intents::empty())
+        .http_client(http.clone())
+        .gateway_url(Some("ws://127.0.0.1:1".to_string()))
+        .shard_scheme(ShardScheme::try_from((0..=0, 1)).unwrap())
+        .build()
+        .await
+        .unwrap();
+
+    (cluster, http)
+}
+
+#[tokio::test]
+async fn replays_register_no_webhooks() {
+    let dir = env::temp_dir().join(format!("musicm8-replay-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("webhooks.json"),
+        format!(r#"{{"{}": "http://127.0.0.1:1/hook"}}"#, GUILD_ID),
+    )
+    .unwrap();
+    env::set_current_dir(&dir).unwrap();
+
+    let config = || Config {
+        data_dir: None,
+        ..Config::default()
+    };
+
+    let (cluster, http) = parts().await;
+    let live = StateRef::new(
+        config(),
+        Arc::new(SystemClock),
+        cluster,
+        http,
+        BOT_ID,
+        LogBuffer::default(),
+        Box::new(FakeResolver::default()),
+    )
+    .unwrap();
+    assert!(live.has_webhook(GUILD_ID));
+
+    let (cluster, http) = parts().await;
+    let replay = StateRef::offline(
+        config(),
+        Arc::new(SystemClock),
+        cluster,
+        http,
+        BOT_ID,
+        LogBuffer::default(),
+    )
+    .unwrap();
+    assert!(!replay.has_webhook(GUILD_ID));
+
+    fs::remove_dir_all(&dir).unwrap();
+}